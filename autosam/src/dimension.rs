@@ -0,0 +1,721 @@
+use crate::{
+    midi::{Intervals, InvalidDataByte, PatchSelect, Pitch},
+    NoteLayers, VelocityOrder,
+};
+
+/// An axis of the sampling grid
+///
+/// The grid is visited like an odometer: the last dimension in a
+/// [`Dimensions`] list changes on every zone, and the first changes least
+/// often. The built-in dimensions take their values from the rest of the
+/// [`Config`](crate::Config), while the others carry their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    /// Notes from [`Config::notes`](crate::Config::notes), every [`Config::step`](crate::Config::step),
+    /// or those in [`Config::note_list`](crate::Config::note_list)
+    Pitch,
+    /// Layers from [`Config::velocity_levels`](crate::Config::velocity_levels), loudest first
+    Velocity,
+    /// Transitions from [`Config::legato`](crate::Config::legato), if enabled
+    Interval,
+    /// Variations from [`Config::round_robins`](crate::Config::round_robins)
+    RoundRobin,
+    /// A controller, set to each value before the zone's notes
+    Controller {
+        /// Controller number
+        controller: u8,
+        /// Values to visit
+        values: Values,
+    },
+    /// A Program Change to each program before the zone's notes
+    Program {
+        /// Coarse part of the bank to select along with each program
+        bank_msb: Option<u8>,
+        /// Fine part of the bank to select along with each program
+        bank_lsb: Option<u8>,
+        /// Programs to visit
+        programs: Values,
+    },
+    /// An articulation, selected by tapping a keyswitch note before the zone's notes
+    Keyswitch(Values),
+}
+
+impl Dimension {
+    /// Whether the dimension takes its values from the rest of the configuration
+    pub fn is_built_in(&self) -> bool {
+        matches!(
+            self,
+            Self::Pitch | Self::Velocity | Self::Interval | Self::RoundRobin
+        )
+    }
+}
+
+/// The setting a zone uses from one of the non-built-in dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    /// A controller value
+    Controller {
+        /// Controller number
+        controller: u8,
+        /// Controller value
+        value: u8,
+    },
+    /// A program number
+    Program(PatchSelect),
+    /// A keyswitch note
+    Keyswitch(Pitch),
+}
+
+/// An ordered list of [`Dimension`]s, from outermost to innermost
+///
+/// Built-in dimensions that are left out are added innermost, in their
+/// default order. The default list is `[Pitch, Velocity, Interval, RoundRobin]`.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::dimension::*;
+/// // iterate a controller outermost and velocity innermost
+/// let sustain = Values::range(0..=127, 127).unwrap();
+/// let dimensions = Dimensions::new()
+///     .with(Dimension::Controller { controller: 64, values: sustain })
+///     .unwrap()
+///     .with(Dimension::Pitch)
+///     .unwrap()
+///     .with(Dimension::Velocity)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
+    items: [Dimension; Dimensions::CAPACITY],
+    len: u8,
+}
+
+impl Dimensions {
+    /// Largest number of dimensions in a grid, including the built-in ones
+    pub const CAPACITY: usize = 8;
+
+    const BUILT_IN: [Dimension; 4] = [
+        Dimension::Pitch,
+        Dimension::Velocity,
+        Dimension::Interval,
+        Dimension::RoundRobin,
+    ];
+
+    /// Create an empty list
+    pub const fn new() -> Self {
+        Self {
+            items: [Dimension::Pitch; Self::CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Visit the whole grid once per round robin, in passes
+    ///
+    /// Each pass plays every zone once before the next pass begins, so
+    /// consecutive round robins of a zone are recorded far apart rather than
+    /// back-to-back.
+    pub const fn round_robin_passes() -> Self {
+        let mut items = [Dimension::Pitch; Self::CAPACITY];
+        items[0] = Dimension::RoundRobin;
+        items[1] = Dimension::Pitch;
+        items[2] = Dimension::Velocity;
+        items[3] = Dimension::Interval;
+
+        Self { items, len: 4 }
+    }
+
+    /// Add a dimension inside the ones already listed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a built-in dimension is listed twice, or if there
+    /// would not be room for the built-in dimensions.
+    pub fn with(mut self, dimension: Dimension) -> Result<Self, DimensionError> {
+        if dimension.is_built_in() && self.contains(&dimension) {
+            return Err(DimensionError::Duplicate(dimension));
+        }
+
+        let missing_built_ins = Self::BUILT_IN
+            .iter()
+            .filter(|d| **d != dimension && !self.contains(d))
+            .count();
+
+        if usize::from(self.len) + 1 + missing_built_ins > Self::CAPACITY {
+            return Err(DimensionError::Full);
+        }
+
+        self.items[usize::from(self.len)] = dimension;
+        self.len += 1;
+
+        Ok(self)
+    }
+
+    /// The listed dimensions, from outermost to innermost
+    pub fn as_slice(&self) -> &[Dimension] {
+        &self.items[..usize::from(self.len)]
+    }
+
+    fn contains(&self, dimension: &Dimension) -> bool {
+        self.as_slice().contains(dimension)
+    }
+
+    /// Fill in any missing built-in dimensions
+    fn complete(mut self) -> Self {
+        for dimension in Self::BUILT_IN {
+            if !self.contains(&dimension) {
+                self.items[usize::from(self.len)] = dimension;
+                self.len += 1;
+            }
+        }
+
+        self
+    }
+}
+
+impl Default for Dimensions {
+    fn default() -> Self {
+        Self::new().complete()
+    }
+}
+
+/// A problem with a list of [`Dimensions`]
+#[derive(Debug)]
+pub enum DimensionError {
+    /// A built-in dimension was listed more than once
+    Duplicate(Dimension),
+    /// There are more dimensions than fit in the list
+    Full,
+    /// A dimension has no values to visit
+    Empty(Dimension),
+    /// A program dimension's bank is invalid
+    Bank(InvalidDataByte),
+}
+
+impl core::fmt::Display for DimensionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DimensionError::Duplicate(d) => write!(f, "Dimension {d:?} was listed twice"),
+            DimensionError::Full => write!(
+                f,
+                "At most {} dimensions can be used, including the built-in ones",
+                Dimensions::CAPACITY
+            ),
+            DimensionError::Empty(d) => write!(f, "Dimension {d:?} has no values"),
+            DimensionError::Bank(e) => write!(f, "Invalid bank: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for DimensionError {}
+
+/// A set of 7-bit values (controller values, programs or notes)
+///
+/// Iterates in ascending order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Values(u128);
+
+impl Values {
+    /// Create an empty set
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Every `step`th value in a range
+    pub fn range(range: core::ops::RangeInclusive<u8>, step: u8) -> Result<Self, InvalidDataByte> {
+        let mut values = Self::new();
+        for value in range.step_by(usize::from(step.max(1))) {
+            values = values.with(value)?;
+        }
+
+        Ok(values)
+    }
+
+    /// Add a value to the set
+    pub fn with(self, value: u8) -> Result<Self, InvalidDataByte> {
+        if value > 127 {
+            return Err(InvalidDataByte::new(value));
+        }
+
+        Ok(Self(self.0 | 1 << value))
+    }
+
+    /// Check whether a value is in the set
+    pub fn contains(&self, value: u8) -> bool {
+        value <= 127 && self.0 & 1 << value != 0
+    }
+
+    /// Number of values in the set
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterate over the values in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u8> {
+        let bits = self.0;
+        (0..=127).filter(move |v| bits & 1 << v != 0)
+    }
+}
+
+/// A list of zones to sample, leaving out the rest of the grid
+///
+/// Each zone is a pitch, a velocity layer (counting from the loudest) and a
+/// round robin. Listed zones are still played in the order of the grid, at
+/// each value of any other dimensions, and only if the grid contains them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoneList {
+    zones: [(u8, u8, u8); ZoneList::CAPACITY],
+    len: u16,
+}
+
+impl ZoneList {
+    /// Largest number of zones in a list
+    ///
+    /// This is enough for four velocity layers of every MIDI note.
+    pub const CAPACITY: usize = 512;
+
+    /// Create an empty list
+    pub const fn new() -> Self {
+        Self {
+            zones: [(0, 0, 0); Self::CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Add a zone to the list, unless it is already there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the list is full.
+    pub fn with(
+        mut self,
+        pitch: Pitch,
+        velocity_layer: u8,
+        round_robin: u8,
+    ) -> Result<Self, ZoneListFull> {
+        let zone = (pitch.note_number(), velocity_layer, round_robin);
+        if self.as_slice().contains(&zone) {
+            return Ok(self);
+        }
+
+        if usize::from(self.len) == Self::CAPACITY {
+            return Err(ZoneListFull);
+        }
+
+        self.zones[usize::from(self.len)] = zone;
+        self.len += 1;
+
+        Ok(self)
+    }
+
+    /// Check whether a zone is in the list
+    pub fn contains(&self, pitch: u8, velocity_layer: u8, round_robin: u8) -> bool {
+        self.as_slice()
+            .contains(&(pitch, velocity_layer, round_robin))
+    }
+
+    /// Number of zones in the list
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Whether the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn as_slice(&self) -> &[(u8, u8, u8)] {
+        &self.zones[..usize::from(self.len)]
+    }
+}
+
+impl Default for ZoneList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`ZoneList`] has no room for another zone
+#[derive(Debug)]
+pub struct ZoneListFull;
+
+impl core::fmt::Display for ZoneListFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "At most {} zones can be listed", ZoneList::CAPACITY)
+    }
+}
+
+impl core::error::Error for ZoneListFull {}
+
+/// Combine a program dimension's bank with one of its programs
+fn patch(
+    bank_msb: Option<u8>,
+    bank_lsb: Option<u8>,
+    program: u8,
+) -> Result<PatchSelect, InvalidDataByte> {
+    let mut patch = PatchSelect::new(program)?;
+
+    if let Some(msb) = bank_msb {
+        patch = patch.with_bank_msb(msb)?;
+    }
+
+    if let Some(lsb) = bank_lsb {
+        patch = patch.with_bank_lsb(lsb)?;
+    }
+
+    Ok(patch)
+}
+
+/// Indices into each dimension of a [`Grid`], in the same order
+pub(crate) type Position = [u8; Dimensions::CAPACITY];
+
+/// The sampling grid, combining every dimension's values
+#[derive(Debug, Clone)]
+pub(crate) struct Grid {
+    dimensions: Dimensions,
+    sizes: [u8; Dimensions::CAPACITY],
+    pitches: [u8; 128],
+    velocity_order: VelocityOrder,
+    intervals: [i8; 128],
+    note_layers: [Option<NoteLayers>; 128],
+    zone_list: Option<ZoneList>,
+}
+
+impl Grid {
+    pub(crate) fn new(
+        dimensions: Dimensions,
+        pitches: Values,
+        velocity_levels: u8,
+        velocity_order: VelocityOrder,
+        intervals: Intervals,
+        round_robins: u8,
+        note_layers: [Option<NoteLayers>; 128],
+    ) -> Result<Self, DimensionError> {
+        let dimensions = dimensions.complete();
+
+        let mut sizes = [0; Dimensions::CAPACITY];
+        for (size, dimension) in sizes.iter_mut().zip(dimensions.as_slice()) {
+            *size = match dimension {
+                Dimension::Pitch => pitches.len() as u8,
+                Dimension::Velocity => velocity_levels,
+                Dimension::Interval => intervals.len() as u8,
+                Dimension::RoundRobin => round_robins,
+                Dimension::Program {
+                    bank_msb,
+                    bank_lsb,
+                    programs,
+                } => {
+                    patch(*bank_msb, *bank_lsb, 0).map_err(DimensionError::Bank)?;
+                    programs.len() as u8
+                }
+                Dimension::Controller { values, .. } | Dimension::Keyswitch(values) => {
+                    values.len() as u8
+                }
+            };
+
+            if *size == 0 && !dimension.is_built_in() {
+                return Err(DimensionError::Empty(*dimension));
+            }
+        }
+
+        let mut pitch_values = [0; 128];
+        for (value, pitch) in pitch_values.iter_mut().zip(pitches.iter()) {
+            *value = pitch;
+        }
+
+        let mut interval_values = [0; 128];
+        for (value, interval) in interval_values.iter_mut().zip(intervals.iter()) {
+            *value = interval;
+        }
+
+        Ok(Self {
+            dimensions,
+            sizes,
+            pitches: pitch_values,
+            velocity_order,
+            intervals: interval_values,
+            note_layers,
+            zone_list: None,
+        })
+    }
+
+    /// Leave out the zones that are not in a list
+    pub(crate) fn with_zone_list(mut self, zone_list: Option<ZoneList>) -> Self {
+        self.zone_list = zone_list;
+        self
+    }
+
+    fn len(&self) -> usize {
+        self.dimensions.as_slice().len()
+    }
+
+    fn find_dimension(&self, dimension: Dimension) -> usize {
+        self.dimensions
+            .as_slice()
+            .iter()
+            .position(|d| *d == dimension)
+            .unwrap_or_default()
+    }
+
+    fn pitch_at(&self, index: u8) -> u8 {
+        self.pitches[usize::from(index)]
+    }
+
+    fn interval_at(&self, index: u8) -> i8 {
+        self.intervals[usize::from(index)]
+    }
+
+    pub(crate) fn pitch(&self, position: &Position) -> u8 {
+        self.pitch_at(position[self.find_dimension(Dimension::Pitch)])
+    }
+
+    pub(crate) fn velocity_layer(&self, position: &Position) -> u8 {
+        self.velocity_index(position[self.find_dimension(Dimension::Velocity)])
+    }
+
+    /// Convert between a velocity layer and its index along the dimension
+    ///
+    /// Works in both directions, as the order is either kept or reversed.
+    fn velocity_index(&self, value: u8) -> u8 {
+        match self.velocity_order {
+            VelocityOrder::LoudToSoft => value,
+            VelocityOrder::SoftToLoud => {
+                self.sizes[self.find_dimension(Dimension::Velocity)].saturating_sub(value + 1)
+            }
+        }
+    }
+
+    pub(crate) fn interval(&self, position: &Position) -> i8 {
+        self.interval_at(position[self.find_dimension(Dimension::Interval)])
+    }
+
+    pub(crate) fn round_robin(&self, position: &Position) -> u8 {
+        position[self.find_dimension(Dimension::RoundRobin)]
+    }
+
+    /// Number of velocity levels sampled at a zone's pitch
+    pub(crate) fn velocity_levels(&self, position: &Position) -> u8 {
+        let velocity_dimension = self.find_dimension(Dimension::Velocity);
+        let allowed = self.allowed(velocity_dimension, self.pitch(position));
+        allowed.end - allowed.start
+    }
+
+    /// Number of round robins sampled at a zone's pitch
+    pub(crate) fn round_robins(&self, position: &Position) -> u8 {
+        let round_robin_dimension = self.find_dimension(Dimension::RoundRobin);
+        self.allowed(round_robin_dimension, self.pitch(position))
+            .end
+    }
+
+    /// Indices along a dimension that are sampled at a pitch
+    ///
+    /// Only the velocity and round robin dimensions can be narrowed, by
+    /// [`Config::note_layers`](crate::Config::note_layers).
+    fn allowed(&self, dimension: usize, pitch: u8) -> core::ops::Range<u8> {
+        let size = self.sizes[dimension];
+        let Some(layers) = self.note_layers[usize::from(pitch)] else {
+            return 0..size;
+        };
+
+        match self.dimensions.as_slice()[dimension] {
+            Dimension::Velocity => match self.velocity_order {
+                VelocityOrder::LoudToSoft => 0..layers.velocity_levels.get(),
+                VelocityOrder::SoftToLoud => size - layers.velocity_levels.get()..size,
+            },
+            Dimension::RoundRobin => 0..layers.round_robins.get(),
+            _ => 0..size,
+        }
+    }
+
+    /// Settings of the non-built-in dimensions, from outermost to innermost
+    pub(crate) fn settings<'a>(
+        &'a self,
+        position: &'a Position,
+    ) -> impl Iterator<Item = Setting> + 'a {
+        self.dimensions
+            .as_slice()
+            .iter()
+            .zip(position)
+            .filter_map(|(dimension, index)| {
+                let nth = |values: &Values| values.iter().nth(usize::from(*index));
+
+                match dimension {
+                    Dimension::Controller { controller, values } => {
+                        nth(values).map(|value| Setting::Controller {
+                            controller: *controller,
+                            value,
+                        })
+                    }
+                    Dimension::Program {
+                        bank_msb,
+                        bank_lsb,
+                        programs,
+                    } => nth(programs)
+                        .and_then(|program| patch(*bank_msb, *bank_lsb, program).ok())
+                        .map(Setting::Program),
+                    Dimension::Keyswitch(values) => {
+                        nth(values).map(|v| Setting::Keyswitch(Pitch(v)))
+                    }
+                    _ => None,
+                }
+            })
+    }
+
+    /// Whether a pitch and interval stay within the note range
+    fn playable(&self, pitch: u8, interval: i8) -> bool {
+        (0..=127).contains(&(i16::from(pitch) + i16::from(interval)))
+    }
+
+    /// Whether a zone is part of the grid, whether or not it is listed
+    fn is_sampled(&self, position: &Position) -> bool {
+        let pitch = self.pitch(position);
+        self.playable(pitch, self.interval(position))
+            && [Dimension::Velocity, Dimension::RoundRobin]
+                .into_iter()
+                .map(|dimension| self.find_dimension(dimension))
+                .all(|dimension| {
+                    self.allowed(dimension, pitch)
+                        .contains(&position[dimension])
+                })
+    }
+
+    /// Whether a zone is in the [`ZoneList`], if there is one
+    pub(crate) fn is_listed(&self, position: &Position) -> bool {
+        self.zone_list.map_or(true, |list| {
+            list.contains(
+                self.pitch(position),
+                self.velocity_layer(position),
+                self.round_robin(position),
+            )
+        })
+    }
+
+    fn is_valid(&self, position: &Position) -> bool {
+        self.is_sampled(position) && self.is_listed(position)
+    }
+
+    /// The first zone of the grid
+    pub(crate) fn first(&self) -> Option<Position> {
+        let position = [0; Dimensions::CAPACITY];
+
+        if self.sizes[..self.len()].contains(&0) {
+            return None;
+        }
+
+        if self.is_valid(&position) {
+            Some(position)
+        } else {
+            self.next(&position)
+        }
+    }
+
+    /// The zone after the given one
+    pub(crate) fn next(&self, position: &Position) -> Option<Position> {
+        let mut position = *position;
+
+        loop {
+            // increment like an odometer, innermost first
+            let mut dimension = self.len();
+            loop {
+                dimension = dimension.checked_sub(1)?;
+                position[dimension] += 1;
+
+                if position[dimension] < self.sizes[dimension] {
+                    break;
+                }
+
+                position[dimension] = 0;
+            }
+
+            if self.is_valid(&position) {
+                return Some(position);
+            }
+        }
+    }
+
+    /// The first zone for a pitch, velocity layer and round robin, whether or not it is listed
+    pub(crate) fn find(&self, pitch: u8, velocity_layer: u8, round_robin: u8) -> Option<Position> {
+        let pitch_index = (0..self.sizes[self.find_dimension(Dimension::Pitch)])
+            .find(|i| self.pitch_at(*i) == pitch)?;
+
+        let interval_dimension = self.find_dimension(Dimension::Interval);
+        let interval_index = (0..self.sizes[interval_dimension])
+            .find(|i| self.playable(pitch, self.interval_at(*i)))?;
+
+        let mut position = [0; Dimensions::CAPACITY];
+        position[self.find_dimension(Dimension::Pitch)] = pitch_index;
+        position[self.find_dimension(Dimension::Velocity)] = self.velocity_index(velocity_layer);
+        position[interval_dimension] = interval_index;
+        position[self.find_dimension(Dimension::RoundRobin)] = round_robin;
+
+        self.is_sampled(&position).then_some(position)
+    }
+
+    /// Number of zones from the given one (inclusive) to the end
+    pub(crate) fn remaining(&self, position: &Position) -> usize {
+        // listed zones are too scattered to count, so walk through them
+        if self.zone_list.is_some() {
+            return usize::from(self.is_valid(position))
+                + core::iter::successors(self.next(position), |p| self.next(p)).count();
+        }
+
+        let pitch_dimension = self.find_dimension(Dimension::Pitch);
+        let interval_dimension = self.find_dimension(Dimension::Interval);
+        let velocity_dimension = self.find_dimension(Dimension::Velocity);
+        let round_robin_dimension = self.find_dimension(Dimension::RoundRobin);
+        let per_pitch = [
+            pitch_dimension,
+            interval_dimension,
+            velocity_dimension,
+            round_robin_dimension,
+        ];
+
+        // indices of a dimension that can follow the given position, when
+        // the dimensions outside `level` are unchanged and `level` itself moves forward
+        let choices = |dimension: usize, level: usize| {
+            let size = self.sizes[dimension];
+            match dimension.cmp(&level) {
+                core::cmp::Ordering::Less => position[dimension]..position[dimension] + 1,
+                core::cmp::Ordering::Equal => position[dimension] + 1..size,
+                core::cmp::Ordering::Greater => 0..size,
+            }
+        };
+
+        let count = |level: usize| -> usize {
+            let others: usize = (0..self.len())
+                .filter(|d| !per_pitch.contains(d))
+                .map(|d| choices(d, level).len())
+                .product();
+
+            if others == 0 {
+                return 0;
+            }
+
+            let playable: usize = choices(pitch_dimension, level)
+                .map(|p| {
+                    let pitch = self.pitch_at(p);
+                    let layers = |dimension: usize| {
+                        let (choices, allowed) =
+                            (choices(dimension, level), self.allowed(dimension, pitch));
+                        (choices.start.max(allowed.start)..choices.end.min(allowed.end)).len()
+                    };
+
+                    choices(interval_dimension, level)
+                        .filter(|i| self.playable(pitch, self.interval_at(*i)))
+                        .count()
+                        * layers(velocity_dimension)
+                        * layers(round_robin_dimension)
+                })
+                .sum();
+
+            others * playable
+        };
+
+        usize::from(self.is_valid(position)) + (0..self.len()).map(count).sum::<usize>()
+    }
+}
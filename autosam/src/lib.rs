@@ -6,7 +6,7 @@
 //! let config = Config { notes: 48..=72, ..Default::default() };
 //! let mut sequencer = Sequencer::new(config, 48_000).unwrap();
 //!
-//! let AdvanceResult::Event { position, note } = sequencer.advance(1) else { panic!() };
+//! let AdvanceResult::Event { position, event: midi::Event::Note(note) } = sequencer.advance(1) else { panic!() };
 //! assert_eq!(position, 0);
 //! assert_eq!(note.state(), midi::NoteState::On);
 //! assert_eq!(note.pitch().note_number(), 48);
@@ -18,11 +18,20 @@
 
 use core::{num::NonZeroU8, time::Duration};
 
+/// Axes of the sampling grid, and the order they are visited in
+pub mod dimension;
 /// Data types representing MIDI concepts
 pub mod midi;
+/// Real-time playback of a sequence with an async runtime
+#[cfg(feature = "stream")]
+pub mod stream;
 mod tests;
 
-use midi::{InvalidMidiNote, Note, NoteState};
+use dimension::{DimensionError, Dimensions, Grid, Position, Setting, Values, ZoneList};
+use midi::{
+    Channel, ChannelRotation, Event, Intervals, InvalidDataByte, InvalidMidiNote, Mpe, Note,
+    NoteState, Pitch, PitchBend, Protocol,
+};
 
 /// Internal utilities for the library
 pub mod util {
@@ -50,8 +59,7 @@ pub mod util {
         }
     }
 
-    #[cfg(feature = "std")]
-    impl<const MAX: u8> std::error::Error for OutOfBounds<MAX> {}
+    impl<const MAX: u8> core::error::Error for OutOfBounds<MAX> {}
 }
 
 /// Configuration for an autosampling run
@@ -63,14 +71,74 @@ pub struct Config {
     pub notes: core::ops::RangeInclusive<u8>,
     /// The interval (in semitones) to step through the range by
     pub step: NonZeroU8,
+    /// Sample exactly these notes, instead of every [`step`](Self::step) of [`notes`](Self::notes)
+    pub note_list: Option<Values>,
     /// The number of velocity levels to sample
     pub velocity_levels: NonZeroU8,
+    /// The range of velocities (in MIDI 1.0 steps) that the levels divide up
+    ///
+    /// Each level is played at the top of its share of the range.
+    pub velocity_range: core::ops::RangeInclusive<u8>,
+    /// The direction to step through the velocity levels in
+    pub velocity_order: VelocityOrder,
     /// The number of duplicate samples to record at each pitch and velocity
     pub round_robins: NonZeroU8,
+    /// Fewer velocity levels or round robins for some notes, indexed by note number
+    ///
+    /// Each must be within [`velocity_levels`](Self::velocity_levels) and
+    /// [`round_robins`](Self::round_robins), which the other notes use.
+    pub note_layers: [Option<NoteLayers>; 128],
+    /// Sample only these zones of the grid, e.g. to record a few of them again
+    pub zone_list: Option<ZoneList>,
     /// The sustain time to hold the note for
     pub length: Duration,
     /// The release time to allow before a new note begins
     pub gap: Duration,
+    /// The message encoding the instrument will receive
+    ///
+    /// Determines the resolution of the velocity grid.
+    pub protocol: Protocol,
+    /// The channel to send events on
+    ///
+    /// Ignored if [`mpe`](Self::mpe) is set, in which case the zone determines the channels.
+    pub channel: Channel,
+    /// Sample an MPE instrument, giving each note its own member channel
+    pub mpe: Option<Mpe>,
+    /// Play each round robin on the next of these channels, in place of [`channel`](Self::channel)
+    ///
+    /// An instrument with the same patch on each of the channels then plays
+    /// every round robin with a voice of its own. Settings such as
+    /// controllers and programs are sent on each zone's channel, and
+    /// cleanup messages on all of them. Cannot be combined with [MPE](Self::mpe).
+    pub round_robin_channels: Option<ChannelRotation>,
+    /// Messages to send on every channel in use once all notes have been played
+    pub cleanup: Cleanup,
+    /// Messages to send on every channel in use before each zone, once the gap after the previous one is over
+    ///
+    /// These keep a long release or stuck modulation from carrying over into
+    /// the next zone, without cutting short the one before.
+    pub zone_reset: Cleanup,
+    /// Sample transitions between pairs of notes instead of single notes
+    pub legato: Option<Legato>,
+    /// Vary the timing and velocity of each zone
+    pub humanize: Option<Humanize>,
+    /// Send MIDI Timing Clock at this tempo, with Start and Stop around the sequence
+    ///
+    /// Note lengths can be given in beats using [`Tempo::beats`].
+    pub clock: Option<Tempo>,
+    /// The order that the grid is visited in, and any extra dimensions to visit
+    pub dimensions: Dimensions,
+    /// Repeat each note several times within its zone
+    pub burst: Option<Burst>,
+    /// Send per-note aftertouch while each zone's (first) note is held
+    pub poly_pressure: Option<PolyPressure>,
+    /// Pitch bend to send before each note, indexed by note number, for sampling in other tunings
+    ///
+    /// The bend is sent on the note's channel just before its NoteOn, in
+    /// place of the [MPE](Self::mpe) pitch bend if there is one. Without MPE,
+    /// both notes of a [legato](Self::legato) transition share the bend of
+    /// the later one.
+    pub tuning: Option<[PitchBend; 128]>,
 }
 
 impl Default for Config {
@@ -78,28 +146,415 @@ impl Default for Config {
         Self {
             notes: 0..=127,
             step: NonZeroU8::new(1).unwrap(),
+            note_list: None,
             velocity_levels: NonZeroU8::new(1).unwrap(),
+            velocity_range: 0..=127,
+            velocity_order: VelocityOrder::default(),
             round_robins: NonZeroU8::new(1).unwrap(),
+            note_layers: [None; 128],
+            zone_list: None,
             length: Duration::from_millis(500),
             gap: Duration::from_millis(500),
+            protocol: Protocol::Midi1,
+            channel: Channel::default(),
+            mpe: None,
+            round_robin_channels: None,
+            cleanup: Cleanup::default(),
+            zone_reset: Cleanup::default(),
+            legato: None,
+            humanize: None,
+            clock: None,
+            dimensions: Dimensions::default(),
+            burst: None,
+            poly_pressure: None,
+            tuning: None,
         }
     }
 }
 
-/// An entity that can drive the auto-sampling process
+/// The direction to step through velocity layers in
+///
+/// Layer indices always count from the loudest layer, whichever order they
+/// are visited in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityOrder {
+    /// Start with the loudest layer
+    #[default]
+    LoudToSoft,
+    /// Start with the softest layer
+    SoftToLoud,
+}
+
+/// Channel Mode messages that return an instrument to a known state
+///
+/// Sent as the final events of a sequence, so that an aborted or crashed host
+/// does not leave the instrument with a hanging note, and optionally before
+/// each zone as well.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cleanup {
+    /// Send "All Notes Off" (controller 123)
+    pub all_notes_off: bool,
+    /// Send "All Sound Off" (controller 120)
+    pub all_sound_off: bool,
+    /// Send "Reset All Controllers" (controller 121)
+    pub reset_all_controllers: bool,
+}
+
+impl Cleanup {
+    /// Send every available cleanup message
+    pub const ALL: Self = Self {
+        all_notes_off: true,
+        all_sound_off: true,
+        reset_all_controllers: true,
+    };
+
+    /// Get the `index`th enabled controller number
+    fn controller(&self, index: usize) -> Option<u8> {
+        [
+            (self.all_notes_off, 123),
+            (self.all_sound_off, 120),
+            (self.reset_all_controllers, 121),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, controller)| enabled.then_some(controller))
+        .nth(index)
+    }
+
+    fn len(&self) -> usize {
+        usize::from(self.all_notes_off)
+            + usize::from(self.all_sound_off)
+            + usize::from(self.reset_all_controllers)
+    }
+}
+
+/// Deterministic variation of each zone's timing and velocity
+///
+/// Every zone (including each round robin) gets its own variation, derived
+/// from the seed and the zone's position in the grid. Running the same
+/// configuration with the same seed always produces the same sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Humanize {
+    /// Source of the variation
+    pub seed: u64,
+    /// Largest offset of a zone's start, earlier or later
+    ///
+    /// Must be at most half of the gap between notes.
+    pub timing: Duration,
+    /// Largest change to a zone's velocity, in MIDI 1.0 steps
+    pub velocity: u8,
+}
+
+impl Humanize {
+    /// A random number for a zone of the grid
+    fn random(&self, zone: Option<Position>, salt: u8) -> u64 {
+        // SplitMix64, over the seed combined with the zone
+        let key = u64::from_le_bytes(zone.unwrap_or_default()) ^ u64::from(salt) << 7;
+        let mut z = self.seed ^ key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// A musical tempo, for measuring time in beats
+///
+/// # Example
+///
+/// ```
+/// # use autosam::Tempo;
+/// # use core::time::Duration;
+/// let tempo = Tempo::from_bpm(120.0).unwrap();
+/// assert_eq!(tempo.beats(1.5), Duration::from_millis(750));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo(f64);
+
+impl Tempo {
+    /// MIDI Timing Clock messages sent per beat (quarter note)
+    pub const TICKS_PER_BEAT: u8 = 24;
+
+    /// Get an instance of [`Tempo`] from a number of beats per minute
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tempo is not a positive, finite number.
+    pub fn from_bpm(bpm: f64) -> Result<Self, InvalidTempo> {
+        if !bpm.is_finite() || bpm <= 0.0 {
+            return Err(InvalidTempo(bpm));
+        }
+
+        Ok(Self(bpm))
+    }
+
+    /// Number of beats per minute
+    pub fn bpm(&self) -> f64 {
+        self.0
+    }
+
+    /// Length of time taken by a number of beats
+    ///
+    /// # Panics
+    ///
+    /// Panics if `beats` is negative, not finite, or overflows a [`Duration`].
+    pub fn beats(&self, beats: f64) -> Duration {
+        Duration::from_secs_f64(beats * 60.0 / self.0)
+    }
+
+    /// Position of a clock tick, in frames from the first tick
+    fn tick_frame(&self, tick: usize, sample_rate: u32) -> usize {
+        (tick as f64 * f64::from(sample_rate) * 60.0 / (self.0 * f64::from(Self::TICKS_PER_BEAT)))
+            as usize
+    }
+
+    /// Number of clock ticks at or before a frame
+    fn ticks_until(&self, frame: usize, sample_rate: u32) -> usize {
+        // estimate, then correct for rounding
+        let mut ticks = (frame as f64 * self.0 * f64::from(Self::TICKS_PER_BEAT)
+            / (f64::from(sample_rate) * 60.0)) as usize;
+
+        while ticks > 0 && self.tick_frame(ticks, sample_rate) > frame {
+            ticks -= 1;
+        }
+
+        while self.tick_frame(ticks + 1, sample_rate) <= frame {
+            ticks += 1;
+        }
+
+        ticks + 1
+    }
+}
+
+/// A tempo that is not a positive, finite number was provided
 #[derive(Debug)]
+pub struct InvalidTempo(f64);
+
+impl InvalidTempo {
+    /// Get the invalid number of beats per minute
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+impl core::fmt::Display for InvalidTempo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Tempo of {} BPM is not a positive number.", self.0)
+    }
+}
+
+impl core::error::Error for InvalidTempo {}
+
+/// MIDI Timing Clock progress, measured from the start of the sequence
+#[derive(Debug, Clone)]
+struct Clock {
+    tempo: Tempo,
+    elapsed: usize,
+    ticks: usize,
+    started: bool,
+    stopped: bool,
+}
+
+impl Clock {
+    fn new(tempo: Tempo) -> Self {
+        Self {
+            tempo,
+            elapsed: 0,
+            ticks: 0,
+            started: false,
+            stopped: false,
+        }
+    }
+}
+
+/// The velocity levels and round robins to sample one note with
+///
+/// The levels divide up [`Config::velocity_range`] among themselves, so a
+/// note with fewer levels has them spread further apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteLayers {
+    /// The number of velocity levels to sample
+    pub velocity_levels: NonZeroU8,
+    /// The number of duplicate samples to record at each velocity
+    pub round_robins: NonZeroU8,
+}
+
+/// Transitions between overlapping pairs of notes, for sampling legato playing
+///
+/// Each zone starts with a note at the sampled pitch. After the configured
+/// length, a second note starts at a fixed interval away, and the first note
+/// is released once they have overlapped for the configured time. The second
+/// note is then held for the rest of its own length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Legato {
+    /// Distances (in semitones) from each sampled pitch to the note it moves to
+    ///
+    /// Transitions that would leave the MIDI note range are skipped.
+    pub intervals: Intervals,
+    /// How long both notes are held at once
+    pub overlap: Duration,
+}
+
+/// Rapid repetitions of each note, for sampling rolls and tremolo
+///
+/// Each zone plays its note `hits` times, starting one `interval` apart. Every
+/// hit is held for the note length or until the next one starts, whichever
+/// is sooner, so the last hit is held for the full length before the gap.
+/// Cannot be combined with [`Legato`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Burst {
+    /// Number of times to play each note
+    pub hits: NonZeroU8,
+    /// Time between the starts of consecutive hits
+    pub interval: Duration,
+}
+
+/// A sweep of Polyphonic Key Pressure across the sustain of a note
+///
+/// The first message is sent along with the NoteOn, and the rest are spread
+/// evenly over the time the note is held, moving in equal steps from `start`
+/// to `end`. A single step holds the pressure at `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolyPressure {
+    /// Pressure sent with the NoteOn
+    pub start: u8,
+    /// Pressure sent last
+    pub end: u8,
+    /// Number of messages to send
+    pub steps: NonZeroU8,
+}
+
+impl PolyPressure {
+    /// Pressure of one of the messages
+    fn value(&self, index: u8) -> u8 {
+        let Some(last) = self.steps.get().checked_sub(1).filter(|&n| n > 0) else {
+            return self.start;
+        };
+
+        let (start, end) = (i16::from(self.start), i16::from(self.end));
+        (start + (end - start) * i16::from(index) / i16::from(last)) as u8
+    }
+}
+
+/// A cell of the sampling grid, captured as a single recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zone {
+    pitch: u8,
+    velocity_layer: u8,
+    velocity: u16,
+    round_robin: u8,
+    legato_target: Option<u8>,
+    settings: [Option<Setting>; Dimensions::CAPACITY],
+}
+
+impl Zone {
+    /// The (first) note's pitch
+    pub fn pitch(&self) -> Pitch {
+        Pitch(self.pitch)
+    }
+
+    /// Index of the velocity layer, where `0` is the loudest
+    pub fn velocity_layer(&self) -> u8 {
+        self.velocity_layer
+    }
+
+    /// The notes' velocity (up to 127)
+    pub fn velocity(&self) -> u8 {
+        midi::ump::scale_down_velocity(self.velocity)
+    }
+
+    /// The notes' velocity at MIDI 2.0 resolution (up to 65535)
+    pub fn velocity_16(&self) -> u16 {
+        self.velocity
+    }
+
+    /// Index of the round robin variation
+    pub fn round_robin(&self) -> u8 {
+        self.round_robin
+    }
+
+    /// Pitch that a legato transition moves to
+    pub fn legato_target(&self) -> Option<Pitch> {
+        self.legato_target.map(Pitch)
+    }
+
+    /// Values of the non-built-in [dimensions](Config::dimensions), from outermost to innermost
+    pub fn settings(&self) -> impl Iterator<Item = Setting> + '_ {
+        self.settings.iter().flatten().copied()
+    }
+}
+
+/// An entity that can drive the auto-sampling process
+#[derive(Debug, Clone)]
 pub struct Sequencer {
+    sample_rate: u32,
+    length_time: Duration,
+    gap_time: Duration,
+    overlap_time: Duration,
+    burst_time: Duration,
     length: usize,
     gap: usize,
-    pitch: u8,
-    pitch_step: u8,
-    final_pitch: u8,
-    velocity: u8,
-    velocity_step: u8,
-    round_robin: u8,
-    round_robin_count: u8,
+    overlap: usize,
+    /// Frames between the starts of consecutive hits
+    burst_interval: usize,
+    hits: u8,
+    poly_pressure: Option<PolyPressure>,
+    tuning: Option<[PitchBend; 128]>,
+    grid: Grid,
+    position: Option<Position>,
+    velocity_levels: u8,
+    /// Lowest and highest velocity, at the protocol's resolution
+    velocity_range: (u16, u16),
+    protocol: Protocol,
+    legato: bool,
+    channel: Channel,
+    mpe: Option<Mpe>,
+    round_robin_channels: Option<ChannelRotation>,
+    member: u8,
+    cleanup: Cleanup,
+    zone_reset: Cleanup,
+    skip: Option<Position>,
+    /// Pitch whose remaining zones are passed over once the current zone ends
+    skip_pitch: Option<u8>,
+    /// Zones passed over by [`Sequencer::skip_note`]
+    skipped: usize,
+    /// The zone that ended most recently, while the gap after it lasts
+    previous: Option<Position>,
+    humanize: Option<Humanize>,
+    /// Largest start offset, in frames
+    humanize_frames: usize,
+    clock: Option<Clock>,
     samples_remaining: usize,
-    next_status: NoteState,
+    next_step: Step,
+}
+
+/// The next event a [`Sequencer`] will produce
+///
+/// Steps that apply to a note carry the index of the voice within the zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// One of the controller messages in the MPE Configuration Message
+    MpeConfiguration(usize),
+    /// One of the messages applying the zone's settings
+    Setting(usize),
+    /// Per-note pitch bend on the note's member channel
+    MpePitchBend(u8),
+    /// Per-note pressure on the note's member channel
+    MpePressure(u8),
+    /// Pitch bend tuning the note, without MPE
+    TuningBend(u8),
+    NoteOn(u8),
+    NoteOff(u8),
+    /// Release of a hit before the one with this index
+    RepeatOff(u8),
+    /// Start of the hit with this index
+    RepeatOn(u8),
+    /// One of the per-note pressure messages during the first note's sustain
+    PolyPressure(u8),
+    /// One of the reset messages before a zone, on one of the channels in use
+    Reset(usize),
+    /// One of the cleanup messages, on one of the channels in use
+    Cleanup(usize),
+    /// No events remain
+    Complete,
 }
 
 impl Sequencer {
@@ -112,101 +567,1211 @@ impl Sequencer {
         let Config {
             notes,
             step,
+            note_list,
             velocity_levels,
+            velocity_range,
+            velocity_order,
             round_robins,
+            note_layers,
+            zone_list,
             length,
             gap,
+            protocol,
+            channel,
+            mpe,
+            round_robin_channels,
+            cleanup,
+            zone_reset,
+            legato,
+            humanize,
+            clock,
+            dimensions,
+            burst,
+            poly_pressure,
+            tuning,
         } = config;
 
-        let pitch = midi::Pitch::new(*notes.start())
-            .map_err(SequencerError::StartNote)?
-            .note_number();
+        midi::Pitch::new(*notes.start()).map_err(SequencerError::StartNote)?;
+        midi::Pitch::new(*notes.end()).map_err(SequencerError::EndNote)?;
 
-        let final_pitch = midi::Pitch::new(*notes.end())
-            .map_err(SequencerError::EndNote)?
-            .note_number();
+        let (start, end) = notes.clone().into_inner();
+        if start > end {
+            return Err(SequencerError::NoteRange { start, end });
+        }
+
+        // the step past the last note must still be a valid u8
+        let last = end - (end - start) % step.get();
+        if last.checked_add(step.get()).is_none() {
+            return Err(SequencerError::Step(step));
+        }
+
+        let pitches = match note_list {
+            Some(list) if list.is_empty() => return Err(SequencerError::NoteList),
+            Some(list) => list,
+            None => Values::range(notes, step.get()).map_err(SequencerError::EndNote)?,
+        };
+
+        let (low, high) = velocity_range.into_inner();
+        midi::data_byte(high).map_err(SequencerError::VelocityRange)?;
+        if low > high {
+            return Err(SequencerError::VelocityOrder { low, high });
+        }
+
+        let velocity_range = match protocol {
+            Protocol::Midi1 => (u16::from(low), u16::from(high)),
+            Protocol::Midi2 => (
+                midi::ump::scale_up_velocity(low),
+                midi::ump::scale_up_velocity(high),
+            ),
+        };
 
         let velocity_levels = velocity_levels.get();
-        let velocity_step = (128 + velocity_levels / 2) / velocity_levels;
-        if velocity_step == 0 {
+        if u32::from(velocity_levels) > u32::from(velocity_range.1 - velocity_range.0) + 1 {
             return Err(SequencerError::VelocityLevels(velocity_levels));
         }
 
-        Ok(Self {
-            length: ((length * sample_rate).as_millis() / 1_000) as usize,
-            gap: ((gap * sample_rate).as_millis() / 1_000) as usize,
-            pitch,
-            pitch_step: step.get(),
-            final_pitch,
-            velocity: 127,
-            velocity_step,
-            round_robin: 0,
-            round_robin_count: round_robins.get(),
+        for (note, layers) in note_layers.iter().enumerate() {
+            if let Some(layers) = layers.filter(|layers| {
+                layers.velocity_levels.get() > velocity_levels || layers.round_robins > round_robins
+            }) {
+                return Err(SequencerError::NoteLayers {
+                    pitch: Pitch(note as u8),
+                    layers,
+                });
+            }
+        }
+
+        if zone_list.is_some_and(|list| list.is_empty()) {
+            return Err(SequencerError::ZoneList);
+        }
+
+        if let Some(mpe) = &mpe {
+            mpe.validate()?;
+        }
+
+        match round_robin_channels {
+            Some(channels) if channels.is_empty() => {
+                return Err(SequencerError::RoundRobinChannels)
+            }
+            Some(_) if mpe.is_some() => return Err(SequencerError::RoundRobinChannelsMpe),
+            _ => {}
+        }
+
+        let (intervals, overlap) = match legato {
+            Some(Legato { intervals, overlap }) => {
+                if intervals.is_empty() {
+                    return Err(SequencerError::LegatoIntervals);
+                }
+
+                if overlap > length {
+                    return Err(SequencerError::LegatoOverlap(overlap));
+                }
+
+                (intervals, overlap)
+            }
+            None => (Intervals::UNISON, Duration::ZERO),
+        };
+
+        if let Some(Humanize { timing, .. }) = humanize {
+            if timing.checked_mul(2).map_or(true, |span| span > gap) {
+                return Err(SequencerError::HumanizeTiming(timing));
+            }
+        }
+
+        if let Some(Burst { interval, .. }) = burst {
+            if legato.is_some() {
+                return Err(SequencerError::BurstLegato);
+            }
+
+            if interval.is_zero() {
+                return Err(SequencerError::BurstInterval(interval));
+            }
+        }
+
+        if let Some(PolyPressure { start, end, .. }) = poly_pressure {
+            midi::data_byte(start)
+                .and(midi::data_byte(end))
+                .map_err(SequencerError::PolyPressure)?;
+        }
+
+        let grid = Grid::new(
+            dimensions,
+            pitches,
+            velocity_levels,
+            velocity_order,
+            intervals,
+            round_robins.get(),
+            note_layers,
+        )
+        .map_err(SequencerError::Dimensions)?
+        .with_zone_list(zone_list);
+
+        let mut sequencer = Self {
+            sample_rate,
+            length_time: length,
+            gap_time: gap,
+            overlap_time: overlap,
+            burst_time: burst.map_or(Duration::ZERO, |b| b.interval),
+            length: frames(length, sample_rate),
+            gap: frames(gap, sample_rate),
+            overlap: frames(overlap, sample_rate),
+            burst_interval: burst.map_or(0, |b| frames(b.interval, sample_rate)),
+            hits: burst.map_or(1, |b| b.hits.get()),
+            poly_pressure,
+            tuning,
+            grid,
+            position: None,
+            velocity_levels,
+            velocity_range,
+            protocol,
+            legato: legato.is_some(),
+            channel,
+            mpe,
+            round_robin_channels,
+            member: 0,
+            cleanup,
+            zone_reset,
+            skip: None,
+            skip_pitch: None,
+            skipped: 0,
+            previous: None,
+            humanize,
+            humanize_frames: humanize.map_or(0, |h| frames(h.timing, sample_rate)),
+            clock: clock.map(Clock::new),
             samples_remaining: 0,
-            next_status: NoteState::On,
+            next_step: Step::Complete,
+        };
+
+        sequencer.reset();
+
+        Ok(sequencer)
+    }
+
+    /// Return to the beginning of the sequence
+    ///
+    /// The next event produced will be the first one in the sequence,
+    /// at the start of the next call to [`Sequencer::advance`].
+    pub fn reset(&mut self) {
+        self.position = self.grid.first();
+        self.member = 0;
+        self.skip = None;
+        self.skip_pitch = None;
+        self.skipped = 0;
+        self.previous = None;
+        self.samples_remaining = 0;
+        if let Some(clock) = &mut self.clock {
+            *clock = Clock::new(clock.tempo);
+        }
+
+        if self.mpe.is_some() {
+            self.next_step = Step::MpeConfiguration(0);
+        } else {
+            self.next_step = self.zone_start();
+            self.samples_remaining = self.start_offset();
+        }
+    }
+
+    /// Move to a particular zone of the sampling grid
+    ///
+    /// The velocity layer and round robin are zero-based indices, with layer
+    /// `0` being the loudest. If a note is currently being held, its NoteOff
+    /// is produced as usual and the following gap leads into the requested
+    /// zone. Otherwise, the zone begins at the start of the next call to
+    /// [`Sequencer::advance`]. The rest of the sequence continues from there.
+    ///
+    /// When sampling legato transitions, this moves to the first transition
+    /// from the requested pitch. Any other [dimensions](Config::dimensions)
+    /// move to their first value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error (and leaves the sequence unchanged) if the target zone is not
+    /// part of the configured grid.
+    pub fn skip_to(
+        &mut self,
+        pitch: Pitch,
+        velocity_layer: u8,
+        round_robin: u8,
+    ) -> Result<(), SkipToError> {
+        let Some(target) = self.grid.find(pitch.note_number(), 0, 0) else {
+            return Err(SkipToError::Pitch(pitch));
+        };
+
+        if velocity_layer >= self.grid.velocity_levels(&target) {
+            return Err(SkipToError::VelocityLayer(velocity_layer));
+        }
+
+        if round_robin >= self.grid.round_robins(&target) {
+            return Err(SkipToError::RoundRobin(round_robin));
+        }
+
+        let target = self
+            .grid
+            .find(pitch.note_number(), velocity_layer, round_robin)
+            .unwrap_or(target);
+
+        if !self.grid.is_listed(&target) {
+            return Err(SkipToError::Unlisted {
+                pitch,
+                velocity_layer,
+                round_robin,
+            });
+        }
+
+        match self.next_step {
+            // let the configuration message complete before the first note
+            Step::MpeConfiguration(_) => {}
+            _ if self.note_held() => {
+                self.skip = Some(target);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        self.position = Some(target);
+        self.previous = None;
+
+        if !matches!(self.next_step, Step::MpeConfiguration(_)) {
+            self.next_step = self.zone_start();
+            self.samples_remaining = self.start_offset();
+        }
+
+        Ok(())
+    }
+
+    /// Play a zone again, e.g. because the instrument did not sound
+    ///
+    /// While a note is held, the current zone is played again once it
+    /// ends. In the gap after a zone, that zone is played again in place of
+    /// the next one. Returns `false` (and leaves the sequence unchanged)
+    /// at any other time, e.g. once the next zone has begun.
+    pub fn repeat_zone(&mut self) -> bool {
+        if self.note_held() {
+            self.skip = self.position;
+            return self.skip.is_some();
+        }
+
+        match self.previous {
+            Some(previous) if self.next_step == self.zone_start() => {
+                self.position = Some(previous);
+                self.next_step = self.zone_start();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move past the rest of the current note's zones without playing them
+    ///
+    /// This is for leaving out a note that cannot be sampled, e.g. because
+    /// the instrument does not sound there. If a note is currently being
+    /// held, its zone is completed as usual, and the zones of the same pitch
+    /// that would follow it are passed over. In the gap between zones, the
+    /// zones of the pitch about to be played are passed over, without
+    /// shortening the gap. Zones of the pitch that come later in the
+    /// sequence, e.g. in another round robin pass, are still played.
+    pub fn skip_note(&mut self) {
+        let Some(position) = &self.position else {
+            return;
+        };
+
+        self.skip_pitch = Some(self.grid.pitch(position));
+        if self.note_held() || matches!(self.next_step, Step::MpeConfiguration(_)) {
+            return;
+        }
+
+        self.pass_skipped_note();
+        if !matches!(self.next_step, Step::Complete | Step::Cleanup(_)) {
+            self.next_step = self.zone_start();
+        }
+    }
+
+    /// Number of zones passed over by [`Sequencer::skip_note`] so far
+    pub fn skipped_zones(&self) -> usize {
+        self.skipped
+    }
+
+    /// Move past the zones of a note that was asked to be skipped
+    fn pass_skipped_note(&mut self) {
+        let Some(pitch) = self.skip_pitch.take() else {
+            return;
+        };
+
+        while let Some(position) = &self.position {
+            if self.grid.pitch(position) != pitch {
+                break;
+            }
+
+            self.position = self.grid.next(position);
+            self.member = self.member.wrapping_add(self.voices());
+            self.skipped += 1;
+        }
+    }
+
+    /// Move past a number of zones without playing them
+    ///
+    /// This is for continuing a sequence that was interrupted, e.g. by a
+    /// crash, once that many zones have been recorded. Zones are counted
+    /// from the current one. If a note is currently being held, nothing is
+    /// skipped. Returns the number of zones skipped, which is less than
+    /// `count` if the sequence runs out of zones first.
+    pub fn skip_zones(&mut self, count: usize) -> usize {
+        if self.note_held() {
+            return 0;
+        }
+
+        let mut skipped = 0;
+        while skipped < count {
+            let Some(position) = &self.position else {
+                break;
+            };
+
+            self.position = self.grid.next(position);
+            skipped += 1;
+        }
+
+        if skipped == 0 {
+            return 0;
+        }
+
+        self.previous = None;
+
+        self.member =
+            ((usize::from(self.member) + skipped * usize::from(self.voices())) % 256) as u8;
+
+        // let the configuration message complete before the first note
+        if !matches!(self.next_step, Step::MpeConfiguration(_)) {
+            self.next_step = self.zone_start();
+            self.samples_remaining = self.start_offset();
+        }
+
+        skipped
+    }
+
+    /// Cut short the gap after the current zone, so that the next one begins now
+    ///
+    /// This lets a host move on as soon as a sound has decayed, instead of
+    /// waiting for the whole configured gap. Any humanized start offset of
+    /// the next zone is kept. Returns `false` (and does nothing) if the
+    /// sequence is not waiting between zones, e.g. while a note is held.
+    pub fn end_gap(&mut self) -> bool {
+        if self.note_held() || matches!(self.next_step, Step::MpeConfiguration(_) | Step::Complete)
+        {
+            return false;
+        }
+
+        self.samples_remaining = self.samples_remaining.min(self.start_offset());
+        true
+    }
+
+    /// Change the sample rate that frame counts are measured in
+    ///
+    /// The time remaining until the next event is preserved (to the nearest
+    /// frame at the new rate), as are all other sequencing state.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate == self.sample_rate {
+            return;
+        }
+
+        let rescale = |frames: usize| {
+            (frames as u128 * u128::from(sample_rate) / u128::from(self.sample_rate).max(1))
+                .try_into()
+                .unwrap_or(usize::MAX)
+        };
+
+        self.samples_remaining = rescale(self.samples_remaining);
+        if let Some(clock) = &mut self.clock {
+            clock.elapsed = rescale(clock.elapsed);
+        }
+
+        self.sample_rate = sample_rate;
+        self.length = frames(self.length_time, sample_rate);
+        self.gap = frames(self.gap_time, sample_rate);
+        self.overlap = frames(self.overlap_time, sample_rate);
+        self.burst_interval = frames(self.burst_time, sample_rate);
+        self.humanize_frames = self.humanize.map_or(0, |h| frames(h.timing, sample_rate));
+    }
+
+    /// The sample rate that frame counts are measured in
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The zone that the most recently produced event belongs to
+    ///
+    /// Before a zone's first event, this is the zone about to begin. Returns
+    /// `None` once every zone has been played.
+    pub fn zone(&self) -> Option<Zone> {
+        let position = self.position.as_ref()?;
+
+        let mut settings = [None; Dimensions::CAPACITY];
+        for (slot, setting) in settings.iter_mut().zip(self.grid.settings(position)) {
+            *slot = Some(setting);
+        }
+
+        Some(Zone {
+            pitch: self.grid.pitch(position),
+            velocity_layer: self.grid.velocity_layer(position),
+            velocity: self.velocity(),
+            round_robin: self.grid.round_robin(position),
+            legato_target: self.legato.then(|| self.voice_pitch(1)),
+            settings,
         })
     }
 
-    /// Try to move forward, producing any note events that will occur
+    /// Number of events this sequence will produce from its current position
+    pub fn remaining_events(&self) -> usize {
+        let per_zone = self.zone_steps().count();
+        let cleanup = self.cleanup.len() * self.channels_in_use();
+
+        let current_zones = self.current_zones();
+
+        let sequence = match self.next_step {
+            Step::Complete => 0,
+            Step::Cleanup(idx) => cleanup.saturating_sub(idx),
+            Step::MpeConfiguration(idx) => {
+                Mpe::CONFIGURATION_LEN - idx + current_zones * per_zone + cleanup
+            }
+            _ if current_zones == 0 => cleanup,
+            step => {
+                let done_in_zone = self
+                    .zone_steps()
+                    .position(|s| s == step)
+                    .unwrap_or_default();
+
+                per_zone - done_in_zone + self.following_zones() * per_zone + cleanup
+            }
+        };
+
+        let clock = self.clock.as_ref().map_or(0, |clock| {
+            let end = clock.elapsed + self.remaining_frames();
+            let ticks = if self.next_step == Step::Complete {
+                0
+            } else {
+                clock
+                    .tempo
+                    .ticks_until(end, self.sample_rate)
+                    .saturating_sub(clock.ticks)
+            };
+
+            ticks + usize::from(!clock.started) + usize::from(!clock.stopped)
+        });
+
+        sequence + clock
+    }
+
+    /// Every event from `start` (inclusive) until `end` (exclusive)
+    ///
+    /// Frames are counted from the beginning of the sequence, as are the
+    /// positions of the events produced. The window is computed from the
+    /// configuration alone, so this sequencer's own progress is unaffected.
+    /// Zones that finish before the window are skipped without producing
+    /// their events.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use autosam::*;
+    /// # use core::time::Duration;
+    /// let config = Config {
+    ///     notes: 60..=72,
+    ///     length: Duration::from_millis(100),
+    ///     gap: Duration::from_millis(100),
+    ///     ..Default::default()
+    /// };
+    /// let sequencer = Sequencer::new(config, 1000).unwrap();
+    ///
+    /// // the third zone's NoteOn and NoteOff
+    /// let positions = sequencer.events_between(400, 600).map(|(position, _)| position);
+    /// assert!(positions.eq([400, 500]));
+    /// ```
+    pub fn events_between(&self, start: usize, end: usize) -> impl Iterator<Item = (usize, Event)> {
+        let mut sequencer = self.clone();
+        sequencer.reset();
+        let skipped = sequencer.skip_zones_before(start);
+
+        sequencer
+            .into_iter()
+            .map(move |(position, event)| (skipped + position, event))
+            .skip_while(move |(position, _)| *position < start)
+            .take_while(move |(position, _)| *position < end)
+    }
+
+    /// Move past the zones that finish before a frame, from the beginning of the sequence
+    ///
+    /// Returns the frame that the sequence has moved to.
+    fn skip_zones_before(&mut self, frame: usize) -> usize {
+        let period: usize = self.zone_steps().map(|s| self.step_delay(s)).sum();
+
+        // a zone's events all come before the start of the following period,
+        // so stop a full period short of the frame to be safe
+        let Some(zones) = frame.checked_div(period).and_then(|n| n.checked_sub(1)) else {
+            return 0;
+        };
+
+        let mut skipped = 0;
+        while skipped < zones && self.position.is_some() {
+            self.position = self
+                .position
+                .as_ref()
+                .and_then(|position| self.grid.next(position));
+            skipped += 1;
+        }
+
+        if skipped == 0 {
+            return 0;
+        }
+
+        let elapsed = skipped * period;
+        self.member =
+            ((usize::from(self.member) + skipped * usize::from(self.voices())) % 256) as u8;
+        self.next_step = self.zone_start();
+        self.samples_remaining = self.start_offset();
+
+        if let Some(clock) = &mut self.clock {
+            clock.started = true;
+            clock.elapsed = elapsed;
+            clock.ticks = clock.tempo.ticks_until(elapsed - 1, self.sample_rate);
+        }
+
+        elapsed
+    }
+
+    /// Number of frames until the final event of the sequence
+    fn remaining_frames(&self) -> usize {
+        let current_zones = self.current_zones();
+        let zone_frames: usize = self.zone_steps().map(|s| self.step_delay(s)).sum();
+
+        // zone start offsets cancel out, apart from the current zone's
+
+        self.samples_remaining
+            + match self.next_step {
+                Step::Complete | Step::Cleanup(_) => 0,
+                Step::MpeConfiguration(_) => current_zones * zone_frames,
+                _ if current_zones == 0 => 0,
+                step => {
+                    let rest_of_zone: usize = self
+                        .zone_steps()
+                        .skip_while(|s| *s != step)
+                        .map(|s| self.step_delay(s))
+                        .sum();
+
+                    rest_of_zone + self.following_zones() * zone_frames - self.start_offset()
+                }
+            }
+    }
+
+    /// Number of zones from the current one (inclusive) to the end
+    fn current_zones(&self) -> usize {
+        self.position
+            .as_ref()
+            .map_or(0, |position| self.grid.remaining(position))
+    }
+
+    /// Number of zones after the current one
+    fn following_zones(&self) -> usize {
+        match &self.skip {
+            Some(position) => self.grid.remaining(position),
+            None => self.current_zones().saturating_sub(1),
+        }
+    }
+
+    /// The message encoding the instrument will receive
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Velocity of the current layer, at MIDI 2.0 resolution
+    fn velocity(&self) -> u16 {
+        let (layer, levels) =
+            self.position
+                .as_ref()
+                .map_or((0, self.velocity_levels), |position| {
+                    (
+                        self.grid.velocity_layer(position),
+                        self.grid.velocity_levels(position),
+                    )
+                });
+
+        // spread layers evenly downward from the top of the range
+        let (low, high) = (
+            u32::from(self.velocity_range.0),
+            u32::from(self.velocity_range.1),
+        );
+        let (layer, levels, width) = (u32::from(layer), u32::from(levels), high - low + 1);
+        // MIDI 1.0 layers are a whole step apart, unless that would run past the bottom
+        let step = (width + levels / 2) / levels;
+        let offset = match self.protocol {
+            Protocol::Midi1 if step * (levels - 1) < width => layer * step,
+            _ => (layer * width + levels / 2) / levels,
+        };
+        let velocity = i32::from((high - offset) as u16);
+
+        let jitter = self.humanize.map_or(0, |humanize| {
+            let range = 2 * u64::from(humanize.velocity) + 1;
+            (humanize.random(self.position, 1) % range) as i32 - i32::from(humanize.velocity)
+        });
+
+        match self.protocol {
+            Protocol::Midi1 => {
+                midi::ump::scale_up_velocity((velocity + jitter).clamp(1, 127) as u8)
+            }
+            // keep steps the same size as at MIDI 1.0 resolution
+            Protocol::Midi2 => (velocity + jitter * 512).clamp(1, 0xFFFF) as u16,
+        }
+    }
+
+    /// Frames to delay the start of the current zone by
+    fn start_offset(&self) -> usize {
+        match self.humanize {
+            Some(humanize) if self.humanize_frames > 0 && self.position.is_some() => {
+                let range = 2 * self.humanize_frames as u64 + 1;
+                (humanize.random(self.position, 0) % range) as usize
+            }
+            _ => 0,
+        }
+    }
+
+    /// Number of notes played in each zone
+    fn voices(&self) -> u8 {
+        if self.legato {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Pitch of one of the notes in the current zone
+    fn voice_pitch(&self, voice: u8) -> u8 {
+        let Some(position) = &self.position else {
+            return 0;
+        };
+
+        let pitch = self.grid.pitch(position);
+        if voice == 0 {
+            pitch
+        } else {
+            pitch.saturating_add_signed(self.grid.interval(position))
+        }
+    }
+
+    /// Channel one of the notes in the current zone is played on
+    fn note_channel(&self, voice: u8) -> Channel {
+        match &self.mpe {
+            Some(mpe) => mpe.member_channel(self.member.wrapping_add(voice)),
+            None => self.zone_channel(),
+        }
+    }
+
+    /// Channel that messages applying to the whole instrument are sent on
+    fn control_channel(&self) -> Channel {
+        match &self.mpe {
+            Some(mpe) => mpe.master_channel(),
+            None => self.zone_channel(),
+        }
+    }
+
+    /// Channel of the current zone without MPE, taking turns between round robins if asked to
+    fn zone_channel(&self) -> Channel {
+        let round_robin = self
+            .position
+            .as_ref()
+            .map_or(0, |position| self.grid.round_robin(position));
+
+        self.round_robin_channels
+            .and_then(|channels| channels.get(usize::from(round_robin)))
+            .unwrap_or(self.channel)
+    }
+
+    fn note(&self, state: NoteState, voice: u8) -> Note {
+        Note {
+            pitch: self.voice_pitch(voice),
+            velocity: self.velocity(),
+            state,
+            channel: self.note_channel(voice),
+        }
+    }
+
+    /// The first step in the process of playing a note
+    fn note_start(&self, voice: u8) -> Step {
+        if self.mpe.is_some() {
+            Step::MpePitchBend(voice)
+        } else if self.tuning.is_some() {
+            Step::TuningBend(voice)
+        } else {
+            Step::NoteOn(voice)
+        }
+    }
+
+    /// Pitch bend that tunes one of the notes in the current zone, if any
+    fn tuning_bend(&self, voice: u8) -> Option<PitchBend> {
+        self.tuning
+            .map(|tuning| tuning[usize::from(self.voice_pitch(voice) & 0x7F)])
+    }
+
+    /// The first step in the process of playing a zone
+    fn zone_start(&self) -> Step {
+        if self.zone_reset.len() > 0 {
+            Step::Reset(0)
+        } else {
+            self.settings_start()
+        }
+    }
+
+    /// The first step of a zone after any reset messages
+    fn settings_start(&self) -> Step {
+        if self.setting_events().next().is_some() {
+            Step::Setting(0)
+        } else {
+            self.note_start(0)
+        }
+    }
+
+    /// Events that apply the current zone's settings
+    fn setting_events(&self) -> impl Iterator<Item = Event> + '_ {
+        let channel = self.control_channel();
+
+        self.position
+            .iter()
+            .flat_map(|position| self.grid.settings(position))
+            .flat_map(move |setting| {
+                let keyswitch = |pitch: Pitch, state| {
+                    Event::Note(Note {
+                        pitch: pitch.note_number(),
+                        velocity: u16::MAX,
+                        state,
+                        channel,
+                    })
+                };
+
+                let mut events = [None; 3];
+                match setting {
+                    Setting::Controller { controller, value } => {
+                        events[0] = Some(Event::ControlChange {
+                            channel,
+                            controller,
+                            value,
+                        });
+                    }
+                    Setting::Program(patch) => {
+                        for (slot, event) in events.iter_mut().zip(patch.events(channel)) {
+                            *slot = Some(event);
+                        }
+                    }
+                    Setting::Keyswitch(pitch) => {
+                        events[0] = Some(keyswitch(pitch, NoteState::On));
+                        events[1] = Some(keyswitch(pitch, NoteState::Off));
+                    }
+                }
+
+                events.into_iter().flatten()
+            })
+    }
+
+    /// Every step involved in playing a zone, in order
+    fn zone_steps(&self) -> impl Iterator<Item = Step> + '_ {
+        let voices = self.voices();
+
+        (0..self.zone_reset.len() * self.channels_in_use())
+            .map(Step::Reset)
+            .chain((0..self.setting_events().count()).map(Step::Setting))
+            .chain((0..voices).flat_map(move |voice| {
+                let setup = match (&self.mpe, &self.tuning) {
+                    (Some(_), _) => [
+                        Some(Step::MpePitchBend(voice)),
+                        Some(Step::MpePressure(voice)),
+                    ],
+                    (None, Some(_)) => [Some(Step::TuningBend(voice)), None],
+                    (None, None) => [None, None],
+                };
+
+                setup
+                    .into_iter()
+                    .flatten()
+                    .chain([Step::NoteOn(voice)])
+                    .chain((0..self.pressure_steps(voice)).map(Step::PolyPressure))
+            }))
+            .chain((1..self.hits).flat_map(|hit| [Step::RepeatOff(hit), Step::RepeatOn(hit)]))
+            .chain((0..voices).map(Step::NoteOff))
+    }
+
+    /// Number of per-note pressure messages sent after a voice starts
+    fn pressure_steps(&self, voice: u8) -> u8 {
+        match self.poly_pressure {
+            Some(pressure) if voice == 0 => pressure.steps.get(),
+            _ => 0,
+        }
+    }
+
+    /// The step after a voice's NoteOn and any per-note pressure
+    fn after_note_on(&self, voice: u8) -> Step {
+        if voice + 1 < self.voices() {
+            self.note_start(voice + 1)
+        } else if self.hits > 1 {
+            Step::RepeatOff(1)
+        } else {
+            Step::NoteOff(0)
+        }
+    }
+
+    /// Frames from a voice's NoteOn until the step after it
+    fn note_on_delay(&self, voice: u8) -> usize {
+        match voice {
+            // hold until the next hit
+            _ if self.hits > 1 => self.hit_length(),
+            // hold until the next note starts
+            voice if voice + 1 < self.voices() => self.length,
+            // hold both notes at once
+            voice if voice > 0 => self.overlap,
+            _ => self.length,
+        }
+    }
+
+    /// Frames each hit of a burst is held for, apart from the last
+    fn hit_length(&self) -> usize {
+        self.length.min(self.burst_interval)
+    }
+
+    /// Frames between a step and the one after it
+    fn step_delay(&self, step: Step) -> usize {
+        match step {
+            // pressure messages divide up the hold
+            Step::NoteOn(voice) if self.pressure_steps(voice) > 0 => 0,
+            Step::NoteOn(voice) => self.note_on_delay(voice),
+            Step::PolyPressure(index) => {
+                let hold = self.note_on_delay(0);
+                let steps = usize::from(self.pressure_steps(0));
+                let index = usize::from(index);
+                hold * (index + 1) / steps - hold * index / steps
+            }
+            Step::RepeatOff(_) => self.burst_interval - self.hit_length(),
+            Step::RepeatOn(hit) if hit + 1 < self.hits => self.hit_length(),
+            Step::RepeatOn(_) => self.length,
+            // hold the next note for the rest of its length
+            Step::NoteOff(voice) if voice + 1 < self.voices() => self.length - self.overlap,
+            Step::NoteOff(_) => self.gap,
+            _ => 0,
+        }
+    }
+
+    /// Whether a note of the current zone is being held
+    ///
+    /// This is `false` in the gap between zones, once every note of the
+    /// previous zone has been released.
+    pub fn note_held(&self) -> bool {
+        match self.next_step {
+            Step::NoteOff(_) | Step::RepeatOff(_) | Step::RepeatOn(_) | Step::PolyPressure(_) => {
+                true
+            }
+            Step::MpePitchBend(voice)
+            | Step::MpePressure(voice)
+            | Step::TuningBend(voice)
+            | Step::NoteOn(voice) => voice > 0,
+            _ => false,
+        }
+    }
+
+    /// Number of channels in use by the sequence
+    fn channels_in_use(&self) -> usize {
+        match (&self.mpe, &self.round_robin_channels) {
+            (Some(mpe), _) => 1 + usize::from(mpe.member_channels.get()),
+            (None, Some(channels)) => channels.len(),
+            (None, None) => 1,
+        }
+    }
+
+    /// Get the `index`th channel in use by the sequence
+    fn channel_in_use(&self, index: usize) -> Option<Channel> {
+        match (&self.mpe, &self.round_robin_channels) {
+            (Some(mpe), _) if index == 0 => Some(mpe.master_channel()),
+            (Some(mpe), _) => (index <= usize::from(mpe.member_channels.get()))
+                .then(|| mpe.member_channel(index as u8 - 1)),
+            (None, Some(channels)) => channels.iter().nth(index),
+            (None, None) => (index == 0).then_some(self.channel),
+        }
+    }
+
+    /// Produce the pending event and prepare the one after it
+    fn step(&mut self) -> Option<Event> {
+        let event = match self.next_step {
+            // would start a zone outside the grid
+            Step::Reset(_)
+            | Step::Setting(_)
+            | Step::MpePitchBend(0)
+            | Step::MpePressure(0)
+            | Step::TuningBend(0)
+            | Step::NoteOn(0)
+                if self.position.is_none() =>
+            {
+                self.next_step = Step::Cleanup(0);
+                return self.step();
+            }
+            Step::Cleanup(idx) => {
+                let per_channel = self.cleanup.len();
+                let Some((channel, controller)) = idx
+                    .checked_div(per_channel)
+                    .and_then(|channel| self.channel_in_use(channel))
+                    .zip(self.cleanup.controller(idx % per_channel.max(1)))
+                else {
+                    self.next_step = Step::Complete;
+                    return None;
+                };
+
+                self.next_step = Step::Cleanup(idx + 1);
+
+                Event::ControlChange {
+                    channel,
+                    controller,
+                    value: 0,
+                }
+            }
+            Step::Complete => return None,
+            Step::MpeConfiguration(idx) => {
+                let mpe = self.mpe.as_ref()?;
+                let (controller, value) = mpe.configuration()[idx];
+                let channel = mpe.master_channel();
+
+                self.next_step = if idx + 1 < Mpe::CONFIGURATION_LEN {
+                    Step::MpeConfiguration(idx + 1)
+                } else {
+                    self.samples_remaining = self.start_offset();
+                    self.zone_start()
+                };
+
+                Event::ControlChange {
+                    channel,
+                    controller,
+                    value,
+                }
+            }
+            Step::Reset(idx) => {
+                let per_channel = self.zone_reset.len();
+                let (channel, controller) = self
+                    .channel_in_use(idx / per_channel)
+                    .zip(self.zone_reset.controller(idx % per_channel))?;
+
+                self.next_step = if idx + 1 < per_channel * self.channels_in_use() {
+                    Step::Reset(idx + 1)
+                } else {
+                    self.settings_start()
+                };
+
+                Event::ControlChange {
+                    channel,
+                    controller,
+                    value: 0,
+                }
+            }
+            Step::Setting(idx) => {
+                let event = self.setting_events().nth(idx)?;
+                let more = self.setting_events().nth(idx + 1).is_some();
+
+                self.next_step = if more {
+                    Step::Setting(idx + 1)
+                } else {
+                    self.note_start(0)
+                };
+
+                event
+            }
+            Step::MpePitchBend(voice) => {
+                let mpe = self.mpe.as_ref()?;
+                self.next_step = Step::MpePressure(voice);
+
+                Event::PitchBend {
+                    channel: self.note_channel(voice),
+                    bend: self.tuning_bend(voice).unwrap_or(mpe.pitch_bend),
+                }
+            }
+            Step::TuningBend(voice) => {
+                let bend = self.tuning_bend(voice)?;
+                self.next_step = Step::NoteOn(voice);
+
+                Event::PitchBend {
+                    channel: self.note_channel(voice),
+                    bend,
+                }
+            }
+            Step::MpePressure(voice) => {
+                let mpe = self.mpe.as_ref()?;
+                self.next_step = Step::NoteOn(voice);
+
+                Event::ChannelPressure {
+                    channel: self.note_channel(voice),
+                    pressure: mpe.pressure,
+                }
+            }
+            // begin note
+            Step::NoteOn(voice) => {
+                self.samples_remaining = self.step_delay(Step::NoteOn(voice));
+                self.next_step = if self.pressure_steps(voice) > 0 {
+                    Step::PolyPressure(0)
+                } else {
+                    self.after_note_on(voice)
+                };
+
+                Event::Note(self.note(NoteState::On, voice))
+            }
+            Step::PolyPressure(index) => {
+                let pressure = self.poly_pressure?;
+                self.samples_remaining = self.step_delay(Step::PolyPressure(index));
+                self.next_step = if index + 1 < self.pressure_steps(0) {
+                    Step::PolyPressure(index + 1)
+                } else {
+                    self.after_note_on(0)
+                };
+
+                Event::PolyPressure {
+                    channel: self.note_channel(0),
+                    pitch: Pitch(self.voice_pitch(0)),
+                    pressure: pressure.value(index),
+                }
+            }
+            // repeat note
+            Step::RepeatOff(hit) => {
+                self.samples_remaining = self.step_delay(Step::RepeatOff(hit));
+                self.next_step = Step::RepeatOn(hit);
+
+                Event::Note(self.note(NoteState::Off, 0))
+            }
+            Step::RepeatOn(hit) => {
+                self.samples_remaining = self.step_delay(Step::RepeatOn(hit));
+                self.next_step = if hit + 1 < self.hits {
+                    Step::RepeatOff(hit + 1)
+                } else {
+                    Step::NoteOff(0)
+                };
+
+                Event::Note(self.note(NoteState::On, 0))
+            }
+            // end note
+            Step::NoteOff(voice) if voice + 1 < self.voices() => {
+                self.samples_remaining = self.step_delay(Step::NoteOff(voice));
+                self.next_step = Step::NoteOff(voice + 1);
+
+                Event::Note(self.note(NoteState::Off, voice))
+            }
+            // end zone
+            Step::NoteOff(voice) => {
+                let event = Event::Note(self.note(NoteState::Off, voice));
+
+                // shorten the gap by this zone's offset, to keep the next one on the grid
+                self.samples_remaining =
+                    self.step_delay(Step::NoteOff(voice)) - self.start_offset();
+
+                // prepare state for next note-on
+                self.member = self.member.wrapping_add(self.voices());
+
+                self.previous = self.position;
+                self.position = match self.skip.take() {
+                    Some(position) => Some(position),
+                    None => self
+                        .position
+                        .as_ref()
+                        .and_then(|position| self.grid.next(position)),
+                };
+                self.pass_skipped_note();
+
+                self.next_step = self.zone_start();
+                self.samples_remaining += self.start_offset();
+
+                event
+            }
+        };
+
+        Some(event)
+    }
+
+    /// Try to move forward, producing any events that will occur
     ///
     /// If an event is produced, the internal frame counter has only
-    /// advanced by its `sample_offset`.
+    /// advanced by its `position`. Several events may occur at the same
+    /// position, in which case the ones after the first are produced at
+    /// position `0` by subsequent calls.
+    ///
+    /// With a [clock](Config::clock) configured, the sequence begins with a
+    /// Start message and ends with a Stop message, and Timing Clock messages
+    /// are produced in between (before any other events at the same position).
     pub fn advance(&mut self, num_frames: usize) -> AdvanceResult {
+        if let Some(event) = self.advance_clock(num_frames) {
+            return event;
+        }
+
         match self.samples_remaining.checked_sub(num_frames) {
             None => {
-                let result = AdvanceResult::Event {
-                    position: core::mem::take(&mut self.samples_remaining),
-                    note: Note {
-                        pitch: self.pitch,
-                        velocity: self.velocity,
-                        state: self.next_status,
-                    },
-                };
+                let position = core::mem::take(&mut self.samples_remaining);
+                if let Some(clock) = &mut self.clock {
+                    clock.elapsed += position;
+                }
 
-                match self.next_status {
-                    // would start a note outside the range
-                    NoteState::On if self.pitch > self.final_pitch => {
-                        return AdvanceResult::SequenceComplete;
-                    }
-                    // begin note
-                    NoteState::On => {
-                        self.samples_remaining = self.length;
-                        self.next_status = NoteState::Off;
-                    }
-                    // end note
-                    NoteState::Off => {
-                        self.samples_remaining = self.gap;
-                        self.next_status = NoteState::On;
-
-                        // prepare state for next note-on
-                        self.round_robin += 1;
-                        if self.round_robin == self.round_robin_count {
-                            self.round_robin = 0;
-
-                            if let Some(next_velocity) =
-                                self.velocity.checked_sub(self.velocity_step)
-                            {
-                                self.velocity = next_velocity;
-                            } else {
-                                self.velocity = 127;
-                                self.pitch += self.pitch_step;
+                match self.step() {
+                    Some(event) => AdvanceResult::Event { position, event },
+                    None => match &mut self.clock {
+                        Some(clock) if !clock.stopped => {
+                            clock.stopped = true;
+                            AdvanceResult::Event {
+                                position,
+                                event: Event::Stop,
                             }
                         }
-                    }
+                        _ => AdvanceResult::SequenceComplete,
+                    },
                 }
-
-                result
             }
             Some(further) => {
                 self.samples_remaining = further;
+                if let Some(clock) = &mut self.clock {
+                    clock.elapsed += num_frames;
+                }
+
                 AdvanceResult::NoEventsInFrame
             }
         }
     }
+
+    /// Produce a clock event, if one is due before the next sequence event
+    fn advance_clock(&mut self, num_frames: usize) -> Option<AdvanceResult> {
+        let clock = self.clock.as_mut()?;
+
+        if !clock.started {
+            clock.started = true;
+            return Some(AdvanceResult::Event {
+                position: 0,
+                event: Event::Start,
+            });
+        }
+
+        if self.next_step == Step::Complete {
+            return None;
+        }
+
+        let position = clock
+            .tempo
+            .tick_frame(clock.ticks, self.sample_rate)
+            .saturating_sub(clock.elapsed);
+
+        if position >= num_frames || position > self.samples_remaining {
+            return None;
+        }
+
+        clock.ticks += 1;
+        clock.elapsed += position;
+        self.samples_remaining -= position;
+
+        Some(AdvanceResult::Event {
+            position,
+            event: Event::TimingClock,
+        })
+    }
+}
+
+/// Convert a span of time to a whole number of frames
+fn frames(duration: Duration, sample_rate: u32) -> usize {
+    ((duration * sample_rate).as_millis() / 1_000) as usize
 }
 
 impl IntoIterator for Sequencer {
-    type Item = (usize, Note);
+    type Item = (usize, Event);
     type IntoIter = SequencerIntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -217,7 +1782,7 @@ impl IntoIterator for Sequencer {
     }
 }
 
-/// An iterator that produces all note events in a [`Sequencer`]
+/// An iterator that produces all events in a [`Sequencer`]
 ///
 /// Events will be produced with a corresponding sample position, starting when
 /// the sequencer was converted into an iterator and never resetting.
@@ -237,29 +1802,36 @@ pub struct SequencerIntoIter {
 }
 
 impl Iterator for SequencerIntoIter {
-    type Item = (usize, Note);
+    type Item = (usize, Event);
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.sequencer.advance(usize::MAX) {
             AdvanceResult::SequenceComplete => None,
-            AdvanceResult::Event { position, note } => {
+            AdvanceResult::Event { position, event } => {
                 self.position = self.position.wrapping_add(position);
-                Some((self.position, note))
+                Some((self.position, event))
             }
             AdvanceResult::NoEventsInFrame => {
                 unreachable!(
                     "A {} with length {} samples was produced",
-                    match self.sequencer.next_status {
-                        NoteState::On => "gap",
-                        NoteState::Off => "note",
+                    match self.sequencer.next_step {
+                        Step::NoteOff(_) | Step::RepeatOn(_) => "note",
+                        _ => "gap",
                     },
                     usize::MAX
                 )
             }
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.sequencer.remaining_events();
+        (remaining, Some(remaining))
+    }
 }
 
+impl ExactSizeIterator for SequencerIntoIter {}
+
 /// The outcome of trying to advance the state of a [`Sequencer`]
 #[derive(Debug, PartialEq, Eq)]
 pub enum AdvanceResult {
@@ -271,8 +1843,8 @@ pub enum AdvanceResult {
         ///
         /// The [`Sequencer`]'s internal state has only been updated to this point.
         position: usize,
-        /// The note event
-        note: Note,
+        /// The MIDI event
+        event: Event,
     },
     /// No more events will be produced by this [`Sequencer`].
     SequenceComplete,
@@ -285,8 +1857,59 @@ pub enum SequencerError {
     StartNote(InvalidMidiNote),
     /// Invalid end of note range
     EndNote(InvalidMidiNote),
+    /// Start of note range is above its end
+    NoteRange {
+        /// First note of the range
+        start: u8,
+        /// Last note of the range
+        end: u8,
+    },
+    /// Step between notes would overflow past the end of the range
+    Step(NonZeroU8),
+    /// The list of notes to sample is empty
+    NoteList,
+    /// The list of zones to sample is empty
+    ZoneList,
+    /// A note has more velocity levels or round robins than the rest
+    NoteLayers {
+        /// The note
+        pitch: Pitch,
+        /// Its layers
+        layers: NoteLayers,
+    },
     /// Too many velocity levels
     VelocityLevels(u8),
+    /// Invalid top of velocity range
+    VelocityRange(InvalidDataByte),
+    /// Bottom of velocity range is above its top
+    VelocityOrder {
+        /// Lowest velocity of the range
+        low: u8,
+        /// Highest velocity of the range
+        high: u8,
+    },
+    /// Too many MPE member channels
+    MemberChannels(u8),
+    /// Invalid MPE per-note pressure
+    Pressure(InvalidDataByte),
+    /// No legato intervals to sample
+    LegatoIntervals,
+    /// Legato overlap is longer than the note length
+    LegatoOverlap(Duration),
+    /// Humanized timing is longer than half of the gap between notes
+    HumanizeTiming(Duration),
+    /// Invalid grid dimensions
+    Dimensions(DimensionError),
+    /// Bursts and legato transitions cannot be sampled together
+    BurstLegato,
+    /// Burst interval is zero
+    BurstInterval(Duration),
+    /// Invalid per-note pressure
+    PolyPressure(InvalidDataByte),
+    /// The channels to play round robins on are empty
+    RoundRobinChannels,
+    /// Round robins cannot take turns between channels with MPE
+    RoundRobinChannelsMpe,
 }
 
 impl core::fmt::Display for SequencerError {
@@ -294,12 +1917,104 @@ impl core::fmt::Display for SequencerError {
         match self {
             SequencerError::StartNote(e) => write!(f, "Invalid start of note range: {e}"),
             SequencerError::EndNote(e) => write!(f, "Invalid end of note range: {e}"),
+            SequencerError::NoteRange { start, end } => {
+                write!(f, "Start of note range {start} is above its end {end}")
+            }
+            SequencerError::Step(step) => write!(
+                f,
+                "Step of {step} semitones overflows past the end of the note range"
+            ),
+            SequencerError::NoteList => write!(f, "No notes were listed to sample"),
+            SequencerError::ZoneList => write!(f, "No zones were listed to sample"),
+            SequencerError::NoteLayers { pitch, layers } => write!(
+                f,
+                "Note {pitch} has {} velocity layers and {} round robins, \
+                more than the rest of the notes",
+                layers.velocity_levels, layers.round_robins
+            ),
             SequencerError::VelocityLevels(n) => {
-                write!(f, "Maximum 128 possible velocity layers, specified {n}")
+                write!(f, "Too many velocity layers ({n}) for the velocity range")
+            }
+            SequencerError::VelocityRange(e) => write!(f, "Invalid top of velocity range: {e}"),
+            SequencerError::VelocityOrder { low, high } => {
+                write!(f, "Bottom of velocity range {low} is above its top {high}")
+            }
+            SequencerError::MemberChannels(n) => {
+                write!(f, "Maximum 15 possible MPE member channels, specified {n}")
+            }
+            SequencerError::Pressure(e) => write!(f, "Invalid MPE per-note pressure: {e}"),
+            SequencerError::LegatoIntervals => write!(f, "No legato intervals were specified"),
+            SequencerError::LegatoOverlap(d) => {
+                write!(f, "Legato overlap of {d:?} is longer than the note length")
+            }
+            SequencerError::Dimensions(e) => write!(f, "Invalid grid dimensions: {e}"),
+            SequencerError::HumanizeTiming(d) => write!(
+                f,
+                "Humanized timing of {d:?} is longer than half of the gap between notes"
+            ),
+            SequencerError::BurstLegato => {
+                write!(f, "Bursts cannot be combined with legato transitions")
+            }
+            SequencerError::BurstInterval(d) => {
+                write!(f, "Burst interval of {d:?} must be longer than zero")
+            }
+            SequencerError::PolyPressure(e) => write!(f, "Invalid per-note pressure: {e}"),
+            SequencerError::RoundRobinChannels => {
+                write!(f, "No channels were given to play round robins on")
+            }
+            SequencerError::RoundRobinChannelsMpe => {
+                write!(
+                    f,
+                    "Round robins cannot take turns between channels with MPE"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for SequencerError {}
+
+/// A zone requested from [`Sequencer::skip_to`] is not part of the sampling grid
+#[derive(Debug)]
+pub enum SkipToError {
+    /// Pitch is outside the note range, or not on a step
+    Pitch(Pitch),
+    /// Velocity layer index is too large
+    VelocityLayer(u8),
+    /// Round robin index is too large
+    RoundRobin(u8),
+    /// The zone is left out of the [`Config::zone_list`]
+    Unlisted {
+        /// Pitch of the zone
+        pitch: Pitch,
+        /// Its velocity layer
+        velocity_layer: u8,
+        /// Its round robin
+        round_robin: u8,
+    },
+}
+
+impl core::fmt::Display for SkipToError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SkipToError::Pitch(p) => write!(f, "Pitch {p} is not sampled in this sequence"),
+            SkipToError::VelocityLayer(n) => {
+                write!(f, "Velocity layer {n} is not sampled in this sequence")
+            }
+            SkipToError::RoundRobin(n) => {
+                write!(f, "Round robin {n} is not sampled in this sequence")
             }
+            SkipToError::Unlisted {
+                pitch,
+                velocity_layer,
+                round_robin,
+            } => write!(
+                f,
+                "Velocity layer {velocity_layer}, round robin {round_robin} of {pitch} \
+                is not in the list of zones to sample"
+            ),
         }
     }
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for SequencerError {}
+impl core::error::Error for SkipToError {}
@@ -1,21 +1,32 @@
+use core::num::NonZeroU8;
+
+/// Byte stream encoding for serial (DIN) MIDI
+pub mod serial;
+/// MIDI Tuning Standard messages
+pub mod tuning;
+/// Universal MIDI Packet (MIDI 2.0) encoding
+pub mod ump;
+
 /// A note event
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Note {
     /// Pitch (as MIDI note number)
     pub(crate) pitch: u8,
-    /// Velocity (up to 127)
-    pub(crate) velocity: u8,
+    /// Velocity (at MIDI 2.0 resolution)
+    pub(crate) velocity: u16,
     /// Event type
     pub(crate) state: NoteState,
+    /// Channel the note is played on
+    pub(crate) channel: Channel,
 }
 
 impl Note {
     /// Format as a 3-byte MIDI message
-    pub fn as_midi_message(&self, channel: Channel) -> [u8; 3] {
+    pub fn as_midi_message(&self) -> [u8; 3] {
         [
-            self.state.as_midi_message(channel),
+            self.state.as_midi_message(self.channel),
             self.pitch,
-            self.velocity,
+            self.velocity(),
         ]
     }
 
@@ -24,8 +35,13 @@ impl Note {
         Pitch(self.pitch)
     }
 
-    /// Get the velocity of the note
+    /// Get the velocity of the note (up to 127)
     pub fn velocity(&self) -> u8 {
+        ump::scale_down_velocity(self.velocity)
+    }
+
+    /// Get the velocity of the note at MIDI 2.0 resolution (up to 65535)
+    pub fn velocity_16(&self) -> u16 {
         self.velocity
     }
 
@@ -33,6 +49,190 @@ impl Note {
     pub fn state(&self) -> NoteState {
         self.state
     }
+
+    /// Get the channel the note is played on
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+/// A MIDI event produced by a [`Sequencer`](crate::Sequencer)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A note starting or stopping
+    Note(Note),
+    /// A Control Change message
+    ControlChange {
+        /// Channel to send on
+        channel: Channel,
+        /// Controller number
+        controller: u8,
+        /// Controller value
+        value: u8,
+    },
+    /// A Pitch Bend message
+    PitchBend {
+        /// Channel to send on
+        channel: Channel,
+        /// Amount of bend
+        bend: PitchBend,
+    },
+    /// A Channel Pressure (aftertouch) message
+    ChannelPressure {
+        /// Channel to send on
+        channel: Channel,
+        /// Amount of pressure
+        pressure: u8,
+    },
+    /// A Polyphonic Key Pressure (per-note aftertouch) message
+    PolyPressure {
+        /// Channel to send on
+        channel: Channel,
+        /// Note the pressure applies to
+        pitch: Pitch,
+        /// Amount of pressure
+        pressure: u8,
+    },
+    /// A Program Change message
+    ProgramChange {
+        /// Channel to send on
+        channel: Channel,
+        /// Program number
+        program: u8,
+    },
+    /// A Timing Clock message, sent 24 times per quarter note
+    TimingClock,
+    /// A Start message, beginning playback of synced patterns
+    Start,
+    /// A Stop message, ending playback of synced patterns
+    Stop,
+}
+
+impl Event {
+    /// Format as a MIDI 1.0 message
+    pub fn as_midi_message(&self) -> Message {
+        match self {
+            Self::Note(note) => note.as_midi_message().into(),
+            Self::ControlChange {
+                channel,
+                controller,
+                value,
+            } => [0xB0 | channel.0, *controller, *value].into(),
+            Self::PitchBend { channel, bend } => channel.pitch_bend(*bend).into(),
+            Self::ChannelPressure { channel, pressure } => [0xD0 | channel.0, *pressure].into(),
+            Self::PolyPressure {
+                channel,
+                pitch,
+                pressure,
+            } => [0xA0 | channel.0, pitch.0, *pressure].into(),
+            Self::ProgramChange { channel, program } => [0xC0 | channel.0, *program].into(),
+            Self::TimingClock => [0xF8].into(),
+            Self::Start => [0xFA].into(),
+            Self::Stop => [0xFC].into(),
+        }
+    }
+
+    /// Get the channel the event is sent on
+    ///
+    /// Returns `None` for System Real Time messages, which apply to every channel.
+    pub fn channel(&self) -> Option<Channel> {
+        match self {
+            Self::Note(note) => Some(note.channel),
+            Self::ControlChange { channel, .. }
+            | Self::PitchBend { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::PolyPressure { channel, .. }
+            | Self::ProgramChange { channel, .. } => Some(*channel),
+            Self::TimingClock | Self::Start | Self::Stop => None,
+        }
+    }
+
+    /// Get the note information, if this is a note event
+    pub fn note(&self) -> Option<&Note> {
+        match self {
+            Self::Note(note) => Some(note),
+            _ => None,
+        }
+    }
+}
+
+/// A short MIDI 1.0 message (up to three bytes)
+///
+/// Dereferences to a byte slice of the appropriate length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    bytes: [u8; 3],
+    len: u8,
+}
+
+impl core::ops::Deref for Message {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.bytes[..usize::from(self.len)]
+    }
+}
+
+impl AsRef<[u8]> for Message {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Message {
+    /// The same message with its status byte left out
+    pub(crate) fn without_status(&self) -> Self {
+        let [_, first, second] = self.bytes;
+
+        Self {
+            bytes: [first, second, 0],
+            len: self.len.saturating_sub(1),
+        }
+    }
+}
+
+impl From<[u8; 1]> for Message {
+    fn from([status]: [u8; 1]) -> Self {
+        Self {
+            bytes: [status, 0, 0],
+            len: 1,
+        }
+    }
+}
+
+impl From<[u8; 2]> for Message {
+    fn from([status, data]: [u8; 2]) -> Self {
+        Self {
+            bytes: [status, data, 0],
+            len: 2,
+        }
+    }
+}
+
+impl From<[u8; 3]> for Message {
+    fn from(bytes: [u8; 3]) -> Self {
+        Self { bytes, len: 3 }
+    }
+}
+
+/// Message encoding used to communicate with an instrument
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// MIDI 1.0 byte stream, with 7-bit velocity
+    #[default]
+    Midi1,
+    /// MIDI 2.0 Universal MIDI Packets, with 16-bit velocity
+    Midi2,
+}
+
+impl Protocol {
+    /// Largest velocity value that can be expressed
+    pub const fn max_velocity(&self) -> u16 {
+        match self {
+            Self::Midi1 => 127,
+            Self::Midi2 => u16::MAX,
+        }
+    }
 }
 
 /// Type of note event
@@ -56,7 +256,7 @@ impl NoteState {
 }
 
 /// A MIDI channel
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Channel(u8);
 
 impl Channel {
@@ -74,18 +274,600 @@ impl Channel {
         self.0
     }
 
+    /// Produce a MIDI Control Change message on this instance's channel
+    pub fn control_change(&self, controller: u8, value: u8) -> Result<[u8; 3], InvalidDataByte> {
+        Ok([0xB0 | self.0, data_byte(controller)?, data_byte(value)?])
+    }
+
+    /// Produce a MIDI Pitch Bend message on this instance's channel
+    pub fn pitch_bend(&self, bend: PitchBend) -> [u8; 3] {
+        [0xE0 | self.0, bend.lsb(), bend.msb()]
+    }
+
+    /// Produce a MIDI Channel Pressure (aftertouch) message on this instance's channel
+    pub fn channel_pressure(&self, pressure: u8) -> Result<[u8; 2], InvalidDataByte> {
+        Ok([0xD0 | self.0, data_byte(pressure)?])
+    }
+
     /// Produce a MIDI "All Sound Off" message on this instance's channel
     pub fn all_sound_off(&self) -> [u8; 3] {
         [0xB0 | self.0, 120, 0]
     }
+
+    /// Produce a MIDI "Reset All Controllers" message on this instance's channel
+    pub fn reset_all_controllers(&self) -> [u8; 3] {
+        [0xB0 | self.0, 121, 0]
+    }
+
+    /// Produce a MIDI "All Notes Off" message on this instance's channel
+    pub fn all_notes_off(&self) -> [u8; 3] {
+        [0xB0 | self.0, 123, 0]
+    }
+
+    /// Produce the MIDI Control Change messages that set a parameter's value
+    ///
+    /// The parameter is selected, its value is sent with both Data Entry
+    /// controllers, and then the null parameter is selected so that stray
+    /// Data Entry messages cannot change it later.
+    pub fn parameter(
+        &self,
+        parameter: Parameter,
+        msb: u8,
+        lsb: u8,
+    ) -> Result<[[u8; 3]; 6], InvalidDataByte> {
+        let [select_msb, select_lsb] = parameter.controllers();
+        let [null_msb, null_lsb] = Parameter::NULL.controllers();
+
+        Ok([
+            [0xB0 | self.0, select_msb, parameter.msb],
+            [0xB0 | self.0, select_lsb, parameter.lsb],
+            self.control_change(6, msb)?,
+            self.control_change(38, lsb)?,
+            [0xB0 | self.0, null_msb, 0x7F],
+            [0xB0 | self.0, null_lsb, 0x7F],
+        ])
+    }
+
+    /// Produce the MIDI messages that set the pitch bend range (RPN 0)
+    ///
+    /// The range applies in each direction from the center.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use autosam::midi::Channel;
+    /// let messages = Channel::default().pitch_bend_range(2, 0).unwrap();
+    /// assert_eq!(messages[..4], [[0xB0, 101, 0], [0xB0, 100, 0], [0xB0, 6, 2], [0xB0, 38, 0]]);
+    /// ```
+    pub fn pitch_bend_range(
+        &self,
+        semitones: u8,
+        cents: u8,
+    ) -> Result<[[u8; 3]; 6], InvalidDataByte> {
+        self.parameter(Parameter::PITCH_BEND_SENSITIVITY, semitones, cents)
+    }
+}
+
+/// Channels that round robins are played on in turn
+///
+/// Each channel is only listed once, so there can be up to 16.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::{Channel, ChannelRotation};
+/// let parts = ChannelRotation::new()
+///     .with(Channel::new(2).unwrap())
+///     .with(Channel::new(0).unwrap());
+/// assert!(parts.iter().map(|c| c.number()).eq([2, 0]));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelRotation {
+    channels: [Channel; 16],
+    len: u8,
+}
+
+impl ChannelRotation {
+    /// Create an empty rotation
+    pub const fn new() -> Self {
+        Self {
+            channels: [Channel(0); 16],
+            len: 0,
+        }
+    }
+
+    /// Add a channel to the end of the rotation, unless it is already in it
+    pub fn with(mut self, channel: Channel) -> Self {
+        if !self.iter().any(|c| c == channel) {
+            self.channels[usize::from(self.len)] = channel;
+            self.len += 1;
+        }
+
+        self
+    }
+
+    /// Number of channels in the rotation
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Whether the rotation is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the `index`th channel, going round again after the last
+    pub fn get(&self, index: usize) -> Option<Channel> {
+        let channels = &self.channels[..self.len()];
+        channels.get(index.checked_rem(channels.len())?).copied()
+    }
+
+    /// Iterate over the channels in order
+    pub fn iter(&self) -> impl Iterator<Item = Channel> + '_ {
+        self.channels[..self.len()].iter().copied()
+    }
+}
+
+/// A bank and program to select
+///
+/// Expands to Bank Select (controllers 0 and 32, for the parts of the bank
+/// that are set) followed by Program Change.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::*;
+/// let patch = PatchSelect::new(5).unwrap().with_bank(1, 2).unwrap();
+/// let channel = Channel::default();
+///
+/// assert!(patch.events(channel).eq([
+///     Event::ControlChange { channel, controller: 0, value: 1 },
+///     Event::ControlChange { channel, controller: 32, value: 2 },
+///     Event::ProgramChange { channel, program: 5 },
+/// ]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatchSelect {
+    bank_msb: Option<u8>,
+    bank_lsb: Option<u8>,
+    program: u8,
+}
+
+impl PatchSelect {
+    /// Select a program in the current bank
+    pub fn new(program: u8) -> Result<Self, InvalidDataByte> {
+        Ok(Self {
+            bank_msb: None,
+            bank_lsb: None,
+            program: data_byte(program)?,
+        })
+    }
+
+    /// Select both parts of the bank
+    pub fn with_bank(self, msb: u8, lsb: u8) -> Result<Self, InvalidDataByte> {
+        self.with_bank_msb(msb)?.with_bank_lsb(lsb)
+    }
+
+    /// Select the coarse part of the bank (controller 0)
+    pub fn with_bank_msb(self, msb: u8) -> Result<Self, InvalidDataByte> {
+        Ok(Self {
+            bank_msb: Some(data_byte(msb)?),
+            ..self
+        })
+    }
+
+    /// Select the fine part of the bank (controller 32)
+    pub fn with_bank_lsb(self, lsb: u8) -> Result<Self, InvalidDataByte> {
+        Ok(Self {
+            bank_lsb: Some(data_byte(lsb)?),
+            ..self
+        })
+    }
+
+    /// The coarse part of the bank, if selected
+    pub fn bank_msb(&self) -> Option<u8> {
+        self.bank_msb
+    }
+
+    /// The fine part of the bank, if selected
+    pub fn bank_lsb(&self) -> Option<u8> {
+        self.bank_lsb
+    }
+
+    /// The program number
+    pub fn program(&self) -> u8 {
+        self.program
+    }
+
+    /// The events that select this patch, in order
+    pub fn events(&self, channel: Channel) -> impl Iterator<Item = Event> {
+        let bank = |controller, value: Option<u8>| {
+            value.map(|value| Event::ControlChange {
+                channel,
+                controller,
+                value,
+            })
+        };
+
+        bank(0, self.bank_msb)
+            .into_iter()
+            .chain(bank(32, self.bank_lsb))
+            .chain(core::iter::once(Event::ProgramChange {
+                channel,
+                program: self.program,
+            }))
+    }
+}
+
+/// A Registered (RPN) or Non-Registered (NRPN) Parameter Number
+///
+/// Parameters are selected with a pair of controllers and then set through
+/// the Data Entry controllers. See [`Channel::parameter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameter {
+    registered: bool,
+    msb: u8,
+    lsb: u8,
+}
+
+impl Parameter {
+    /// Pitch bend range, in semitones (MSB) and cents (LSB)
+    pub const PITCH_BEND_SENSITIVITY: Self = Self::rpn(0, 0);
+    /// Fine tuning, centered on `[0x40, 0x00]`
+    pub const FINE_TUNING: Self = Self::rpn(0, 1);
+    /// Coarse tuning in semitones (MSB), centered on `0x40`
+    pub const COARSE_TUNING: Self = Self::rpn(0, 2);
+    /// MIDI Tuning Standard program (MSB)
+    pub const TUNING_PROGRAM: Self = Self::rpn(0, 3);
+    /// MIDI Tuning Standard bank (MSB)
+    pub const TUNING_BANK: Self = Self::rpn(0, 4);
+    /// Deselects any parameter
+    pub const NULL: Self = Self::rpn(0x7F, 0x7F);
+
+    const fn rpn(msb: u8, lsb: u8) -> Self {
+        Self {
+            registered: true,
+            msb,
+            lsb,
+        }
+    }
+
+    /// A Registered Parameter Number, from its two halves
+    pub fn registered(msb: u8, lsb: u8) -> Result<Self, InvalidDataByte> {
+        Ok(Self::rpn(data_byte(msb)?, data_byte(lsb)?))
+    }
+
+    /// A Non-Registered Parameter Number, from its two halves
+    pub fn non_registered(msb: u8, lsb: u8) -> Result<Self, InvalidDataByte> {
+        Ok(Self {
+            registered: false,
+            msb: data_byte(msb)?,
+            lsb: data_byte(lsb)?,
+        })
+    }
+
+    /// Whether this is a Registered Parameter Number
+    pub fn is_registered(&self) -> bool {
+        self.registered
+    }
+
+    /// The parameter number's two halves
+    pub fn number(&self) -> [u8; 2] {
+        [self.msb, self.lsb]
+    }
+
+    /// Controllers that select the MSB and LSB of the parameter number
+    fn controllers(&self) -> [u8; 2] {
+        if self.registered {
+            [101, 100]
+        } else {
+            [99, 98]
+        }
+    }
+}
+
+/// Layout of a MIDI Polyphonic Expression (MPE) zone
+///
+/// Each note is played on its own member channel, preceded by its per-note
+/// pitch bend and pressure. The zone is announced at the start of the sequence
+/// with an MPE Configuration Message on the master channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mpe {
+    /// Which end of the channel range the zone occupies
+    pub zone: MpeZone,
+    /// Number of member channels in the zone (up to 15)
+    pub member_channels: NonZeroU8,
+    /// Pitch bend sent on a note's member channel before it starts
+    pub pitch_bend: PitchBend,
+    /// Channel pressure sent on a note's member channel before it starts
+    pub pressure: u8,
+}
+
+impl Default for Mpe {
+    fn default() -> Self {
+        Self {
+            zone: MpeZone::Lower,
+            member_channels: NonZeroU8::new(15).unwrap(),
+            pitch_bend: PitchBend::CENTER,
+            pressure: 0,
+        }
+    }
+}
+
+impl Mpe {
+    pub(crate) const CONFIGURATION_LEN: usize = 3;
+
+    pub(crate) fn validate(&self) -> Result<(), crate::SequencerError> {
+        if self.member_channels.get() > 15 {
+            return Err(crate::SequencerError::MemberChannels(
+                self.member_channels.get(),
+            ));
+        }
+
+        data_byte(self.pressure).map_err(crate::SequencerError::Pressure)?;
+
+        Ok(())
+    }
+
+    /// The channel that carries zone-wide messages
+    pub fn master_channel(&self) -> Channel {
+        match self.zone {
+            MpeZone::Lower => Channel(0),
+            MpeZone::Upper => Channel(15),
+        }
+    }
+
+    /// Get a member channel, counting inward from the master channel
+    ///
+    /// Indices beyond the number of member channels wrap around.
+    pub fn member_channel(&self, index: u8) -> Channel {
+        let offset = index % self.member_channels.get().min(15);
+
+        match self.zone {
+            MpeZone::Lower => Channel(1 + offset),
+            MpeZone::Upper => Channel(14 - offset),
+        }
+    }
+
+    /// Controller number and value pairs making up the MPE Configuration Message
+    ///
+    /// Sent on the master channel, this sets RPN 6 to the member channel count.
+    pub fn configuration(&self) -> [(u8, u8); Self::CONFIGURATION_LEN] {
+        [(101, 0), (100, 6), (6, self.member_channels.get())]
+    }
+}
+
+/// Position of an MPE zone in the channel range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpeZone {
+    /// Master channel 1, member channels counting up from 2
+    Lower,
+    /// Master channel 16, member channels counting down from 15
+    Upper,
+}
+
+/// A set of signed intervals, in semitones
+///
+/// Can hold any interval from -64 to 63, and iterates in ascending order.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::Intervals;
+/// let fifths = Intervals::new().with(-7).unwrap().with(7).unwrap();
+/// assert!(fifths.iter().eq([-7, 7]));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Intervals(u128);
+
+impl Intervals {
+    /// Smallest interval that can be stored
+    pub const MIN: i8 = -64;
+    /// Largest interval that can be stored
+    pub const MAX: i8 = 63;
+
+    /// The set containing only zero
+    pub const UNISON: Self = Self(1 << 64);
+
+    /// Create an empty set
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Add an interval to the set
+    pub const fn with(self, semitones: i8) -> Result<Self, InvalidInterval> {
+        if semitones < Self::MIN || semitones > Self::MAX {
+            return Err(InvalidInterval(semitones));
+        }
+
+        Ok(Self(self.0 | 1 << (semitones as i16 - Self::MIN as i16)))
+    }
+
+    /// Check whether an interval is in the set
+    pub fn contains(&self, semitones: i8) -> bool {
+        (Self::MIN..=Self::MAX).contains(&semitones)
+            && self.0 & 1 << (i16::from(semitones) - i16::from(Self::MIN)) != 0
+    }
+
+    /// Number of intervals in the set
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterate over the intervals in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = i8> {
+        let bits = self.0;
+        (Self::MIN..=Self::MAX)
+            .filter(move |i| bits & 1 << (i16::from(*i) - i16::from(Self::MIN)) != 0)
+    }
 }
 
+/// An interval outside of the range that [`Intervals`] can store was provided
+#[derive(Debug)]
+pub struct InvalidInterval(i8);
+
+impl InvalidInterval {
+    /// Get the interval that was out of range
+    pub fn value(&self) -> i8 {
+        self.0
+    }
+}
+
+impl core::fmt::Display for InvalidInterval {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Interval {} is outside of the range {} to {}.",
+            self.0,
+            Intervals::MIN,
+            Intervals::MAX
+        )
+    }
+}
+
+impl core::error::Error for InvalidInterval {}
+
 /// A MIDI channel greater than 15 was provided
 pub type InvalidMidiChannel = crate::util::OutOfBounds<15>;
 
 /// A MIDI note number greater than 127 was provided
 pub type InvalidMidiNote = crate::util::OutOfBounds<127>;
 
+/// A MIDI data byte (controller number, value, etc.) greater than 127 was provided
+pub type InvalidDataByte = crate::util::OutOfBounds<127>;
+
+pub(crate) const fn data_byte(value: u8) -> Result<u8, InvalidDataByte> {
+    if value > 127 {
+        return Err(InvalidDataByte::new(value));
+    }
+
+    Ok(value)
+}
+
+/// A 14-bit MIDI pitch bend amount
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::*;
+/// let channel = Channel::new(2).unwrap();
+/// assert_eq!(channel.pitch_bend(PitchBend::CENTER), [0xE2, 0x00, 0x40]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PitchBend(u16);
+
+impl PitchBend {
+    /// Maximum downward bend
+    pub const MIN: Self = Self(0);
+    /// No bend
+    pub const CENTER: Self = Self(0x2000);
+    /// Maximum upward bend
+    pub const MAX: Self = Self(0x3FFF);
+
+    /// Create and validate a pitch bend amount
+    pub const fn new(value: u16) -> Result<Self, InvalidPitchBend> {
+        if value > Self::MAX.0 {
+            return Err(InvalidPitchBend(value));
+        }
+
+        Ok(Self(value))
+    }
+
+    /// Get the inner 14-bit value
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+
+    fn lsb(&self) -> u8 {
+        (self.0 & 0x7F) as u8
+    }
+
+    fn msb(&self) -> u8 {
+        (self.0 >> 7) as u8
+    }
+}
+
+impl Default for PitchBend {
+    fn default() -> Self {
+        Self::CENTER
+    }
+}
+
+/// A pitch bend value larger than 14 bits was provided
+#[derive(Debug)]
+pub struct InvalidPitchBend(u16);
+
+impl InvalidPitchBend {
+    /// Get the value that was out of range
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+}
+
+impl core::fmt::Display for InvalidPitchBend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Pitch bend value {} is larger than maximum {}.",
+            self.0,
+            PitchBend::MAX.0
+        )
+    }
+}
+
+impl core::error::Error for InvalidPitchBend {}
+
+/// A System Exclusive message
+///
+/// Wraps a payload of 7-bit data bytes, and produces it framed between the
+/// `0xF0` start and `0xF7` end markers.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::SysEx;
+/// // GM System On
+/// let sysex = SysEx::new(&[0x7E, 0x7F, 0x09, 0x01]).unwrap();
+/// assert!(sysex.bytes().eq([0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SysEx<'a>(&'a [u8]);
+
+impl<'a> SysEx<'a> {
+    /// Create and validate a SysEx message from its payload
+    ///
+    /// The payload should not include the start and end markers.
+    pub fn new(payload: &'a [u8]) -> Result<Self, InvalidDataByte> {
+        for byte in payload {
+            data_byte(*byte)?;
+        }
+
+        Ok(Self(payload))
+    }
+
+    /// Get the payload (without framing)
+    pub fn payload(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Length of the message in bytes, including the start and end markers
+    pub fn framed_len(&self) -> usize {
+        self.0.len() + 2
+    }
+
+    /// Produce the bytes of the framed message
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + 'a {
+        core::iter::once(0xF0)
+            .chain(self.0.iter().copied())
+            .chain(core::iter::once(0xF7))
+    }
+}
+
 /// A MIDI pitch value
 ///
 /// Implements [`Display`] as its note name.
@@ -99,7 +881,7 @@ pub type InvalidMidiNote = crate::util::OutOfBounds<127>;
 ///
 /// [`Display`]: core::fmt::Display
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Pitch(u8);
+pub struct Pitch(pub(crate) u8);
 
 impl Pitch {
     /// Create and validate a MIDI pitch value
@@ -209,5 +991,4 @@ impl core::fmt::Display for ParsePitchError {
     }
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for ParsePitchError {}
+impl core::error::Error for ParsePitchError {}
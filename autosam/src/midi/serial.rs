@@ -0,0 +1,99 @@
+use super::{Event, Message, Note, NoteState};
+
+/// Converts events to the bytes sent over a serial MIDI connection
+///
+/// With running status enabled, a message's status byte is left out when it
+/// matches the previous one. System Real Time messages can be sent in between
+/// without interrupting running status.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::{*, serial::Encoder};
+/// let mut encoder = Encoder::new().running_status(true);
+/// let channel = Channel::default();
+///
+/// let events = [
+///     Event::ControlChange { channel, controller: 7, value: 100 },
+///     Event::TimingClock,
+///     Event::ControlChange { channel, controller: 10, value: 64 },
+/// ];
+///
+/// assert!(encoder.encode_all(events).eq([0xB0, 7, 100, 0xF8, 10, 64]));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Encoder {
+    running_status: bool,
+    note_off_as_note_on: bool,
+    status: Option<u8>,
+}
+
+impl Encoder {
+    /// Create an encoder that always sends status bytes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave out status bytes that repeat the previous one
+    pub fn running_status(mut self, enabled: bool) -> Self {
+        self.running_status = enabled;
+        self
+    }
+
+    /// Send NoteOff as NoteOn with zero velocity, so notes can share running status
+    ///
+    /// The release velocity is lost.
+    pub fn note_off_as_note_on(mut self, enabled: bool) -> Self {
+        self.note_off_as_note_on = enabled;
+        self
+    }
+
+    /// Forget the running status, so that the next message includes its status byte
+    ///
+    /// Useful to resynchronize a receiver, e.g. after reconnecting.
+    pub fn reset(&mut self) {
+        self.status = None;
+    }
+
+    /// Get the bytes to send for an event
+    pub fn encode(&mut self, event: &Event) -> Message {
+        let message = match event {
+            Event::Note(
+                note @ Note {
+                    state: NoteState::Off,
+                    ..
+                },
+            ) if self.note_off_as_note_on => [0x90 | note.channel.0, note.pitch, 0].into(),
+            _ => event.as_midi_message(),
+        };
+
+        let status = message[0];
+
+        // real-time messages can appear anywhere, and leave running status alone
+        if status >= 0xF8 {
+            return message;
+        }
+
+        if !self.running_status {
+            return message;
+        }
+
+        if self.status == Some(status) {
+            return message.without_status();
+        }
+
+        self.status = Some(status);
+        message
+    }
+
+    /// Get the bytes to send for a series of events
+    pub fn encode_all<'a>(
+        &'a mut self,
+        events: impl IntoIterator<Item = Event> + 'a,
+    ) -> impl Iterator<Item = u8> + 'a {
+        events.into_iter().flat_map(move |event| {
+            let message = self.encode(&event);
+            (0..message.len()).map(move |i| message[i])
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use super::{data_byte, InvalidDataByte, Pitch};
+
+/// Sub-ID marking a MIDI Tuning Standard message
+const MIDI_TUNING: u8 = 0x08;
+
+/// Sub-ID of a real-time Single Note Tuning Change
+const SINGLE_NOTE_TUNING_CHANGE: u8 = 0x02;
+
+/// Device ID that addresses every device
+pub const ALL_DEVICES: u8 = 0x7F;
+
+/// A frequency in MIDI Tuning Standard format
+///
+/// Made up of an equal-tempered semitone and a 14-bit fraction of the
+/// distance to the next one.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::{Pitch, tuning::Tuning};
+/// let quarter_sharp = Tuning::from_cents(6050.0).unwrap();
+/// assert_eq!(quarter_sharp.semitone(), Pitch::new(60).unwrap());
+/// assert_eq!(quarter_sharp.fraction(), 0x2000);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    semitone: u8,
+    fraction: u16,
+}
+
+impl Tuning {
+    /// Get an instance of [`Tuning`] from a distance above MIDI note 0
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frequency is below note 0 or not below note 127.
+    pub fn from_cents(cents: f64) -> Result<Self, InvalidTuning> {
+        if !(0.0..12_700.0).contains(&cents) {
+            return Err(InvalidTuning(cents));
+        }
+
+        let semitone = (cents / 100.0) as u8;
+        let fraction = ((cents / 100.0 - f64::from(semitone)) * 16_384.0 + 0.5) as u16;
+
+        // rounded up to the next semitone
+        if fraction > 0x3FFF {
+            return Ok(Self {
+                semitone: semitone + 1,
+                fraction: 0,
+            });
+        }
+
+        Ok(Self { semitone, fraction })
+    }
+
+    /// The equal-tempered semitone at or below this frequency
+    pub fn semitone(&self) -> Pitch {
+        Pitch(self.semitone)
+    }
+
+    /// Distance above the semitone, in units of 1/16384 of a semitone
+    pub fn fraction(&self) -> u16 {
+        self.fraction
+    }
+
+    /// Distance above MIDI note 0
+    pub fn cents(&self) -> f64 {
+        f64::from(self.semitone) * 100.0 + f64::from(self.fraction) * 100.0 / 16_384.0
+    }
+
+    /// The three data bytes representing this frequency
+    fn bytes(&self) -> [u8; 3] {
+        [
+            self.semitone,
+            (self.fraction >> 7) as u8,
+            (self.fraction & 0x7F) as u8,
+        ]
+    }
+}
+
+impl From<Pitch> for Tuning {
+    fn from(pitch: Pitch) -> Self {
+        Self {
+            semitone: pitch.0,
+            fraction: 0,
+        }
+    }
+}
+
+/// A frequency outside the range of the MIDI Tuning Standard was provided
+#[derive(Debug)]
+pub struct InvalidTuning(f64);
+
+impl InvalidTuning {
+    /// Get the frequency (in cents above MIDI note 0) that was out of range
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+impl core::fmt::Display for InvalidTuning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Tuning of {} cents is outside of the range 0 to 12700.",
+            self.0
+        )
+    }
+}
+
+impl core::error::Error for InvalidTuning {}
+
+/// Produce a real-time Single Note Tuning Change, retuning notes of a tuning program
+///
+/// The message is framed as a System Exclusive message. Up to 127 notes can
+/// be retuned at once.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::midi::{Pitch, tuning::*};
+/// let a4 = Pitch::new(69).unwrap();
+/// let changes = [(a4, Tuning::from(a4))];
+/// let message = single_note_tuning_change(ALL_DEVICES, 0, &changes).unwrap();
+///
+/// assert!(message.eq([0xF0, 0x7F, 0x7F, 0x08, 0x02, 0, 1, 69, 69, 0, 0, 0xF7]));
+/// ```
+pub fn single_note_tuning_change(
+    device: u8,
+    program: u8,
+    changes: &[(Pitch, Tuning)],
+) -> Result<impl Iterator<Item = u8> + '_, InvalidDataByte> {
+    let count = data_byte(u8::try_from(changes.len()).unwrap_or(u8::MAX))?;
+
+    let header = [
+        0xF0,
+        0x7F,
+        data_byte(device)?,
+        MIDI_TUNING,
+        SINGLE_NOTE_TUNING_CHANGE,
+        data_byte(program)?,
+        count,
+    ];
+
+    let changes = changes.iter().flat_map(|(pitch, tuning)| {
+        let [semitone, msb, lsb] = tuning.bytes();
+        [pitch.note_number(), semitone, msb, lsb]
+    });
+
+    Ok(header
+        .into_iter()
+        .chain(changes)
+        .chain(core::iter::once(0xF7)))
+}
@@ -0,0 +1,203 @@
+use super::{Channel, Event, Note, NoteState, Pitch};
+
+/// Message type nibble for System Real Time and System Common messages
+const SYSTEM: u32 = 0x1;
+
+/// Message type nibble for MIDI 2.0 Channel Voice messages
+const CHANNEL_VOICE: u32 = 0x4;
+
+/// A 64-bit MIDI 2.0 Channel Voice message
+///
+/// 32-bit messages only use the first word, leaving the second as `0`.
+pub type Packet = [u32; 2];
+
+/// A Universal MIDI Packet group
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Group(u8);
+
+impl Group {
+    /// Get an instance of [`Group`]
+    pub fn new(group: u8) -> Result<Self, InvalidGroup> {
+        if group > 15 {
+            return Err(InvalidGroup::new(group));
+        }
+
+        Ok(Self(group))
+    }
+
+    /// The group number (zero based)
+    pub fn number(&self) -> u8 {
+        self.0
+    }
+}
+
+/// A UMP group greater than 15 was provided
+pub type InvalidGroup = crate::util::OutOfBounds<15>;
+
+fn header(group: Group, status: u8, channel: Channel, index: u8, extra: u8) -> u32 {
+    CHANNEL_VOICE << 28
+        | u32::from(group.0) << 24
+        | u32::from(status) << 20
+        | u32::from(channel.number()) << 16
+        | u32::from(index) << 8
+        | u32::from(extra)
+}
+
+fn system(group: Group, status: u8) -> Packet {
+    [
+        SYSTEM << 28 | u32::from(group.0) << 24 | u32::from(status) << 16,
+        0,
+    ]
+}
+
+impl Note {
+    /// Format as a MIDI 2.0 Channel Voice message, with full velocity resolution
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use autosam::{*, midi::{*, ump::*}};
+    /// let config = Config { notes: 60..=60, protocol: Protocol::Midi2, ..Default::default() };
+    /// let mut sequencer = Sequencer::new(config, 48_000).unwrap();
+    ///
+    /// let AdvanceResult::Event { event: Event::Note(note), .. } = sequencer.advance(1) else { panic!() };
+    /// assert_eq!(note.as_ump(Group::default()), [0x4090_3C00, 0xFFFF_0000]);
+    /// ```
+    pub fn as_ump(&self, group: Group) -> Packet {
+        let status = match self.state {
+            NoteState::On => 0x9,
+            NoteState::Off => 0x8,
+        };
+
+        [
+            header(group, status, self.channel, self.pitch, 0),
+            u32::from(self.velocity) << 16,
+        ]
+    }
+}
+
+impl Event {
+    /// Format as a MIDI 2.0 Channel Voice message
+    ///
+    /// System Real Time messages are formatted as 32-bit System messages instead.
+    /// Values are translated to 32 bits using the MIDI 2.0 "min-center-max" scaling.
+    pub fn as_ump(&self, group: Group) -> Packet {
+        match self {
+            Self::Note(note) => note.as_ump(group),
+            Self::ControlChange {
+                channel,
+                controller,
+                value,
+            } => [
+                header(group, 0xB, *channel, *controller, 0),
+                scale_up(u32::from(*value), 7),
+            ],
+            Self::PitchBend { channel, bend } => [
+                header(group, 0xE, *channel, 0, 0),
+                scale_up(u32::from(bend.value()), 14),
+            ],
+            Self::ChannelPressure { channel, pressure } => [
+                header(group, 0xD, *channel, 0, 0),
+                scale_up(u32::from(*pressure), 7),
+            ],
+            Self::PolyPressure {
+                channel,
+                pitch,
+                pressure,
+            } => [
+                header(group, 0xA, *channel, pitch.note_number(), 0),
+                scale_up(u32::from(*pressure), 7),
+            ],
+            Self::ProgramChange { channel, program } => [
+                header(group, 0xC, *channel, 0, 0),
+                u32::from(*program) << 24,
+            ],
+            Self::TimingClock => system(group, 0xF8),
+            Self::Start => system(group, 0xFA),
+            Self::Stop => system(group, 0xFC),
+        }
+    }
+}
+
+/// Produce a Registered Per-Note Controller message
+pub fn registered_per_note_controller(
+    group: Group,
+    channel: Channel,
+    pitch: Pitch,
+    index: u8,
+    value: u32,
+) -> Packet {
+    [
+        header(group, 0x0, channel, pitch.note_number(), index),
+        value,
+    ]
+}
+
+/// Produce an Assignable Per-Note Controller message
+pub fn assignable_per_note_controller(
+    group: Group,
+    channel: Channel,
+    pitch: Pitch,
+    index: u8,
+    value: u32,
+) -> Packet {
+    [
+        header(group, 0x1, channel, pitch.note_number(), index),
+        value,
+    ]
+}
+
+/// Produce a Per-Note Pitch Bend message
+///
+/// The value is unsigned, with `0x8000_0000` representing no bend.
+pub fn per_note_pitch_bend(group: Group, channel: Channel, pitch: Pitch, value: u32) -> Packet {
+    [header(group, 0x6, channel, pitch.note_number(), 0), value]
+}
+
+/// Translate a value of the given bit width to 32 bits, using the MIDI 2.0 "min-center-max" scaling
+const fn scale_up(value: u32, bits: u32) -> u32 {
+    let scale_bits = 32 - bits;
+    let shifted = value << scale_bits;
+
+    if value <= 1 << (bits - 1) {
+        return shifted;
+    }
+
+    // repeat the lower bits of the source value to fill the lower bits
+    let repeat_bits = bits - 1;
+    let mut repeat = value & ((1 << repeat_bits) - 1);
+    if scale_bits > repeat_bits {
+        repeat <<= scale_bits - repeat_bits;
+    } else {
+        repeat >>= repeat_bits - scale_bits;
+    }
+
+    let mut scaled = shifted;
+    while repeat != 0 {
+        scaled |= repeat;
+        repeat >>= repeat_bits;
+    }
+
+    scaled
+}
+
+/// Translate a 7-bit velocity to 16 bits, using the MIDI 2.0 "min-center-max" scaling
+///
+/// Values are expanded such that scaling back down with [`scale_down_velocity`]
+/// is lossless.
+pub const fn scale_up_velocity(velocity: u8) -> u16 {
+    let shifted = (velocity as u16) << 9;
+
+    if velocity <= 64 {
+        return shifted;
+    }
+
+    // repeat the lower 6 bits of the source value to fill the lower bits
+    let repeat = (velocity as u16 & 0x3F) << 3;
+    shifted | repeat | repeat >> 6
+}
+
+/// Translate a 16-bit velocity to 7 bits
+pub const fn scale_down_velocity(velocity: u16) -> u8 {
+    (velocity >> 9) as u8
+}
@@ -0,0 +1,127 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_core::Stream;
+
+use crate::{midi::Event, AdvanceResult, Sequencer};
+
+/// Plays a [`Sequencer`] in real time, as a [`Stream`] of timestamped events
+///
+/// Waiting is left to a sleep function from the host's async runtime (e.g.
+/// `tokio::time::sleep` or `async_std::task::sleep`), so no threads are
+/// spawned. Each event is produced once its time has come, together with
+/// its time since the stream was first polled. Delays are measured from that
+/// start rather than from the previous event, so the timer's lateness does
+/// not accumulate over the sequence.
+///
+/// # Example
+///
+/// ```
+/// # use autosam::{*, stream::EventStream};
+/// # use core::time::Duration;
+/// # async fn play() {
+/// # let sleep = |_| core::future::ready(());
+/// let config = Config { notes: 48..=72, ..Default::default() };
+/// let sequencer = Sequencer::new(config, 48_000).unwrap();
+///
+/// // e.g. tokio::time::sleep
+/// let mut events = EventStream::new(sequencer, sleep);
+/// # let _: &mut dyn futures_core::Stream<Item = (Duration, midi::Event)> = &mut events;
+/// # }
+/// ```
+pub struct EventStream<S, F> {
+    sequencer: Sequencer,
+    sleep: S,
+    start: Option<Instant>,
+    frames: usize,
+    pending: Option<Event>,
+    delay: Option<Pin<Box<F>>>,
+}
+
+impl<S, F> EventStream<S, F>
+where
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    /// Play a sequence, waiting between events with the provided sleep function
+    pub fn new(sequencer: Sequencer, sleep: S) -> Self {
+        Self {
+            sequencer,
+            sleep,
+            start: None,
+            frames: 0,
+            pending: None,
+            delay: None,
+        }
+    }
+
+    /// The sequence being played
+    pub fn sequencer(&self) -> &Sequencer {
+        &self.sequencer
+    }
+
+    /// Stop playing, and get the sequence back
+    ///
+    /// An event that was waiting for its time to come is lost.
+    pub fn into_inner(self) -> Sequencer {
+        self.sequencer
+    }
+
+    /// Time from the start of the stream until the most recent event
+    fn elapsed(&self) -> Duration {
+        let nanos =
+            self.frames as u128 * 1_000_000_000 / u128::from(self.sequencer.sample_rate().max(1));
+        Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX))
+    }
+}
+
+impl<S, F> Stream for EventStream<S, F>
+where
+    S: FnMut(Duration) -> F + Unpin,
+    F: Future<Output = ()>,
+{
+    type Item = (Duration, Event);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let start = *this.start.get_or_insert_with(Instant::now);
+
+        loop {
+            if let Some(delay) = &mut this.delay {
+                if delay.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+
+                this.delay = None;
+            }
+
+            if let Some(event) = this.pending.take() {
+                return Poll::Ready(Some((this.elapsed(), event)));
+            }
+
+            match this.sequencer.advance(usize::MAX) {
+                AdvanceResult::Event { position, event } => {
+                    this.frames += position;
+                    this.pending = Some(event);
+
+                    let remaining =
+                        (start + this.elapsed()).saturating_duration_since(Instant::now());
+                    if !remaining.is_zero() {
+                        this.delay = Some(Box::pin((this.sleep)(remaining)));
+                    }
+                }
+                AdvanceResult::SequenceComplete => return Poll::Ready(None),
+                AdvanceResult::NoEventsInFrame => {}
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.sequencer.remaining_events() + usize::from(self.pending.is_some());
+        (remaining, Some(remaining))
+    }
+}
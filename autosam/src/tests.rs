@@ -17,11 +17,12 @@ fn one_note_sequence() {
         seq.advance(1),
         AdvanceResult::Event {
             position: 0,
-            note: Note {
+            event: Event::Note(Note {
                 pitch: 60,
-                velocity: 127,
-                state: NoteState::On
-            }
+                velocity: u16::MAX,
+                state: NoteState::On,
+                channel: Channel::default(),
+            })
         }
     );
 
@@ -29,11 +30,12 @@ fn one_note_sequence() {
         seq.advance(101),
         AdvanceResult::Event {
             position: 100,
-            note: Note {
+            event: Event::Note(Note {
                 pitch: 60,
-                velocity: 127,
-                state: NoteState::Off
-            }
+                velocity: u16::MAX,
+                state: NoteState::Off,
+                channel: Channel::default(),
+            })
         }
     );
 
@@ -57,11 +59,12 @@ fn octave_sequence() {
             seq.advance(1),
             AdvanceResult::Event {
                 position: 0,
-                note: Note {
+                event: Event::Note(Note {
                     pitch: octave * 12,
-                    velocity: 127,
-                    state: NoteState::On
-                }
+                    velocity: u16::MAX,
+                    state: NoteState::On,
+                    channel: Channel::default(),
+                })
             }
         );
 
@@ -69,11 +72,12 @@ fn octave_sequence() {
             seq.advance(101),
             AdvanceResult::Event {
                 position: 100,
-                note: Note {
+                event: Event::Note(Note {
                     pitch: octave * 12,
-                    velocity: 127,
-                    state: NoteState::Off
-                }
+                    velocity: u16::MAX,
+                    state: NoteState::Off,
+                    channel: Channel::default(),
+                })
             }
         );
 
@@ -97,33 +101,35 @@ fn velocity_layer_sequence() {
 
     let mut seq = Sequencer::new(cfg, 1000).unwrap();
 
-    let mut current_velocity = 128;
+    let mut current_velocity = u32::from(u16::MAX) + 1;
     for _layer in 0..5 {
         let AdvanceResult::Event {
             position: 0,
-            note: Note {
+            event: Event::Note(Note {
                 pitch: actual_pitch,
                 velocity,
-                state: NoteState::On
-            }
+                state: NoteState::On,
+                ..
+            })
         } = seq.advance(1) else {
             panic!("Expected a NoteOn event at position 0, found none.");
         };
 
         assert_eq!(actual_pitch, pitch);
-        assert!(velocity < current_velocity);
+        assert!(u32::from(velocity) < current_velocity);
 
-        current_velocity = velocity;
+        current_velocity = u32::from(velocity);
 
         assert_eq!(
             seq.advance(101),
             AdvanceResult::Event {
                 position: 100,
-                note: Note {
+                event: Event::Note(Note {
                     pitch,
-                    velocity: current_velocity,
-                    state: NoteState::Off
-                }
+                    velocity,
+                    state: NoteState::Off,
+                    channel: Channel::default(),
+                })
             }
         );
 
@@ -152,11 +158,12 @@ fn round_robin_sequence() {
             seq.advance(1),
             AdvanceResult::Event {
                 position: 0,
-                note: Note {
+                event: Event::Note(Note {
                     pitch,
-                    velocity: 127,
-                    state: NoteState::On
-                }
+                    velocity: u16::MAX,
+                    state: NoteState::On,
+                    channel: Channel::default(),
+                })
             }
         );
 
@@ -164,11 +171,12 @@ fn round_robin_sequence() {
             seq.advance(101),
             AdvanceResult::Event {
                 position: 100,
-                note: Note {
+                event: Event::Note(Note {
                     pitch,
-                    velocity: 127,
-                    state: NoteState::Off
-                }
+                    velocity: u16::MAX,
+                    state: NoteState::Off,
+                    channel: Channel::default(),
+                })
             }
         );
 
@@ -177,3 +185,1841 @@ fn round_robin_sequence() {
 
     assert_eq!(seq.advance(101), AdvanceResult::SequenceComplete);
 }
+
+#[test]
+fn channel_messages() {
+    let channel = midi::Channel::new(3).unwrap();
+
+    assert_eq!(channel.control_change(74, 64).unwrap(), [0xB3, 74, 64]);
+    assert!(channel.control_change(128, 0).is_err());
+    assert!(channel.control_change(0, 128).is_err());
+
+    assert_eq!(channel.pitch_bend(midi::PitchBend::MIN), [0xE3, 0x00, 0x00]);
+    assert_eq!(channel.pitch_bend(midi::PitchBend::MAX), [0xE3, 0x7F, 0x7F]);
+    assert_eq!(
+        channel.pitch_bend(midi::PitchBend::new(0x2001).unwrap()),
+        [0xE3, 0x01, 0x40]
+    );
+    assert!(midi::PitchBend::new(0x4000).is_err());
+
+    assert_eq!(channel.channel_pressure(100).unwrap(), [0xD3, 100]);
+    assert!(channel.channel_pressure(200).is_err());
+}
+
+#[test]
+fn sysex_framing() {
+    let sysex = midi::SysEx::new(&[0x43, 0x10, 0x7F]).unwrap();
+    assert_eq!(sysex.framed_len(), 5);
+    assert!(sysex.bytes().eq([0xF0, 0x43, 0x10, 0x7F, 0xF7]));
+
+    assert!(midi::SysEx::new(&[0x43, 0xF7]).is_err());
+    assert!(midi::SysEx::new(&[]).unwrap().bytes().eq([0xF0, 0xF7]));
+}
+
+#[test]
+fn midi2_velocity_grid() {
+    let cfg = Config {
+        notes: 60..=60,
+        velocity_levels: NonZeroU8::new(200).unwrap(),
+        protocol: Protocol::Midi2,
+        length: Duration::from_millis(1),
+        gap: Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    let mut previous = u32::from(u16::MAX) + 1;
+    let mut layers = 0;
+    for (_, event) in Sequencer::new(cfg.clone(), 1000).unwrap() {
+        if let Some(note) = event.note().filter(|n| n.state() == NoteState::On) {
+            assert!(u32::from(note.velocity_16()) < previous);
+            previous = u32::from(note.velocity_16());
+            layers += 1;
+        }
+    }
+
+    assert_eq!(layers, 200);
+
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                protocol: Protocol::Midi1,
+                ..cfg
+            },
+            1000
+        ),
+        Err(SequencerError::VelocityLevels(200))
+    ));
+}
+
+#[test]
+fn note_list_pitches() {
+    let list = dimension::Values::new()
+        .with(36)
+        .and_then(|v| v.with(38))
+        .and_then(|v| v.with(42))
+        .unwrap();
+    let cfg = Config {
+        notes: 36..=42,
+        note_list: Some(list),
+        length: Duration::from_millis(1),
+        gap: Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    let pitches = Sequencer::new(cfg.clone(), 1000)
+        .unwrap()
+        .into_iter()
+        .filter_map(|(_, event)| match event {
+            Event::Note(note) if note.state() == NoteState::On => Some(note.pitch().note_number()),
+            _ => None,
+        });
+    assert!(pitches.eq([36, 38, 42]));
+
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                note_list: Some(dimension::Values::new()),
+                ..cfg
+            },
+            1000
+        ),
+        Err(SequencerError::NoteList)
+    ));
+}
+
+#[test]
+fn zone_list_narrows_grid() {
+    let list = dimension::ZoneList::new()
+        .with(midi::Pitch::new(52).unwrap(), 0, 1)
+        .and_then(|l| l.with(midi::Pitch::new(48).unwrap(), 2, 0))
+        .and_then(|l| l.with(midi::Pitch::new(48).unwrap(), 2, 0))
+        .unwrap();
+    assert_eq!(list.len(), 2);
+
+    let cfg = Config {
+        notes: 48..=52,
+        step: NonZeroU8::new(4).unwrap(),
+        velocity_levels: NonZeroU8::new(3).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        zone_list: Some(list),
+        length: Duration::from_millis(1),
+        gap: Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert_eq!(seq.remaining_events(), 4);
+    assert!(matches!(
+        seq.skip_to(midi::Pitch::new(52).unwrap(), 0, 0),
+        Err(SkipToError::Unlisted { .. })
+    ));
+
+    // listed zones play in the order of the grid, not of the list
+    let mut zones = seq.into_iter().filter_map(|(_, event)| match event {
+        Event::Note(note) if note.state() == NoteState::On => {
+            Some((note.pitch().note_number(), note.velocity()))
+        }
+        _ => None,
+    });
+    let (pitch, velocity) = zones.next().unwrap();
+    assert_eq!(pitch, 48);
+    assert!(velocity < 64);
+    assert_eq!(zones.next(), Some((52, 127)));
+    assert_eq!(zones.next(), None);
+
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                zone_list: Some(dimension::ZoneList::new()),
+                ..cfg
+            },
+            1000
+        ),
+        Err(SequencerError::ZoneList)
+    ));
+}
+
+#[test]
+fn zone_list_holds_four_layers_of_every_note() {
+    let mut list = dimension::ZoneList::new();
+    for note in 0..128 {
+        for layer in 0..4 {
+            list = list
+                .with(midi::Pitch::new(note).unwrap(), layer, 0)
+                .unwrap();
+        }
+    }
+    assert_eq!(list.len(), dimension::ZoneList::CAPACITY);
+
+    // zones already listed still fit
+    let list = list.with(midi::Pitch::new(0).unwrap(), 0, 0).unwrap();
+    assert!(list.with(midi::Pitch::new(0).unwrap(), 4, 0).is_err());
+}
+
+#[test]
+fn note_layers_narrow_a_note() {
+    let mut note_layers = [None; 128];
+    note_layers[62] = Some(NoteLayers {
+        velocity_levels: NonZeroU8::new(1).unwrap(),
+        round_robins: NonZeroU8::new(1).unwrap(),
+    });
+    let cfg = Config {
+        notes: 60..=62,
+        step: NonZeroU8::new(2).unwrap(),
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        note_layers,
+        length: Duration::from_millis(1),
+        gap: Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    for (velocity_order, expected) in [
+        (
+            VelocityOrder::LoudToSoft,
+            [(60, 127), (60, 127), (60, 63), (60, 63), (62, 127)],
+        ),
+        (
+            VelocityOrder::SoftToLoud,
+            [(60, 63), (60, 63), (60, 127), (60, 127), (62, 127)],
+        ),
+    ] {
+        let seq = Sequencer::new(
+            Config {
+                velocity_order,
+                ..cfg.clone()
+            },
+            1000,
+        )
+        .unwrap();
+        assert_eq!(seq.remaining_events(), seq.clone().into_iter().count());
+
+        let notes = seq.into_iter().filter_map(|(_, event)| match event {
+            Event::Note(note) if note.state() == NoteState::On => {
+                Some((note.pitch().note_number(), note.velocity()))
+            }
+            _ => None,
+        });
+        assert!(notes.eq(expected));
+    }
+
+    let mut seq = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert!(matches!(
+        seq.skip_to(midi::Pitch::new(62).unwrap(), 1, 0),
+        Err(SkipToError::VelocityLayer(1))
+    ));
+
+    note_layers[62] = Some(NoteLayers {
+        velocity_levels: NonZeroU8::new(3).unwrap(),
+        round_robins: NonZeroU8::new(1).unwrap(),
+    });
+    assert!(matches!(
+        Sequencer::new(Config { note_layers, ..cfg }, 1000),
+        Err(SequencerError::NoteLayers { .. })
+    ));
+}
+
+#[test]
+fn velocity_range_layers() {
+    let cfg = Config {
+        notes: 60..=60,
+        velocity_levels: NonZeroU8::new(4).unwrap(),
+        velocity_range: 20..=127,
+        length: Duration::from_millis(1),
+        gap: Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    let velocities = Sequencer::new(cfg.clone(), 1000)
+        .unwrap()
+        .into_iter()
+        .filter_map(|(_, event)| match event {
+            Event::Note(note) if note.state() == NoteState::On => Some(note.velocity()),
+            _ => None,
+        });
+    assert!(velocities.eq([127, 100, 73, 46]));
+
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                velocity_range: core::ops::RangeInclusive::new(100, 90),
+                ..cfg.clone()
+            },
+            1000
+        ),
+        Err(SequencerError::VelocityOrder { low: 100, high: 90 })
+    ));
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                velocity_range: 125..=127,
+                ..cfg
+            },
+            1000
+        ),
+        Err(SequencerError::VelocityLevels(4))
+    ));
+}
+
+#[test]
+fn midi1_layer_velocities() {
+    let velocities = |levels: u8| {
+        let cfg = Config {
+            notes: 60..=60,
+            velocity_levels: NonZeroU8::new(levels).unwrap(),
+            length: Duration::from_millis(1),
+            gap: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        Sequencer::new(cfg, 1000)
+            .unwrap()
+            .into_iter()
+            .filter_map(|(_, event)| match event {
+                Event::Note(note) if note.state() == NoteState::On => Some(note.velocity()),
+                _ => None,
+            })
+    };
+
+    assert!(velocities(5).eq([127, 101, 75, 49, 23]));
+    // the step lands one above zero, which used to add an eighth layer
+    assert!(velocities(7).eq([127, 109, 91, 73, 55, 37, 19]));
+}
+
+#[test]
+fn velocity_scaling_round_trips() {
+    for velocity in 0..=127 {
+        let scaled = midi::ump::scale_up_velocity(velocity);
+        assert_eq!(midi::ump::scale_down_velocity(scaled), velocity);
+    }
+
+    assert_eq!(midi::ump::scale_up_velocity(0), 0);
+    assert_eq!(midi::ump::scale_up_velocity(64), 0x8000);
+    assert_eq!(midi::ump::scale_up_velocity(127), 0xFFFF);
+}
+
+#[test]
+fn mpe_sequence() {
+    let cfg = Config {
+        notes: 60..=62,
+        mpe: Some(midi::Mpe {
+            zone: midi::MpeZone::Upper,
+            member_channels: NonZeroU8::new(2).unwrap(),
+            pressure: 10,
+            ..Default::default()
+        }),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut events = Sequencer::new(cfg, 1000).unwrap().into_iter();
+
+    let master = Channel::new(15).unwrap();
+    for (controller, value) in [(101, 0), (100, 6), (6, 2)] {
+        assert_eq!(
+            events.next(),
+            Some((
+                0,
+                Event::ControlChange {
+                    channel: master,
+                    controller,
+                    value
+                }
+            ))
+        );
+    }
+
+    for (idx, (pitch, channel)) in [(60, 14), (61, 13), (62, 14)].into_iter().enumerate() {
+        let start = idx * 200;
+        let channel = Channel::new(channel).unwrap();
+
+        assert_eq!(
+            events.next(),
+            Some((
+                start,
+                Event::PitchBend {
+                    channel,
+                    bend: midi::PitchBend::CENTER
+                }
+            ))
+        );
+        assert_eq!(
+            events.next(),
+            Some((
+                start,
+                Event::ChannelPressure {
+                    channel,
+                    pressure: 10
+                }
+            ))
+        );
+
+        let (position, event) = events.next().unwrap();
+        assert_eq!(position, start);
+        assert_eq!(event.note().unwrap().pitch().note_number(), pitch);
+        assert_eq!(event.note().unwrap().state(), NoteState::On);
+        assert_eq!(event.channel(), Some(channel));
+
+        let (position, event) = events.next().unwrap();
+        assert_eq!(position, start + 100);
+        assert_eq!(event.note().unwrap().state(), NoteState::Off);
+        assert_eq!(event.channel(), Some(channel));
+    }
+
+    assert_eq!(events.next(), None);
+}
+
+#[test]
+fn cleanup_at_completion() {
+    let cfg = Config {
+        notes: 60..=60,
+        channel: Channel::new(4).unwrap(),
+        cleanup: Cleanup {
+            all_notes_off: true,
+            reset_all_controllers: true,
+            ..Default::default()
+        },
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+
+    assert!(matches!(
+        seq.advance(1),
+        AdvanceResult::Event { position: 0, .. }
+    ));
+    assert!(matches!(
+        seq.advance(101),
+        AdvanceResult::Event { position: 100, .. }
+    ));
+
+    for controller in [123, 121] {
+        assert_eq!(
+            seq.advance(101),
+            AdvanceResult::Event {
+                position: if controller == 123 { 100 } else { 0 },
+                event: Event::ControlChange {
+                    channel: Channel::new(4).unwrap(),
+                    controller,
+                    value: 0
+                }
+            }
+        );
+    }
+
+    assert_eq!(seq.advance(101), AdvanceResult::SequenceComplete);
+    assert_eq!(seq.advance(101), AdvanceResult::SequenceComplete);
+}
+
+#[test]
+fn mpe_cleanup_covers_zone() {
+    let cfg = Config {
+        notes: 60..=60,
+        mpe: Some(midi::Mpe {
+            member_channels: NonZeroU8::new(3).unwrap(),
+            ..Default::default()
+        }),
+        cleanup: Cleanup {
+            all_sound_off: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut channels = 0u16;
+    for (_, event) in Sequencer::new(cfg, 1000).unwrap() {
+        if let Event::ControlChange {
+            channel,
+            controller: 120,
+            ..
+        } = event
+        {
+            channels |= 1 << channel.number();
+        }
+    }
+
+    assert_eq!(channels, 0b1111);
+}
+
+#[test]
+fn round_robins_rotate_channels() {
+    let rotation = midi::ChannelRotation::new()
+        .with(Channel::new(2).unwrap())
+        .with(Channel::new(5).unwrap())
+        .with(Channel::new(2).unwrap());
+    assert_eq!(rotation.len(), 2);
+
+    let cfg = Config {
+        notes: 60..=61,
+        round_robins: NonZeroU8::new(3).unwrap(),
+        round_robin_channels: Some(rotation),
+        cleanup: Cleanup {
+            all_sound_off: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut notes = [None; 12];
+    let mut cleaned = 0u16;
+    let events = Sequencer::new(cfg.clone(), 1000).unwrap();
+    for (slot, (_, event)) in notes.iter_mut().zip(
+        events
+            .clone()
+            .into_iter()
+            .filter(|(_, e)| e.note().is_some()),
+    ) {
+        *slot = event
+            .note()
+            .map(|note| (note.state(), note.channel().number()));
+    }
+    for (_, event) in events {
+        if let Event::ControlChange {
+            channel,
+            controller: 120,
+            ..
+        } = event
+        {
+            cleaned |= 1 << channel.number();
+        }
+    }
+
+    // each note's On and Off share a channel, which starts over on the next pitch
+    let expected: [_; 12] = core::array::from_fn(|idx| {
+        let state = if idx % 2 == 0 {
+            NoteState::On
+        } else {
+            NoteState::Off
+        };
+        Some((state, [2, 5, 2][idx / 2 % 3]))
+    });
+    assert_eq!(notes, expected);
+    assert_eq!(cleaned, 1 << 2 | 1 << 5);
+
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                round_robin_channels: Some(midi::ChannelRotation::new()),
+                ..cfg.clone()
+            },
+            1000
+        ),
+        Err(SequencerError::RoundRobinChannels)
+    ));
+    assert!(matches!(
+        Sequencer::new(
+            Config {
+                mpe: Some(midi::Mpe::default()),
+                ..cfg
+            },
+            1000
+        ),
+        Err(SequencerError::RoundRobinChannelsMpe)
+    ));
+}
+
+#[test]
+fn zone_reset_follows_gap() {
+    let cfg = Config {
+        notes: 60..=61,
+        channel: Channel::new(2).unwrap(),
+        zone_reset: Cleanup {
+            all_sound_off: true,
+            reset_all_controllers: true,
+            ..Default::default()
+        },
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut iter = Sequencer::new(cfg, 1000).unwrap().into_iter();
+    let events: [_; 8] = core::array::from_fn(|_| iter.next().unwrap());
+    assert!(iter.next().is_none());
+    let reset = |position, controller| {
+        (
+            position,
+            Event::ControlChange {
+                channel: Channel::new(2).unwrap(),
+                controller,
+                value: 0,
+            },
+        )
+    };
+
+    // each zone's reset comes at its start, after the whole gap before it
+    assert_eq!(events[..2], [reset(0, 120), reset(0, 121)]);
+    assert_eq!(events[4..6], [reset(200, 120), reset(200, 121)]);
+    assert!(events[3]
+        .1
+        .note()
+        .is_some_and(|n| n.state() == NoteState::Off));
+    assert_eq!(events[6].0, 200);
+    assert_eq!(events[6].1.note().unwrap().pitch().note_number(), 61);
+}
+
+#[test]
+fn tuning_bends_each_note() {
+    let mut tuning = [PitchBend::CENTER; 128];
+    tuning[61] = PitchBend::new(0x2100).unwrap();
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        tuning: Some(tuning),
+        ..Default::default()
+    };
+
+    let mut iter = Sequencer::new(cfg.clone(), 1000).unwrap().into_iter();
+    let events: [_; 6] = core::array::from_fn(|_| iter.next().unwrap());
+    assert!(iter.next().is_none());
+
+    let channel = Channel::default();
+    assert_eq!(
+        events[0],
+        (
+            0,
+            Event::PitchBend {
+                channel,
+                bend: PitchBend::CENTER
+            }
+        )
+    );
+    assert_eq!(
+        events[3],
+        (
+            200,
+            Event::PitchBend {
+                channel,
+                bend: tuning[61]
+            }
+        )
+    );
+    assert_eq!(events[4].0, 200);
+    assert_eq!(events[4].1.note().unwrap().pitch().note_number(), 61);
+
+    // with MPE, the tuning takes the place of the per-note bend
+    let mpe = Config {
+        mpe: Some(Mpe::default()),
+        ..cfg
+    };
+    let bends = Sequencer::new(mpe, 1000)
+        .unwrap()
+        .into_iter()
+        .filter(|(_, event)| matches!(event, Event::PitchBend { .. }));
+    assert!(bends
+        .map(|(_, event)| event)
+        .eq([0, 1].map(|member| Event::PitchBend {
+            channel: Mpe::default().member_channel(member),
+            bend: tuning[60 + usize::from(member)]
+        })));
+}
+
+#[test]
+fn reset_restarts_sequence() {
+    let cfg = Config {
+        notes: 48..=50,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    let first = seq.advance(1);
+
+    // stop partway through a note, well into the sequence
+    for _ in 0..11 {
+        seq.advance(101);
+    }
+
+    seq.reset();
+    assert_eq!(seq.advance(1), first);
+}
+
+#[test]
+fn skip_to_zone() {
+    let cfg = Config {
+        notes: 48..=60,
+        step: NonZeroU8::new(4).unwrap(),
+        velocity_levels: NonZeroU8::new(3).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+
+    assert!(matches!(
+        seq.skip_to(midi::Pitch::new(50).unwrap(), 0, 0),
+        Err(SkipToError::Pitch(_))
+    ));
+    assert!(matches!(
+        seq.skip_to(midi::Pitch::new(64).unwrap(), 0, 0),
+        Err(SkipToError::Pitch(_))
+    ));
+    assert!(matches!(
+        seq.skip_to(midi::Pitch::new(52).unwrap(), 3, 0),
+        Err(SkipToError::VelocityLayer(3))
+    ));
+    assert!(matches!(
+        seq.skip_to(midi::Pitch::new(52).unwrap(), 0, 2),
+        Err(SkipToError::RoundRobin(2))
+    ));
+
+    // skipping while a note is held lets it finish first
+    let AdvanceResult::Event { event: first, .. } = seq.advance(1) else {
+        panic!("Expected the first NoteOn");
+    };
+    seq.skip_to(midi::Pitch::new(56).unwrap(), 2, 1).unwrap();
+
+    let AdvanceResult::Event { event, .. } = seq.advance(101) else {
+        panic!("Expected the first NoteOff");
+    };
+    assert_eq!(event.note().unwrap().pitch(), first.note().unwrap().pitch());
+    assert_eq!(event.note().unwrap().state(), NoteState::Off);
+
+    let AdvanceResult::Event { position, event } = seq.advance(101) else {
+        panic!("Expected a NoteOn in the requested zone");
+    };
+    assert_eq!(position, 100);
+    assert_eq!(event.note().unwrap().pitch().note_number(), 56);
+    assert!(event.note().unwrap().velocity() < 64);
+
+    // the last round robin of the last layer moves on to the next pitch
+    seq.advance(101);
+    let AdvanceResult::Event { event, .. } = seq.advance(101) else {
+        panic!("Expected a NoteOn at the next pitch");
+    };
+    assert_eq!(event.note().unwrap().pitch().note_number(), 60);
+    assert_eq!(event.note().unwrap().velocity(), 127);
+
+    // skipping between notes takes effect immediately
+    seq.advance(101);
+    seq.skip_to(midi::Pitch::new(48).unwrap(), 1, 0).unwrap();
+    let AdvanceResult::Event { position, event } = seq.advance(1) else {
+        panic!("Expected an immediate NoteOn");
+    };
+    assert_eq!(position, 0);
+    assert_eq!(event.note().unwrap().pitch().note_number(), 48);
+}
+
+#[test]
+fn remaining_event_count() {
+    let base = Config {
+        notes: 40..=52,
+        step: NonZeroU8::new(5).unwrap(),
+        velocity_levels: NonZeroU8::new(3).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        length: Duration::from_millis(10),
+        gap: Duration::from_millis(10),
+        ..Default::default()
+    };
+
+    let configs = [
+        base.clone(),
+        Config {
+            mpe: Some(midi::Mpe::default()),
+            cleanup: Cleanup::ALL,
+            ..base.clone()
+        },
+        Config {
+            mpe: Some(midi::Mpe::default()),
+            zone_reset: Cleanup::ALL,
+            ..base.clone()
+        },
+        Config {
+            tuning: Some([PitchBend::MAX; 128]),
+            legato: Some(Legato {
+                intervals: Intervals::UNISON,
+                overlap: Duration::ZERO,
+            }),
+            ..base.clone()
+        },
+        Config {
+            cleanup: Cleanup {
+                all_sound_off: true,
+                ..Default::default()
+            },
+            ..base
+        },
+    ];
+
+    for cfg in configs {
+        let mut seq = Sequencer::new(cfg, 1000).unwrap();
+        let total = seq.remaining_events();
+
+        let mut produced = 0;
+        loop {
+            match seq.advance(7) {
+                AdvanceResult::NoEventsInFrame => {}
+                AdvanceResult::Event { .. } => {
+                    produced += 1;
+                    assert_eq!(seq.remaining_events(), total - produced);
+                }
+                AdvanceResult::SequenceComplete => break,
+            }
+        }
+
+        assert_eq!(seq.remaining_events(), 0);
+
+        seq.reset();
+        assert_eq!(seq.remaining_events(), total);
+        assert_eq!(seq.into_iter().len(), total);
+    }
+}
+
+#[test]
+fn remaining_event_count_after_skip() {
+    let cfg = Config {
+        notes: 60..=64,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    seq.advance(1);
+    seq.skip_to(midi::Pitch::new(64).unwrap(), 1, 0).unwrap();
+
+    assert_eq!(seq.remaining_events(), 3);
+    assert_eq!(seq.into_iter().count(), 3);
+}
+
+#[test]
+fn sample_rate_change_mid_note() {
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(50),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    assert!(matches!(
+        seq.advance(1),
+        AdvanceResult::Event { position: 0, .. }
+    ));
+
+    // 40ms into the note, double the rate
+    assert_eq!(seq.advance(40), AdvanceResult::NoEventsInFrame);
+    seq.set_sample_rate(2000);
+    assert_eq!(seq.sample_rate(), 2000);
+
+    let AdvanceResult::Event { position, event } = seq.advance(usize::MAX) else {
+        panic!("Expected a NoteOff");
+    };
+    assert_eq!(position, 120);
+    assert_eq!(event.note().unwrap().state(), NoteState::Off);
+
+    // subsequent spans are measured at the new rate
+    let AdvanceResult::Event { position, .. } = seq.advance(usize::MAX) else {
+        panic!("Expected a NoteOn");
+    };
+    assert_eq!(position, 100);
+
+    let AdvanceResult::Event { position, .. } = seq.advance(usize::MAX) else {
+        panic!("Expected a NoteOff");
+    };
+    assert_eq!(position, 200);
+}
+
+#[test]
+fn legato_transitions() {
+    let cfg = Config {
+        notes: 60..=126,
+        step: NonZeroU8::new(66).unwrap(),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(50),
+        legato: Some(Legato {
+            intervals: Intervals::new().with(-2).unwrap().with(2).unwrap(),
+            overlap: Duration::from_millis(30),
+        }),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    // 60 -> 58, 60 -> 62, 126 -> 124 (126 -> 128 is out of range)
+    assert_eq!(seq.remaining_events(), 12);
+
+    for (source, target) in [(60, 58), (60, 62), (126, 124)] {
+        let zone = seq.zone().unwrap();
+        assert_eq!(zone.pitch().note_number(), source);
+        assert_eq!(zone.legato_target().unwrap().note_number(), target);
+
+        for (position, pitch, state) in [
+            (50, source, NoteState::On),
+            (100, target, NoteState::On),
+            (30, source, NoteState::Off),
+            (70, target, NoteState::Off),
+        ] {
+            let AdvanceResult::Event {
+                position: actual,
+                event: Event::Note(note),
+            } = seq.advance(1000)
+            else {
+                panic!("expected a note");
+            };
+
+            // the sequence starts immediately, without a gap
+            let first = source == 60 && target == 58 && pitch == source && state == NoteState::On;
+            assert_eq!(actual, if first { 0 } else { position });
+            assert_eq!(note.pitch().note_number(), pitch);
+            assert_eq!(note.state(), state);
+        }
+    }
+
+    assert!(seq.zone().is_none());
+    assert_eq!(seq.remaining_events(), 0);
+    assert_eq!(seq.advance(1000), AdvanceResult::SequenceComplete);
+}
+
+#[test]
+fn legato_validation() {
+    let cfg = Config {
+        legato: Some(Legato {
+            intervals: Intervals::new(),
+            overlap: Duration::ZERO,
+        }),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::LegatoIntervals)
+    ));
+
+    let cfg = Config {
+        length: Duration::from_millis(10),
+        legato: Some(Legato {
+            intervals: Intervals::UNISON,
+            overlap: Duration::from_millis(20),
+        }),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::LegatoOverlap(_))
+    ));
+
+    assert!(Intervals::new().with(64).is_err());
+    assert!(Intervals::new().with(-64).unwrap().contains(-64));
+}
+
+#[test]
+fn tempo_clock() {
+    let tempo = Tempo::from_bpm(120.0).unwrap();
+    let cfg = Config {
+        notes: 60..=61,
+        length: tempo.beats(1.0),
+        gap: tempo.beats(1.0),
+        clock: Some(tempo),
+        ..Default::default()
+    };
+
+    let seq = Sequencer::new(cfg, 4800).unwrap();
+    // 24 ticks per 2400-frame beat, across 4 beats (inclusive of the last)
+    assert_eq!(seq.remaining_events(), 4 + 97 + 2);
+
+    let mut events = seq.into_iter();
+    assert_eq!(events.len(), 103);
+    assert_eq!(events.next(), Some((0, Event::Start)));
+    assert_eq!(events.next(), Some((0, Event::TimingClock)));
+    assert!(matches!(events.next(), Some((0, Event::Note(_)))));
+
+    let mut ticks = 1;
+    let mut notes = 1;
+    for (position, event) in events.by_ref() {
+        match event {
+            Event::TimingClock => {
+                assert_eq!(position, ticks * 100);
+                ticks += 1;
+            }
+            Event::Note(_) => {
+                assert_eq!(position % 2400, 0);
+                notes += 1;
+            }
+            Event::Stop => assert_eq!(position, 9600),
+            _ => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    assert_eq!(ticks, 97);
+    assert_eq!(notes, 4);
+    assert_eq!(events.len(), 0);
+
+    assert!(Tempo::from_bpm(0.0).is_err());
+    assert!(Tempo::from_bpm(f64::NAN).is_err());
+    assert_eq!(&*Event::TimingClock.as_midi_message(), &[0xF8]);
+    assert_eq!(
+        Event::Start.as_ump(midi::ump::Group::default()),
+        [0x10FA_0000, 0]
+    );
+}
+
+#[test]
+fn humanized_zones() {
+    let cfg = Config {
+        notes: 60..=62,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        round_robins: NonZeroU8::new(3).unwrap(),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        humanize: Some(Humanize {
+            seed: 42,
+            timing: Duration::from_millis(10),
+            velocity: 5,
+        }),
+        ..Default::default()
+    };
+
+    let plain = Sequencer::new(
+        Config {
+            humanize: None,
+            ..cfg.clone()
+        },
+        1000,
+    )
+    .unwrap();
+    let humanized = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert_eq!(humanized.remaining_events(), plain.remaining_events());
+
+    let mut varied = false;
+    for ((plain_position, plain_event), (position, event)) in plain.into_iter().zip(humanized) {
+        let (Event::Note(plain_note), Event::Note(note)) = (plain_event, event) else {
+            panic!("expected notes");
+        };
+
+        // shifted by up to the timing span, and never later than twice that
+        assert!(position.abs_diff(plain_position + 10) <= 10);
+        assert!(plain_note.velocity().abs_diff(note.velocity()) <= 5);
+        varied |= plain_note.velocity() != note.velocity();
+    }
+    assert!(varied);
+
+    // reproducible from the seed alone
+    let a = Sequencer::new(cfg.clone(), 1000).unwrap();
+    let b = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert!(a.into_iter().eq(b));
+
+    let mut a = Sequencer::new(cfg.clone(), 1000).unwrap();
+    let mut b = Sequencer::new(cfg.clone(), 1000).unwrap();
+    a.skip_to(Pitch::new(62).unwrap(), 1, 2).unwrap();
+    while b.zone().is_some() && b.zone() != a.zone() {
+        let _ = b.advance(usize::MAX);
+    }
+    assert!(b.zone().is_some());
+    assert_eq!(a.remaining_events(), b.remaining_events());
+
+    let cfg = Config {
+        gap: Duration::from_millis(15),
+        ..cfg
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::HumanizeTiming(_))
+    ));
+}
+
+#[test]
+fn parameter_messages() {
+    let channel = Channel::new(2).unwrap();
+
+    assert_eq!(
+        channel.pitch_bend_range(12, 50).unwrap(),
+        [
+            [0xB2, 101, 0],
+            [0xB2, 100, 0],
+            [0xB2, 6, 12],
+            [0xB2, 38, 50],
+            [0xB2, 101, 127],
+            [0xB2, 100, 127],
+        ]
+    );
+
+    let nrpn = midi::Parameter::non_registered(1, 8).unwrap();
+    assert!(!nrpn.is_registered());
+    assert_eq!(
+        channel.parameter(nrpn, 64, 0).unwrap()[..2],
+        [[0xB2, 99, 1], [0xB2, 98, 8]]
+    );
+
+    assert!(midi::Parameter::registered(128, 0).is_err());
+    assert!(channel.pitch_bend_range(128, 0).is_err());
+}
+
+#[test]
+fn tuning_messages() {
+    use midi::tuning::*;
+
+    let tuning = Tuning::from_cents(6_999.999).unwrap();
+    assert_eq!(tuning.semitone(), Pitch::new(70).unwrap());
+    assert_eq!(tuning.fraction(), 0);
+
+    let tuning = Tuning::from_cents(6_925.0).unwrap();
+    assert_eq!(tuning.fraction(), 0x1000);
+    assert!((tuning.cents() - 6_925.0).abs() < 0.01);
+
+    assert!(Tuning::from_cents(-1.0).is_err());
+    assert!(Tuning::from_cents(12_700.0).is_err());
+
+    let changes = [
+        (
+            Pitch::new(60).unwrap(),
+            Tuning::from_cents(6_050.0).unwrap(),
+        ),
+        (
+            Pitch::new(61).unwrap(),
+            Tuning::from_cents(6_150.0).unwrap(),
+        ),
+    ];
+    let expected = [
+        0xF0, 0x7F, 0x10, 0x08, 0x02, 3, 2, 60, 60, 0x40, 0, 61, 61, 0x40, 0, 0xF7,
+    ];
+    assert!(single_note_tuning_change(0x10, 3, &changes)
+        .unwrap()
+        .eq(expected));
+
+    assert!(single_note_tuning_change(0x80, 3, &changes).is_err());
+}
+
+#[test]
+fn dimension_order() {
+    use dimension::*;
+
+    let cfg = Config {
+        notes: 60..=61,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        dimensions: Dimensions::new().with(Dimension::Velocity).unwrap(),
+        ..Default::default()
+    };
+
+    let seq = Sequencer::new(cfg, 1000).unwrap();
+    let notes = seq
+        .into_iter()
+        .filter_map(|(_, event)| event.note().copied())
+        .filter(|note| note.state() == NoteState::On);
+
+    // pitch changes fastest now
+    let expected = [(60, 127), (61, 127), (60, 63), (61, 63)];
+    assert!(notes
+        .map(|note| (note.pitch().note_number(), note.velocity()))
+        .eq(expected));
+}
+
+#[test]
+fn controller_dimension() {
+    use dimension::*;
+
+    let cfg = Config {
+        notes: 60..=61,
+        channel: Channel::new(3).unwrap(),
+        dimensions: Dimensions::new()
+            .with(Dimension::Controller {
+                controller: 64,
+                values: Values::range(0..=127, 127).unwrap(),
+            })
+            .unwrap()
+            .with(Dimension::Keyswitch(Values::new().with(24).unwrap()))
+            .unwrap(),
+        ..Default::default()
+    };
+
+    let seq = Sequencer::new(cfg, 1000).unwrap();
+    assert_eq!(seq.zone().unwrap().settings().count(), 2);
+    assert_eq!(seq.remaining_events(), 4 * 5);
+
+    let channel = Channel::new(3).unwrap();
+    let mut events = seq.into_iter().map(|(_, event)| event);
+
+    for value in [0, 0, 127, 127] {
+        assert_eq!(
+            events.next(),
+            Some(Event::ControlChange {
+                channel,
+                controller: 64,
+                value
+            })
+        );
+
+        for state in [NoteState::On, NoteState::Off] {
+            let keyswitch = events.next().unwrap();
+            assert_eq!(keyswitch.note().unwrap().pitch().note_number(), 24);
+            assert_eq!(keyswitch.note().unwrap().state(), state);
+        }
+
+        assert_eq!(
+            events.next().unwrap().note().unwrap().state(),
+            NoteState::On
+        );
+        assert_eq!(
+            events.next().unwrap().note().unwrap().state(),
+            NoteState::Off
+        );
+    }
+
+    assert_eq!(events.next(), None);
+}
+
+#[test]
+fn reordered_legato_count() {
+    use dimension::*;
+
+    let cfg = Config {
+        notes: 120..=127,
+        step: NonZeroU8::new(3).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        legato: Some(Legato {
+            intervals: Intervals::new().with(-5).unwrap().with(5).unwrap(),
+            overlap: Duration::ZERO,
+        }),
+        dimensions: Dimensions::new()
+            .with(Dimension::RoundRobin)
+            .unwrap()
+            .with(Dimension::Program {
+                bank_msb: Some(3),
+                bank_lsb: None,
+                programs: Values::range(0..=1, 1).unwrap(),
+            })
+            .unwrap()
+            .with(Dimension::Interval)
+            .unwrap(),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    for _ in 0..10 {
+        let _ = seq.advance(usize::MAX);
+    }
+
+    let remaining = seq.remaining_events();
+    assert_eq!(seq.into_iter().count(), remaining);
+}
+
+#[test]
+fn dimension_validation() {
+    use dimension::*;
+
+    assert!(matches!(
+        Dimensions::new()
+            .with(Dimension::Pitch)
+            .unwrap()
+            .with(Dimension::Pitch),
+        Err(DimensionError::Duplicate(Dimension::Pitch))
+    ));
+
+    let program = Dimension::Program {
+        bank_msb: None,
+        bank_lsb: None,
+        programs: Values::new().with(1).unwrap(),
+    };
+    let mut dimensions = Dimensions::new();
+    for _ in 0..4 {
+        dimensions = dimensions.with(program).unwrap();
+    }
+    assert!(matches!(
+        dimensions.with(program),
+        Err(DimensionError::Full)
+    ));
+
+    let cfg = Config {
+        dimensions: Dimensions::new()
+            .with(Dimension::Program {
+                bank_msb: None,
+                bank_lsb: None,
+                programs: Values::new(),
+            })
+            .unwrap(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::Dimensions(DimensionError::Empty(_)))
+    ));
+}
+
+#[test]
+fn running_status_encoding() {
+    use midi::serial::Encoder;
+
+    let channel = Channel::new(1).unwrap();
+    let note = |pitch, state| {
+        Event::Note(Note {
+            pitch,
+            velocity: u16::MAX,
+            state,
+            channel,
+        })
+    };
+    let events = [
+        note(60, NoteState::On),
+        note(62, NoteState::On),
+        Event::TimingClock,
+        note(60, NoteState::Off),
+        note(62, NoteState::Off),
+        Event::ControlChange {
+            channel,
+            controller: 123,
+            value: 0,
+        },
+    ];
+
+    let mut plain = Encoder::new();
+    assert_eq!(plain.encode_all(events).count(), 3 * 5 + 1);
+
+    let mut running = Encoder::new().running_status(true);
+    let expected = [
+        0x91, 60, 127, 62, 127, 0xF8, 0x81, 60, 127, 62, 127, 0xB1, 123, 0,
+    ];
+    assert!(running.encode_all(events).eq(expected));
+
+    let mut zero_velocity = Encoder::new()
+        .running_status(true)
+        .note_off_as_note_on(true);
+    let expected = [0x91, 60, 127, 62, 127, 0xF8, 60, 0, 62, 0, 0xB1, 123, 0];
+    assert!(zero_velocity.encode_all(events).eq(expected));
+
+    zero_velocity.reset();
+    assert_eq!(&*zero_velocity.encode(&events[0]), &[0x91, 60, 127]);
+}
+
+#[test]
+fn program_dimension_selects_bank() {
+    use dimension::*;
+
+    let cfg = Config {
+        notes: 60..=60,
+        dimensions: Dimensions::new()
+            .with(Dimension::Program {
+                bank_msb: Some(1),
+                bank_lsb: Some(2),
+                programs: Values::range(4..=5, 1).unwrap(),
+            })
+            .unwrap(),
+        ..Default::default()
+    };
+
+    let seq = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert_eq!(
+        seq.zone().unwrap().settings().next(),
+        Some(Setting::Program(
+            midi::PatchSelect::new(4).unwrap().with_bank(1, 2).unwrap()
+        ))
+    );
+    assert_eq!(seq.remaining_events(), 2 * 5);
+
+    let programs = seq.into_iter().filter_map(|(_, event)| match event {
+        Event::ProgramChange { program, .. } => Some(program),
+        _ => None,
+    });
+    assert!(programs.eq([4, 5]));
+
+    let cfg = Config {
+        dimensions: Dimensions::new()
+            .with(Dimension::Program {
+                bank_msb: Some(128),
+                bank_lsb: None,
+                programs: Values::range(4..=5, 1).unwrap(),
+            })
+            .unwrap(),
+        ..cfg
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::Dimensions(DimensionError::Bank(_)))
+    ));
+}
+
+#[test]
+fn burst_repetitions() {
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(50),
+        burst: Some(Burst {
+            hits: NonZeroU8::new(3).unwrap(),
+            interval: Duration::from_millis(40),
+        }),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert_eq!(seq.remaining_events(), 12);
+
+    let mut elapsed = 0;
+    let mut notes = [(0, NoteState::Off); 12];
+    for slot in &mut notes {
+        let AdvanceResult::Event {
+            position,
+            event: Event::Note(note),
+        } = seq.advance(1000)
+        else {
+            panic!("expected a note");
+        };
+
+        elapsed += position;
+        *slot = (elapsed, note.state());
+    }
+
+    assert_eq!(seq.remaining_events(), 0);
+    assert_eq!(
+        notes[..6],
+        [
+            (0, NoteState::On),
+            (40, NoteState::Off),
+            (40, NoteState::On),
+            (80, NoteState::Off),
+            (80, NoteState::On),
+            (180, NoteState::Off),
+        ]
+    );
+    assert_eq!(notes[6], (230, NoteState::On));
+
+    // hits shorter than the interval leave space between them
+    let cfg = Config {
+        length: Duration::from_millis(10),
+        ..cfg
+    };
+    let seq = Sequencer::new(cfg.clone(), 1000).unwrap();
+    let positions = seq.into_iter().take(4).map(|(position, _)| position);
+    assert!(positions.eq([0, 10, 40, 50]));
+
+    let cfg = Config {
+        burst: Some(Burst {
+            hits: NonZeroU8::new(2).unwrap(),
+            interval: Duration::ZERO,
+        }),
+        ..cfg
+    };
+    assert!(matches!(
+        Sequencer::new(cfg.clone(), 1000),
+        Err(SequencerError::BurstInterval(_))
+    ));
+
+    let cfg = Config {
+        burst: Some(Burst {
+            hits: NonZeroU8::new(2).unwrap(),
+            interval: Duration::from_millis(40),
+        }),
+        legato: Some(Legato {
+            intervals: Intervals::UNISON,
+            overlap: Duration::ZERO,
+        }),
+        ..cfg
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::BurstLegato)
+    ));
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn event_stream() {
+    use std::{
+        cell::Cell,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    use futures_core::Stream;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(50),
+        ..Default::default()
+    };
+
+    let sleeps = Cell::new(Duration::ZERO);
+    let seq = Sequencer::new(cfg, 1000).unwrap();
+    let mut events = stream::EventStream::new(seq, |duration| {
+        sleeps.set(sleeps.get().max(duration));
+        core::future::ready(())
+    });
+    assert_eq!(events.size_hint(), (4, Some(4)));
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+
+    for (time, pitch, state) in [
+        (0, 60, NoteState::On),
+        (100, 60, NoteState::Off),
+        (150, 61, NoteState::On),
+        (250, 61, NoteState::Off),
+    ] {
+        let Poll::Ready(Some((actual, Event::Note(note)))) =
+            Pin::new(&mut events).poll_next(&mut cx)
+        else {
+            panic!("expected a note");
+        };
+
+        assert_eq!(actual, Duration::from_millis(time));
+        assert_eq!(note.pitch().note_number(), pitch);
+        assert_eq!(note.state(), state);
+    }
+
+    assert_eq!(Pin::new(&mut events).poll_next(&mut cx), Poll::Ready(None));
+    // waits are measured from the start, and no time has really passed
+    assert!(sleeps.get() > Duration::from_millis(150));
+    assert!(sleeps.get() <= Duration::from_millis(250));
+}
+
+#[test]
+fn errors_without_std() {
+    fn root_cause(e: &dyn core::error::Error) -> bool {
+        e.source().is_none()
+    }
+
+    assert!(root_cause(&SequencerError::LegatoIntervals));
+    assert!(root_cause(&midi::Channel::new(16).unwrap_err()));
+    assert!(root_cause(&"H#4".parse::<midi::Pitch>().unwrap_err()));
+}
+
+#[test]
+fn note_range_validation() {
+    let cfg = Config {
+        notes: core::ops::RangeInclusive::new(72, 60),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::NoteRange { start: 72, end: 60 })
+    ));
+
+    let cfg = Config {
+        notes: 60..=127,
+        step: NonZeroU8::new(200).unwrap(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Sequencer::new(cfg.clone(), 1000),
+        Err(SequencerError::Step(_))
+    ));
+
+    let cfg = Config {
+        notes: 0..=127,
+        step: NonZeroU8::new(127).unwrap(),
+        ..cfg
+    };
+    assert_eq!(Sequencer::new(cfg, 1000).unwrap().remaining_events(), 4);
+}
+
+#[test]
+fn events_between_matches_playback() {
+    let cfg = Config {
+        notes: 48..=72,
+        step: NonZeroU8::new(3).unwrap(),
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        length: Duration::from_millis(80),
+        gap: Duration::from_millis(40),
+        mpe: Some(Mpe::default()),
+        cleanup: Cleanup::ALL,
+        humanize: Some(Humanize {
+            seed: 7,
+            timing: Duration::from_millis(20),
+            velocity: 3,
+        }),
+        clock: Some(Tempo::from_bpm(97.0).unwrap()),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    for (start, end) in [
+        (0, 0),
+        (0, 50),
+        (120, 121),
+        (333, 1234),
+        (1000, 5000),
+        (0, 99_999),
+    ] {
+        let expected = seq
+            .clone()
+            .into_iter()
+            .filter(|(position, _)| (start..end).contains(position));
+        assert!(seq.events_between(start, end).eq(expected));
+    }
+
+    // the window is measured from the beginning, and progress is left alone
+    let fresh = seq.clone();
+    seq.advance(1000);
+    let remaining = seq.remaining_events();
+    assert!(seq
+        .events_between(500, 1000)
+        .eq(fresh.events_between(500, 1000)));
+    assert_eq!(seq.remaining_events(), remaining);
+}
+
+#[test]
+fn poly_pressure_sweep() {
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(50),
+        poly_pressure: Some(PolyPressure {
+            start: 0,
+            end: 120,
+            steps: NonZeroU8::new(4).unwrap(),
+        }),
+        ..Default::default()
+    };
+
+    let seq = Sequencer::new(cfg.clone(), 1000).unwrap();
+    assert_eq!(seq.remaining_events(), 2 * 6);
+
+    let mut events = seq.into_iter();
+    let (position, event) = events.next().unwrap();
+    assert_eq!(position, 0);
+    assert_eq!(event.note().unwrap().state(), NoteState::On);
+
+    for (position, pressure) in [(0, 0), (25, 40), (50, 80), (75, 120)] {
+        assert_eq!(
+            events.next(),
+            Some((
+                position,
+                Event::PolyPressure {
+                    channel: Channel::default(),
+                    pitch: Pitch::new(60).unwrap(),
+                    pressure,
+                }
+            ))
+        );
+    }
+
+    let (position, event) = events.next().unwrap();
+    assert_eq!(position, 100);
+    assert_eq!(event.note().unwrap().state(), NoteState::Off);
+    assert_eq!(events.next().unwrap().0, 150);
+
+    let message = Event::PolyPressure {
+        channel: Channel::new(2).unwrap(),
+        pitch: Pitch::new(60).unwrap(),
+        pressure: 64,
+    }
+    .as_midi_message();
+    assert_eq!(&*message, [0xA2, 60, 64]);
+
+    let cfg = Config {
+        poly_pressure: Some(PolyPressure {
+            start: 0,
+            end: 128,
+            steps: NonZeroU8::new(1).unwrap(),
+        }),
+        ..cfg
+    };
+    assert!(matches!(
+        Sequencer::new(cfg, 1000),
+        Err(SequencerError::PolyPressure(_))
+    ));
+}
+
+#[test]
+fn soft_to_loud_velocity() {
+    let cfg = Config {
+        notes: 60..=61,
+        velocity_levels: NonZeroU8::new(3).unwrap(),
+        velocity_order: VelocityOrder::SoftToLoud,
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    for (pitch, layer, velocity) in [(60, 2, 41), (60, 1, 84), (60, 0, 127), (61, 2, 41)] {
+        let zone = seq.zone().unwrap();
+        assert_eq!(zone.pitch().note_number(), pitch);
+        assert_eq!(zone.velocity_layer(), layer);
+        assert_eq!(zone.velocity(), velocity);
+
+        seq.advance(usize::MAX);
+        seq.advance(usize::MAX);
+    }
+
+    // layers still count from the loudest
+    seq.skip_to(Pitch::new(60).unwrap(), 1, 0).unwrap();
+    assert_eq!(seq.zone().unwrap().velocity(), 84);
+    assert_eq!(seq.remaining_events(), 2 * 5);
+}
+
+#[test]
+fn round_robin_passes() {
+    let cfg = Config {
+        notes: 60..=61,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        round_robins: NonZeroU8::new(2).unwrap(),
+        dimensions: dimension::Dimensions::round_robin_passes(),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    assert_eq!(seq.remaining_events(), 2 * 8);
+
+    for (round_robin, pitch, layer) in [
+        (0, 60, 0),
+        (0, 60, 1),
+        (0, 61, 0),
+        (0, 61, 1),
+        (1, 60, 0),
+        (1, 60, 1),
+        (1, 61, 0),
+        (1, 61, 1),
+    ] {
+        let zone = seq.zone().unwrap();
+        assert_eq!(zone.round_robin(), round_robin);
+        assert_eq!(zone.pitch().note_number(), pitch);
+        assert_eq!(zone.velocity_layer(), layer);
+
+        seq.advance(usize::MAX);
+        seq.advance(usize::MAX);
+    }
+
+    assert!(seq.zone().is_none());
+}
+
+#[test]
+fn end_gap_early() {
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(500),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+
+    assert!(matches!(
+        seq.advance(1),
+        AdvanceResult::Event { position: 0, .. }
+    ));
+
+    // a held note is not cut short
+    assert!(seq.note_held());
+    assert!(!seq.end_gap());
+    assert!(matches!(
+        seq.advance(200),
+        AdvanceResult::Event { position: 100, .. }
+    ));
+
+    assert!(matches!(seq.advance(50), AdvanceResult::NoEventsInFrame));
+    assert!(!seq.note_held());
+    assert!(seq.end_gap());
+
+    let AdvanceResult::Event { position, event } = seq.advance(1) else {
+        panic!("Expected the next NoteOn");
+    };
+    assert_eq!(position, 0);
+    assert_eq!(event.note().unwrap().pitch().note_number(), 61);
+    assert_eq!(event.note().unwrap().state(), NoteState::On);
+}
+
+#[test]
+fn skip_zones_to_resume() {
+    let cfg = Config {
+        notes: 60..=62,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+    assert_eq!(seq.skip_zones(3), 3);
+
+    let zone = seq.zone().unwrap();
+    assert_eq!(zone.pitch().note_number(), 61);
+    assert_eq!(zone.velocity_layer(), 1);
+    assert_eq!(seq.remaining_events(), 3 * 2);
+
+    // nothing is skipped while a note is held
+    seq.advance(1);
+    assert_eq!(seq.skip_zones(1), 0);
+
+    seq.advance(usize::MAX);
+    assert_eq!(seq.skip_zones(5), 2);
+    assert!(seq.zone().is_none());
+    assert!(matches!(
+        seq.advance(usize::MAX),
+        AdvanceResult::SequenceComplete
+    ));
+}
+
+#[test]
+fn repeat_zone_after_failure() {
+    let cfg = Config {
+        notes: 60..=61,
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+
+    // nothing has been played yet
+    assert!(!seq.repeat_zone());
+
+    // repeat the first zone from its gap
+    seq.advance(1);
+    seq.advance(usize::MAX);
+    assert_eq!(seq.zone().unwrap().pitch().note_number(), 61);
+    assert!(seq.repeat_zone());
+    assert_eq!(seq.zone().unwrap().pitch().note_number(), 60);
+
+    // and again while it is held
+    seq.advance(usize::MAX);
+    assert!(seq.note_held());
+    assert!(seq.repeat_zone());
+    seq.advance(usize::MAX);
+    assert_eq!(seq.zone().unwrap().pitch().note_number(), 60);
+    assert_eq!(seq.remaining_events(), 2 * 2);
+
+    // the last zone can be repeated too
+    for _ in 0..4 {
+        seq.advance(usize::MAX);
+    }
+    assert!(seq.zone().is_none());
+    assert!(seq.repeat_zone());
+    assert_eq!(seq.zone().unwrap().pitch().note_number(), 61);
+    assert_eq!(seq.remaining_events(), 2);
+}
+
+#[test]
+fn skip_note_passes_its_zones() {
+    let cfg = Config {
+        notes: 60..=62,
+        velocity_levels: NonZeroU8::new(2).unwrap(),
+        length: Duration::from_millis(100),
+        gap: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut seq = Sequencer::new(cfg, 1000).unwrap();
+
+    // the held zone is completed before the rest of its note is passed over
+    seq.advance(1);
+    seq.skip_note();
+    assert_eq!(seq.zone().unwrap().pitch().note_number(), 60);
+    let AdvanceResult::Event { event, .. } = seq.advance(usize::MAX) else {
+        panic!("Expected the NoteOff");
+    };
+    assert_eq!(event.note().unwrap().state(), NoteState::Off);
+    assert_eq!(seq.zone().unwrap().pitch().note_number(), 61);
+    assert_eq!(seq.skipped_zones(), 1);
+
+    // in the gap, the note about to be played is passed over, and the gap is kept
+    seq.skip_note();
+    assert_eq!(seq.skipped_zones(), 3);
+    let AdvanceResult::Event { position, event } = seq.advance(usize::MAX) else {
+        panic!("Expected the next NoteOn");
+    };
+    assert_eq!(position, 100);
+    assert_eq!(event.note().unwrap().pitch().note_number(), 62);
+    assert_eq!(event.note().unwrap().state(), NoteState::On);
+}
@@ -195,12 +195,12 @@ impl<'a> Multisample<'a> {
     }
 
     /// Groups that can be referenced from the sample list
-    pub fn groups(&self) -> &[Group] {
+    pub fn groups(&self) -> &[Group<'_>] {
         &self.groups
     }
 
     /// Sample mappings in this instrument
-    pub fn samples(&self) -> &[Sample] {
+    pub fn samples(&self) -> &[Sample<'_>] {
         &self.samples
     }
 }
@@ -227,7 +227,12 @@ pub struct Group<'a> {
         skip_serializing_if = "str::is_empty"
     )]
     name: Cow<'a, str>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        rename = "@color",
+        with = "hex_color",
+        skip_serializing_if = "Option::is_none"
+    )]
     color: Option<Color>,
 }
 
@@ -262,6 +267,40 @@ impl<'a> Group<'a> {
 /// RGB hex value
 pub type Color = [u8; 3];
 
+/// Colors are written as six hex digits, like `ff8000`
+mod hex_color {
+    use super::Color;
+
+    pub fn serialize<S: serde::Serializer>(
+        color: &Option<Color>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match color {
+            Some([r, g, b]) => serializer.serialize_str(&format!("{r:02x}{g:02x}{b:02x}")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Color>, D::Error> {
+        let text: std::borrow::Cow<'de, str> = serde::Deserialize::deserialize(deserializer)?;
+        let text = text.trim_start_matches('#');
+
+        let channel = |idx: usize| {
+            text.get(idx..idx + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| serde::de::Error::custom(format!("invalid color `{text}`")))
+        };
+
+        if text.len() != 6 {
+            return Err(serde::de::Error::custom(format!("invalid color `{text}`")));
+        }
+
+        Ok(Some([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
 /// Mapping information for a sample file
 #[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Sample<'a> {
@@ -36,6 +36,24 @@ fn just_groups() {
     );
 }
 
+#[test]
+fn colored_groups() {
+    let multi: Multisample =
+        quick_xml::de::from_str(include_str!("data/colored_groups.xml")).unwrap();
+
+    assert_eq!(
+        multi.groups(),
+        [
+            Group::default()
+                .with_name("Loud")
+                .with_color([0xd9, 0x2e, 0x24]),
+            Group::default()
+                .with_name("Soft")
+                .with_color([0x00, 0xa0, 0x94]),
+        ]
+    );
+}
+
 #[test]
 fn more_detailed() {
     let multi: Multisample = quick_xml::de::from_str(include_str!("data/details.xml")).unwrap();
@@ -30,6 +30,17 @@ fn just_groups() {
     );
 }
 
+#[test]
+fn colored_groups() {
+    assert_eq!(
+        write(Multisample::default().with_groups(vec![
+            Group::default().with_name("Loud").with_color([0xd9, 0x2e, 0x24]),
+            Group::default().with_name("Soft").with_color([0x00, 0xa0, 0x94]),
+        ],)),
+        include_str!("data/colored_groups.xml")
+    );
+}
+
 #[test]
 fn more_detailed() {
     assert_eq!(
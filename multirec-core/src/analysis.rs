@@ -0,0 +1,858 @@
+use std::{ops::Range, path::Path};
+
+/// Frames compared around a pair of candidate loop points
+const MATCH_WINDOW: usize = 256;
+
+/// Number of zero crossings at the end of the sustain to try as loop ends
+const END_CANDIDATES: usize = 16;
+
+/// Highest mismatch between the audio leading into the loop start and end that is crossfaded
+const CROSSFADE_MISMATCH: f32 = 0.5;
+
+/// Frames in each block of the spectrum when removing noise
+const SPECTRUM_SIZE: usize = 2048;
+
+/// Frames between the starts of overlapping spectrum blocks
+const SPECTRUM_HOP: usize = SPECTRUM_SIZE / 4;
+
+/// Share of each frequency's level that noise removal always leaves, to avoid watery artifacts
+const SPECTRAL_FLOOR: f64 = 0.05;
+
+/// Frames compared with a delayed copy of themselves when measuring pitch
+const PITCH_WINDOW: usize = 2048;
+
+/// Highest fundamental that pitch detection looks for, in Hz
+const MAX_PITCH: u32 = 4_000;
+
+/// Level below which the normalized difference marks a period (YIN's threshold)
+const PERIOD_THRESHOLD: f32 = 0.15;
+
+/// Length of each block that loudness is measured over (BS.1770's gating block)
+const LOUDNESS_BLOCK: f64 = 0.4;
+
+/// Blocks that loudness is measured over start this far apart, overlapping by 75%
+const LOUDNESS_STEP: f64 = 0.1;
+
+/// Blocks quieter than this, in LUFS, are left out of the integrated loudness
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks this far below the loudness of the blocks above the absolute gate are left out too
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Factor that the audio is oversampled by to find its true peak
+const OVERSAMPLING: usize = 4;
+
+/// Taps of the interpolation filter for each oversampled phase
+const INTERPOLATION_TAPS: usize = 12;
+
+/// Zero crossings on each side of the center of the resampling filter
+const RESAMPLE_ZERO_CROSSINGS: usize = 32;
+
+/// Points of the tabulated resampling filter between each pair of zero crossings
+const RESAMPLE_TABLE_STEPS: usize = 512;
+
+/// Shape of the resampling filter's Kaiser window, for about 90dB of stopband rejection
+const RESAMPLE_BETA: f64 = 9.0;
+
+/// Share of the lower of the two Nyquist frequencies that resampling keeps
+const RESAMPLE_BANDWIDTH: f64 = 0.95;
+
+/// Read a WAV file as interleaved samples, returning the number of channels too
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<(usize, Vec<f32>)> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    Ok((usize::from(spec.channels.max(1)), samples))
+}
+
+/// Read a WAV file, mixing all of its channels down to one
+pub fn read_mono(path: impl AsRef<Path>) -> anyhow::Result<Vec<f32>> {
+    let (channels, samples) = read(path)?;
+
+    Ok(samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect())
+}
+
+/// Read a WAV file as the peak level of each frame, across all of its channels
+pub fn peak_levels(path: impl AsRef<Path>) -> anyhow::Result<Vec<f32>> {
+    let (channels, samples) = read(path)?;
+
+    Ok(samples
+        .chunks(channels)
+        .map(|frame| frame.iter().fold(0.0, |peak: f32, s| peak.max(s.abs())))
+        .collect())
+}
+
+/// The highest peak level in a WAV file, across all of its channels
+pub fn peak_level(path: impl AsRef<Path>) -> anyhow::Result<f32> {
+    let (_, samples) = read(path)?;
+
+    Ok(samples.iter().fold(0.0, |peak: f32, s| peak.max(s.abs())))
+}
+
+/// The RMS level of a WAV file, across all of its channels
+pub fn rms_level(path: impl AsRef<Path>) -> anyhow::Result<f32> {
+    let (_, samples) = read(path)?;
+    let power = samples.iter().map(|s| f64::from(*s).powi(2)).sum::<f64>();
+
+    Ok((power / samples.len().max(1) as f64).sqrt() as f32)
+}
+
+/// Loudness of a recording, as broadcast meters measure it (ITU-R BS.1770)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS, or `None` if every block is below the gate
+    pub integrated: Option<f32>,
+    /// Highest level between the samples as well as at them, in dBTP, or `None` if silent
+    pub true_peak: Option<f32>,
+}
+
+/// Measure the integrated loudness and true peak of a WAV file
+///
+/// Every channel is weighted equally. A file shorter than one gating block
+/// is measured as a single block.
+pub fn loudness(path: impl AsRef<Path>) -> anyhow::Result<Loudness> {
+    let sample_rate = hound::WavReader::open(path.as_ref())?.spec().sample_rate;
+    let (channels, samples) = read(path)?;
+    let frames = samples.len() / channels;
+
+    // mean square of the K-weighted audio in each frame, summed over the channels
+    let mut power = vec![0.0; frames];
+    for channel in 0..channels {
+        let mut filters = k_weighting(f64::from(sample_rate));
+        for (frame, power) in power.iter_mut().enumerate() {
+            let mut s = f64::from(samples[frame * channels + channel]);
+            for filter in &mut filters {
+                s = filter.process(s);
+            }
+            *power += s * s;
+        }
+    }
+
+    let block = ((LOUDNESS_BLOCK * f64::from(sample_rate)) as usize).clamp(1, frames.max(1));
+    let step = ((LOUDNESS_STEP * f64::from(sample_rate)) as usize).max(1);
+    let mut total = 0.0;
+    let mut running = Vec::with_capacity(frames + 1);
+    running.push(0.0);
+    for p in &power {
+        total += p;
+        running.push(total);
+    }
+    let blocks: Vec<f64> = (0..=frames.saturating_sub(block))
+        .step_by(step)
+        .map(|start| (running[start + block] - running[start]) / block as f64)
+        .collect();
+
+    let lufs = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean_above = |gate: f64| {
+        let gated: Vec<f64> = blocks.iter().copied().filter(|p| lufs(*p) > gate).collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+    let integrated = mean_above(ABSOLUTE_GATE)
+        .and_then(|ungated| mean_above(lufs(ungated) + RELATIVE_GATE))
+        .map(|power| lufs(power) as f32);
+
+    let peak = true_peak(&samples, channels);
+    Ok(Loudness {
+        integrated,
+        true_peak: (peak > 0.0).then(|| 20.0 * peak.log10()),
+    })
+}
+
+/// A second-order IIR filter, in direct form I
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two stages of BS.1770's K-weighting, a high shelf and then a high pass, at any sample rate
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let k = (std::f64::consts::PI * 1_681.974_450_955_533 / sample_rate).tan();
+    let q = 0.707_175_236_955_419_6;
+    let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let k = (std::f64::consts::PI * 38.135_470_876_024_44 / sample_rate).tan();
+    let q = 0.500_327_037_323_877_3;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+/// The highest level of interleaved audio, between the samples as well as at them
+///
+/// The audio is oversampled with a windowed sinc filter, finding the
+/// peaks that a DAC would reconstruct between the samples.
+fn true_peak(samples: &[f32], channels: usize) -> f32 {
+    let taps = OVERSAMPLING * INTERPOLATION_TAPS;
+    let center = (taps - 1) as f64 / 2.0;
+    let filter: Vec<f64> = (0..taps)
+        .map(|i| {
+            let t = (i as f64 - center) / OVERSAMPLING as f64;
+            let sinc = if t == 0.0 {
+                1.0
+            } else {
+                (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t)
+            };
+            let window =
+                0.5 - 0.5 * (2.0 * std::f64::consts::PI * (i as f64 + 0.5) / taps as f64).cos();
+            sinc * window
+        })
+        .collect();
+
+    // each phase passes a constant unchanged
+    let phases: Vec<Vec<f64>> = (0..OVERSAMPLING)
+        .map(|phase| {
+            let coefficients: Vec<f64> = filter
+                .iter()
+                .skip(phase)
+                .step_by(OVERSAMPLING)
+                .copied()
+                .collect();
+            let sum: f64 = coefficients.iter().sum();
+            coefficients.iter().map(|c| c / sum).collect()
+        })
+        .collect();
+
+    let frames = samples.len() / channels;
+    let mut peak = samples.iter().fold(0.0, |peak: f32, s| peak.max(s.abs()));
+
+    for channel in 0..channels {
+        let at = |frame: usize| f64::from(samples[frame * channels + channel]);
+        for frame in INTERPOLATION_TAPS..frames {
+            for phase in &phases {
+                let level: f64 = phase
+                    .iter()
+                    .enumerate()
+                    .map(|(k, c)| c * at(frame - k))
+                    .sum();
+                peak = peak.max(level.abs() as f32);
+            }
+        }
+    }
+
+    peak
+}
+
+/// Shorten a WAV file to a number of frames, leaving its samples untouched
+pub fn truncate(path: impl AsRef<Path>, frames: usize) -> anyhow::Result<()> {
+    if hound::WavReader::open(path.as_ref())?.duration() as usize <= frames {
+        return Ok(());
+    }
+
+    rewrite(path, |mut samples, channels| {
+        samples.truncate(frames.saturating_mul(channels));
+        (samples, channels)
+    })
+}
+
+/// Remove a number of frames from the start of a WAV file
+pub fn drop_start(path: impl AsRef<Path>, frames: usize) -> anyhow::Result<()> {
+    rewrite(path, |mut samples, channels| {
+        samples.drain(..frames.saturating_mul(channels).min(samples.len()));
+        (samples, channels)
+    })
+}
+
+/// Change the level of a WAV file by a linear gain
+pub fn apply_gain(path: impl AsRef<Path>, gain: f32) -> anyhow::Result<()> {
+    rewrite(path, |mut samples, channels| {
+        samples.iter_mut().for_each(|s| *s *= f64::from(gain));
+        (samples, channels)
+    })
+}
+
+/// What the two channels of a stereo recording hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoContent {
+    /// Both channels are the same, to within the tolerance
+    Identical,
+    /// Only the left channel rises above the tolerance
+    LeftOnly,
+    /// Only the right channel rises above the tolerance
+    RightOnly,
+    /// The channels differ
+    Stereo,
+}
+
+/// Compare the channels of a stereo WAV file, or return `None` if it is not stereo
+///
+/// Levels at or below `tolerance` count as silence, as do differences between the channels.
+pub fn stereo_content(
+    path: impl AsRef<Path>,
+    tolerance: f32,
+) -> anyhow::Result<Option<StereoContent>> {
+    let (channels, samples) = read(path)?;
+    if channels != 2 {
+        return Ok(None);
+    }
+
+    let (left, right, difference) = samples.chunks(2).fold(
+        (0.0, 0.0, 0.0),
+        |(left, right, difference): (f32, f32, f32), frame| {
+            (
+                left.max(frame[0].abs()),
+                right.max(frame[1].abs()),
+                difference.max((frame[0] - frame[1]).abs()),
+            )
+        },
+    );
+
+    Ok(Some(match () {
+        _ if difference <= tolerance => StereoContent::Identical,
+        _ if right <= tolerance => StereoContent::LeftOnly,
+        _ if left <= tolerance => StereoContent::RightOnly,
+        _ => StereoContent::Stereo,
+    }))
+}
+
+/// Turn a stereo WAV file into a mono one, keeping what `content` says is there
+///
+/// Identical channels keep the left one, a single channel is kept as it is,
+/// and different channels are mixed at equal levels.
+pub fn to_mono(path: impl AsRef<Path>, content: StereoContent) -> anyhow::Result<()> {
+    rewrite(path, |samples, channels| {
+        if channels != 2 {
+            return (samples, channels);
+        }
+
+        let mono = samples
+            .chunks(2)
+            .map(|frame| match content {
+                StereoContent::Identical | StereoContent::LeftOnly => frame[0],
+                StereoContent::RightOnly => frame[1],
+                StereoContent::Stereo => (frame[0] + frame[1]) / 2.0,
+            })
+            .collect();
+
+        (mono, 1)
+    })
+}
+
+/// Write a WAV file back in the same sample format, after processing its samples
+///
+/// The samples are given interleaved, at their stored scale, along with the
+/// number of channels. The processing returns the new samples and number of
+/// channels. Integer samples are rounded and limited to their range.
+fn rewrite(
+    path: impl AsRef<Path>,
+    process: impl FnOnce(Vec<f64>, usize) -> (Vec<f64>, usize),
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (mut spec, samples) = read_stored(path)?;
+
+    let (samples, channels) = process(samples, usize::from(spec.channels));
+    spec.channels = channels.try_into()?;
+
+    write_stored(path, spec, samples)
+}
+
+/// Convert a WAV file to another sample rate
+///
+/// Each output frame is interpolated from the input with a Kaiser-windowed
+/// sinc filter, whose cutoff is lowered below the new Nyquist frequency when
+/// the rate goes down, so that nothing above it folds back into the audio.
+pub fn resample(path: impl AsRef<Path>, rate: u32) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (mut spec, samples) = read_stored(path)?;
+    if spec.sample_rate == rate {
+        return Ok(());
+    }
+
+    let channels = usize::from(spec.channels.max(1));
+    let frames = samples.len() / channels;
+    let ratio = f64::from(rate) / f64::from(spec.sample_rate);
+    let cutoff = ratio.min(1.0) * RESAMPLE_BANDWIDTH;
+    // in frames of the input
+    let radius = RESAMPLE_ZERO_CROSSINGS as f64 / cutoff;
+
+    let bessel_beta = bessel_i0(RESAMPLE_BETA);
+    let table: Vec<f64> = (0..=RESAMPLE_ZERO_CROSSINGS * RESAMPLE_TABLE_STEPS + 1)
+        .map(|i| {
+            let u = i as f64 / RESAMPLE_TABLE_STEPS as f64;
+            let sinc = if u == 0.0 {
+                1.0
+            } else {
+                (std::f64::consts::PI * u).sin() / (std::f64::consts::PI * u)
+            };
+            let x = (u / RESAMPLE_ZERO_CROSSINGS as f64).min(1.0);
+            sinc * bessel_i0(RESAMPLE_BETA * (1.0 - x * x).sqrt()) / bessel_beta
+        })
+        .collect();
+
+    let output_frames = (frames as f64 * ratio).round() as usize;
+    let mut output = vec![0.0; output_frames * channels];
+    for (frame, out) in output.chunks_exact_mut(channels).enumerate() {
+        let t = frame as f64 / ratio;
+        let first = (t - radius).ceil().max(0.0) as usize;
+        let last = ((t + radius).floor() as usize).min(frames.saturating_sub(1));
+
+        for input in first..=last {
+            let position = (t - input as f64).abs() * cutoff * RESAMPLE_TABLE_STEPS as f64;
+            let idx = position as usize;
+            let Some([a, b]) = table.get(idx..idx + 2).map(|pair| [pair[0], pair[1]]) else {
+                continue;
+            };
+            // the cutoff scales the filter's gain back to one
+            let weight = cutoff * (a + (b - a) * (position - idx as f64));
+
+            let frame = &samples[input * channels..(input + 1) * channels];
+            for (out, sample) in out.iter_mut().zip(frame) {
+                *out += weight * sample;
+            }
+        }
+    }
+
+    spec.sample_rate = rate;
+    write_stored(path, spec, output)
+}
+
+/// The modified Bessel function of the first kind and order zero, for Kaiser windows
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..100 {
+        term *= (x / (2.0 * k as f64)).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Write interleaved samples at their stored scale to a WAV file
+fn write_stored(path: &Path, spec: hound::WavSpec, samples: Vec<f64>) -> anyhow::Result<()> {
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    match spec.sample_format {
+        hound::SampleFormat::Float => samples
+            .into_iter()
+            .try_for_each(|s| writer.write_sample(s as f32))?,
+        hound::SampleFormat::Int => {
+            let max = (1_i64 << (spec.bits_per_sample - 1)) as f64;
+            samples
+                .into_iter()
+                .try_for_each(|s| writer.write_sample(s.round().clamp(-max, max - 1.0) as i32))?
+        }
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Read a WAV file as interleaved samples at their stored scale
+fn read_stored(path: &Path) -> anyhow::Result<(hound::WavSpec, Vec<f64>)> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .map(|s| s.map(f64::from))
+            .collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => reader
+            .samples::<i32>()
+            .map(|s| s.map(f64::from))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    Ok((spec, samples))
+}
+
+/// The average level of each frequency in a recording of noise, across its channels
+///
+/// Levels are kept at the stored scale of the file, so that they can be taken
+/// away from recordings saved at the same bit depth.
+pub fn noise_profile(path: impl AsRef<Path>) -> anyhow::Result<Vec<f64>> {
+    let (spec, samples) = read_stored(path.as_ref())?;
+    let channels = usize::from(spec.channels.max(1));
+
+    let mut profile = vec![0.0; SPECTRUM_SIZE / 2 + 1];
+    let mut blocks = 0;
+    for channel in 0..channels {
+        let audio: Vec<_> = samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .copied()
+            .collect();
+
+        for start in (0..audio.len().saturating_sub(SPECTRUM_SIZE - 1)).step_by(SPECTRUM_HOP) {
+            let (re, im) = spectrum(&audio[start..start + SPECTRUM_SIZE]);
+            for (level, (re, im)) in profile.iter_mut().zip(re.iter().zip(&im)) {
+                *level += re.hypot(*im);
+            }
+            blocks += 1;
+        }
+    }
+
+    if blocks == 0 {
+        return Err(anyhow::Error::msg(
+            "Noise recording is too short to measure",
+        ));
+    }
+
+    profile
+        .iter_mut()
+        .for_each(|level| *level /= f64::from(blocks));
+    Ok(profile)
+}
+
+/// Take the level of a noise profile away from every frequency of a WAV file
+///
+/// This is spectral subtraction: the file is split into overlapping blocks,
+/// and each frequency in each block is turned down by `strength` times the
+/// noise at that frequency, keeping its phase.
+pub fn denoise(path: impl AsRef<Path>, profile: &[f64], strength: f64) -> anyhow::Result<()> {
+    rewrite(path, |samples, channels| {
+        let frames = samples.len() / channels.max(1);
+        let mut output = vec![0.0; samples.len()];
+
+        for channel in 0..channels {
+            // pad so that every frame is covered by the same number of blocks
+            let mut audio = vec![0.0; SPECTRUM_SIZE];
+            audio.extend(samples.iter().skip(channel).step_by(channels));
+            audio.resize(audio.len() + SPECTRUM_SIZE * 2, 0.0);
+
+            let mut cleaned = vec![0.0; audio.len()];
+            for start in (0..audio.len() - SPECTRUM_SIZE).step_by(SPECTRUM_HOP) {
+                let (mut re, mut im) = spectrum(&audio[start..start + SPECTRUM_SIZE]);
+
+                for (bin, noise) in profile.iter().enumerate().take(SPECTRUM_SIZE / 2 + 1) {
+                    let level = re[bin].hypot(im[bin]);
+                    if level == 0.0 {
+                        continue;
+                    }
+
+                    let kept = (level - strength * noise).max(SPECTRAL_FLOOR * level) / level;
+                    re[bin] *= kept;
+                    im[bin] *= kept;
+                    // the upper half mirrors the lower half
+                    if bin > 0 && bin < SPECTRUM_SIZE / 2 {
+                        re[SPECTRUM_SIZE - bin] *= kept;
+                        im[SPECTRUM_SIZE - bin] *= kept;
+                    }
+                }
+
+                fft(&mut re, &mut im, true);
+                for (i, s) in re.iter().enumerate() {
+                    cleaned[start + i] += s / SPECTRUM_SIZE as f64;
+                }
+            }
+
+            // Hann windows overlapping by three quarters add up to 2
+            for (frame, s) in cleaned[SPECTRUM_SIZE..SPECTRUM_SIZE + frames]
+                .iter()
+                .enumerate()
+            {
+                output[frame * channels + channel] = s / 2.0;
+            }
+        }
+
+        (output, channels)
+    })
+}
+
+/// The spectrum of a block of audio, after applying a Hann window
+fn spectrum(block: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let size = block.len() as f64;
+    let mut re: Vec<_> = block
+        .iter()
+        .enumerate()
+        .map(|(i, s)| s * (0.5 - 0.5 * (std::f64::consts::TAU * i as f64 / size).cos()))
+        .collect();
+    let mut im = vec![0.0; block.len()];
+
+    fft(&mut re, &mut im, false);
+    (re, im)
+}
+
+/// Fast Fourier transform in place, for a power of two number of values
+///
+/// The inverse transform is not scaled.
+fn fft(re: &mut [f64], im: &mut [f64], inverse: bool) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // put the values in bit-reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * std::f64::consts::TAU / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// The frame after which a sample has decayed for good
+///
+/// This is `hold` frames after the last frame above the threshold, so that
+/// the very end of the decay is kept.
+pub fn decay_end(levels: &[f32], threshold: f32, hold: usize) -> usize {
+    let last_above = levels.iter().rposition(|level| *level > threshold);
+    let end = last_above.map_or(0, |frame| frame + 1) + hold;
+
+    end.min(levels.len())
+}
+
+/// Find a seamless loop within the sustained part of a sample
+///
+/// Both loop points are upward zero crossings. The end is taken from the last
+/// few crossings in the region, and the start from the first half of it, so
+/// that the loop covers at least half of the region. Of those, the pair whose
+/// surrounding audio correlates best is chosen. Returns `None` if the region
+/// does not contain enough crossings.
+pub fn find_loop(samples: &[f32], region: Range<usize>) -> Option<Range<usize>> {
+    let half = MATCH_WINDOW / 2;
+    let region = region.start.max(half)..region.end.min(samples.len().saturating_sub(half));
+    let middle = region.start + region.len() / 2;
+
+    let crossings: Vec<_> = region
+        .clone()
+        .skip(1)
+        .filter(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0)
+        .collect();
+
+    let starts = crossings.iter().take_while(|&&i| i < middle);
+    let ends = crossings.iter().rev().take(END_CANDIDATES);
+
+    starts
+        .flat_map(|&start| {
+            ends.clone()
+                .filter(move |&&end| end > middle)
+                .map(move |&end| (start, end))
+        })
+        .map(|(start, end)| {
+            (
+                mismatch(
+                    &samples[start - half..start + half],
+                    &samples[end - half..end + half],
+                ),
+                start..end,
+            )
+        })
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, points)| points)
+}
+
+/// A crossfade length for a loop, in frames
+///
+/// Lengths double from the match window up to half of the loop, and the
+/// longest over which the audio before the loop end still resembles the audio
+/// before the loop start is chosen, so that the fade blends similar sound.
+/// Returns `None` if there is no room for a crossfade before the loop start.
+pub fn crossfade_length(samples: &[f32], points: &Range<usize>) -> Option<usize> {
+    let longest = (points.len() / 2).min(points.start);
+    let end = points.end.min(samples.len());
+
+    std::iter::successors(Some(MATCH_WINDOW), |length| length.checked_mul(2))
+        .take_while(|&length| length <= longest && length <= end)
+        .take_while(|&length| {
+            mismatch(
+                &samples[points.start - length..points.start],
+                &samples[end - length..end],
+            ) <= CROSSFADE_MISMATCH
+        })
+        .last()
+        .or((MATCH_WINDOW <= longest).then_some(MATCH_WINDOW))
+}
+
+/// Crossfade the end of a loop in a WAV file into the audio before its start
+///
+/// The last `length` frames of the loop fade into the `length` frames leading
+/// up to the loop start, so that the loop end flows straight into the start.
+pub fn render_crossfade(
+    path: impl AsRef<Path>,
+    points: Range<usize>,
+    length: usize,
+) -> anyhow::Result<()> {
+    rewrite(path, |mut samples, channels| {
+        let length = length.min(points.start).min(points.len());
+        if points.end * channels > samples.len() {
+            return (samples, channels);
+        }
+
+        for i in 0..length {
+            let mix = (i + 1) as f64 / length as f64;
+            let from = (points.start - length + i) * channels;
+            let to = (points.end - length + i) * channels;
+            for channel in 0..channels {
+                samples[to + channel] =
+                    samples[to + channel] * (1.0 - mix) + samples[from + channel] * mix;
+            }
+        }
+
+        (samples, channels)
+    })
+}
+
+/// Find the zero crossing in `range` nearest to `frame`
+///
+/// Crossings in the direction given by `rising` are preferred, falling back to
+/// any crossing. The frame returned is whichever side of the crossing is
+/// closer to zero. Returns `None` if there are no crossings in the range.
+pub fn zero_crossing(
+    samples: &[f32],
+    frame: usize,
+    range: Range<usize>,
+    rising: Option<bool>,
+) -> Option<usize> {
+    let range = range.start.max(1)..range.end.min(samples.len());
+    let crossings = range.filter(|&i| (samples[i - 1] < 0.0) != (samples[i] < 0.0));
+    let nearest =
+        |crossings: &mut dyn Iterator<Item = usize>| crossings.min_by_key(|&i| i.abs_diff(frame));
+
+    let crossing = nearest(
+        &mut crossings
+            .clone()
+            .filter(|&i| rising.map_or(true, |rising| rising == (samples[i - 1] < 0.0))),
+    )
+    .or_else(|| nearest(&mut crossings.clone()))?;
+
+    if samples[crossing - 1].abs() < samples[crossing].abs() {
+        Some(crossing - 1)
+    } else {
+        Some(crossing)
+    }
+}
+
+/// Measure the fundamental frequency of the sustained part of a sample, in Hz
+///
+/// Uses the YIN method in the middle of the region: the first delay at which
+/// the audio closely matches itself is taken as the period. Returns `None` if
+/// the region is too short or nothing periodic is found.
+pub fn detect_pitch(samples: &[f32], region: Range<usize>, sample_rate: u32) -> Option<f32> {
+    let region = region.start..region.end.min(samples.len());
+    let window = PITCH_WINDOW.min(region.len() / 2);
+    let min_lag = (sample_rate / MAX_PITCH).max(2) as usize;
+    if window <= min_lag * 2 {
+        return None;
+    }
+
+    let start = region.start + (region.len() - window * 2) / 2;
+    let audio = &samples[start..start + window * 2];
+
+    // cumulative mean normalized difference, for each delay
+    let mut normalized = vec![1.0; window];
+    let mut total = 0.0;
+    for lag in 1..window {
+        let difference: f32 = (0..window)
+            .map(|i| (audio[i] - audio[i + lag]).powi(2))
+            .sum();
+        total += difference;
+        normalized[lag] = if total > 0.0 {
+            difference * lag as f32 / total
+        } else {
+            1.0
+        };
+    }
+
+    let mut lag = (min_lag..window).find(|&lag| normalized[lag] < PERIOD_THRESHOLD)?;
+    while lag + 1 < window && normalized[lag + 1] < normalized[lag] {
+        lag += 1;
+    }
+
+    // fit a parabola through the dip for a period between whole frames
+    let period = if lag + 1 < window {
+        let (before, at, after) = (normalized[lag - 1], normalized[lag], normalized[lag + 1]);
+        let curve = before - 2.0 * at + after;
+        if curve > 0.0 {
+            lag as f32 + (before - after) / (2.0 * curve)
+        } else {
+            lag as f32
+        }
+    } else {
+        lag as f32
+    };
+
+    Some(sample_rate as f32 / period)
+}
+
+/// How different two windows of audio are, relative to their level
+fn mismatch(a: &[f32], b: &[f32]) -> f32 {
+    let (difference, energy) = a
+        .iter()
+        .zip(b)
+        .fold((0.0, 0.0), |(difference, energy), (a, b)| {
+            (difference + (a - b).powi(2), energy + a * a + b * b)
+        });
+
+    if energy == 0.0 {
+        return f32::INFINITY;
+    }
+
+    difference / energy
+}
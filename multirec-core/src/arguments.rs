@@ -0,0 +1,2026 @@
+use std::{
+    num::{NonZeroU16, NonZeroU32, NonZeroU8, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+
+use autosam::{
+    dimension::{Values, ZoneList, ZoneListFull},
+    midi::{Channel, PatchSelect, Pitch},
+    Cleanup, Tempo, Zone,
+};
+
+use crate::{
+    count_in::{CountIn as CountInCue, Cue},
+    notify, scala, ONE,
+};
+pub use crate::{
+    hook::PostCommand,
+    mapping::KeyMap,
+    naming::{Dynamics, Keyswitch, Layout, NameTemplate},
+    simulate::Simulation,
+    sysex::SysEx,
+    util::{Decibels, Matcher},
+};
+
+/// The range of a piano's keys, which a run samples unless told otherwise
+const LOWEST_PIANO_KEY: Pitch = pitch(21);
+const HIGHEST_PIANO_KEY: Pitch = pitch(108);
+/// Note that a Scala tuning starts from unless told otherwise
+const MIDDLE_C: Pitch = pitch(60);
+/// Throwaway notes played to measure the latency
+const CALIBRATION_NOTES: NonZeroU8 = match NonZeroU8::new(4) {
+    Some(notes) => notes,
+    None => unreachable!(),
+};
+
+/// A pitch for a constant, checked as it is compiled
+const fn pitch(note_number: u8) -> Pitch {
+    match Pitch::new(note_number) {
+        Ok(pitch) => pitch,
+        Err(_) => panic!("not a MIDI note number"),
+    }
+}
+
+/// The instrument to sample, and the devices that play and record it
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+#[cfg_attr(
+    feature = "clap",
+    command(group(clap::ArgGroup::new("instrument").args(["plugin", "simulate", "input_file"])))
+)]
+pub struct Setup {
+    /// Select an audio host by index
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub host: Option<Matcher>,
+    /// Select an audio input to record from
+    ///
+    /// Repeat to record from several inputs at once, such as close and room
+    /// mics on separate interfaces. Each input's files go in a subdirectory
+    /// of their own (mic1, mic2 and so on) with a manifest of their own, all
+    /// cut at the same moments as the first input's.
+    #[cfg_attr(feature = "clap", arg(long, short = 'i'))]
+    pub input_device: Vec<Matcher>,
+    /// Record these input channels (one, or a pair separated by a comma), counting from 1,
+    /// from each input [default: the first two]
+    #[cfg_attr(feature = "clap", arg(long, value_delimiter = ',', num_args = 1..=2))]
+    pub channels: Vec<NonZeroU16>,
+    /// Frames in each buffer of audio from the inputs [default: the smallest
+    /// power of two the device allows, from 32]
+    ///
+    /// Some interfaces glitch at the default size, and most run more
+    /// reliably at a larger one.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "FRAMES"))]
+    pub buffer_size: Option<NonZeroU32>,
+    /// Open the inputs for this program alone, bypassing the system's mixer
+    ///
+    /// Only ALSA supports this, by opening the hardware device of the
+    /// selected input's card (`hw:CARD=...`). Elsewhere, the inputs are
+    /// shared as usual.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub exclusive: bool,
+    /// Select a MIDI port to output to
+    #[cfg_attr(feature = "clap", arg(long, default_value = "0"))]
+    pub midi_port: Matcher,
+    /// Send MIDI to a network (AppleMIDI / RTP-MIDI) session at this
+    /// address, e.g. 192.168.1.20:5004, instead of a MIDI port
+    ///
+    /// The address is the session's control port, with its data port on the
+    /// port after it.
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "ADDRESS", conflicts_with = "midi_port")
+    )]
+    pub rtp_midi: Option<std::net::SocketAddr>,
+    /// Log every MIDI message sent, with the time and the frame of the
+    /// sequence it was played at, to this file [default: standard error]
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "PATH",
+            num_args = 0..=1,
+            default_missing_value = "-"
+        )
+    )]
+    pub midi_monitor: Option<PathBuf>,
+    /// Play and record a CLAP plugin directly, instead of a MIDI port and audio input
+    ///
+    /// The first plugin in the `.clap` file or bundle is loaded. VST3 plugins
+    /// are not supported; host them in a DAW and record them over MIDI instead.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "PATH", conflicts_with_all = ["host", "input_device", "buffer_size", "exclusive", "midi_port", "rtp_midi", "midi_monitor", "sysex_file", "sysex", "dump_request"]))]
+    pub plugin: Option<PathBuf>,
+    /// Record synthetic audio instead of a MIDI port and audio input, to try
+    /// out a run's files and manifests without any hardware
+    ///
+    /// `tones` plays a sine tone at the pitch of each note, louder the higher
+    /// its velocity, and `silence` plays nothing.
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "AUDIO",
+            num_args = 0..=1,
+            default_missing_value = "tones",
+            conflicts_with_all = ["host", "input_device", "buffer_size", "exclusive", "midi_port", "rtp_midi", "midi_monitor", "sysex_file", "sysex", "dump_request"]
+        )
+    )]
+    pub simulate: Option<Simulation>,
+    /// Take the audio from a recording of the run made by another program,
+    /// instead of a MIDI port and audio input, and cut it into samples
+    ///
+    /// The recording is a WAV file, or `-` for raw 16-bit little-endian
+    /// audio on standard input, and must start at the first frame of the
+    /// sequence. As its timing is fixed, no zone is recorded again and gaps
+    /// are not shortened.
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["host", "input_device", "buffer_size", "exclusive", "midi_port", "rtp_midi", "midi_monitor", "sysex_file", "sysex", "dump_request"]
+        )
+    )]
+    pub input_file: Option<PathBuf>,
+    /// Channels of raw audio on standard input
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 2, requires = "input_file")
+    )]
+    pub raw_channels: u16,
+    /// Sample rate to run the plugin or simulation at, or of raw audio on standard input
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 48_000, requires = "instrument")
+    )]
+    pub plugin_sample_rate: u32,
+    /// Render the plugin or simulation as fast as possible, instead of in real time
+    #[cfg_attr(feature = "clap", arg(long, requires = "instrument"))]
+    pub offline: bool,
+    /// Select a MIDI channel to send on
+    #[cfg_attr(feature = "clap", arg(long, short = 'c', default_value_t = ONE))]
+    pub midi_channel: NonZeroU8,
+    /// Sample an MPE instrument, using a lower zone with this many member channels
+    #[cfg_attr(feature = "clap", arg(long, value_name = "MEMBER_CHANNELS"))]
+    pub mpe: Option<NonZeroU8>,
+    /// Select this program before sampling
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub program: Option<u8>,
+    /// Select this bank along with the program, as controller 0 and optionally 32
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "MSB[,LSB]", requires = "program")
+    )]
+    pub bank: Option<Bank>,
+    /// Time to wait after selecting the program, for the instrument to load it, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 0.5))]
+    pub program_delay: f64,
+    /// Send the SysEx messages in this file before sampling, e.g. to put the
+    /// instrument in a known state
+    ///
+    /// Repeat to send several files, in order. Their messages are sent
+    /// before those of --sysex, and before the program is selected.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "PATH"))]
+    pub sysex_file: Vec<PathBuf>,
+    /// Send this SysEx message before sampling, as hex bytes, e.g. "F0 7E 7F 09 01 F7"
+    ///
+    /// Repeat to send several messages, in order.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "HEX"))]
+    pub sysex: Vec<SysEx>,
+    /// Time to wait after sending each SysEx message, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 0.1))]
+    pub sysex_delay: f64,
+    /// Ask the instrument for a dump of its patch with this SysEx message,
+    /// and save what it sends back as patch.syx alongside the recordings
+    #[cfg_attr(feature = "clap", arg(long, value_name = "HEX"))]
+    pub dump_request: Option<SysEx>,
+    /// Select a MIDI input port to receive the patch dump on
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value = "0", requires = "dump_request")
+    )]
+    pub dump_port: Matcher,
+    /// Time to wait for the patch dump, and for each part of it, in seconds
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 2.0, requires = "dump_request")
+    )]
+    pub dump_timeout: f64,
+    /// Listen for OSC commands on this address, e.g. 0.0.0.0:9000, and send
+    /// the run's progress to every client heard from
+    ///
+    /// Commands: /multirec/start, /multirec/pause, /multirec/skip (the rest
+    /// of the current note) and /multirec/abort. Status: /multirec/progress,
+    /// /multirec/zone and /multirec/state.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "ADDRESS"))]
+    pub osc: Option<std::net::SocketAddr>,
+    /// Wait for /multirec/start before playing anything
+    #[cfg_attr(feature = "clap", arg(long, requires = "osc"))]
+    pub osc_wait: bool,
+    /// Serve the run's progress as JSON on this address, at /status, e.g.
+    /// 127.0.0.1:8080, or 0.0.0.0:8080 to let other machines see it
+    #[cfg_attr(feature = "clap", arg(long, value_name = "ADDRESS"))]
+    pub status_address: Option<std::net::SocketAddr>,
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self {
+            host: None,
+            input_device: Vec::new(),
+            channels: Vec::new(),
+            buffer_size: None,
+            exclusive: false,
+            midi_port: Matcher::Index(0),
+            rtp_midi: None,
+            midi_monitor: None,
+            plugin: None,
+            simulate: None,
+            input_file: None,
+            raw_channels: 2,
+            plugin_sample_rate: 48_000,
+            offline: false,
+            midi_channel: ONE,
+            mpe: None,
+            program: None,
+            bank: None,
+            program_delay: 0.5,
+            sysex_file: Vec::new(),
+            sysex: Vec::new(),
+            sysex_delay: 0.1,
+            dump_request: None,
+            dump_port: Matcher::Index(0),
+            dump_timeout: 2.0,
+            osc: None,
+            osc_wait: false,
+            status_address: None,
+        }
+    }
+}
+
+impl Setup {
+    /// Get the bank and program to select, if any
+    pub fn patch(&self) -> anyhow::Result<Option<PatchSelect>> {
+        let Some(program) = self.program else {
+            return Ok(None);
+        };
+
+        let mut patch = PatchSelect::new(program)?;
+        if let Some(bank) = self.bank {
+            patch = patch.with_bank_msb(bank.msb)?;
+            if let Some(lsb) = bank.lsb {
+                patch = patch.with_bank_lsb(lsb)?;
+            }
+        }
+
+        Ok(Some(patch))
+    }
+
+    /// Get the time to wait after selecting a program
+    pub fn program_delay(&self) -> anyhow::Result<Duration> {
+        Ok(Duration::try_from_secs_f64(self.program_delay)?)
+    }
+
+    /// Get the SysEx messages to send before sampling, from files and then the command line
+    pub fn sysex(&self) -> anyhow::Result<Vec<SysEx>> {
+        let mut messages = Vec::new();
+        for path in &self.sysex_file {
+            messages.extend(SysEx::read_file(path)?);
+        }
+        messages.extend(self.sysex.iter().cloned());
+
+        Ok(messages)
+    }
+
+    /// Get the time to wait after each SysEx message
+    pub fn sysex_delay(&self) -> anyhow::Result<Duration> {
+        Ok(Duration::try_from_secs_f64(self.sysex_delay)?)
+    }
+
+    /// Get the time to wait for a patch dump
+    pub fn dump_timeout(&self) -> anyhow::Result<Duration> {
+        Ok(Duration::try_from_secs_f64(self.dump_timeout)?)
+    }
+}
+
+/// What to do with the instrument
+// parsed once at startup, so the size of `Run` does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Subcommand))]
+pub enum Task {
+    /// Run the auto-sampling routine
+    Run(RunOptions),
+    /// Play a single note to check routing configuration
+    Test {
+        /// Print configuration and exit
+        #[cfg_attr(feature = "clap", clap(long, short = 'n'))]
+        dry_run: bool,
+        /// Note to test (MIDI note name or number)
+        #[cfg_attr(feature = "clap", arg(long, default_value = "48"))]
+        note: Pitch,
+        #[cfg_attr(feature = "clap", clap(flatten))]
+        timing: Timing,
+    },
+    /// Play one note at a range of velocities, and suggest velocity layers from how loud each is
+    ///
+    /// Enough layers are suggested that each spans no more than
+    /// --layer-spacing, with the velocities at which the loudness steps
+    /// evenly. The options for a run are printed alone on standard output,
+    /// so they can be passed straight on, e.g. `multirec run $(multirec
+    /// measure --note C4)`.
+    Measure {
+        /// Note to play (MIDI note name or number)
+        #[cfg_attr(feature = "clap", arg(long, default_value = "48"))]
+        note: Pitch,
+        /// Number of velocities to play, spread evenly up to 127
+        #[cfg_attr(feature = "clap", arg(long, default_value = "16"))]
+        points: NonZeroU8,
+        /// Most difference in loudness for a velocity layer to span
+        #[cfg_attr(feature = "clap", arg(long, default_value = "6dB"))]
+        layer_spacing: Decibels,
+        /// Level that a velocity must be heard above to be mapped
+        #[cfg_attr(
+            feature = "clap",
+            arg(long, default_value = "-60dB", allow_hyphen_values = true)
+        )]
+        floor: Decibels,
+        #[cfg_attr(feature = "clap", clap(flatten))]
+        timing: Timing,
+    },
+}
+
+/// The options of a run, which records every zone of its plan
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct RunOptions {
+    /// Multi-sample package format to generate
+    ///
+    /// Archives are packed from the output directory once every file is
+    /// recorded and processed, and each file is moved into the archive as
+    /// it is packed, so the recordings only take up their size once on disk.
+    #[cfg_attr(feature = "clap", arg(long, short = 'f', default_value = "raw"))]
+    pub format: OutputFormat,
+    /// Keep the recorded files after packing them into a zip or Bitwig archive
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub keep_raw: bool,
+    /// Add to an existing Bitwig multisample of the same name instead of replacing it
+    ///
+    /// Samples recorded again replace those of the same name, and the key and
+    /// velocity ranges of every sample are worked out again.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub append: bool,
+    /// Shell command to run on each recorded file, such as "sox {in} {out} ..."
+    ///
+    /// `{in}` is the recorded file, and what the command writes to `{out}` replaces it.
+    /// Without `{out}`, the command should change the file where it is.
+    /// `{name}` is the file name without its extension.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "COMMAND"))]
+    pub post_cmd: Option<PostCommand>,
+    /// Number of files to run the post-processing command on at once [default: one per CPU]
+    #[cfg_attr(feature = "clap", arg(long, requires = "post_cmd"))]
+    pub post_jobs: Option<NonZeroUsize>,
+    /// Sample format of the recorded WAV files
+    #[cfg_attr(feature = "clap", arg(long, default_value = "16"))]
+    pub bit_depth: BitDepth,
+    /// Convert the recorded files to this sample rate, in Hz
+    ///
+    /// The audio is recorded at the input's rate, and converted with a
+    /// band-limited (windowed sinc) filter before the post-processing
+    /// command runs. Loop points and offsets in manifests count frames at
+    /// the new rate.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(8_000..=384_000)))]
+    pub target_rate: Option<u32>,
+    /// Directory to save recordings in [default: current]
+    #[cfg_attr(feature = "clap", arg(long, short = 'o'))]
+    pub output_directory: Option<PathBuf>,
+    /// Start recording even if the output directory's volume seems to
+    /// lack space for it, instead of refusing to
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub ignore_disk_space: bool,
+    /// What to do if files or a bundle that the run would write already exist
+    ///
+    /// This is checked before anything is recorded. Resumed runs and
+    /// --append expect to find their files, so are not checked.
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "POLICY", default_value = "overwrite")
+    )]
+    pub on_conflict: OnConflict,
+    /// Prefix for file names
+    #[cfg_attr(feature = "clap", arg(long, short = 'p'))]
+    pub file_prefix: Option<String>,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub metadata: Metadata,
+    /// Pattern for file names, without the extension
+    ///
+    /// Tokens: {prefix}, {pitch} (e.g. C#4), {note} (e.g. C#), {octave},
+    /// {midi} (note number), {vel} (velocity), {rr} (round robin, from 1),
+    /// {dyn} (velocity named by --dynamics), {art} (articulation
+    /// selected by --keyswitch), {cc} (values of controllers swept by
+    /// --cc, e.g. CC74-64) and {pad} (pad named by --drumkit). A width pads the
+    /// value, with zeros if it starts with 0, e.g. {vel:03}. Text in
+    /// square brackets is left out if a token inside it has no value,
+    /// e.g. {vel} with a single velocity layer.
+    ///
+    /// [default: "[{prefix}_][{art}_]{pitch}[_V{vel}][_{cc}][_RR{rr}]", or
+    /// with --dynamics "[{prefix}_][{art}_]{pitch}[_{dyn}][_{cc}][_RR{rr}]";
+    /// with --drumkit "[{prefix}_]{pad}[_V{vel}][_RR{rr}]", or with
+    /// --dynamics as well "[{prefix}_]{pad}[_{dyn}][_RR{rr}]"]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub name_template: Option<NameTemplate>,
+    /// How to sort the recorded files into directories, with manifests
+    /// pointing into them
+    ///
+    /// `per-note` makes a directory for each note (or drum kit pad), and
+    /// `per-layer` one for each velocity layer, named like the layers in
+    /// file names.
+    #[cfg_attr(feature = "clap", arg(long, default_value = "flat"))]
+    pub layout: Layout,
+    /// Name velocity layers with dynamic markings in file and group names
+    ///
+    /// The table lists each name with the lowest velocity it applies to.
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "TABLE",
+            num_args = 0..=1,
+            default_missing_value = crate::naming::DEFAULT_DYNAMICS
+        )
+    )]
+    pub dynamics: Option<Dynamics>,
+    /// Print configuration and exit
+    #[cfg_attr(feature = "clap", clap(long, short = 'n'))]
+    pub dry_run: bool,
+    /// Start recording without asking to confirm the plan
+    ///
+    /// The plan of the run (how many files, how long it takes and how much
+    /// space they need) is printed first either way. Without this, it has
+    /// to be confirmed at a terminal.
+    #[cfg_attr(feature = "clap", clap(long, short = 'y'))]
+    pub yes: bool,
+    /// Lowest note to sample (MIDI note name or number)
+    #[cfg_attr(feature = "clap", arg(long, default_value = "21"))]
+    pub start: Pitch,
+    /// Highest note to sample (MIDI note name or number)
+    #[cfg_attr(feature = "clap", arg(long, default_value = "108"))]
+    pub end: Pitch,
+    /// Step between notes, in semitones
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = ONE))]
+    pub step: NonZeroU8,
+    /// Sample only these notes, e.g. `C1,E1,G1` (MIDI note names or numbers)
+    #[cfg_attr(feature = "clap", arg(long, value_name = "NOTES", conflicts_with_all = ["start", "end", "step"]))]
+    pub notes: Option<NoteList>,
+    /// Sample only the notes of a scale from --start to --end, e.g. `C-minor-pentatonic`
+    ///
+    /// Scales: major, minor, harmonic-minor, melodic-minor, dorian,
+    /// phrygian, lydian, mixolydian, locrian, major-pentatonic,
+    /// minor-pentatonic, blues, whole-tone and chromatic.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "ROOT-NAME", conflicts_with_all = ["notes", "step"]))]
+    pub scale: Option<Scale>,
+    /// Number of velocity layers to sample
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = ONE))]
+    pub velocity_layers: NonZeroU8,
+    /// Range of velocities to spread the velocity layers over, e.g. `20..127`
+    ///
+    /// Each layer is recorded at the top of its share of the range, so
+    /// the softest layer stands in for the velocities below it as well.
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "LOW..HIGH", default_value = "0..127")
+    )]
+    pub velocity_range: VelocityRange,
+    /// Sample the pads of a drum kit, listed in a file one per line, e.g. `36=Kick`
+    ///
+    /// Only the listed notes are sampled, and files and groups are named
+    /// after the pads, with each pad on its own key. A pad can take its
+    /// own velocity layers and round robins in place of --velocity-layers
+    /// and --round-robins, e.g. `38=Snare,layers=4,rr=3`.
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["start", "end", "step", "notes", "scale", "key_map", "key_xfade"]
+        )
+    )]
+    pub drumkit: Option<PathBuf>,
+    /// Record the notes, velocity layers and round robins of an existing Bitwig multisample
+    ///
+    /// Takes the place of --start, --end, --step, --notes, --scale, --velocity-layers,
+    /// --velocity-range and --round-robins, for recording an instrument again with the
+    /// same mapping. Exactly the bundle's notes and velocities are played, so a
+    /// bundle whose velocities no run would play, e.g. humanized ones, is an error.
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = [
+                "start", "end", "step", "notes", "scale", "drumkit", "velocity_layers", "velocity_range",
+                "round_robins"
+            ]
+        )
+    )]
+    pub from_multisample: Option<PathBuf>,
+    /// Record only these zones of the plan, e.g. `C2@v127 rr2, F#3@v64`
+    ///
+    /// Each zone is a note, optionally followed by the velocity it is
+    /// played at and its round robin, counting from 1. A zone without a
+    /// velocity or round robin stands for every one at its note. At most
+    /// 512 zones can be recorded this way.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "ZONES"))]
+    pub zones: Option<ZoneSelection>,
+    /// Record velocity layers from softest to loudest
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub soft_first: bool,
+    /// Number of round-robin samples to take of each velocity layer
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = ONE))]
+    pub round_robins: NonZeroU8,
+    /// Record every note once per round robin, in passes, instead of back-to-back
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub round_robin_passes: bool,
+    /// Play each round robin on the next of these MIDI channels, counting from 1
+    ///
+    /// With the same patch on each channel of a multitimbral instrument,
+    /// every round robin is played by a voice of its own. Replaces
+    /// --midi-channel, and cannot be combined with --mpe.
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "CHANNELS", value_delimiter = ',')
+    )]
+    pub rr_channels: Vec<NonZeroU8>,
+    /// Tap this note before each zone to select an articulation, named
+    /// after the note or the label given, e.g. `C0=legato`
+    ///
+    /// Repeat to record each articulation in turn, with the articulation
+    /// in file and group names.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "NOTE[=LABEL]"))]
+    pub keyswitch: Vec<Keyswitch>,
+    /// Record every zone at each of these values of a controller, e.g. `74=0,64,127`
+    ///
+    /// Repeat to sweep several controllers. The values are included in
+    /// file names, and mapped to controller ranges in SFZ output.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "CONTROLLER=VALUES"))]
+    pub cc: Vec<ControllerSweep>,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub trim_start: TrimStart,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub trim_end: TrimEnd,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub mono: Mono,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub gap: Gap,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub calibration: Calibration,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub noise_floor: NoiseFloor,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub count_in: CountIn,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub retries: Retries,
+    /// Make up for the delay between sending each note and hearing it;
+    /// `trim` drops that many frames from the start of each file, and
+    /// `offset` sets the start of each sample in SFZ and Bitwig output
+    #[cfg_attr(feature = "clap", arg(long, default_value = "off"))]
+    pub latency_compensation: LatencyCompensation,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub normalization: Normalization,
+    /// How the keys between sampled notes are shared out among the samples in SFZ and Bitwig output
+    #[cfg_attr(feature = "clap", arg(long, default_value = "nearest"))]
+    pub key_map: KeyMap,
+    /// Overlap neighbouring samples by this many keys in SFZ and Bitwig output, fading between them
+    #[cfg_attr(feature = "clap", arg(long, value_name = "KEYS", default_value_t = 0))]
+    pub key_xfade: u8,
+    /// Find loop points in the sustain of each sample, for SFZ and Bitwig output
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub detect_loops: bool,
+    /// Crossfade each detected loop in the audio itself, instead of
+    /// setting the crossfade in SFZ and Bitwig output
+    #[cfg_attr(feature = "clap", arg(long, requires = "detect_loops"))]
+    pub render_loop_xfade: bool,
+    /// Move trim points and loop points to the nearest zero crossing, to avoid clicks
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub snap_to_zero: bool,
+    /// Measure the pitch of each sample, warning about any that sound
+    /// the wrong note and tuning the rest exactly in SFZ and Bitwig output
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub detect_pitch: bool,
+    /// Write a report describing every recorded file, for scripts and QA review
+    ///
+    /// The report is JSON, unless PATH ends in `.csv` for a spreadsheet
+    /// or `.html` for a table to read in a browser, with a row per file
+    /// giving its duration, levels, tuning, latency and warnings. Repeat
+    /// to write the report in several formats.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "PATH"))]
+    pub report: Vec<PathBuf>,
+    /// Say when the run is over, whether it completed or stopped early:
+    /// `desktop` for a desktop notification, or a webhook URL to POST a
+    /// JSON summary to
+    ///
+    /// Repeat to notify in several ways. Webhooks include a `text` field
+    /// for chat services such as Slack, and are sent by running `curl`,
+    /// which must be on the PATH. It comes with macOS, Windows 10 and
+    /// later and most Linux distributions, but not older Windows.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "desktop|URL"))]
+    pub notify: Vec<notify::Target>,
+    /// Play each sample back as soon as it is recorded, to hear any
+    /// problems while the instrument is still set up
+    ///
+    /// When samples finish faster than they can be played, only the latest is played.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub audition: bool,
+    /// Select an audio output to audition samples on [default: the host's default]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "DEVICE", requires = "audition")
+    )]
+    pub audition_device: Option<Matcher>,
+    /// Record the whole run into one file, and split it into a file per
+    /// zone once the run is over
+    ///
+    /// The files are cut where each zone was played, each moved to start
+    /// the same time before the onset heard in it. This avoids switching
+    /// files while recording, which can cause dropouts on slow disks.
+    #[cfg_attr(feature = "clap", arg(long, conflicts_with = "audition"))]
+    pub single_take: bool,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub timing: Timing,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub humanize: Humanize,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub burst: Burst,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub poly_pressure: PolyPressure,
+    #[cfg_attr(feature = "clap", clap(flatten))]
+    pub microtuning: Microtuning,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Raw,
+            keep_raw: false,
+            append: false,
+            post_cmd: None,
+            post_jobs: None,
+            bit_depth: BitDepth::Int16,
+            target_rate: None,
+            output_directory: None,
+            ignore_disk_space: false,
+            on_conflict: OnConflict::Overwrite,
+            file_prefix: None,
+            metadata: Metadata::default(),
+            name_template: None,
+            layout: Layout::Flat,
+            dynamics: None,
+            dry_run: false,
+            yes: false,
+            start: LOWEST_PIANO_KEY,
+            end: HIGHEST_PIANO_KEY,
+            step: ONE,
+            notes: None,
+            scale: None,
+            velocity_layers: ONE,
+            velocity_range: VelocityRange(0..=127),
+            drumkit: None,
+            from_multisample: None,
+            zones: None,
+            soft_first: false,
+            round_robins: ONE,
+            round_robin_passes: false,
+            rr_channels: Vec::new(),
+            keyswitch: Vec::new(),
+            cc: Vec::new(),
+            trim_start: TrimStart::default(),
+            trim_end: TrimEnd::default(),
+            mono: Mono::default(),
+            gap: Gap::default(),
+            calibration: Calibration::default(),
+            noise_floor: NoiseFloor::default(),
+            count_in: CountIn::default(),
+            retries: Retries::default(),
+            latency_compensation: LatencyCompensation::Off,
+            normalization: Normalization::default(),
+            key_map: KeyMap::Nearest,
+            key_xfade: 0,
+            detect_loops: false,
+            render_loop_xfade: false,
+            snap_to_zero: false,
+            detect_pitch: false,
+            report: Vec::new(),
+            notify: Vec::new(),
+            audition: false,
+            audition_device: None,
+            single_take: false,
+            timing: Timing::default(),
+            humanize: Humanize::default(),
+            burst: Burst::default(),
+            poly_pressure: PolyPressure::default(),
+            microtuning: Microtuning::default(),
+        }
+    }
+}
+
+/// The options for building an instrument from existing samples
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct MapOptions {
+    /// Directory of WAV files to map
+    pub directory: PathBuf,
+    /// Multi-sample package format to generate
+    #[cfg_attr(feature = "clap", arg(long, short = 'f', default_value = "sfz"))]
+    pub format: MapFormat,
+    /// Name of the instrument [default: the directory's name]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub name: Option<String>,
+    /// Dynamic markings that may stand for velocities in file names
+    ///
+    /// The table lists each name with the lowest velocity it applies to,
+    /// and each marking is mapped to the top of its range.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "TABLE", default_value = crate::naming::DEFAULT_DYNAMICS))]
+    pub dynamics: Dynamics,
+    /// Measure the pitch of each sample, finding the note of files whose
+    /// name has none and tuning the rest exactly
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub detect_pitch: bool,
+    /// How the keys between sampled notes are shared out among the samples
+    #[cfg_attr(feature = "clap", arg(long, default_value = "nearest"))]
+    pub key_map: KeyMap,
+    /// Overlap neighbouring samples by this many keys, fading between them
+    #[cfg_attr(feature = "clap", arg(long, value_name = "KEYS", default_value_t = 0))]
+    pub key_xfade: u8,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Timing {
+    /// Length of each note before sending NoteOff message, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 1.0))]
+    pub sustain: f64,
+    /// Time to wait after NoteOff before starting next note, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 0.5))]
+    pub release: f64,
+    /// Measure sustain and release in beats at this tempo, and send MIDI clock
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub bpm: Option<f64>,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            sustain: 1.0,
+            release: 0.5,
+            bpm: None,
+        }
+    }
+}
+
+impl Timing {
+    /// Get the note length, gap length and clock tempo
+    pub fn resolve(&self) -> anyhow::Result<(Duration, Duration, Option<Tempo>)> {
+        Ok(match self.bpm {
+            Some(bpm) => {
+                let tempo = Tempo::from_bpm(bpm)?;
+                (
+                    tempo.beats(self.sustain),
+                    tempo.beats(self.release),
+                    Some(tempo),
+                )
+            }
+            None => (
+                Duration::from_secs_f64(self.sustain),
+                Duration::from_secs_f64(self.release),
+                None,
+            ),
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Humanize {
+    /// Vary each sample's timing and velocity, reproducibly from this seed
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub humanize_seed: Option<u64>,
+    /// Largest change to a note's start time, in seconds
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 0.01, requires = "humanize_seed")
+    )]
+    pub humanize_timing: f64,
+    /// Largest change to a note's velocity
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 4, requires = "humanize_seed")
+    )]
+    pub humanize_velocity: u8,
+}
+
+impl Default for Humanize {
+    fn default() -> Self {
+        Self {
+            humanize_seed: None,
+            humanize_timing: 0.01,
+            humanize_velocity: 4,
+        }
+    }
+}
+
+impl Humanize {
+    pub fn resolve(&self) -> Option<autosam::Humanize> {
+        self.humanize_seed.map(|seed| autosam::Humanize {
+            seed,
+            timing: Duration::from_secs_f64(self.humanize_timing),
+            velocity: self.humanize_velocity,
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Microtuning {
+    /// Sample in the tuning of a Scala file, bending each note to its pitch
+    ///
+    /// Pitch detection then measures each sample against its tuned pitch,
+    /// so that the tuning is kept in SFZ and Bitwig output.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "PATH"))]
+    pub scala: Option<PathBuf>,
+    /// Note that the first degree of the scale falls on, at its equal-tempered pitch
+    #[cfg_attr(feature = "clap", arg(long, default_value = "60", requires = "scala"))]
+    pub scala_root: Pitch,
+    /// Pitch bend range of the instrument, in semitones
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 2, requires = "scala"))]
+    pub bend_range: u8,
+}
+
+impl Default for Microtuning {
+    fn default() -> Self {
+        Self {
+            scala: None,
+            scala_root: MIDDLE_C,
+            bend_range: 2,
+        }
+    }
+}
+
+impl Microtuning {
+    /// Get the tuning, its root note and the bend range, if there is one
+    pub fn resolve(&self) -> anyhow::Result<Option<(scala::Tuning, u8, u8)>> {
+        let Some(path) = &self.scala else {
+            return Ok(None);
+        };
+
+        if self.bend_range == 0 {
+            anyhow::bail!("A bend range of 0 semitones cannot tune any notes");
+        }
+
+        Ok(Some((
+            scala::Tuning::read(path)?,
+            self.scala_root.note_number(),
+            self.bend_range,
+        )))
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Burst {
+    /// Play each note this many times in quick succession, for sampling rolls
+    #[cfg_attr(feature = "clap", arg(long, value_name = "HITS"))]
+    pub burst: Option<NonZeroU8>,
+    /// Time between the starts of consecutive hits, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 0.1, requires = "burst"))]
+    pub burst_interval: f64,
+}
+
+impl Default for Burst {
+    fn default() -> Self {
+        Self {
+            burst: None,
+            burst_interval: 0.1,
+        }
+    }
+}
+
+impl Burst {
+    pub fn resolve(&self) -> Option<autosam::Burst> {
+        self.burst.map(|hits| autosam::Burst {
+            hits,
+            interval: Duration::from_secs_f64(self.burst_interval),
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct PolyPressure {
+    /// Send per-note aftertouch, starting at this value, while each note is held
+    #[cfg_attr(feature = "clap", arg(long, value_name = "PRESSURE"))]
+    pub poly_pressure: Option<u8>,
+    /// Per-note aftertouch value to sweep to [default: no change]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "PRESSURE", requires = "poly_pressure")
+    )]
+    pub poly_pressure_end: Option<u8>,
+    /// Number of per-note aftertouch messages to send for each note
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = ONE, requires = "poly_pressure"))]
+    pub poly_pressure_steps: NonZeroU8,
+}
+
+impl Default for PolyPressure {
+    fn default() -> Self {
+        Self {
+            poly_pressure: None,
+            poly_pressure_end: None,
+            poly_pressure_steps: ONE,
+        }
+    }
+}
+
+impl PolyPressure {
+    pub fn resolve(&self) -> Option<autosam::PolyPressure> {
+        self.poly_pressure.map(|start| autosam::PolyPressure {
+            start,
+            end: self.poly_pressure_end.unwrap_or(start),
+            steps: self.poly_pressure_steps,
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct TrimStart {
+    /// Discard silence at the beginning of each sample
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub trim_start: bool,
+    /// Level that the signal must rise above to count as the start of a note
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            default_value = "-60dB",
+            allow_hyphen_values = true,
+            requires = "trim_start"
+        )
+    )]
+    pub trim_threshold: Decibels,
+    /// Time to keep before the start of each note, in seconds
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 0.005, requires = "trim_start")
+    )]
+    pub trim_guard: f64,
+}
+
+impl Default for TrimStart {
+    fn default() -> Self {
+        Self {
+            trim_start: false,
+            trim_threshold: Decibels(-60.0),
+            trim_guard: 0.005,
+        }
+    }
+}
+
+impl TrimStart {
+    /// Get the threshold amplitude and guard time
+    pub fn resolve(&self) -> Option<(f32, Duration)> {
+        self.trim_start.then(|| {
+            (
+                self.trim_threshold.amplitude(),
+                Duration::from_secs_f64(self.trim_guard),
+            )
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Mono {
+    /// Write mono files; `auto` does so only if the channels are identical or one is silent
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "MODE",
+            default_value = "off",
+            default_missing_value = "on",
+            num_args = 0..=1
+        )
+    )]
+    pub mono: MonoMode,
+    /// Level below which a channel, or the difference between channels, counts as silence
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value = "-60dB", allow_hyphen_values = true)
+    )]
+    pub mono_threshold: Decibels,
+}
+
+impl Default for Mono {
+    fn default() -> Self {
+        Self {
+            mono: MonoMode::Off,
+            mono_threshold: Decibels(-60.0),
+        }
+    }
+}
+
+impl Mono {
+    /// Get the mode and the silence threshold amplitude, if files may become mono
+    pub fn resolve(&self) -> Option<(MonoMode, f32)> {
+        match self.mono {
+            MonoMode::Off => None,
+            mode => Some((mode, self.mono_threshold.amplitude())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum MonoMode {
+    /// Keep both channels
+    Off,
+    /// Always write mono, mixing different channels together
+    On,
+    /// Write mono if every recording's channels are identical or one is silent
+    Auto,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct TrimEnd {
+    /// Discard the end of each sample once the signal has decayed
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub trim_end: bool,
+    /// Level that the signal must stay below to count as decayed
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            default_value = "-60dB",
+            allow_hyphen_values = true,
+            requires = "trim_end"
+        )
+    )]
+    pub trim_end_threshold: Decibels,
+    /// Time to keep after the signal has decayed, in seconds
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 0.05, requires = "trim_end")
+    )]
+    pub trim_end_hold: f64,
+}
+
+impl Default for TrimEnd {
+    fn default() -> Self {
+        Self {
+            trim_end: false,
+            trim_end_threshold: Decibels(-60.0),
+            trim_end_hold: 0.05,
+        }
+    }
+}
+
+impl TrimEnd {
+    /// Get the threshold amplitude and hold time
+    pub fn resolve(&self) -> Option<(f32, Duration)> {
+        self.trim_end.then(|| {
+            (
+                self.trim_end_threshold.amplitude(),
+                Duration::from_secs_f64(self.trim_end_hold),
+            )
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Gap {
+    /// How long to wait after each zone; `auto` moves on once the signal has decayed,
+    /// waiting at most the release time
+    #[cfg_attr(feature = "clap", arg(long, default_value = "fixed"))]
+    pub gap: GapMode,
+    /// Level that the signal must stay below to count as decayed
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value = "-60dB", allow_hyphen_values = true)
+    )]
+    pub gap_threshold: Decibels,
+    /// Time that the signal must stay decayed for, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 0.25))]
+    pub gap_hold: f64,
+    /// Keep recording for at least this long after each NoteOff, in seconds,
+    /// so that long release tails are not cut off
+    ///
+    /// Waits longer than the release time if need be, and `--gap auto` does
+    /// not move on before the tail is over. SFZ loops are then only played
+    /// while the key is held, so the tail is heard after it is released.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "SECONDS"))]
+    pub tail: Option<f64>,
+    /// Message to send on every channel in use once each gap is over,
+    /// before the next zone starts, so nothing carries over into it
+    #[cfg_attr(feature = "clap", arg(long, default_value = "none"))]
+    pub reset_between_notes: NoteReset,
+    /// Send "Reset All Controllers" along with the reset between notes
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub reset_controllers: bool,
+}
+
+impl Default for Gap {
+    fn default() -> Self {
+        Self {
+            gap: GapMode::Fixed,
+            gap_threshold: Decibels(-60.0),
+            gap_hold: 0.25,
+            tail: None,
+            reset_between_notes: NoteReset::None,
+            reset_controllers: false,
+        }
+    }
+}
+
+impl Gap {
+    /// Get the threshold amplitude and hold time, if the gap is adaptive
+    pub fn resolve(&self) -> Option<(f32, Duration)> {
+        match self.gap {
+            GapMode::Fixed => None,
+            GapMode::Auto => Some((
+                self.gap_threshold.amplitude(),
+                Duration::from_secs_f64(self.gap_hold),
+            )),
+        }
+    }
+
+    /// Get the least time to record after each NoteOff, if there is one
+    pub fn tail(&self) -> Option<Duration> {
+        self.tail.map(Duration::from_secs_f64)
+    }
+
+    /// Get the messages to send before each zone
+    pub fn zone_reset(&self) -> Cleanup {
+        Cleanup {
+            all_notes_off: matches!(self.reset_between_notes, NoteReset::AllNotesOff),
+            all_sound_off: matches!(self.reset_between_notes, NoteReset::AllSoundOff),
+            reset_all_controllers: self.reset_controllers,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum NoteReset {
+    /// Send nothing
+    None,
+    /// Release any notes still held (controller 123)
+    AllNotesOff,
+    /// Silence the instrument at once, tails included (controller 120)
+    AllSoundOff,
+}
+
+/// Details to describe the instrument with, in Bitwig output and in each file
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Metadata {
+    /// Category of the instrument, such as "Piano" or "Pad"
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub category: Option<String>,
+    /// Who made the instrument
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub creator: Option<String>,
+    /// A description of the instrument
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub description: Option<String>,
+    /// Keywords to find the instrument by, separated by commas
+    #[cfg_attr(feature = "clap", arg(long, value_delimiter = ','))]
+    pub keywords: Vec<String>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Retries {
+    /// Times to record a zone again if nothing is heard before its release
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 2))]
+    pub retries: u8,
+    /// Level that the signal must rise above for a zone to count as heard
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value = "-60dB", allow_hyphen_values = true)
+    )]
+    pub dead_threshold: Decibels,
+    /// Also record a zone again if samples were lost because writing fell behind
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub rerecord_dropouts: bool,
+}
+
+impl Default for Retries {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            dead_threshold: Decibels(-60.0),
+            rerecord_dropouts: false,
+        }
+    }
+}
+
+impl Retries {
+    /// Get the threshold amplitude, the number of retries and whether to retry after dropouts
+    pub fn resolve(&self) -> (f32, u8, bool) {
+        (
+            self.dead_threshold.amplitude(),
+            self.retries,
+            self.rerecord_dropouts,
+        )
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Calibration {
+    /// Measure the latency with a few throwaway notes, and confirm it, before recording
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub calibrate: bool,
+    /// Number of throwaway notes to play
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value = "4", requires = "calibrate")
+    )]
+    pub calibrate_notes: NonZeroU8,
+    /// Largest difference between measurements to accept, in seconds
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 0.005, requires = "calibrate")
+    )]
+    pub calibrate_tolerance: f64,
+    /// Level that counts as the start of a note
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            default_value = "-50dB",
+            allow_hyphen_values = true,
+            requires = "calibrate"
+        )
+    )]
+    pub calibrate_threshold: Decibels,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            calibrate: false,
+            calibrate_notes: CALIBRATION_NOTES,
+            calibrate_tolerance: 0.005,
+            calibrate_threshold: Decibels(-50.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct NoiseFloor {
+    /// Record the noise floor of the input before the run, saving it with
+    /// the samples as `noise.wav`
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub capture_noise: bool,
+    /// Length of the noise floor recording, in seconds
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = 3.0))]
+    pub noise_length: f64,
+    /// Take the recorded noise floor away from every sample, by spectral subtraction
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub denoise: bool,
+    /// How much of the noise floor to take away; more removes more noise, but
+    /// also more of the sound
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, default_value_t = 1.0, requires = "denoise")
+    )]
+    pub denoise_strength: f64,
+}
+
+impl Default for NoiseFloor {
+    fn default() -> Self {
+        Self {
+            capture_noise: false,
+            noise_length: 3.0,
+            denoise: false,
+            denoise_strength: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct CountIn {
+    /// Play this many beats before the first note, to leave time to get out
+    /// of the room or turn the monitors down
+    ///
+    /// Beats are a second long, or a beat long at --bpm.
+    #[cfg_attr(feature = "clap", arg(long, value_name = "BEATS"))]
+    pub count_in: Option<NonZeroU8>,
+    /// Select an audio output to beep the count-in on [default: the host's default]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "DEVICE", requires = "count_in")
+    )]
+    pub count_in_device: Option<Matcher>,
+    /// Play the count-in as this note over MIDI, instead of beeping
+    #[cfg_attr(
+        feature = "clap",
+        arg(
+            long,
+            value_name = "NOTE",
+            requires = "count_in",
+            conflicts_with = "count_in_device"
+        )
+    )]
+    pub count_in_note: Option<Pitch>,
+    /// MIDI channel to play the count-in note on, counting from 1 [default: --midi-channel]
+    #[cfg_attr(
+        feature = "clap",
+        arg(long, value_name = "CHANNEL", requires = "count_in_note")
+    )]
+    pub count_in_channel: Option<NonZeroU8>,
+}
+
+impl CountIn {
+    /// Get the count-in, if there is one, with beats of `beat` and notes on `channel` by default
+    pub fn resolve(
+        self,
+        beat: Duration,
+        channel: Channel,
+    ) -> anyhow::Result<Option<CountInCue<Option<Matcher>>>> {
+        let Some(beats) = self.count_in else {
+            return Ok(None);
+        };
+
+        let cue = match self.count_in_note {
+            Some(pitch) => {
+                let channel = match self.count_in_channel {
+                    Some(number) => Channel::new(number.get() - 1)?,
+                    None => channel,
+                };
+                Cue::Note(channel, pitch)
+            }
+            None => Cue::Beep(self.count_in_device),
+        };
+        Ok(Some(CountInCue { beats, beat, cue }))
+    }
+}
+
+impl NoiseFloor {
+    /// Get the length of noise to record, and the strength to remove it at, if it is wanted
+    pub fn resolve(&self) -> Option<(Duration, Option<f64>)> {
+        (self.capture_noise || self.denoise).then(|| {
+            (
+                Duration::from_secs_f64(self.noise_length),
+                self.denoise.then_some(self.denoise_strength),
+            )
+        })
+    }
+}
+
+impl Calibration {
+    /// Get the number of notes, tolerance and threshold amplitude, if calibrating
+    pub fn resolve(&self) -> Option<(NonZeroU8, Duration, f32)> {
+        self.calibrate.then(|| {
+            (
+                self.calibrate_notes,
+                Duration::from_secs_f64(self.calibrate_tolerance),
+                self.calibrate_threshold.amplitude(),
+            )
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum LatencyCompensation {
+    /// Keep the latency at the start of each file
+    Off,
+    /// Remove the latency from each file
+    Trim,
+    /// Keep the files intact, but start playback after the latency
+    Offset,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum GapMode {
+    /// Always wait for the full release time
+    Fixed,
+    /// Continue as soon as the signal has decayed
+    Auto,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Normalization {
+    /// Adjust the level of each sample to a common target
+    #[cfg_attr(feature = "clap", arg(long, default_value = "off"))]
+    pub normalize: Normalize,
+    /// Level to normalize to [default: -1dB for peak, -20dB for rms]
+    #[cfg_attr(feature = "clap", arg(long, allow_hyphen_values = true))]
+    pub normalize_level: Option<Decibels>,
+    /// Use one gain for all velocity layers and round robins of each note, keeping their relative levels
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub normalize_linked: bool,
+    /// Write each sample's gain to the SFZ or Bitwig manifest, leaving the recordings untouched
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub normalize_in_manifest: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            normalize: Normalize::Off,
+            normalize_level: None,
+            normalize_linked: false,
+            normalize_in_manifest: false,
+        }
+    }
+}
+
+impl Normalization {
+    /// Get the mode, target amplitude, whether gains are linked and whether they go in the manifest
+    pub fn resolve(&self) -> Option<(Normalize, f32, bool, bool)> {
+        let default_level = match self.normalize {
+            Normalize::Off => return None,
+            Normalize::Peak => Decibels(-1.0),
+            Normalize::Rms => Decibels(-20.0),
+        };
+
+        let level = self.normalize_level.unwrap_or(default_level);
+        Some((
+            self.normalize,
+            level.amplitude(),
+            self.normalize_linked,
+            self.normalize_in_manifest,
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Normalize {
+    /// Leave levels as recorded
+    Off,
+    /// Match the highest peak
+    Peak,
+    /// Match the average (RMS) level
+    Rms,
+}
+
+/// A controller to set to each of several values, recording every zone at each
+#[derive(Clone, Debug)]
+pub struct ControllerSweep {
+    pub controller: u8,
+    pub values: Values,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControllerSweepError {
+    #[error("Expected `controller=value,value,...`")]
+    Format,
+    #[error("Invalid controller number `{0}`")]
+    Controller(String),
+    #[error("Invalid controller value `{0}`")]
+    Value(String),
+}
+
+impl std::str::FromStr for ControllerSweep {
+    type Err = ControllerSweepError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (controller, list) = s.split_once('=').ok_or(ControllerSweepError::Format)?;
+        let controller = controller.trim();
+        // 120 and above are channel mode messages
+        let controller = controller
+            .parse::<u8>()
+            .ok()
+            .filter(|c| *c < 120)
+            .ok_or_else(|| ControllerSweepError::Controller(controller.to_string()))?;
+
+        let mut values = Values::new();
+        for value in list.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            values = value
+                .parse()
+                .ok()
+                .and_then(|v| values.with(v).ok())
+                .ok_or_else(|| ControllerSweepError::Value(value.to_string()))?;
+        }
+
+        if values.is_empty() {
+            return Err(ControllerSweepError::Format);
+        }
+
+        Ok(Self { controller, values })
+    }
+}
+
+impl ControllerSweep {
+    /// The controller values that a recording at `value` stands in for,
+    /// reaching halfway to the neighbouring values
+    pub fn range(&self, value: u8) -> std::ops::RangeInclusive<u8> {
+        let low = self
+            .values
+            .iter()
+            .filter(|v| *v < value)
+            .last()
+            .map_or(0, |below| below + (value - below) / 2 + 1);
+        let high = self
+            .values
+            .iter()
+            .find(|v| *v > value)
+            .map_or(127, |above| value + (above - value) / 2);
+
+        low..=high
+    }
+}
+
+/// A bank to select, as controller 0 and optionally 32, e.g. `1,5`
+#[derive(Clone, Copy, Debug)]
+pub struct Bank {
+    msb: u8,
+    lsb: Option<u8>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BankError {
+    #[error("Expected `MSB` or `MSB,LSB`")]
+    Format,
+    #[error("Invalid bank `{0}`, expected 0-127")]
+    Value(String),
+}
+
+impl std::str::FromStr for Bank {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = |v: &str| {
+            let v = v.trim();
+            v.parse::<u8>()
+                .ok()
+                .filter(|v| *v <= 127)
+                .ok_or_else(|| BankError::Value(v.to_string()))
+        };
+
+        match s.split(',').collect::<Vec<_>>()[..] {
+            [msb] => Ok(Self {
+                msb: value(msb)?,
+                lsb: None,
+            }),
+            [msb, lsb] => Ok(Self {
+                msb: value(msb)?,
+                lsb: Some(value(lsb)?),
+            }),
+            _ => Err(BankError::Format),
+        }
+    }
+}
+
+/// A list of notes to sample, e.g. `C1,E1,G1`
+#[derive(Clone, Debug)]
+pub struct NoteList(pub Values);
+
+#[derive(Debug, thiserror::Error)]
+pub enum NoteListError {
+    #[error("Expected `note,note,...`")]
+    Format,
+    #[error("Invalid note `{0}`: {1}")]
+    Note(String, autosam::midi::ParsePitchError),
+}
+
+impl std::str::FromStr for NoteList {
+    type Err = NoteListError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut notes = Values::new();
+        for note in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let pitch = note
+                .parse::<Pitch>()
+                .map_err(|e| NoteListError::Note(note.to_string(), e))?;
+            // a valid pitch is always a valid value
+            notes = notes.with(pitch.note_number()).unwrap();
+        }
+
+        if notes.is_empty() {
+            return Err(NoteListError::Format);
+        }
+
+        Ok(Self(notes))
+    }
+}
+
+/// A zone of a plan, e.g. `C2@v127 rr2`
+#[derive(Clone, Copy, Debug)]
+pub struct ZoneSelector {
+    pitch: Pitch,
+    velocity: Option<u8>,
+    /// Counting from 1
+    round_robin: Option<NonZeroU8>,
+}
+
+impl ZoneSelector {
+    fn matches(&self, zone: &Zone) -> bool {
+        zone.pitch() == self.pitch
+            && self.velocity.map_or(true, |v| zone.velocity() == v)
+            && self
+                .round_robin
+                .map_or(true, |rr| zone.round_robin() + 1 == rr.get())
+    }
+}
+
+impl std::fmt::Display for ZoneSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pitch)?;
+        if let Some(velocity) = self.velocity {
+            write!(f, "@v{velocity}")?;
+        }
+        if let Some(round_robin) = self.round_robin {
+            write!(f, " rr{round_robin}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A list of zones to record, e.g. `C2@v127 rr2, F#3@v64`
+#[derive(Clone, Debug)]
+pub struct ZoneSelection(pub Vec<ZoneSelector>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZoneSelectionError {
+    #[error("Expected `note[@vVELOCITY][ rrN],...`")]
+    Format,
+    #[error("Invalid note `{0}`: {1}")]
+    Note(String, autosam::midi::ParsePitchError),
+    #[error("Invalid velocity `{0}`, expected 0-127")]
+    Velocity(String),
+    #[error("Invalid round robin `{0}`, expected rr1 or above")]
+    RoundRobin(String),
+    #[error("Zone {0} is not part of the plan")]
+    Unplanned(ZoneSelector),
+    #[error(transparent)]
+    Full(#[from] ZoneListFull),
+}
+
+impl ZoneSelection {
+    /// Find the planned zones that each selector stands for
+    pub fn resolve(&self, planned: &[Zone]) -> Result<ZoneList, ZoneSelectionError> {
+        let mut list = ZoneList::new();
+        for selector in &self.0 {
+            let mut found = false;
+            for zone in planned.iter().filter(|zone| selector.matches(zone)) {
+                list = list.with(zone.pitch(), zone.velocity_layer(), zone.round_robin())?;
+                found = true;
+            }
+
+            if !found {
+                return Err(ZoneSelectionError::Unplanned(*selector));
+            }
+        }
+
+        Ok(list)
+    }
+}
+
+impl std::fmt::Display for ZoneSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, selector) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{selector}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for ZoneSelection {
+    type Err = ZoneSelectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut selectors = Vec::new();
+        for zone in s.split(',').map(str::trim).filter(|z| !z.is_empty()) {
+            let mut parts = zone.split_whitespace();
+            let note = parts.next().ok_or(ZoneSelectionError::Format)?;
+            let (note, velocity) = match note.split_once('@') {
+                Some((note, velocity)) => (note, Some(velocity)),
+                None => (note, None),
+            };
+            let round_robin = parts.next();
+            if parts.next().is_some() {
+                return Err(ZoneSelectionError::Format);
+            }
+
+            let pitch = note
+                .parse::<Pitch>()
+                .map_err(|e| ZoneSelectionError::Note(note.to_string(), e))?;
+            let velocity = velocity
+                .map(|v| {
+                    v.strip_prefix(['v', 'V'])
+                        .and_then(|n| n.parse::<u8>().ok())
+                        .filter(|n| *n <= 127)
+                        .ok_or_else(|| ZoneSelectionError::Velocity(v.to_string()))
+                })
+                .transpose()?;
+            let round_robin = round_robin
+                .map(|rr| {
+                    rr.get(..2)
+                        .filter(|prefix| prefix.eq_ignore_ascii_case("rr"))
+                        .and_then(|_| rr[2..].parse::<NonZeroU8>().ok())
+                        .ok_or_else(|| ZoneSelectionError::RoundRobin(rr.to_string()))
+                })
+                .transpose()?;
+
+            selectors.push(ZoneSelector {
+                pitch,
+                velocity,
+                round_robin,
+            });
+        }
+
+        if selectors.is_empty() {
+            return Err(ZoneSelectionError::Format);
+        }
+
+        Ok(Self(selectors))
+    }
+}
+
+/// A scale to pick the notes to sample from, e.g. `C-minor-pentatonic`
+#[derive(Clone, Copy, Debug)]
+pub struct Scale {
+    /// Pitch class of the root, from C = 0
+    root: u8,
+    /// Semitones above the root that are in the scale
+    degrees: &'static [u8],
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScaleError {
+    #[error("Expected `root-name`, e.g. `C-minor-pentatonic`")]
+    Format,
+    #[error("Invalid root note `{0}`")]
+    Root(String),
+    #[error("Unknown scale `{0}`")]
+    Name(String),
+}
+
+impl Scale {
+    const SCALES: [(&'static str, &'static [u8]); 14] = [
+        ("major", &[0, 2, 4, 5, 7, 9, 11]),
+        ("minor", &[0, 2, 3, 5, 7, 8, 10]),
+        ("harmonic-minor", &[0, 2, 3, 5, 7, 8, 11]),
+        ("melodic-minor", &[0, 2, 3, 5, 7, 9, 11]),
+        ("dorian", &[0, 2, 3, 5, 7, 9, 10]),
+        ("phrygian", &[0, 1, 3, 5, 7, 8, 10]),
+        ("lydian", &[0, 2, 4, 6, 7, 9, 11]),
+        ("mixolydian", &[0, 2, 4, 5, 7, 9, 10]),
+        ("locrian", &[0, 1, 3, 5, 6, 8, 10]),
+        ("major-pentatonic", &[0, 2, 4, 7, 9]),
+        ("minor-pentatonic", &[0, 3, 5, 7, 10]),
+        ("blues", &[0, 3, 5, 6, 7, 10]),
+        ("whole-tone", &[0, 2, 4, 6, 8, 10]),
+        ("chromatic", &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]),
+    ];
+
+    /// The notes of the scale within a range
+    pub fn notes(&self, range: std::ops::RangeInclusive<u8>) -> Values {
+        range
+            .filter(|note| self.degrees.contains(&((note + 12 - self.root) % 12)))
+            .fold(Values::new(), |notes, note| {
+                notes.with(note).unwrap_or(notes)
+            })
+    }
+}
+
+impl std::str::FromStr for Scale {
+    type Err = ScaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root, name) = s.trim().split_once('-').ok_or(ScaleError::Format)?;
+        // any octave will do, only the pitch class is kept
+        let root = format!("{root}4")
+            .parse::<Pitch>()
+            .map_err(|_| ScaleError::Root(root.to_string()))?
+            .note_number()
+            % 12;
+        let degrees = Self::SCALES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, degrees)| *degrees)
+            .ok_or_else(|| ScaleError::Name(name.to_string()))?;
+
+        Ok(Self { root, degrees })
+    }
+}
+
+/// A range of MIDI velocities, e.g. `20..127`
+#[derive(Clone, Debug)]
+pub struct VelocityRange(pub std::ops::RangeInclusive<u8>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum VelocityRangeError {
+    #[error("Expected `low..high`")]
+    Format,
+    #[error("Invalid velocity `{0}`")]
+    Velocity(String),
+    #[error("Lowest velocity {0} is above highest velocity {1}")]
+    Order(u8, u8),
+}
+
+impl std::str::FromStr for VelocityRange {
+    type Err = VelocityRangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (low, high) = s.split_once("..").ok_or(VelocityRangeError::Format)?;
+        let velocity = |v: &str| {
+            let v = v.trim().trim_start_matches('=');
+            v.parse::<u8>()
+                .ok()
+                .filter(|v| *v <= 127)
+                .ok_or_else(|| VelocityRangeError::Velocity(v.to_string()))
+        };
+
+        let (low, high) = (velocity(low)?, velocity(high)?);
+        if low > high {
+            return Err(VelocityRangeError::Order(low, high));
+        }
+
+        Ok(Self(low..=high))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum OnConflict {
+    /// Replace them, with a warning
+    Overwrite,
+    /// Leave them as they are, and record nothing
+    Skip,
+    /// Record into a new directory beside the output directory, named after it with -2, -3 and so on
+    Suffix,
+    /// Stop with an error
+    Abort,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// The recordings, in the output directory
+    Raw,
+    /// A zip archive of the recordings, packed after the run
+    Zip,
+    /// An SFZ file beside the recordings
+    Sfz,
+    /// A Bitwig multisample bundle, packed after the run
+    Bitwig,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum MapFormat {
+    /// An SFZ file beside the samples
+    Sfz,
+    /// A Bitwig multisample bundle beside the directory
+    Bitwig,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ConvertFormat {
+    /// An SFZ file with the samples
+    Sfz,
+    /// A DecentSampler preset with the samples
+    Decent,
+    /// A Bitwig multisample bundle
+    Bitwig,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum BitDepth {
+    /// 16 bit signed integer
+    #[cfg_attr(feature = "clap", value(name = "16"))]
+    Int16,
+    /// 24 bit signed integer
+    #[cfg_attr(feature = "clap", value(name = "24"))]
+    Int24,
+    /// 32 bit floating point
+    #[cfg_attr(feature = "clap", value(name = "32f"))]
+    Float32,
+}
+
+impl BitDepth {
+    pub fn spec(self, channels: u16, sample_rate: u32) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            Self::Int16 => (16, hound::SampleFormat::Int),
+            Self::Int24 => (24, hound::SampleFormat::Int),
+            Self::Float32 => (32, hound::SampleFormat::Float),
+        };
+
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    /// Whether samples in an input format lose precision when stored at this depth
+    pub fn is_reduction_from(self, input: cpal::SampleFormat) -> bool {
+        let bits = match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Float32 => return false,
+        };
+
+        input.is_float() || input.sample_size() * 8 > bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use autosam::{Config, Sequencer};
+
+    use super::*;
+
+    fn planned() -> Vec<Zone> {
+        let config = Config {
+            notes: 60..=62,
+            velocity_levels: NonZeroU8::new(2).unwrap(),
+            round_robins: NonZeroU8::new(2).unwrap(),
+            length: Duration::from_millis(1),
+            gap: Duration::from_millis(1),
+            ..Default::default()
+        };
+        crate::plan::zones(Sequencer::new(config, 1000).unwrap())
+    }
+
+    #[test]
+    fn zone_selection_round_trip() {
+        for text in ["C4", "C2@v127 rr2, F#3@v64", "C#-1 rr1, G9@v0"] {
+            let selection = text.parse::<ZoneSelection>().unwrap();
+            assert_eq!(selection.to_string(), text);
+        }
+
+        let selection = " c4@V100  RR3 ,, d4 ,".parse::<ZoneSelection>().unwrap();
+        assert_eq!(selection.to_string(), "C4@v100 rr3, D4");
+    }
+
+    #[test]
+    fn zone_selection_errors() {
+        for text in ["", " , ", "C4 rr1 rr2"] {
+            assert!(matches!(
+                text.parse::<ZoneSelection>(),
+                Err(ZoneSelectionError::Format)
+            ));
+        }
+        assert!(matches!(
+            "H4".parse::<ZoneSelection>(),
+            Err(ZoneSelectionError::Note(..))
+        ));
+        for text in ["C4@v128", "C4@127", "C4@v", "C4@v-1"] {
+            assert!(matches!(
+                text.parse::<ZoneSelection>(),
+                Err(ZoneSelectionError::Velocity(..))
+            ));
+        }
+        for text in ["C4 rr0", "C4 r1", "C4 rr", "C4 rr256"] {
+            assert!(matches!(
+                text.parse::<ZoneSelection>(),
+                Err(ZoneSelectionError::RoundRobin(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn zone_selection_resolves_planned_zones() {
+        let planned = planned();
+        let resolve = |text: &str| text.parse::<ZoneSelection>().unwrap().resolve(&planned);
+
+        // a bare note stands for every layer and round robin at it
+        assert_eq!(resolve("C4").unwrap().len(), 4);
+        assert_eq!(resolve("C4 rr2").unwrap().len(), 2);
+        assert_eq!(resolve("C4@v127 rr1").unwrap().len(), 1);
+        assert_eq!(resolve("C4@v127").unwrap().len(), 2);
+
+        // zones picked out more than once are listed once
+        assert_eq!(resolve("C4, C4 rr1, D4@v127 rr2").unwrap().len(), 5);
+
+        let list = resolve("C#4@v127 rr2").unwrap();
+        assert!(list.contains(61, 0, 1));
+        assert!(!list.contains(61, 0, 0));
+
+        for text in ["B3", "C4 rr3", "C4@v100"] {
+            assert!(matches!(
+                resolve(text),
+                Err(ZoneSelectionError::Unplanned(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use log::{debug, warn};
+
+use crate::analysis;
+
+/// Time left after each file's end for the output to finish playing it
+const PLAYBACK_MARGIN: Duration = Duration::from_millis(100);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuditionError {
+    #[error("Output device {0} cannot play at {1}Hz")]
+    SampleRate(String, u32),
+    #[error("Unsupported output sample format '{0}'")]
+    SampleFormat(cpal::SampleFormat),
+}
+
+/// Plays each recording through an audio output as soon as it is finished
+///
+/// Recordings are played one at a time. If several finish while one is
+/// playing, only the latest of them is played next, so playback never falls
+/// behind the recording.
+pub struct Audition {
+    sender: mpsc::Sender<PathBuf>,
+    handle: std::thread::JoinHandle<()>,
+}
+
+impl Audition {
+    /// Start playing back through an output device, at the sample rate of the recordings
+    pub fn start(device: cpal::Device, sample_rate: u32) -> anyhow::Result<Self> {
+        let name = device.name()?;
+        let supported = device
+            .supported_output_configs()?
+            .filter(|config| {
+                (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&sample_rate)
+            })
+            .max_by_key(|config| config.sample_format() == cpal::SampleFormat::F32)
+            .ok_or_else(|| AuditionError::SampleRate(name.clone(), sample_rate))?
+            .with_sample_rate(cpal::SampleRate(sample_rate));
+        let sample_format = supported.sample_format();
+        let config = supported.config();
+
+        let (sender, receiver) = mpsc::channel::<PathBuf>();
+        let handle = std::thread::Builder::new()
+            .name("audition".into())
+            .spawn(move || {
+                while let Ok(mut path) = receiver.recv() {
+                    // skip straight to the latest recording
+                    while let Ok(newer) = receiver.try_recv() {
+                        debug!("Not auditioning {}", path.display());
+                        path = newer;
+                    }
+
+                    if let Err(e) = play(&device, &config, sample_format, &path) {
+                        warn!("Could not audition {}: {e}", path.display());
+                    }
+                }
+            })?;
+
+        Ok(Self { sender, handle })
+    }
+
+    /// Queue a finished recording to be played
+    pub fn play(&self, path: PathBuf) {
+        let _ = self.sender.send(path);
+    }
+
+    /// Wait for the last recording to finish playing
+    pub fn finish(self) {
+        drop(self.sender);
+        let _ = self.handle.join();
+    }
+}
+
+/// Play a WAV file through the output, returning once it has been heard
+fn play(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let (channels, samples) = analysis::read(path)?;
+    let frames = samples.len() / channels;
+    debug!("Auditioning {}", path.display());
+
+    let playback = Playback {
+        samples,
+        channels,
+        outputs: usize::from(config.channels),
+        position: 0,
+        done: Arc::new(AtomicBool::new(false)),
+    };
+    let done = playback.done.clone();
+
+    let stream = match sample_format {
+        cpal::SampleFormat::I16 => build_output_stream::<i16>(device, config, playback)?,
+        cpal::SampleFormat::I32 => build_output_stream::<i32>(device, config, playback)?,
+        cpal::SampleFormat::F32 => build_output_stream::<f32>(device, config, playback)?,
+        sample_format => return Err(AuditionError::SampleFormat(sample_format).into()),
+    };
+    stream.play()?;
+
+    let length = Duration::from_secs_f64(frames as f64 / f64::from(config.sample_rate.0));
+    std::thread::sleep(length);
+    // the output may still hold the end of the file
+    while !done.load(Ordering::Acquire) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(PLAYBACK_MARGIN);
+
+    Ok(())
+}
+
+fn build_output_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut playback: Playback,
+) -> anyhow::Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    Ok(device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &_| playback.fill(data),
+        |e| warn!("Error during audition: {e}"),
+        None,
+    )?)
+}
+
+/// A recording being played, with each of its channels on an output of the same number
+///
+/// A mono recording is played on the first two outputs.
+struct Playback {
+    samples: Vec<f32>,
+    channels: usize,
+    outputs: usize,
+    /// Frame to play next
+    position: usize,
+    done: Arc<AtomicBool>,
+}
+
+impl Playback {
+    fn fill<T>(&mut self, data: &mut [T])
+    where
+        T: cpal::Sample + cpal::FromSample<f32>,
+    {
+        for frame in data.chunks_mut(self.outputs) {
+            let start = self.position * self.channels;
+            let input = self.samples.get(start..start + self.channels);
+            if input.is_some() {
+                self.position += 1;
+            } else {
+                self.done.store(true, Ordering::Release);
+            }
+
+            for (output, sample) in frame.iter_mut().enumerate() {
+                let value = match input {
+                    Some(input) if self.channels == 1 && output < 2 => input[0],
+                    Some(input) => input.get(output).copied().unwrap_or_default(),
+                    None => 0.0,
+                };
+                *sample = T::from_sample(value);
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use cpal::{traits::DeviceTrait, traits::StreamTrait, FromSample};
+use log::{debug, error};
+
+use autosam::{
+    midi::{Event, NoteState},
+    AdvanceResult, Sequencer,
+};
+
+use crate::{monitor::MidiOut, runtime::ChannelSelection};
+
+/// Length of each throwaway note
+pub const PING_LENGTH: Duration = Duration::from_millis(200);
+
+/// Time after each throwaway note for it to die away before the next
+pub const PING_GAP: Duration = Duration::from_millis(800);
+
+/// Times each note of a sequence from its NoteOn until it is heard
+struct Pinger {
+    seq: Sequencer,
+    sender: rtrb::Producer<Event>,
+    /// Delays of the notes heard, in frames
+    delays: rtrb::Producer<usize>,
+    channels: usize,
+    selection: ChannelSelection,
+    threshold: f32,
+    timer: Option<usize>,
+    done: Arc<AtomicBool>,
+}
+
+impl Pinger {
+    fn process<T>(&mut self, input: &[T])
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>,
+    {
+        for frame in input.chunks(self.channels) {
+            if let Some(t) = &mut self.timer {
+                *t += 1;
+            }
+
+            loop {
+                match self.seq.advance(1) {
+                    AdvanceResult::NoEventsInFrame => break,
+                    AdvanceResult::SequenceComplete => {
+                        self.done.store(true, Ordering::Release);
+                        break;
+                    }
+                    AdvanceResult::Event { event, .. } => {
+                        if event.note().is_some_and(|n| n.state() == NoteState::On) {
+                            self.timer = Some(0);
+                        }
+
+                        if let Err(e) = self.sender.push(event) {
+                            error!("Out of capacity in event buffer: {e}");
+                        }
+                    }
+                }
+            }
+
+            let (samples, len) = self.selection.pick(frame);
+            if samples[..len].iter().any(|s| s.abs() > self.threshold) {
+                if let Some(t) = self.timer.take() {
+                    if let Err(e) = self.delays.push(t) {
+                        error!("Out of capacity in latency buffer: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Play a sequence of throwaway notes, returning how long each took to be heard, in frames
+///
+/// Notes that were never heard above `threshold` are left out.
+pub fn measure(
+    device: &cpal::Device,
+    sample_format: cpal::SampleFormat,
+    config: &cpal::StreamConfig,
+    selection: ChannelSelection,
+    midi_connection: &mut MidiOut,
+    seq: Sequencer,
+    threshold: f32,
+) -> anyhow::Result<Vec<usize>> {
+    let (note_tx, mut note_rx) = rtrb::RingBuffer::new(64);
+    let (delay_tx, mut delay_rx) = rtrb::RingBuffer::new(256);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let pinger = Pinger {
+        seq,
+        sender: note_tx,
+        delays: delay_tx,
+        channels: usize::from(config.channels),
+        selection,
+        threshold,
+        timer: None,
+        done: done.clone(),
+    };
+
+    let stream = match sample_format {
+        cpal::SampleFormat::I8 => input_stream::<i8>(device, config, pinger)?,
+        cpal::SampleFormat::I16 => input_stream::<i16>(device, config, pinger)?,
+        cpal::SampleFormat::I32 => input_stream::<i32>(device, config, pinger)?,
+        cpal::SampleFormat::F32 => input_stream::<f32>(device, config, pinger)?,
+        sample_format => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported sample format '{sample_format}'"
+            )))
+        }
+    };
+
+    debug!("Measuring latency");
+    stream.play()?;
+
+    let mut delays = Vec::new();
+    loop {
+        let is_done = done.load(Ordering::Acquire);
+
+        while let Ok(event) = note_rx.pop() {
+            let msg = event.as_midi_message();
+            debug!("Sending event {:?}", &*msg);
+            midi_connection.send(&msg, None)?;
+        }
+
+        while let Ok(delay) = delay_rx.pop() {
+            debug!("Heard a note after {delay} frames");
+            delays.push(delay);
+        }
+
+        if is_done {
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    drop(stream);
+
+    Ok(delays)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalibrationError {
+    #[error("Only {heard} of {expected} calibration notes were heard")]
+    NotHeard { heard: usize, expected: usize },
+    #[error("Latency varied from {min} to {max} samples between calibration notes")]
+    Unstable { min: usize, max: usize },
+}
+
+/// Check that every note was heard with about the same delay, and take the median
+pub fn evaluate(
+    delays: &[usize],
+    expected: usize,
+    tolerance: usize,
+) -> Result<usize, CalibrationError> {
+    let mut sorted = delays.to_vec();
+    sorted.sort_unstable();
+
+    let (Some(min), Some(max)) = (sorted.first(), sorted.last()) else {
+        return Err(CalibrationError::NotHeard { heard: 0, expected });
+    };
+
+    if sorted.len() < expected {
+        return Err(CalibrationError::NotHeard {
+            heard: sorted.len(),
+            expected,
+        });
+    }
+
+    if max - min > tolerance {
+        return Err(CalibrationError::Unstable {
+            min: *min,
+            max: *max,
+        });
+    }
+
+    Ok(sorted[sorted.len() / 2])
+}
+
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut pinger: Pinger,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &_| pinger.process(data),
+        |e| error!("Encountered an error while measuring latency: {e}"),
+        None,
+    )
+}
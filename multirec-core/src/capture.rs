@@ -0,0 +1,820 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
+};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use log::{debug, error, info, warn};
+
+use autosam::{
+    midi::{Event, Pitch},
+    Sequencer,
+};
+
+use crate::{
+    analysis, count_in, devices, devices::Devices, drumkit, monitor, naming, noise, osc, runtime,
+    session, settings::Settings, status, take, util, util::MaybeSample, Frontend, Progress,
+    RunError, RunState, Session, Setup,
+};
+
+const NOTE_RINGBUFFER_SIZE: usize = 1024;
+/// Least time that the buffer between the audio callback and the file writer can hold
+const AUDIO_BUFFER_TIME: Duration = Duration::from_secs(2);
+/// Least number of device buffers that the buffer to the file writer can hold
+const AUDIO_BUFFER_PERIODS: usize = 16;
+
+/// Audio kept queued from each further input, to ride out differences in callback timing
+const MIC_PREBUFFER_TIME: Duration = Duration::from_millis(20);
+
+/// A run that is set up, and ready to record its zones
+pub struct Capture<'a, 's> {
+    pub args: &'a Setup,
+    pub host: &'a cpal::Host,
+    pub settings: &'s Settings,
+    pub devices: &'a mut Devices,
+    /// The session that the run continues, whose files are kept
+    pub session: &'a Session,
+    pub sound_off: &'a [[u8; 3]],
+    pub patch_events: &'a [Event],
+    /// The directory of each input's files
+    pub mic_dirs: &'a [PathBuf],
+    /// Pitch and velocity layer of each zone to record
+    pub planned: &'a [(u8, u8)],
+    pub state: &'a Arc<RunState>,
+    pub frontend: &'a dyn Frontend,
+}
+
+/// The files that a run recorded
+pub struct Captured<'s> {
+    /// The files of every zone, resumed ones included, in the order they were played
+    pub entries: Vec<util::NamedFile<'s, &'s String>>,
+    /// Names of the files whose zones slipped out of step with the clock
+    pub slipped: Vec<String>,
+    /// When recording began
+    pub recorded: SystemTime,
+}
+
+impl<'s> Capture<'_, 's> {
+    /// Play the sequence and record it, until the run is done or aborted
+    pub fn run(
+        self,
+        seq: Sequencer,
+        mut midi_connection: Option<monitor::MidiOut>,
+    ) -> anyhow::Result<Captured<'s>> {
+        let Capture {
+            args,
+            host,
+            settings,
+            devices,
+            session,
+            sound_off,
+            patch_events,
+            mic_dirs,
+            planned,
+            state,
+            frontend,
+        } = self;
+        let Settings {
+            output_dir,
+            file_name_prefix,
+            name_template,
+            keyswitches,
+            pads,
+            controller_sweeps,
+            count_in,
+            ..
+        } = settings;
+        let Settings {
+            should_save,
+            bit_depth,
+            single_take,
+            trim_start,
+            auto_gap,
+            tail,
+            retries,
+            velocity_levels,
+            round_robins,
+            ..
+        } = *settings;
+        let Devices {
+            sample_format,
+            selection,
+            channels,
+            layout_width,
+            ..
+        } = *devices;
+        let Devices {
+            plugin,
+            input_device,
+            input_config,
+            mic_devices,
+            audition,
+            mic_channels,
+            mic_formats,
+            ..
+        } = devices;
+        let total_zones = planned.len();
+
+        if plugin.is_some() && count_in.is_some() {
+            warn!("Not counting in, as a plugin or simulation is played directly");
+        }
+
+        if let (Some(count_in), Some(midi_connection)) = (count_in, &mut midi_connection) {
+            match &count_in.cue {
+                count_in::Cue::Beep(matcher) => count_in::beep(
+                    &devices::open_output_device(host, matcher.clone())?,
+                    count_in.beats,
+                    count_in.beat,
+                )?,
+                count_in::Cue::Note(channel, pitch) => count_in::tap(
+                    midi_connection,
+                    *channel,
+                    *pitch,
+                    count_in.beats,
+                    count_in.beat,
+                )?,
+            }
+        }
+
+        let (note_tx, mut note_rx) =
+            rtrb::RingBuffer::<runtime::TimedEvent>::new(NOTE_RINGBUFFER_SIZE);
+        // room for a few seconds of audio, and for many device buffers if those are large
+        let audio_buffer_size = {
+            let channels = layout_width;
+            let period = match input_config.buffer_size {
+                cpal::BufferSize::Fixed(frames) => frames as usize,
+                cpal::BufferSize::Default => 0,
+            };
+
+            (util::frames(AUDIO_BUFFER_TIME, input_config.sample_rate.0) * channels)
+                .max(period * AUDIO_BUFFER_PERIODS * channels)
+        };
+        debug!("Audio buffer size set to {audio_buffer_size} samples");
+        let (audio_tx, mut audio_rx) = rtrb::RingBuffer::new(audio_buffer_size);
+
+        let period = |config: &cpal::StreamConfig| match config.buffer_size {
+            cpal::BufferSize::Fixed(frames) => frames as usize,
+            cpal::BufferSize::Default => 0,
+        };
+        let mut mics = Vec::new();
+        let mut mic_feeds = Vec::new();
+        for (_, _, config, selection) in mic_devices.iter() {
+            let sample_rate = input_config.sample_rate.0;
+            let (producer, consumer) = rtrb::RingBuffer::new(
+                util::frames(AUDIO_BUFFER_TIME, sample_rate) * selection.len(),
+            );
+            let prebuffer = util::frames(MIC_PREBUFFER_TIME, sample_rate)
+                .max(2 * period(config).max(period(input_config)));
+
+            mics.push(runtime::MicInput::new(consumer, selection.len(), prebuffer));
+            mic_feeds.push(runtime::MicFeed {
+                producer,
+                channels: usize::from(config.channels),
+                selection: *selection,
+                state: state.clone(),
+            });
+        }
+
+        let has_vel = velocity_levels > 1;
+        let has_rr = round_robins > 1;
+
+        let osc_handle = match args.osc {
+            Some(address) => {
+                let server = osc::Server::bind(address, state.clone(), total_zones)?;
+                if args.osc_wait {
+                    info!("Waiting for /multirec/start");
+                    state.set_paused(true);
+                }
+                Some(
+                    std::thread::Builder::new()
+                        .name("osc".into())
+                        .spawn(move || server.run())?,
+                )
+            }
+            _ => None,
+        };
+
+        let status_handle = match args.status_address {
+            Some(address) => {
+                let server = status::Server::bind(address, state.clone(), total_zones)?;
+                Some(
+                    std::thread::Builder::new()
+                        .name("status".into())
+                        .spawn(move || server.run())?,
+                )
+            }
+            _ => None,
+        };
+
+        frontend.progress(Progress::Started {
+            zones: total_zones,
+            state,
+        });
+
+        let recorded = SystemTime::now();
+        // names of the files whose zones slipped out of step with the clock
+        let slipped = std::sync::Mutex::new(Vec::new());
+        let entries = std::thread::scope(|scope| {
+            // a plugin is sent the events directly, from the thread that renders it
+            let (player_handle, plugin_events) = match midi_connection {
+                Some(mut midi_connection) => {
+                    let handle = std::thread::Builder::new()
+                        .name("midi-output".into())
+                        .spawn_scoped(scope, {
+                            let state = state.clone();
+
+                            move || loop {
+                                // check before draining, so that nothing sent before the flag was set is lost
+                                let is_abandoned = note_rx.is_abandoned();
+                                let sequence_is_done = state.done();
+
+                                let mut any_messages = false;
+
+                                'notes: loop {
+                                    match note_rx.pop() {
+                                        Err(rtrb::PopError::Empty) => break 'notes,
+                                        Ok(runtime::TimedEvent { frame, event }) => {
+                                            any_messages = true;
+                                            let msg = event.as_midi_message();
+                                            debug!("Sending event {:?}", &*msg);
+                                            if let Err(e) = midi_connection.send(&msg, Some(frame))
+                                            {
+                                                error!("Failed to send MIDI message: {e}");
+                                            }
+                                        }
+                                    }
+                                }
+
+                                if is_abandoned {
+                                    debug!("MIDI producer was dropped");
+                                    break;
+                                }
+
+                                if sequence_is_done {
+                                    debug!("Audio callback has set `done` flag to `true`");
+
+                                    // an aborted sequence has no cleanup of its own
+                                    if state.aborted() {
+                                        for msg in sound_off {
+                                            if let Err(e) = midi_connection.send(msg, None) {
+                                                error!("Failed to send MIDI message: {e}");
+                                            }
+                                        }
+                                    }
+
+                                    break;
+                                }
+
+                                if !any_messages {
+                                    std::thread::sleep(Duration::from_millis(1));
+                                }
+                            }
+                        })?;
+                    (Some(handle), None)
+                }
+                None => (None, Some(note_rx)),
+            };
+
+            let writer_builder = std::thread::Builder::new().name("wav-writer".into());
+
+            let writer_handle = if should_save {
+                let specs: Vec<_> = mic_channels
+                    .iter()
+                    .map(|channels| bit_depth.spec(*channels, input_config.sample_rate.0))
+                    .collect();
+                let mut quantizers: Vec<_> = mic_formats
+                    .iter()
+                    .map(|format| {
+                        util::Quantizer::new(bit_depth, bit_depth.is_reduction_from(*format))
+                    })
+                    .collect();
+                // the input each interleaved sample of a frame belongs to
+                let layout: Vec<usize> = mic_channels
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(mic, channels)| std::iter::repeat(mic).take(usize::from(*channels)))
+                    .collect();
+
+                for dir in std::iter::once(output_dir).chain(mic_dirs) {
+                    if !dir.exists() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                }
+
+                let state = state.clone();
+                let audition = audition.as_ref();
+                let slipped = &slipped;
+
+                let session_args = &session.args;
+                let config_file = session.config.as_ref();
+                let resumed_files = &session.files;
+                let resumed_skipped = session.skipped;
+                let sample_rate = input_config.sample_rate.0;
+
+                writer_builder.spawn_scoped(scope, move || -> anyhow::Result<Vec<_>> {
+                        let mut entries = resumed_files
+                            .iter()
+                            .map(|file| {
+                                file.named(name_template, file_name_prefix.as_ref(), keyswitches, pads)
+                            })
+                            .collect::<anyhow::Result<Vec<_>>>()?;
+                        let first_recorded = entries.len();
+                        session::Session::save(
+                            output_dir,
+                            session_args,
+                            config_file,
+                            &entries,
+                            resumed_skipped + state.skipped(),
+                        )?;
+
+                        // files are reported once written, which in a single take is at the end
+                        let mut reported = first_recorded;
+                        let mut report = |entries: &[util::NamedFile<'_, &String>]| {
+                            for (idx, entry) in entries.iter().enumerate().skip(reported) {
+                                frontend.progress(Progress::Recorded {
+                                    file: &entry.to_string(),
+                                    recorded: idx + 1 - first_recorded,
+                                });
+                            }
+                            reported = entries.len();
+                        };
+
+                        let mut used_names = std::collections::HashSet::new();
+                        let mut create_file_name = |entries: &mut Vec<_>| -> anyhow::Result<String> {
+                            let (pitch, velocity, round_robin) = state.note(Ordering::Acquire);
+
+                            let entry = util::NamedFile {
+                                template: name_template,
+                                prefix: file_name_prefix.as_ref(),
+                                pitch: Pitch::new(pitch)?,
+                                velocity: has_vel.then_some(velocity),
+                                round_robin: has_rr.then_some(round_robin),
+                                keyswitch: state
+                                    .keyswitch(Ordering::Acquire)
+                                    .and_then(|note| naming::Keyswitch::find(keyswitches, note)),
+                                pad: drumkit::Pad::find(pads, pitch),
+                                controllers: controller_sweeps
+                                    .iter()
+                                    .map(|sweep| sweep.controller)
+                                    .zip(state.controllers(Ordering::Acquire))
+                                    .collect(),
+                                loop_points: None,
+                                loop_crossfade: None,
+                                gain: None,
+                                tune: None,
+                                sample_stop: None,
+                            };
+
+                            let name = entry.to_string();
+                            if !used_names.insert(name.clone()) {
+                                warn!("Overwriting {name}, as the name template does not tell zones apart");
+                            }
+
+                            entries.push(entry);
+
+                            Ok(name)
+                        };
+
+                        // each input's file for a zone has the same name, in its own directory
+                        let create_writers = |name: &str| -> anyhow::Result<Vec<_>> {
+                            mic_dirs
+                                .iter()
+                                .zip(&specs)
+                                .map(|(dir, spec)| {
+                                    let path = dir.join(name);
+                                    if let Some(parent) = path.parent() {
+                                        std::fs::create_dir_all(parent)?;
+                                    }
+                                    Ok(hound::WavWriter::create(path, *spec)?)
+                                })
+                                .collect()
+                        };
+                        let finalize = |writers: Vec<hound::WavWriter<_>>| -> anyhow::Result<()> {
+                            for writer in writers {
+                                writer.finalize()?;
+                            }
+                            Ok(())
+                        };
+                        let audition_last = |entries: &Vec<util::NamedFile<'_, _>>| {
+                            if let (Some(audition), Some(entry)) = (audition, entries.last()) {
+                                audition.play(mic_dirs[0].join(entry.to_string()));
+                            }
+                        };
+                        // a single take is cut into the zones' files once it is complete
+                        let split_take = |entries: &[util::NamedFile<'_, _>], cuts: &[usize]| {
+                            let names: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+                            let samples = analysis::read_mono(mic_dirs[0].join(take::FILE_NAME))?;
+                            let (starts, missing) = take::align(&samples, cuts, sample_rate);
+                            for idx in missing {
+                                warn!(
+                                    "Could not hear where {} starts, so it is cut where it was played",
+                                    names[idx]
+                                );
+                            }
+
+                            for dir in mic_dirs {
+                                take::split(dir, &starts, &names)?;
+                                std::fs::remove_file(dir.join(take::FILE_NAME))?;
+                            }
+                            info!("Split the take into {} files", names.len());
+
+                            anyhow::Ok(())
+                        };
+                        let mut slot = 0;
+                        // frames of the take so far, and the frame each zone in it was played at
+                        let mut frames = 0;
+                        let mut cuts = Vec::new();
+
+                        // wait for first note event to start writing, so the file is named after its zone
+                        let mut writers = loop {
+                            match audio_rx.pop() {
+                                Err(rtrb::PopError::Empty) if state.done() => {
+                                    debug!(
+                                    "Audio callback set `done` flag to `true` before any data was recorded"
+                                );
+                                    return Ok(entries);
+                                }
+                                Err(rtrb::PopError::Empty) => {
+                                    std::thread::sleep(Duration::from_millis(1));
+                                }
+                                Ok(MaybeSample::Break) => {
+                                    let name = create_file_name(&mut entries)?;
+                                    if single_take {
+                                        cuts.push(0);
+                                        break create_writers(take::FILE_NAME)?;
+                                    }
+                                    break create_writers(&name)?;
+                                }
+                                _ => {}
+                            }
+                        };
+
+                        loop {
+                            match audio_rx.pop() {
+                                Err(rtrb::PopError::Empty) if state.done() => {
+                                    debug!("I/O thread shutting down");
+                                    finalize(writers)?;
+                                    if single_take {
+                                        split_take(&entries[first_recorded..], &cuts)?;
+                                    }
+
+                                    // the zone in progress when aborted has to be recorded again
+                                    if !state.aborted() {
+                                        audition_last(&entries);
+                                        session::Session::save(
+                                            output_dir,
+                                            session_args,
+                                            config_file,
+                                            &entries,
+                                            resumed_skipped + state.skipped(),
+                                        )?;
+                                        report(&entries);
+                                    }
+
+                                    return Ok(entries);
+                                }
+                                Err(rtrb::PopError::Empty) => {
+                                    std::thread::sleep(Duration::from_millis(1));
+                                }
+                                Ok(MaybeSample::Break) if single_take => {
+                                    create_file_name(&mut entries)?;
+                                    cuts.push(frames);
+                                }
+                                Ok(MaybeSample::Break) => {
+                                    finalize(writers)?;
+                                    audition_last(&entries);
+                                    session::Session::save(
+                                        output_dir,
+                                        session_args,
+                                        config_file,
+                                        &entries,
+                                        resumed_skipped + state.skipped(),
+                                    )?;
+                                    report(&entries);
+                                    debug!("Creating next WAV file");
+                                    writers = create_writers(&create_file_name(&mut entries)?)?;
+                                    slot = 0;
+                                }
+                                Ok(MaybeSample::Slip) => {
+                                    if let Some(entry) = entries.last() {
+                                        let name = entry.to_string();
+                                        warn!("Audio timing slipped while recording {name}, so it may be out of step with its notes");
+                                        if let Ok(mut slipped) = slipped.lock() {
+                                            slipped.push(name);
+                                        }
+                                    }
+                                }
+                                Ok(MaybeSample::Retry) => {
+                                    let Some(entry) = entries.last() else {
+                                        return Err(anyhow::Error::msg(
+                                            "Asked to record a zone again before recording any",
+                                        ));
+                                    };
+                                    warn!("Nothing was heard for {entry}, recording it again");
+                                    if single_take {
+                                        // the zone's earlier attempt is left out of the files
+                                        if let Some(cut) = cuts.last_mut() {
+                                            *cut = frames;
+                                        }
+                                    } else {
+                                        finalize(writers)?;
+                                        writers = create_writers(&entry.to_string())?;
+                                    }
+                                    slot = 0;
+                                }
+                                Ok(MaybeSample::Sample(data)) => {
+                                    let mic = layout[slot];
+                                    quantizers[mic].write(&mut writers[mic], data)?;
+                                    slot = (slot + 1) % layout.len();
+                                    if slot == 0 {
+                                        frames += 1;
+                                    }
+                                }
+                            }
+                        }
+                    })
+            } else {
+                let state = state.clone();
+
+                writer_builder.spawn_scoped(scope, move || loop {
+                    match audio_rx.pop() {
+                        Err(rtrb::PopError::Empty) if state.done() => {
+                            debug!("I/O thread shutting down");
+                            return Ok(Vec::new());
+                        }
+                        Err(rtrb::PopError::Empty) => {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+                        Ok(
+                            MaybeSample::Break
+                            | MaybeSample::Retry
+                            | MaybeSample::Slip
+                            | MaybeSample::Sample(_),
+                        ) => {
+                            // do nothing
+                        }
+                    }
+                })
+            }?;
+
+            let mut processor = runtime::AudioProcessor {
+                seq,
+                sender: note_tx,
+                writer: runtime::WriterQueue::new(audio_tx, state.clone()),
+                channels: usize::from(input_config.channels),
+                selection,
+                state: state.clone(),
+                latency_timer: None,
+                trim_start: trim_start.map(|(threshold, guard)| {
+                    runtime::StartTrimmer::new(
+                        threshold,
+                        util::frames(guard, input_config.sample_rate.0),
+                        layout_width,
+                    )
+                }),
+                auto_gap: auto_gap.map(|(threshold, hold)| {
+                    runtime::GapDetector::new(
+                        threshold,
+                        util::frames(hold, input_config.sample_rate.0),
+                        tail.map_or(0, |tail| util::frames(tail, input_config.sample_rate.0)),
+                    )
+                }),
+                dead_notes: retries
+                    .filter(|(_, retries, _)| *retries > 0)
+                    .map(|(threshold, _, _)| runtime::DeadNoteDetector::new(threshold)),
+                retries: retries.map_or_else(Default::default, |(_, retries, dropouts)| {
+                    runtime::Retries::new(retries, dropouts)
+                }),
+                zone: None,
+                mics,
+                frame: 0,
+                clock: plugin
+                    .is_none()
+                    .then(|| runtime::ClockMonitor::new(input_config.sample_rate.0)),
+            };
+
+            let err_fn = {
+                let state = state.clone();
+                move |e| {
+                    error!("Encountered an error while processing input audio: {e}");
+                    state.dropout();
+                }
+            };
+
+            let mut mic_streams = Vec::new();
+            let (stream, plugin_handle) = match (plugin.as_mut(), plugin_events, input_device) {
+                (Some(plugin), Some(events), _) => {
+                    debug!("Rendering plugin");
+                    let state = state.clone();
+                    let handle = std::thread::Builder::new()
+                        .name("plugin".into())
+                        .spawn_scoped(scope, move || {
+                            plugin.render(
+                                &mut processor,
+                                events,
+                                patch_events,
+                                &state,
+                                args.offline,
+                            )
+                        })?;
+                    (None, Some(handle))
+                }
+                (_, _, Some(input_device)) => {
+                    // the further inputs start first, so they have audio ready for the first
+                    for ((device, format, config, _), feed) in mic_devices.iter().zip(mic_feeds) {
+                        let stream =
+                            build_input_stream(device, config, *format, feed, err_fn.clone())?;
+                        stream.play()?;
+                        mic_streams.push(stream);
+                    }
+
+                    let stream = build_input_stream(
+                        input_device,
+                        input_config,
+                        sample_format,
+                        processor,
+                        err_fn,
+                    )?;
+                    debug!("Capturing input");
+                    stream.play()?;
+                    (Some(stream), None)
+                }
+                _ => unreachable!("there is an input device whenever there is no plugin"),
+            };
+
+            frontend.progress(Progress::Capturing {
+                zones: planned,
+                channels: usize::from(channels),
+                state,
+            });
+
+            if let Some(handle) = plugin_handle {
+                debug!("Waiting for plugin to finish");
+                handle
+                    .join()
+                    .map_err(|e| RunError::PluginPanic(format!("{e:?}")))??;
+            }
+
+            if let Some(handle) = player_handle {
+                debug!("Waiting for MIDI thread to finish");
+                handle
+                    .join()
+                    .map_err(|e| RunError::MidiPanic(format!("{e:?}")))?;
+            }
+
+            debug!("MIDI player exited, waiting for WAV writer");
+
+            let entries = writer_handle
+                .join()
+                .map_err(|e| RunError::IoPanic(format!("{e:?}")))??;
+
+            debug!("WAV writer exited");
+
+            drop(stream);
+            drop(mic_streams);
+
+            Ok::<_, anyhow::Error>(entries)
+        })?;
+
+        if let Some(audition) = audition.take() {
+            audition.finish();
+        }
+
+        // the servers stop once the run is done
+        for handle in [osc_handle, status_handle].into_iter().flatten() {
+            let _ = handle.join();
+        }
+
+        if state.aborted() {
+            return Err(RunError::Aborted(entries.len()).into());
+        }
+
+        let slipped = slipped
+            .lock()
+            .map(|names| names.clone())
+            .unwrap_or_default();
+        Ok(Captured {
+            entries,
+            slipped,
+            recorded,
+        })
+    }
+}
+
+/// Record the noise floor of the first input, if asked to
+///
+/// Returns the recording and the strength to remove it from the files at,
+/// if it is to be removed.
+pub fn noise_floor(
+    settings: &Settings,
+    devices: &Devices,
+) -> anyhow::Result<Option<(PathBuf, f64)>> {
+    let output_dir = &settings.output_dir;
+    let Settings {
+        noise_floor,
+        should_save,
+        bit_depth,
+        ..
+    } = *settings;
+
+    let Devices {
+        plugin,
+        input_device,
+        input_config,
+        ..
+    } = devices;
+    let Devices {
+        sample_format,
+        selection,
+        channels,
+        ..
+    } = *devices;
+
+    if plugin.is_some() && noise_floor.is_some() {
+        warn!("Not recording the noise floor, as a plugin or simulation is played directly without any");
+    }
+
+    Ok(match (noise_floor, input_device) {
+        (Some((length, strength)), Some(input_device)) if should_save => {
+            let sample_rate = input_config.sample_rate.0;
+            info!("Recording {length:?} of the noise floor, keep quiet");
+            let samples = noise::capture(
+                input_device,
+                sample_format,
+                input_config,
+                selection,
+                util::frames(length, sample_rate),
+            )?;
+
+            std::fs::create_dir_all(output_dir)?;
+            let path = output_dir.join(noise::FILE_NAME);
+            let mut writer =
+                hound::WavWriter::create(&path, bit_depth.spec(channels, sample_rate))?;
+            let mut quantizer =
+                util::Quantizer::new(bit_depth, bit_depth.is_reduction_from(sample_format));
+            for sample in samples {
+                quantizer.write(&mut writer, sample)?;
+            }
+            writer.finalize()?;
+
+            strength.map(|strength| (path, strength))
+        }
+        _ => None,
+    })
+}
+
+/// Start recording from an audio input into the processor
+fn build_input_stream(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    mut processor: impl runtime::Capture,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> anyhow::Result<cpal::Stream> {
+    let stream = match sample_format {
+        cpal::SampleFormat::I8 => {
+            info!("Incoming sample format is 8 bit signed");
+            device.build_input_stream(
+                config,
+                move |data, _: &_| processor.write_input_data::<i8>(data),
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::I16 => {
+            info!("Incoming sample format is 16 bit signed");
+            device.build_input_stream(
+                config,
+                move |data, _: &_| processor.write_input_data::<i16>(data),
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::I32 => {
+            info!("Incoming sample format is 32 bit signed");
+            device.build_input_stream(
+                config,
+                move |data, _: &_| processor.write_input_data::<i32>(data),
+                err_fn,
+                None,
+            )?
+        }
+        cpal::SampleFormat::F32 => {
+            info!("Incoming sample format is 32 bit float");
+            device.build_input_stream(
+                config,
+                move |data, _: &_| processor.write_input_data::<f32>(data),
+                err_fn,
+                None,
+            )?
+        }
+        sample_format => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported sample format '{sample_format}'"
+            )))
+        }
+    };
+
+    Ok(stream)
+}
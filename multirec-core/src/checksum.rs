@@ -0,0 +1,219 @@
+use std::io::Read;
+
+/// Name of the list of checksums in a bundle, in the format `sha256sum --check` reads
+pub const FILE_NAME: &str = "checksums.txt";
+
+/// Size of each read when hashing a file
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Round constants, from the cube roots of the first 64 primes
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Initial hash, from the square roots of the first 8 primes
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// A SHA-256 hash, fed a piece at a time
+pub struct Sha256 {
+    state: [u32; 8],
+    /// Bytes of the block not yet full
+    block: [u8; 64],
+    filled: usize,
+    /// Bytes hashed so far
+    length: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self {
+            state: H,
+            block: [0; 64],
+            filled: 0,
+            length: 0,
+        }
+    }
+}
+
+impl Sha256 {
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len() as u64;
+        while !bytes.is_empty() {
+            let taken = (64 - self.filled).min(bytes.len());
+            self.block[self.filled..self.filled + taken].copy_from_slice(&bytes[..taken]);
+            self.filled += taken;
+            bytes = &bytes[taken..];
+
+            if self.filled == 64 {
+                let block = self.block;
+                self.compress(&block);
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Pad out the last block, and give the hash as lowercase hex
+    pub fn finish(mut self) -> String {
+        let bits = self.length * 8;
+        self.update(&[0x80]);
+        while self.filled != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        self.state
+            .iter()
+            .map(|word| format!("{word:08x}"))
+            .collect()
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (k, w) in K.iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(*k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (word, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+}
+
+/// Hash everything that can be read, copying it to `output` on the way
+pub fn copy(input: &mut impl Read, output: &mut impl std::io::Write) -> std::io::Result<String> {
+    let mut hash = Sha256::default();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let read = match input.read(&mut chunk) {
+            Ok(0) => return Ok(hash.finish()),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hash.update(&chunk[..read]);
+        output.write_all(&chunk[..read])?;
+    }
+}
+
+/// Write a line for each file, as `sha256sum` does
+pub fn list(sums: &[(String, String)]) -> String {
+    sums.iter()
+        .map(|(name, hash)| format!("{hash}  {name}\n"))
+        .collect()
+}
+
+/// Read the name and hash of each file from a list, skipping lines that are not checksums
+pub fn parse(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (hash, name) = line.split_once(' ')?;
+            // `sha256sum` marks files hashed in binary mode with a star
+            let name = name.strip_prefix([' ', '*'])?;
+            let is_hash = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+            is_hash.then(|| (name.to_string(), hash.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(bytes: &[u8]) -> String {
+        let mut hash = Sha256::default();
+        hash.update(bytes);
+        hash.finish()
+    }
+
+    #[test]
+    fn known_hashes() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // long enough that the padding spills into a second block
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256(&vec![b'a'; 1_000_000]),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn hash_in_pieces() {
+        let bytes: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        for size in [1, 63, 64, 65, 999] {
+            let mut hash = Sha256::default();
+            for piece in bytes.chunks(size) {
+                hash.update(piece);
+            }
+            assert_eq!(hash.finish(), sha256(&bytes));
+        }
+
+        let mut copied = Vec::new();
+        let hash = copy(&mut bytes.as_slice(), &mut copied).unwrap();
+        assert_eq!(copied, bytes);
+        assert_eq!(hash, sha256(&bytes));
+    }
+
+    #[test]
+    fn list_round_trip() {
+        let sums = vec![
+            ("C4.wav".to_string(), sha256(b"C4")),
+            ("mic 2/C#4.wav".to_string(), sha256(b"C#4")),
+        ];
+        assert_eq!(parse(&list(&sums)), sums);
+
+        // binary mode, uppercase hashes and lines that are not checksums
+        let upper = sha256(b"D4").to_ascii_uppercase();
+        let text = format!("# checksums\n{upper} *D4.wav\nnot a hash  E4.wav\n\n");
+        assert_eq!(parse(&text), vec![("D4.wav".to_string(), sha256(b"D4"))]);
+    }
+}
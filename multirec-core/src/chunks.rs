@@ -0,0 +1,173 @@
+use std::{path::Path, time::SystemTime};
+
+/// Name written as the originator of each file
+const ORIGINATOR: &str = "multirec";
+
+/// Length of the fixed part of a version 1 `bext` chunk
+const BEXT_LEN: usize = 602;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkError {
+    #[error("Not a RIFF WAVE file")]
+    NotWave,
+    #[error("Chunk `{}` runs past the end of the file", String::from_utf8_lossy(.0))]
+    Truncated([u8; 4]),
+    #[error("File is too large to add a chunk to")]
+    TooLarge,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Add a chunk to a WAV file, just before its audio
+///
+/// A chunk with the same ID that is already in the file is replaced.
+pub fn add_chunk(path: impl AsRef<Path>, id: [u8; 4], body: &[u8]) -> Result<(), ChunkError> {
+    let path = path.as_ref();
+    let file = std::fs::read(path)?;
+
+    if file.len() < 12 || &file[..4] != b"RIFF" || &file[8..12] != b"WAVE" {
+        return Err(ChunkError::NotWave);
+    }
+
+    let mut output = Vec::with_capacity(file.len() + body.len() + 9);
+    output.extend_from_slice(&file[..12]);
+
+    let mut position = 12;
+    let mut added = false;
+    while position + 8 <= file.len() {
+        let chunk_id: [u8; 4] = file[position..position + 4].try_into().unwrap();
+        let size = u32::from_le_bytes(file[position + 4..position + 8].try_into().unwrap());
+        // chunks are padded to an even length
+        let end = position + 8 + size as usize + size as usize % 2;
+        if position + 8 + size as usize > file.len() {
+            return Err(ChunkError::Truncated(chunk_id));
+        }
+
+        if chunk_id == *b"data" && !added {
+            write_chunk(&mut output, id, body)?;
+            added = true;
+        }
+        if chunk_id != id {
+            output.extend_from_slice(&file[position..end.min(file.len())]);
+        }
+
+        position = end;
+    }
+
+    if !added {
+        write_chunk(&mut output, id, body)?;
+    }
+
+    let riff_size = u32::try_from(output.len() - 8).map_err(|_| ChunkError::TooLarge)?;
+    output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    std::fs::write(path, output)?;
+    Ok(())
+}
+
+fn write_chunk(output: &mut Vec<u8>, id: [u8; 4], body: &[u8]) -> Result<(), ChunkError> {
+    let size = u32::try_from(body.len()).map_err(|_| ChunkError::TooLarge)?;
+
+    output.extend_from_slice(&id);
+    output.extend_from_slice(&size.to_le_bytes());
+    output.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        output.push(0);
+    }
+
+    Ok(())
+}
+
+/// The body of a Broadcast Wave Format `bext` chunk, version 1
+///
+/// Times are given in UTC, and the time reference counts frames from the
+/// midnight before `recorded`.
+pub fn bext(description: &str, recorded: SystemTime, spec: hound::WavSpec) -> Vec<u8> {
+    let seconds = recorded
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_date(seconds / 86_400);
+    let time_of_day = seconds % 86_400;
+
+    let mut body = Vec::with_capacity(BEXT_LEN);
+    put_text(&mut body, description, 256);
+    put_text(&mut body, ORIGINATOR, 32);
+    put_text(&mut body, "", 32);
+    put_text(&mut body, &format!("{year:04}-{month:02}-{day:02}"), 10);
+    put_text(
+        &mut body,
+        &format!(
+            "{:02}:{:02}:{:02}",
+            time_of_day / 3600,
+            time_of_day / 60 % 60,
+            time_of_day % 60
+        ),
+        8,
+    );
+    body.extend_from_slice(&(time_of_day * u64::from(spec.sample_rate)).to_le_bytes());
+    body.extend_from_slice(&1_u16.to_le_bytes());
+    // no UMID, and the reserved space
+    body.resize(BEXT_LEN, 0);
+
+    let mode = match spec.channels {
+        1 => "mono",
+        2 => "stereo",
+        _ => "multichannel",
+    };
+    body.extend_from_slice(
+        format!(
+            "A=PCM,F={},W={},M={mode},T={ORIGINATOR}\r\n",
+            spec.sample_rate, spec.bits_per_sample
+        )
+        .as_bytes(),
+    );
+
+    body
+}
+
+/// The body of a `LIST` chunk of `INFO` tags, leaving out those without a value
+pub fn info(tags: &[([u8; 4], Option<&str>)]) -> Vec<u8> {
+    let mut body = b"INFO".to_vec();
+
+    for (id, value) in tags {
+        let Some(value) = value else {
+            continue;
+        };
+
+        // values are null-terminated, and padded to an even length
+        let len = value.len() + 1;
+        body.extend_from_slice(id);
+        body.extend_from_slice(&(len as u32).to_le_bytes());
+        body.extend_from_slice(value.as_bytes());
+        body.push(0);
+        if len % 2 == 1 {
+            body.push(0);
+        }
+    }
+
+    body
+}
+
+/// Write ASCII text into a fixed-size field, padded with zeros
+fn put_text(body: &mut Vec<u8>, text: &str, len: usize) {
+    let start = body.len();
+    body.extend(text.bytes().filter(u8::is_ascii).take(len));
+    body.resize(start + len, 0);
+}
+
+/// The year, month and day of a number of days since 1970-01-01
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // shift to a year starting in March, so that leap days come last
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
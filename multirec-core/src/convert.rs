@@ -0,0 +1,384 @@
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use dot_multisample::{LoopMode, Multisample, Sample, ZoneLogic};
+use log::{info, warn};
+use quick_xml::escape::escape;
+
+use crate::{arguments::ConvertFormat, util};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConvertError {
+    #[error("`{}` is already a packed Bitwig multisample", .0.display())]
+    AlreadyPacked(PathBuf),
+    #[error("`{}` would be replaced by its own conversion, give another --output", .0.display())]
+    SamePath(PathBuf),
+}
+
+/// Convert a Bitwig multisample, packed or unpacked, into another format
+///
+/// SFZ and DecentSampler instruments are written to a directory with a copy
+/// of the samples, and an unpacked Bitwig multisample is packed into a
+/// `.multisample` bundle. Returns where the instrument was written.
+pub fn convert(
+    input: &Path,
+    format: ConvertFormat,
+    output: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+    if let ConvertFormat::Bitwig = format {
+        if !input.is_dir() {
+            return Err(ConvertError::AlreadyPacked(input.to_path_buf()).into());
+        }
+
+        let bundle = output.map_or_else(|| input.with_extension("multisample"), Path::to_path_buf);
+        util::read_multisample(&input.join("multisample.xml"))?;
+        util::archive(input, &bundle, zip::CompressionMethod::Stored, false)?;
+        return Ok(bundle);
+    }
+
+    // an unpacked multisample is converted where it is, unless told otherwise
+    let dir = match output {
+        Some(dir) => dir.to_path_buf(),
+        None if input.is_dir() => input.to_path_buf(),
+        None => input.with_extension(""),
+    };
+    if dir.is_file() || (!input.is_dir() && dir == input) {
+        return Err(ConvertError::SamePath(dir).into());
+    }
+
+    let multi = if input.is_dir() {
+        let multi = util::read_multisample(&input.join("multisample.xml"))?;
+        if dir != input {
+            std::fs::create_dir_all(&dir)?;
+            for sample in multi.samples() {
+                let target = dir.join(sample.file());
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                if !target.exists() {
+                    std::fs::copy(input.join(sample.file()), target)?;
+                }
+            }
+        }
+        multi
+    } else {
+        util::unpack(input, &dir)?
+    };
+
+    let name: String = match multi.name() {
+        "" => "instrument".into(),
+        name => name.replace(['/', '\\'], "-"),
+    };
+
+    let path = match format {
+        ConvertFormat::Sfz => {
+            let path = dir.join(format!("{name}.sfz"));
+            write_sfz(&multi, &dir, &path)?;
+            path
+        }
+        ConvertFormat::Decent => {
+            let path = dir.join(format!("{name}.dspreset"));
+            write_decent(&multi, &path)?;
+            path
+        }
+        ConvertFormat::Bitwig => unreachable!("Bitwig multisamples are packed above"),
+    };
+    info!("Wrote {}", path.display());
+
+    Ok(dir)
+}
+
+/// The place of each sample among the round robins it takes turns with, and their number
+///
+/// Samples take turns when they are set to, and are played for exactly
+/// the same keys, velocities and select values.
+fn round_robins(samples: &[Sample<'_>]) -> Vec<Option<(usize, usize)>> {
+    let zone = |sample: &Sample<'_>| {
+        let key = sample.key().as_ref().map(|key| (key.low(), key.high()));
+        let range = |info: &Option<dot_multisample::ZoneInfo>| {
+            info.as_ref().map(|info| (info.low(), info.high()))
+        };
+        (key, range(sample.velocity()), range(sample.select()))
+    };
+
+    samples
+        .iter()
+        .enumerate()
+        .map(|(idx, sample)| {
+            if sample.zone_logic() != Some(ZoneLogic::RoundRobin) {
+                return None;
+            }
+
+            let turns: Vec<_> = samples
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| {
+                    other.zone_logic() == Some(ZoneLogic::RoundRobin) && zone(other) == zone(sample)
+                })
+                .map(|(other_idx, _)| other_idx)
+                .collect();
+            let position = turns.iter().position(|other_idx| *other_idx == idx)?;
+
+            (turns.len() > 1).then_some((position, turns.len()))
+        })
+        .collect()
+}
+
+/// The samples of each group in turn, starting with those in none
+fn by_group<'s, 'a>(multi: &'s Multisample<'a>) -> Vec<(Option<&'s str>, Vec<usize>)> {
+    let in_group = |group: Option<usize>| {
+        multi
+            .samples()
+            .iter()
+            .enumerate()
+            .filter(move |(_, sample)| {
+                // a negative group, or one that doesn't exist, stands for none
+                let index = sample
+                    .group()
+                    .and_then(|idx| usize::try_from(idx).ok())
+                    .filter(|idx| *idx < multi.groups().len());
+                index == group
+            })
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>()
+    };
+
+    std::iter::once((None, in_group(None)))
+        .chain(
+            multi
+                .groups()
+                .iter()
+                .enumerate()
+                .map(|(idx, group)| (Some(group.name()), in_group(Some(idx)))),
+        )
+        .filter(|(_, samples)| !samples.is_empty())
+        .collect()
+}
+
+/// Length of a loop's crossfade in frames, from its share of the loop
+fn loop_crossfade(sample: &Sample<'_>) -> Option<f64> {
+    let points = sample.r#loop().as_ref()?;
+    let length = points.stop()? - points.start()?;
+    Some(points.fade()? * length).filter(|frames| *frames > 0.0)
+}
+
+fn sample_rate(dir: &Path, sample: &Sample<'_>) -> anyhow::Result<u32> {
+    Ok(hound::WavReader::open(dir.join(sample.file()))?
+        .spec()
+        .sample_rate)
+}
+
+fn write_sfz(multi: &Multisample<'_>, dir: &Path, path: &Path) -> anyhow::Result<()> {
+    let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let turns = round_robins(multi.samples());
+
+    for (group, indices) in by_group(multi) {
+        if let Some(name) = group {
+            writeln!(f, "<group> group_label={name}")?;
+        }
+
+        for idx in indices {
+            let sample = &multi.samples()[idx];
+            write!(f, "<region> sample={}", sample.file().display())?;
+
+            if let Some(key) = sample.key() {
+                if let Some(root) = key.root() {
+                    write!(f, " pitch_keycenter={root}")?;
+                }
+                if let Some(low) = key.low() {
+                    write!(f, " lokey={low}")?;
+                }
+                if let Some(high) = key.high() {
+                    write!(f, " hikey={high}")?;
+                }
+                if let Some(fade) = key.low_fade().filter(|fade| *fade > 0) {
+                    let low = key.low().unwrap_or(0);
+                    write!(
+                        f,
+                        " xfin_lokey={low} xfin_hikey={}",
+                        low.saturating_add(fade)
+                    )?;
+                }
+                if let Some(fade) = key.high_fade().filter(|fade| *fade > 0) {
+                    let high = key.high().unwrap_or(127);
+                    write!(
+                        f,
+                        " xfout_lokey={} xfout_hikey={high}",
+                        high.saturating_sub(fade)
+                    )?;
+                }
+                match key.tune().map(|tune| (tune * 100.0).round() as i32) {
+                    Some(0) | None => {}
+                    Some(tune) => write!(f, " tune={tune}")?,
+                }
+                if let Some(track) = key.track().filter(|track| *track != 1.0) {
+                    write!(f, " pitch_keytrack={}", (track * 100.0).round())?;
+                }
+            }
+
+            if let Some(velocity) = sample.velocity() {
+                if let Some(low) = velocity.low() {
+                    write!(f, " lovel={low}")?;
+                }
+                if let Some(high) = velocity.high() {
+                    write!(f, " hivel={high}")?;
+                }
+            }
+
+            if let Some((position, length)) = turns[idx] {
+                write!(f, " seq_length={length} seq_position={}", position + 1)?;
+            }
+
+            if let Some(gain) = sample.gain() {
+                write!(f, " volume={gain:.2}")?;
+            }
+            if let Some(start) = sample.sample_start().filter(|start| *start > 0.0) {
+                write!(f, " offset={}", start.round())?;
+            }
+            if let Some(stop) = sample.sample_stop() {
+                write!(f, " end={}", (stop.round() - 1.0).max(0.0))?;
+            }
+            if sample.reverse() == Some(true) {
+                write!(f, " direction=reverse")?;
+            }
+
+            if let Some(points) = sample
+                .r#loop()
+                .as_ref()
+                .filter(|points| points.mode().unwrap_or_default() != LoopMode::Off)
+            {
+                // with a tail to play, only loop while the key is held
+                let mode = if sample.sample_stop().is_some() {
+                    "loop_sustain"
+                } else {
+                    "loop_continuous"
+                };
+                write!(f, " loop_mode={mode}")?;
+                if points.mode() == Some(LoopMode::PingPong) {
+                    write!(f, " loop_type=alternate")?;
+                }
+                if let Some(start) = points.start() {
+                    write!(f, " loop_start={}", start.round())?;
+                }
+                if let Some(stop) = points.stop() {
+                    write!(f, " loop_end={}", (stop.round() - 1.0).max(0.0))?;
+                }
+                if let Some(frames) = loop_crossfade(sample) {
+                    let seconds = frames / f64::from(sample_rate(dir, sample)?);
+                    write!(f, " loop_crossfade={seconds:.4}")?;
+                }
+            }
+
+            writeln!(f)?;
+        }
+    }
+
+    f.flush()?;
+    Ok(())
+}
+
+fn write_decent(multi: &Multisample<'_>, path: &Path) -> anyhow::Result<()> {
+    let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let turns = round_robins(multi.samples());
+
+    let has_fades = multi.samples().iter().any(|sample| {
+        sample.key().as_ref().is_some_and(|key| {
+            key.low_fade().is_some_and(|fade| fade > 0)
+                || key.high_fade().is_some_and(|fade| fade > 0)
+        })
+    });
+    if has_fades {
+        warn!("DecentSampler has no crossfades between keys, so they are left out");
+    }
+
+    writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(f, r#"<DecentSampler minVersion="1.0.0">"#)?;
+    writeln!(f, "\t<groups>")?;
+
+    for (group, indices) in by_group(multi) {
+        match group {
+            Some(name) => writeln!(f, "\t\t<group name=\"{}\">", escape(name))?,
+            None => writeln!(f, "\t\t<group>")?,
+        }
+
+        for idx in indices {
+            let sample = &multi.samples()[idx];
+            let file = sample.file().to_string_lossy().replace('\\', "/");
+            write!(f, "\t\t\t<sample path=\"{}\"", escape(&file))?;
+
+            if let Some(key) = sample.key() {
+                if let Some(root) = key.root() {
+                    write!(f, r#" rootNote="{root}""#)?;
+                }
+                write!(
+                    f,
+                    r#" loNote="{}" hiNote="{}""#,
+                    key.low().unwrap_or(0),
+                    key.high().unwrap_or(127)
+                )?;
+                if let Some(tune) = key.tune().filter(|tune| *tune != 0.0) {
+                    write!(f, r#" tuning="{tune}""#)?;
+                }
+                if let Some(track) = key.track() {
+                    write!(f, r#" pitchKeyTrack="{track}""#)?;
+                }
+            }
+
+            if let Some(velocity) = sample.velocity() {
+                write!(
+                    f,
+                    r#" loVel="{}" hiVel="{}""#,
+                    velocity.low().unwrap_or(1).max(1),
+                    velocity.high().unwrap_or(127)
+                )?;
+            }
+
+            if let Some((position, length)) = turns[idx] {
+                write!(
+                    f,
+                    r#" seqMode="round_robin" seqLength="{length}" seqPosition="{}""#,
+                    position + 1
+                )?;
+            }
+
+            if let Some(gain) = sample.gain() {
+                write!(f, r#" volume="{gain:.2}dB""#)?;
+            }
+            if let Some(start) = sample.sample_start().filter(|start| *start > 0.0) {
+                write!(f, r#" start="{}""#, start.round())?;
+            }
+            if let Some(stop) = sample.sample_stop() {
+                write!(f, r#" end="{}""#, (stop.round() - 1.0).max(0.0))?;
+            }
+
+            if let Some(points) = sample
+                .r#loop()
+                .as_ref()
+                .filter(|points| points.mode().unwrap_or_default() != LoopMode::Off)
+            {
+                write!(f, r#" loopEnabled="true""#)?;
+                if let Some(start) = points.start() {
+                    write!(f, r#" loopStart="{}""#, start.round())?;
+                }
+                if let Some(stop) = points.stop() {
+                    write!(f, r#" loopEnd="{}""#, (stop.round() - 1.0).max(0.0))?;
+                }
+                if let Some(frames) = loop_crossfade(sample) {
+                    write!(f, r#" loopCrossfade="{}""#, frames.round())?;
+                }
+            }
+
+            writeln!(f, "/>")?;
+        }
+
+        writeln!(f, "\t\t</group>")?;
+    }
+
+    writeln!(f, "\t</groups>")?;
+    writeln!(f, "</DecentSampler>")?;
+
+    f.flush()?;
+    Ok(())
+}
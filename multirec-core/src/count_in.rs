@@ -0,0 +1,156 @@
+use std::{num::NonZeroU8, time::Duration};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use log::{info, warn};
+
+use autosam::midi::{Channel, NoteState, Pitch};
+
+use crate::monitor::MidiOut;
+
+/// Length of each beep
+const BEEP_LENGTH: Duration = Duration::from_millis(80);
+/// Pitch of the beep on the first beat, and of the rest
+const BEEP_FREQUENCIES: (f32, f32) = (1760.0, 880.0);
+/// Level of each beep
+const BEEP_LEVEL: f32 = 0.3;
+/// Time to fade each beep in and out over, so it does not click
+const BEEP_FADE: Duration = Duration::from_millis(5);
+/// Velocity of the note on the first beat, and of the rest
+const NOTE_VELOCITIES: (u8, u8) = (127, 96);
+
+#[derive(Debug, thiserror::Error)]
+pub enum CountInError {
+    #[error("Unsupported count-in output sample format '{0}'")]
+    SampleFormat(cpal::SampleFormat),
+}
+
+/// How the beats of a count-in are heard
+pub enum Cue<D> {
+    /// Beeps through an audio output, selected by `D`
+    Beep(D),
+    /// A note played over MIDI on each beat
+    Note(Channel, Pitch),
+}
+
+/// Beats played before the first note, so the run does not start unannounced
+pub struct CountIn<D> {
+    pub beats: NonZeroU8,
+    pub beat: Duration,
+    pub cue: Cue<D>,
+}
+
+/// Beep each beat through an output device, returning once the last beat is over
+pub fn beep(device: &cpal::Device, beats: NonZeroU8, beat: Duration) -> anyhow::Result<()> {
+    let supported = device.default_output_config()?;
+    let sample_format = supported.sample_format();
+    let config = supported.config();
+    info!(
+        "Counting in {beats} beats on {}, recording starts after the last",
+        device.name()?
+    );
+
+    let clicks = Clicks {
+        rate: config.sample_rate.0 as f32,
+        channels: usize::from(config.channels),
+        beat: beat.as_secs_f32(),
+        beats: f32::from(beats.get()),
+        frame: 0,
+    };
+    let stream = match sample_format {
+        cpal::SampleFormat::I16 => build_output_stream::<i16>(device, &config, clicks)?,
+        cpal::SampleFormat::I32 => build_output_stream::<i32>(device, &config, clicks)?,
+        cpal::SampleFormat::F32 => build_output_stream::<f32>(device, &config, clicks)?,
+        sample_format => return Err(CountInError::SampleFormat(sample_format).into()),
+    };
+    stream.play()?;
+    std::thread::sleep(beat * u32::from(beats.get()));
+
+    Ok(())
+}
+
+/// Play a note over MIDI on each beat, returning once the last beat is over
+pub fn tap(
+    midi_connection: &mut MidiOut,
+    channel: Channel,
+    pitch: Pitch,
+    beats: NonZeroU8,
+    beat: Duration,
+) -> anyhow::Result<()> {
+    info!("Counting in {beats} beats with {pitch}, recording starts after the last");
+
+    let key = pitch.note_number();
+    for count in 0..beats.get() {
+        let velocity = if count == 0 {
+            NOTE_VELOCITIES.0
+        } else {
+            NOTE_VELOCITIES.1
+        };
+        midi_connection.send(
+            &[NoteState::On.as_midi_message(channel), key, velocity],
+            None,
+        )?;
+        std::thread::sleep(beat / 2);
+        midi_connection.send(&[NoteState::Off.as_midi_message(channel), key, 0], None)?;
+        std::thread::sleep(beat - beat / 2);
+    }
+
+    Ok(())
+}
+
+fn build_output_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut clicks: Clicks,
+) -> anyhow::Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    Ok(device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &_| clicks.fill(data),
+        |e| warn!("Error during count-in: {e}"),
+        None,
+    )?)
+}
+
+/// A beep at the start of each beat, on every output
+struct Clicks {
+    rate: f32,
+    channels: usize,
+    /// Length of a beat, in seconds
+    beat: f32,
+    beats: f32,
+    /// Frame to play next
+    frame: u64,
+}
+
+impl Clicks {
+    fn fill<T>(&mut self, data: &mut [T])
+    where
+        T: cpal::Sample + cpal::FromSample<f32>,
+    {
+        let length = BEEP_LENGTH.as_secs_f32();
+        let fade = BEEP_FADE.as_secs_f32();
+        for frame in data.chunks_mut(self.channels) {
+            let time = self.frame as f32 / self.rate;
+            let count = (time / self.beat).floor();
+            let offset = time - count * self.beat;
+            self.frame += 1;
+
+            let value = if count < self.beats && offset < length {
+                let frequency = if count == 0.0 {
+                    BEEP_FREQUENCIES.0
+                } else {
+                    BEEP_FREQUENCIES.1
+                };
+                let envelope = (offset / fade).min((length - offset) / fade).min(1.0);
+                BEEP_LEVEL * envelope * (std::f32::consts::TAU * frequency * offset).sin()
+            } else {
+                0.0
+            };
+            for sample in frame {
+                *sample = T::from_sample(value);
+            }
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    num::{NonZeroU16, NonZeroU32},
+    path::PathBuf,
+    time::Duration,
+};
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use log::{info, warn};
+use midir::{MidiInput, MidiOutput};
+
+use autosam::midi::{Channel, Event, PatchSelect};
+
+use crate::{
+    arguments::*, audition, input_device, input_file, monitor, plugin, rtp, runtime, settings,
+    simulate, sysex, util::get_best_config, RunError,
+};
+
+/// The instrument that a run plays, and the devices that record it
+pub struct Devices {
+    /// A plugin, simulation or input file, which takes the place of both the MIDI port and the audio input
+    pub plugin: Option<plugin::Instrument>,
+    pub input_device: Option<cpal::Device>,
+    pub sample_format: cpal::SampleFormat,
+    pub input_config: cpal::StreamConfig,
+    /// Channels recorded from the first input
+    pub selection: runtime::ChannelSelection,
+    pub channels: u16,
+    /// Further inputs, each with its sample format, config and the channels recorded from it
+    pub mic_devices: Vec<(
+        cpal::Device,
+        cpal::SampleFormat,
+        cpal::StreamConfig,
+        runtime::ChannelSelection,
+    )>,
+    pub audition: Option<audition::Audition>,
+    /// Number of channels recorded from each input, the first included
+    pub mic_channels: Vec<u16>,
+    pub mic_formats: Vec<cpal::SampleFormat>,
+    /// Number of samples in each recorded frame, which holds every input's channels in turn
+    pub layout_width: usize,
+}
+
+impl Devices {
+    /// Open the instrument and the audio inputs, and the output to audition on if there is one
+    pub fn open(
+        args: &Setup,
+        host: &cpal::Host,
+        audition: Option<Option<Matcher>>,
+    ) -> anyhow::Result<Self> {
+        // a plugin, simulation or input file takes the place of both the MIDI port and the audio input
+        let plugin = if let Some(path) = &args.plugin {
+            let plugin = plugin::Plugin::load(path, args.plugin_sample_rate)?;
+            info!("Hosting plugin {}", plugin.name());
+            Some(plugin::Instrument::Plugin(plugin))
+        } else if let Some(simulation) = args.simulate {
+            let simulator = simulate::Simulator::new(simulation, args.plugin_sample_rate);
+            info!("Recording {}", simulator.name());
+            Some(plugin::Instrument::Simulator(simulator))
+        } else if let Some(path) = &args.input_file {
+            let file =
+                input_file::InputFile::open(path, args.raw_channels, args.plugin_sample_rate)?;
+            info!("Reading audio from {}", file.name());
+            Some(plugin::Instrument::File(file))
+        } else {
+            None
+        };
+
+        if args.input_device.len() > runtime::MAX_INPUT_DEVICES {
+            return Err(RunError::TooManyDevices(args.input_device.len()).into());
+        }
+
+        let (input_device, sample_format, mut input_config) = if let Some(plugin) = &plugin {
+            (None, cpal::SampleFormat::F32, plugin.stream_config())
+        } else {
+            let (device, supported_config, config) = open_input_device(
+                host,
+                args.input_device.first().cloned(),
+                args.buffer_size,
+                args.exclusive,
+            )?;
+            (Some(device), supported_config.sample_format(), config)
+        };
+        let selection = select_channels(&args.channels, &mut input_config, plugin.is_none())?;
+        let channels = selection.len() as u16;
+        info!("Channels set to {channels}");
+
+        // further inputs are recorded alongside the first, at the same sample rate
+        let mut mic_devices = Vec::new();
+        for matcher in args.input_device.iter().skip(1) {
+            let (device, supported_config, mut config) = open_input_device(
+                host,
+                Some(matcher.clone()),
+                args.buffer_size,
+                args.exclusive,
+            )?;
+            if config.sample_rate != input_config.sample_rate {
+                return Err(RunError::SampleRateMismatch(
+                    device.name()?,
+                    config.sample_rate.0,
+                    input_config.sample_rate.0,
+                )
+                .into());
+            }
+
+            let selection = select_channels(&args.channels, &mut config, true)?;
+            mic_devices.push((device, supported_config.sample_format(), config, selection));
+        }
+
+        let audition = match audition {
+            Some(matcher) => {
+                let device = open_output_device(host, matcher)?;
+                info!("Auditioning recordings on {}", device.name()?);
+                Some(audition::Audition::start(
+                    device,
+                    input_config.sample_rate.0,
+                )?)
+            }
+            None => None,
+        };
+
+        let mic_channels: Vec<u16> = std::iter::once(channels)
+            .chain(
+                mic_devices
+                    .iter()
+                    .map(|(_, _, _, selection)| selection.len() as u16),
+            )
+            .collect();
+        let mic_formats: Vec<cpal::SampleFormat> = std::iter::once(sample_format)
+            .chain(mic_devices.iter().map(|(_, format, _, _)| *format))
+            .collect();
+        // each recorded frame holds every input's channels in turn
+        let layout_width: usize = mic_channels.iter().map(|c| usize::from(*c)).sum();
+
+        Ok(Devices {
+            plugin,
+            input_device,
+            sample_format,
+            input_config,
+            selection,
+            channels,
+            mic_devices,
+            audition,
+            mic_channels,
+            mic_formats,
+            layout_width,
+        })
+    }
+
+    /// What each device is used for, and its name, for the plan of a run
+    pub fn describe(&self, args: &Setup) -> anyhow::Result<Vec<(&'static str, String)>> {
+        let mut devices = Vec::new();
+        if let Some(plugin) = &self.plugin {
+            devices.push((plugin.kind(), plugin.name().to_string()));
+        }
+        if let Some(device) = &self.input_device {
+            devices.push(("Audio input", device.name()?));
+        }
+        for (device, _, _, _) in &self.mic_devices {
+            devices.push(("Audio input", device.name()?));
+        }
+        if self.plugin.is_none() {
+            let midi = match args.rtp_midi {
+                Some(address) => format!("RTP-MIDI session at {address}"),
+                None => format!("port {}", args.midi_port),
+            };
+            devices.push(("MIDI output", midi));
+        }
+
+        Ok(devices)
+    }
+}
+
+/// What is sent to the instrument to set it up before recording
+pub struct MidiSetup {
+    pub patch: Option<PatchSelect>,
+    pub program_delay: Duration,
+    pub sysex: Vec<SysEx>,
+    pub sysex_delay: Duration,
+    pub dump_timeout: Duration,
+}
+
+impl MidiSetup {
+    /// Read the patch, SysEx messages and delays of a setup
+    pub fn new(args: &Setup) -> anyhow::Result<Self> {
+        Ok(MidiSetup {
+            patch: args.patch()?,
+            program_delay: args.program_delay()?,
+            sysex: args.sysex()?,
+            sysex_delay: args.sysex_delay()?,
+            dump_timeout: args.dump_timeout()?,
+        })
+    }
+
+    /// The events that select the patch, on every channel that plays it
+    pub fn patch_events(&self, settings: &settings::Settings) -> Vec<Event> {
+        match self.patch {
+            Some(patch) => {
+                info!("Selecting program {}", patch.program());
+                // every channel that round robins take turns on plays the same patch
+                let controls: Vec<Channel> = match (&settings.mpe, &settings.round_robin_channels) {
+                    (Some(mpe), _) => vec![mpe.master_channel()],
+                    (None, Some(rotation)) => rotation.iter().collect(),
+                    (None, None) => vec![settings.channel],
+                };
+                controls
+                    .into_iter()
+                    .flat_map(|control| patch.events(control))
+                    .collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Connect to the instrument's MIDI port, silence it and send it the setup
+    ///
+    /// The patch dump, if one is requested, is saved in each of `dump_dirs`.
+    pub fn connect(
+        &self,
+        args: &Setup,
+        sound_off: &[[u8; 3]],
+        patch_events: &[Event],
+        dump_dirs: Option<&[PathBuf]>,
+    ) -> anyhow::Result<monitor::MidiOut> {
+        let connection = match args.rtp_midi {
+            Some(address) => {
+                monitor::Connection::Network(rtp::Session::connect(address, "multirec")?)
+            }
+            None => {
+                let midi_output = MidiOutput::new("MIDI Output")?;
+                let midi_ports = midi_output.ports();
+                let midi_out_port = args
+                    .midi_port
+                    .get(&midi_ports, |p| midi_output.port_name(p))?
+                    .ok_or(match &args.midi_port {
+                        Matcher::Index(i) => RunError::InvalidPortIndex(*i),
+                        Matcher::String(s) => RunError::NoSuchPort(s.clone()),
+                    })?;
+                let port_name = midi_output.port_name(midi_out_port)?;
+                let connection = midi_output
+                    .connect(midi_out_port, "autosam")
+                    .map_err(|e| RunError::MidiConnect(port_name.clone(), e.kind()))?;
+
+                info!("Connected to MIDI output port {port_name}");
+                monitor::Connection::Port(connection)
+            }
+        };
+        let mut midi_connection = monitor::MidiOut::new(connection, args.midi_monitor.as_deref())?;
+
+        for msg in sound_off {
+            midi_connection.send(msg, None)?;
+        }
+
+        if !self.sysex.is_empty() {
+            info!("Sending {} SysEx message(s)", self.sysex.len());
+        }
+        for msg in &self.sysex {
+            midi_connection.send(&msg.0, None)?;
+            std::thread::sleep(self.sysex_delay);
+        }
+
+        if !patch_events.is_empty() {
+            for event in patch_events {
+                midi_connection.send(&event.as_midi_message(), None)?;
+            }
+
+            // let the instrument load the program before anything is recorded
+            std::thread::sleep(self.program_delay);
+        }
+
+        // the dump is taken once the instrument is set up, so it matches the recordings
+        if let (Some(request), Some(dump_dirs)) = (&args.dump_request, dump_dirs) {
+            let midi_input = MidiInput::new("MIDI Input")?;
+            let midi_ports = midi_input.ports();
+            let midi_in_port = args
+                .dump_port
+                .get(&midi_ports, |p| midi_input.port_name(p))?
+                .ok_or(match &args.dump_port {
+                    Matcher::Index(i) => RunError::InvalidPortIndex(*i),
+                    Matcher::String(s) => RunError::NoSuchPort(s.clone()),
+                })?;
+
+            let dump = sysex::capture_dump(
+                midi_input,
+                midi_in_port,
+                &mut midi_connection,
+                request,
+                self.dump_timeout,
+            )?;
+            for dir in dump_dirs {
+                std::fs::create_dir_all(dir)?;
+                std::fs::write(dir.join(sysex::DUMP_FILE_NAME), &dump)?;
+            }
+            info!("Saved a patch dump of {} bytes", dump.len());
+        }
+
+        Ok(midi_connection)
+    }
+}
+
+/// All Sound Off on every channel that a run plays on
+pub fn sound_off(settings: &settings::Settings) -> Vec<[u8; 3]> {
+    match &settings.mpe {
+        Some(mpe) => std::iter::once(mpe.master_channel())
+            .chain((0..mpe.member_channels.get()).map(|m| mpe.member_channel(m)))
+            .map(|channel| channel.all_sound_off())
+            .collect(),
+        None => match &settings.round_robin_channels {
+            Some(rotation) => rotation.iter().map(|c| c.all_sound_off()).collect(),
+            None => vec![settings.channel.all_sound_off()],
+        },
+    }
+}
+
+/// Pick out the channels to record from an input, opening all the ones needed
+fn select_channels(
+    channels: &[NonZeroU16],
+    config: &mut cpal::StreamConfig,
+    is_device: bool,
+) -> anyhow::Result<runtime::ChannelSelection> {
+    if channels.is_empty() {
+        // every channel of a plugin's output is rendered, so the first two are picked out
+        if is_device {
+            config.channels = config.channels.min(2);
+        }
+        return Ok(runtime::ChannelSelection::first(usize::from(
+            config.channels.min(2),
+        )));
+    }
+
+    // open every channel of the device, and pick out the requested ones
+    if let Some(channel) = channels
+        .iter()
+        .find(|channel| channel.get() > config.channels)
+    {
+        return Err(RunError::NoSuchChannel(channel.get(), config.channels).into());
+    }
+
+    let indices: Vec<_> = channels
+        .iter()
+        .map(|channel| usize::from(channel.get() - 1))
+        .collect();
+    Ok(runtime::ChannelSelection::new(&indices))
+}
+
+/// Find an audio output by index or name, or the host's default
+pub fn open_output_device(
+    host: &cpal::Host,
+    matcher: Option<Matcher>,
+) -> anyhow::Result<cpal::Device> {
+    Ok(match matcher {
+        Some(matcher) => {
+            matcher
+                .get(host.output_devices()?, |d| d.name())?
+                .ok_or(match matcher {
+                    Matcher::Index(i) => RunError::InvalidDeviceIndex(i),
+                    Matcher::String(s) => RunError::NoSuchDevice(s),
+                })?
+        }
+        None => host
+            .default_output_device()
+            .ok_or(RunError::NoDefaultOutputDevice)?,
+    })
+}
+
+/// Find the audio input to record from, and the best way to open it
+fn open_input_device(
+    host: &cpal::Host,
+    matcher: Option<Matcher>,
+    buffer_size: Option<NonZeroU32>,
+    exclusive: bool,
+) -> anyhow::Result<(
+    cpal::Device,
+    cpal::SupportedStreamConfig,
+    cpal::StreamConfig,
+)> {
+    let input_device = input_device(host, matcher)?;
+    let input_device = if exclusive {
+        exclusive_device(host, input_device)?
+    } else {
+        input_device
+    };
+    info!("Using audio input device {}", input_device.name()?);
+
+    let supported_input_config = get_best_config(&input_device)?;
+    info!(
+        "Sample rate set to {}",
+        supported_input_config.sample_rate().0
+    );
+
+    let mut input_config = supported_input_config.config();
+    input_config.buffer_size = match (buffer_size, supported_input_config.buffer_size()) {
+        (Some(size), cpal::SupportedBufferSize::Range { min, max })
+            if !(*min..=*max).contains(&size.get()) =>
+        {
+            return Err(RunError::BufferSize(size.get(), *min, *max).into());
+        }
+        (Some(size), supported) => {
+            if matches!(supported, cpal::SupportedBufferSize::Unknown) {
+                warn!("Audio device did not report its buffer sizes, trying {size}");
+            }
+            info!("Buffer size set to {size}");
+            cpal::BufferSize::Fixed(size.get())
+        }
+        (None, cpal::SupportedBufferSize::Range { min, max }) => {
+            let buffer_size = min.next_power_of_two().clamp(32, *max);
+            info!("Buffer size set to {buffer_size}");
+            cpal::BufferSize::Fixed(buffer_size)
+        }
+        (None, cpal::SupportedBufferSize::Unknown) => {
+            warn!("Audio device did not report a buffer size, using the default");
+            cpal::BufferSize::Default
+        }
+    };
+
+    Ok((input_device, supported_input_config, input_config))
+}
+
+/// Find the ALSA hardware device of the card that a device plays through
+///
+/// Other hosts have no way to open a device exclusively, so the device is
+/// kept as it is.
+fn exclusive_device(host: &cpal::Host, device: cpal::Device) -> anyhow::Result<cpal::Device> {
+    let name = device.name()?;
+    if host.id().name() != "ALSA" {
+        warn!(
+            "{} devices cannot be opened exclusively, sharing {name}",
+            host.id().name()
+        );
+        return Ok(device);
+    }
+    if name.starts_with("hw:") {
+        return Ok(device);
+    }
+
+    // ALSA names devices like `sysdefault:CARD=PCH` or `front:CARD=PCH,DEV=0`
+    let setting = |key: &str| {
+        name.split([':', ','])
+            .find_map(|part| part.strip_prefix(key))
+            .map(str::to_string)
+    };
+    let Some(card) = setting("CARD=") else {
+        return Err(RunError::NoCard(name).into());
+    };
+    let hardware = format!(
+        "hw:CARD={card},DEV={}",
+        setting("DEV=").as_deref().unwrap_or("0")
+    );
+
+    for candidate in host.input_devices()? {
+        if candidate.name()? == hardware {
+            return Ok(candidate);
+        }
+    }
+
+    Err(RunError::NoSuchDevice(hardware).into())
+}
@@ -0,0 +1,136 @@
+use std::num::NonZeroU8;
+
+use autosam::midi::{ParsePitchError, Pitch};
+
+/// A drum pad: the note that plays it, its name, and how it is sampled
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pad {
+    pub pitch: Pitch,
+    pub name: String,
+    /// Velocity layers to sample, instead of --velocity-layers
+    pub velocity_layers: Option<NonZeroU8>,
+    /// Round robins to sample, instead of --round-robins
+    pub round_robins: Option<NonZeroU8>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PadError {
+    #[error("Expected `note=name`, found `{0}`")]
+    Format(String),
+    #[error(transparent)]
+    Pitch(#[from] ParsePitchError),
+    #[error("Pad names cannot be empty")]
+    EmptyName,
+    #[error("Pad names cannot contain path separators")]
+    PathSeparator,
+    #[error("Unknown setting `{0}`, expected `layers=N` or `rr=N`")]
+    Setting(String),
+    #[error("Invalid count in `{0}`")]
+    Count(String),
+}
+
+impl Pad {
+    /// Look up the pad for a note
+    pub fn find(pads: &[Self], note: u8) -> Option<&Self> {
+        pads.iter().find(|pad| pad.pitch.note_number() == note)
+    }
+}
+
+impl core::str::FromStr for Pad {
+    type Err = PadError;
+
+    /// Parse a note and name, with any settings after it, e.g. `38=Snare,layers=4,rr=3`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pitch, rest) = s
+            .split_once('=')
+            .ok_or_else(|| PadError::Format(s.trim().to_string()))?;
+        let mut parts = rest.split(',').map(str::trim);
+
+        let name = parts.next().unwrap_or_default().to_string();
+        if name.is_empty() {
+            return Err(PadError::EmptyName);
+        }
+        if name.contains(['/', '\\']) {
+            return Err(PadError::PathSeparator);
+        }
+
+        let mut pad = Self {
+            pitch: pitch.trim().parse()?,
+            name,
+            velocity_layers: None,
+            round_robins: None,
+        };
+
+        for setting in parts.filter(|part| !part.is_empty()) {
+            let (key, count) = setting
+                .split_once('=')
+                .ok_or_else(|| PadError::Setting(setting.to_string()))?;
+            let count = count
+                .trim()
+                .parse::<NonZeroU8>()
+                .map_err(|_| PadError::Count(setting.to_string()))?;
+
+            match key.trim() {
+                "layers" => pad.velocity_layers = Some(count),
+                "rr" => pad.round_robins = Some(count),
+                _ => return Err(PadError::Setting(setting.to_string())),
+            }
+        }
+
+        Ok(pad)
+    }
+}
+
+/// The pads of a drum kit, one per line, e.g. `36=Kick`
+///
+/// Each pad may set its own velocity layers and round robins, e.g.
+/// `38=Snare,layers=4,rr=3`. Blank lines and lines starting with `#` are
+/// ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PadMap(pub Vec<Pad>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum PadMapError {
+    #[error("Line {0}: {1}")]
+    Pad(usize, PadError),
+    #[error("Note {0} is mapped to more than one pad")]
+    Duplicate(Pitch),
+    #[error("No pads are listed")]
+    Empty,
+}
+
+impl core::str::FromStr for PadMap {
+    type Err = PadMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pads: Vec<Pad> = Vec::new();
+
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let pad = line
+                .parse::<Pad>()
+                .map_err(|e| PadMapError::Pad(idx + 1, e))?;
+            if Pad::find(&pads, pad.pitch.note_number()).is_some() {
+                return Err(PadMapError::Duplicate(pad.pitch));
+            }
+
+            pads.push(pad);
+        }
+
+        if pads.is_empty() {
+            return Err(PadMapError::Empty);
+        }
+
+        Ok(Self(pads))
+    }
+}
+
+impl PadMap {
+    pub fn read(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(std::fs::read_to_string(path)?.parse()?)
+    }
+}
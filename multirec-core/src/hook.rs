@@ -0,0 +1,198 @@
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use log::{debug, warn};
+
+/// A shell command run on each recorded file
+///
+/// `{in}` is replaced by the path of the file and `{out}` by a path to write a
+/// new version of it to, which then takes its place. Without `{out}`, the
+/// command is expected to change the file where it is. `{name}` is replaced by
+/// the file name without its extension.
+#[derive(Clone, Debug)]
+pub struct PostCommand(String);
+
+#[derive(Debug, thiserror::Error)]
+pub enum PostCommandError {
+    #[error("Command must contain `{{in}}` to receive each file")]
+    NoInput,
+}
+
+impl std::str::FromStr for PostCommand {
+    type Err = PostCommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains("{in}") {
+            return Err(PostCommandError::NoInput);
+        }
+
+        Ok(Self(s.to_string()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HookError {
+    #[error("Command exited with {0}")]
+    Status(std::process::ExitStatus),
+    #[error("Command did not write `{}`", .0.display())]
+    NoOutput(PathBuf),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl PostCommand {
+    fn writes_output(&self) -> bool {
+        self.0.contains("{out}")
+    }
+
+    fn command_line(&self, input: &Path, output: &Path) -> String {
+        let name = input
+            .file_stem()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+
+        self.0
+            .replace("{in}", &quote(&input.to_string_lossy()))
+            .replace("{out}", &quote(&output.to_string_lossy()))
+            .replace("{name}", &quote(&name))
+    }
+
+    /// Run the command on one file, replacing it with the command's output if there is any
+    pub fn run(&self, path: &Path) -> Result<(), HookError> {
+        let output = path.with_extension("post.wav");
+        let line = self.command_line(path, &output);
+        debug!("Running `{line}`");
+
+        let status = if cfg!(windows) {
+            Command::new("cmd").arg("/C").arg(&line).status()?
+        } else {
+            Command::new("sh").arg("-c").arg(&line).status()?
+        };
+
+        if !status.success() {
+            let _ = std::fs::remove_file(&output);
+            return Err(HookError::Status(status));
+        }
+
+        if self.writes_output() {
+            if !output.is_file() {
+                return Err(HookError::NoOutput(output));
+            }
+            std::fs::rename(&output, path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the command on every file, `jobs` at a time
+    ///
+    /// A file that the command fails on is left as it was, and returned with the reason.
+    pub fn run_all(&self, files: &[PathBuf], jobs: NonZeroUsize) -> Vec<(PathBuf, HookError)> {
+        let next = AtomicUsize::new(0);
+        let failures = Mutex::new(Vec::new());
+
+        std::thread::scope(|scope| {
+            for _ in 0..jobs.get().min(files.len()) {
+                scope.spawn(|| {
+                    while let Some(path) = files.get(next.fetch_add(1, Ordering::AcqRel)) {
+                        if let Err(e) = self.run(path) {
+                            warn!("Post-processing command failed on {}: {e}", path.display());
+                            failures
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .push((path.clone(), e));
+                        }
+                    }
+                });
+            }
+        });
+
+        failures.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Quote a path so that the shell passes it on as one argument
+fn quote(s: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{s}\"")
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_needs_input() {
+        assert!("sox {out} norm".parse::<PostCommand>().is_err());
+        assert!("sox {in} {out} norm".parse::<PostCommand>().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quote_for_sh() {
+        assert_eq!(quote("C4.wav"), "'C4.wav'");
+        assert_eq!(quote("my file.wav"), "'my file.wav'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote(""), "''");
+
+        // the shell hands each one back as it was
+        for text in ["a b", "it's", "$HOME `ls` \"x\" \\ *", "''", "\n"] {
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", quote(text)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), text);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_line_fills_in_paths() {
+        let command: PostCommand = "sox {in} {out} norm -1 # {name}, {name}".parse().unwrap();
+        assert!(command.writes_output());
+        assert_eq!(
+            command.command_line(Path::new("/tmp/it's C4.wav"), Path::new("/tmp/C4.post.wav")),
+            r"sox '/tmp/it'\''s C4.wav' '/tmp/C4.post.wav' norm -1 # 'it'\''s C4', 'it'\''s C4'"
+        );
+
+        let command: PostCommand = "touch {in}".parse().unwrap();
+        assert!(!command.writes_output());
+        assert_eq!(
+            command.command_line(Path::new("C4.wav"), Path::new("C4.post.wav")),
+            "touch 'C4.wav'"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("multirec-hook-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("C4 it's.wav");
+        std::fs::write(&path, "before").unwrap();
+
+        let command: PostCommand = "printf after > {out}; test -f {in}".parse().unwrap();
+        command.run(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after");
+
+        let command: PostCommand = "true {in} {out}".parse().unwrap();
+        assert!(matches!(command.run(&path), Err(HookError::NoOutput(_))));
+        let command: PostCommand = "printf x > {out}; false {in}".parse().unwrap();
+        assert!(matches!(command.run(&path), Err(HookError::Status(_))));
+        assert!(!path.with_extension("post.wav").exists());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
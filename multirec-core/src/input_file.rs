@@ -0,0 +1,174 @@
+use std::{
+    io::{BufReader, Read as _},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::{
+    plugin::BLOCK_SIZE,
+    runtime::{AudioProcessor, Capture, RunState, TimedEvent},
+};
+
+/// Path that stands for raw audio on standard input
+const STDIN: &str = "-";
+
+#[derive(Debug, thiserror::Error)]
+pub enum InputFileError {
+    #[error("Could not open `{}`: {1}", .0.display())]
+    Open(PathBuf, hound::Error),
+    #[error("Could not read `{}`: {1}", .0.display())]
+    Read(PathBuf, hound::Error),
+    #[error("`{}` has no audio channels", .0.display())]
+    NoChannels(PathBuf),
+}
+
+type Samples = Box<dyn Iterator<Item = Result<f32, hound::Error>> + Send>;
+
+/// A recording made elsewhere, played through the run in place of an audio input
+///
+/// Nothing is sent over MIDI: the sequence only marks out where each zone
+/// lies in the recording, which must start at the sequence's first frame.
+pub struct InputFile {
+    path: PathBuf,
+    name: String,
+    samples: Samples,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl InputFile {
+    /// Open a WAV file, or with a path of `-`, raw audio on standard input
+    ///
+    /// Raw audio is read as interleaved 16-bit signed little-endian samples,
+    /// of `raw_channels` channels at `raw_sample_rate`.
+    pub fn open(
+        path: &Path,
+        raw_channels: u16,
+        raw_sample_rate: u32,
+    ) -> Result<Self, InputFileError> {
+        if path == Path::new(STDIN) {
+            if raw_channels == 0 {
+                return Err(InputFileError::NoChannels(path.to_path_buf()));
+            }
+
+            let mut input = BufReader::new(std::io::stdin());
+            let samples = std::iter::from_fn(move || {
+                let mut bytes = [0; 2];
+                match input.read_exact(&mut bytes) {
+                    Ok(()) => Some(Ok(f32::from(i16::from_le_bytes(bytes)) / 32768.0)),
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+                    Err(e) => Some(Err(hound::Error::IoError(e))),
+                }
+            });
+
+            return Ok(Self {
+                path: path.to_path_buf(),
+                name: "standard input".into(),
+                samples: Box::new(samples),
+                channels: raw_channels,
+                sample_rate: raw_sample_rate,
+            });
+        }
+
+        let reader = hound::WavReader::open(path)
+            .map_err(|e| InputFileError::Open(path.to_path_buf(), e))?;
+        let spec = reader.spec();
+        if spec.channels == 0 {
+            return Err(InputFileError::NoChannels(path.to_path_buf()));
+        }
+
+        let samples: Samples = match spec.sample_format {
+            hound::SampleFormat::Float => Box::new(reader.into_samples::<f32>()),
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                Box::new(
+                    reader
+                        .into_samples::<i32>()
+                        .map(move |sample| sample.map(|s| s as f32 * scale)),
+                )
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            name: path.display().to_string(),
+            samples,
+            channels: spec.channels,
+            sample_rate: spec.sample_rate,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The stream that the recording takes the place of
+    pub fn stream_config(&self) -> cpal::StreamConfig {
+        cpal::StreamConfig {
+            channels: self.channels,
+            sample_rate: cpal::SampleRate(self.sample_rate),
+            buffer_size: cpal::BufferSize::Fixed(BLOCK_SIZE),
+        }
+    }
+
+    /// Play the recording into the run as fast as it is written, until the sequence is done
+    ///
+    /// If the recording ends first, the rest of the sequence is given silence.
+    pub fn render(
+        &mut self,
+        processor: &mut AudioProcessor<f32>,
+        mut events: rtrb::Consumer<TimedEvent>,
+        state: &RunState,
+    ) -> anyhow::Result<()> {
+        let block = BLOCK_SIZE as usize;
+        let started = Instant::now();
+        let mut frame = vec![0.0; usize::from(self.channels)];
+        let mut frames = 0;
+        let mut ended = false;
+        while !state.done() {
+            // a block's samples and a marker for each zone that could start in it
+            while !processor.writer.has_room(block * frame.len() + block) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            for _ in 0..block {
+                for sample in &mut frame {
+                    *sample = match self.samples.next() {
+                        Some(Ok(sample)) => sample,
+                        Some(Err(e)) => {
+                            // the processor winds the run down once it sees that it was aborted
+                            state.abort();
+                            processor.write_input_data::<f32>(&[]);
+                            return Err(InputFileError::Read(self.path.clone(), e).into());
+                        }
+                        None => {
+                            if !ended {
+                                warn!(
+                                    "{} ended before the sequence, which is finished in silence",
+                                    self.name
+                                );
+                                ended = true;
+                            }
+                            0.0
+                        }
+                    };
+                }
+                processor.write_input_data::<f32>(&frame);
+                frames += 1;
+
+                // the events were played when the recording was made
+                while events.pop().is_ok() {}
+            }
+        }
+
+        info!(
+            "Read {:?} of audio from {} in {:?}",
+            Duration::from_secs_f64(frames as f64 / f64::from(self.sample_rate)),
+            self.name,
+            started.elapsed()
+        );
+        Ok(())
+    }
+}
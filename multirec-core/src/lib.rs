@@ -0,0 +1,838 @@
+//! The recording engine behind [multirec](https://crates.io/crates/multirec)
+//!
+//! A run is described by a [`RunConfig`]: the [`Setup`] of the instrument
+//! and devices, the [`Task`] to carry out with them, and the [`Session`]
+//! that lets an interrupted run be resumed. It is carried out by [`run`].
+//! Frontends that want to follow along pass a callback to [`run_with`],
+//! which is told about each [`Progress`] step and given the [`RunState`] to
+//! pause, skip notes or abort with. A [`Frontend`] can also be asked to
+//! confirm the plan and the measured latency. Nothing is printed or read
+//! from the terminal; what a run finds out comes back as its [`Outcome`].
+//!
+//! The options can also be parsed from a command line with the `clap`
+//! feature, which derives `clap::Args` for them.
+//!
+//! ```no_run
+//! use multirec_core::{Progress, RunConfig, RunOptions, Task};
+//!
+//! let config = RunConfig {
+//!     setup: Default::default(),
+//!     task: Task::Run(RunOptions {
+//!         yes: true,
+//!         start: "C3".parse()?,
+//!         ..Default::default()
+//!     }),
+//!     session: Default::default(),
+//! };
+//! multirec_core::run_with(config, &|progress: Progress<'_>| {
+//!     if let Progress::Recorded { file, .. } = progress {
+//!         println!("Recorded {file}");
+//!     }
+//! })?;
+//! # Ok::<_, anyhow::Error>(())
+//! ```
+
+use std::{
+    num::NonZeroU8,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use log::{debug, info, warn};
+
+use autosam::{dimension::Setting, midi::Event, Cleanup, Config, Sequencer};
+
+const ONE: NonZeroU8 = unsafe { NonZeroU8::new_unchecked(1) };
+
+const CLEANUP: Cleanup = Cleanup {
+    all_notes_off: true,
+    all_sound_off: true,
+    reset_all_controllers: false,
+};
+
+mod analysis;
+pub mod arguments;
+mod audition;
+mod calibration;
+mod capture;
+mod checksum;
+mod chunks;
+mod convert;
+mod count_in;
+mod devices;
+mod drumkit;
+mod hook;
+mod input_file;
+mod map;
+mod mapping;
+mod monitor;
+mod naming;
+mod noise;
+mod notify;
+mod osc;
+mod packaging;
+mod plan;
+mod plugin;
+mod processing;
+mod report;
+mod response;
+mod rtp;
+mod runtime;
+mod scala;
+mod session;
+mod settings;
+mod simulate;
+mod status;
+mod sysex;
+mod take;
+mod util;
+mod verify;
+
+use arguments::*;
+use util::*;
+
+pub use arguments::{MapOptions, RunOptions, Setup, Task};
+pub use convert::convert;
+pub use plan::Plan;
+pub use response::Recommendation;
+pub use runtime::RunState;
+pub use session::{Session, SessionFile};
+pub use util::clock_time;
+pub use verify::verify;
+
+/// A run to carry out, with everything it needs to know
+pub struct RunConfig {
+    /// The instrument, and the devices that play and record it
+    pub setup: Setup,
+    /// What to do with the instrument
+    pub task: Task,
+    /// The session that the run continues, or a new one
+    ///
+    /// A run saves its session in its output directory as it goes, and the
+    /// zones of the files recorded so far are not played again. The command
+    /// line and config file in it are not read here, but kept for the
+    /// frontend to start the run again with.
+    pub session: Session,
+}
+
+/// A step of a run, reported to the callback given to [`run_with`]
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub enum Progress<'a> {
+    /// Recording is about to begin
+    Started {
+        /// Number of zones to record
+        zones: usize,
+        /// Shared state of the run, which can pause it, skip notes or abort it
+        state: &'a RunState,
+    },
+    /// A zone's file has been written
+    Recorded {
+        /// Name of the file, relative to the output directory
+        file: &'a str,
+        /// Number of zones recorded so far
+        recorded: usize,
+    },
+    /// The zones to record have been planned, and are about to be confirmed
+    Planned(&'a Plan),
+    /// Audio is being captured, until the state of the run says it is done
+    ///
+    /// This is told on the thread that waits for the run to finish, which
+    /// carries on once it returns, so a frontend can show the run until then.
+    Capturing {
+        /// Pitch and velocity layer of each zone, in the order they are played
+        zones: &'a [(u8, u8)],
+        /// Number of channels recorded from the first input
+        channels: usize,
+        state: &'a RunState,
+    },
+    /// Recording is over, and the files are being processed and packaged
+    Processing,
+}
+
+/// Something a run asks its frontend before going on
+#[derive(Clone, Copy)]
+pub enum Question<'a> {
+    /// Whether to record as planned, asked unless the run's options say not to
+    Plan(&'a Plan),
+    /// Whether to record with the latency that calibration measured
+    Latency(Latency),
+}
+
+/// How late the audio of a note arrives after it is sent
+#[derive(Clone, Copy, Debug)]
+pub struct Latency {
+    pub frames: usize,
+    pub sample_rate: u32,
+}
+
+impl Latency {
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.frames as u64 * 1_000) / self.sample_rate
+    }
+}
+
+impl std::fmt::Display for Latency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} ({} samples)", self.duration(), self.frames)
+    }
+}
+
+/// What a run tells and asks whoever started it
+///
+/// Any `Fn(Progress)` callback is a frontend that is only told about progress.
+pub trait Frontend: Sync {
+    /// Be told about a step of the run
+    fn progress(&self, progress: Progress<'_>);
+
+    /// Answer a question, returning whether to go on
+    ///
+    /// With no one to ask, plans are declined and measured latencies are accepted.
+    fn confirm(&self, question: Question<'_>) -> anyhow::Result<bool> {
+        Ok(matches!(question, Question::Latency(_)))
+    }
+}
+
+impl<F: Fn(Progress<'_>) + Sync> Frontend for F {
+    fn progress(&self, progress: Progress<'_>) {
+        self(progress)
+    }
+}
+
+/// What a run found out, for its frontend to show
+#[derive(Debug)]
+pub enum Outcome {
+    /// The zones were recorded, and saved in the chosen format
+    Recorded,
+    /// Nothing was recorded, as the run's files already exist
+    Skipped,
+    /// Nothing was played, and these are the events that would have been, at their sample offsets
+    DryRun(Vec<(usize, Event)>),
+    /// The test note was played, and this is the latency heard, if any
+    Tested(Option<Latency>),
+    /// The level of a note was measured at each velocity, in dBFS
+    Measured {
+        levels: Vec<(u8, f32)>,
+        recommendation: Recommendation,
+    },
+}
+
+/// Carry out a task
+pub fn run(config: RunConfig) -> anyhow::Result<Outcome> {
+    run_with(config, &|_: Progress<'_>| {})
+}
+
+/// Carry out a task, telling `frontend` how a run goes
+///
+/// The frontend is called from the threads of the run, and should return quickly.
+pub fn run_with(config: RunConfig, frontend: &dyn Frontend) -> anyhow::Result<Outcome> {
+    // only a run that records anything says when it is over
+    let notify = match &config.task {
+        Task::Run(RunOptions {
+            notify,
+            output_directory,
+            dry_run: false,
+            ..
+        }) if !notify.is_empty() => Some((notify.clone(), output_directory.clone())),
+        _ => None,
+    };
+    let started = std::time::Instant::now();
+
+    let result = record(config, frontend);
+
+    if let Some((targets, output_directory)) = notify {
+        let (outcome, message) = match &result {
+            Ok(_) => (
+                notify::Outcome::Completed,
+                "Recordings complete".to_string(),
+            ),
+            Err(e) if matches!(e.downcast_ref(), Some(RunError::Aborted(_))) => {
+                (notify::Outcome::Aborted, e.to_string())
+            }
+            Err(e) => (notify::Outcome::Failed, e.to_string()),
+        };
+        let summary = notify::Summary::new(
+            outcome,
+            message,
+            output_directory.or_else(|| std::env::current_dir().ok()),
+            started.elapsed(),
+        );
+        notify::send(&targets, &summary);
+    }
+
+    result
+}
+
+/// Build an SFZ or Bitwig multisample from a directory of existing WAV files
+pub fn map(options: &MapOptions) -> anyhow::Result<()> {
+    let MapOptions {
+        directory,
+        format,
+        name,
+        dynamics,
+        detect_pitch,
+        key_map,
+        key_xfade,
+    } = options;
+
+    let samples = map::scan(directory, dynamics, *detect_pitch)?;
+    if samples.is_empty() {
+        return Err(RunError::NothingToMap(directory.clone()).into());
+    }
+    info!("Mapping {} samples", samples.len());
+
+    let name = match name {
+        Some(name) => name.clone(),
+        None => std::fs::canonicalize(directory)?
+            .file_name()
+            .map_or_else(|| "instrument".into(), |n| n.to_string_lossy().into_owned()),
+    };
+    match format {
+        MapFormat::Sfz => map::write_sfz(directory, &name, &samples, *key_map, *key_xfade),
+        MapFormat::Bitwig => map::write_bitwig(directory, &name, &samples, *key_map, *key_xfade),
+    }
+}
+
+/// Set up the devices and record, or test them
+fn record(config: RunConfig, frontend: &dyn Frontend) -> anyhow::Result<Outcome> {
+    let RunConfig {
+        setup: args,
+        task,
+        session,
+    } = config;
+
+    let midi_setup = devices::MidiSetup::new(&args)?;
+    let host = audio_host(args.host.clone())?;
+    let (mut settings, mut config) = settings::Settings::resolve(task, &args)?;
+
+    let mut devices = devices::Devices::open(&args, &host, settings.audition.take())?;
+
+    // the timing of a recording made elsewhere is fixed, so nothing can be recorded again
+    if let Some(plugin::Instrument::File(_)) = &devices.plugin {
+        if settings.auto_gap.take().is_some() {
+            warn!("Not shortening gaps, as the input file was recorded with its gaps");
+        }
+        settings.retries = settings
+            .retries
+            .map(|(threshold, _, _)| (threshold, 0, false));
+    }
+
+    let state = Arc::new(runtime::RunState::new(*config.notes.start()));
+    let sample_rate = devices.input_config.sample_rate.0;
+
+    // calibrate in the middle of the range, where the instrument is most likely to sound
+    let calibration_note =
+        ((u16::from(*config.notes.start()) + u16::from(*config.notes.end())) / 2) as u8;
+
+    // the zones are picked out of the whole plan, so they can be named by velocity
+    if let Some(selection) = &settings.zone_selection {
+        let whole = plan::zones(Sequencer::new(config.clone(), sample_rate)?);
+        let list = selection.resolve(&whole)?;
+        info!(
+            "Recording {} of the {} zones planned: {selection}",
+            list.len(),
+            whole.len()
+        );
+        config.zone_list = Some(list);
+    }
+
+    let mut seq = Sequencer::new(config, sample_rate)?;
+    if !session.files.is_empty() || session.skipped > 0 {
+        let skipped = seq.skip_zones(session.files.len() + session.skipped);
+        info!("Skipped {skipped} zones that were already recorded or passed over");
+    }
+    let planned_zones = plan::zones(seq.clone());
+    let planned: Vec<_> = planned_zones
+        .iter()
+        .map(|zone| (zone.pitch().note_number(), zone.velocity_layer()))
+        .collect();
+
+    // a resumed run or an appended bundle expects to find its files
+    let is_continued = settings.append || !session.files.is_empty() || session.skipped > 0;
+    if settings.should_save
+        && !settings.is_dry_run
+        && !is_continued
+        && !resolve_conflicts(&mut settings, &planned_zones, devices.mic_devices.len())?
+    {
+        return Ok(Outcome::Skipped);
+    }
+
+    let mic_dirs = input_dirs(&settings.output_dir, devices.mic_devices.len());
+
+    // the files follow on from each other, so the whole sequence is recorded
+    let frames = seq
+        .clone()
+        .into_iter()
+        .last()
+        .map_or(0, |(position, _)| position)
+        + settings
+            .noise_floor
+            .map_or(0, |(length, _)| util::frames(length, sample_rate));
+    let size = settings.should_save.then(|| {
+        let frame_size = devices
+            .mic_channels
+            .iter()
+            .map(|c| u64::from(*c))
+            .sum::<u64>()
+            * u64::from(
+                settings
+                    .bit_depth
+                    .spec(devices.channels, sample_rate)
+                    .bits_per_sample
+                    / 8,
+            );
+        frames as u64 * frame_size
+    });
+
+    if let Some(size) = size {
+        // archiving moves the files, unless a second copy of them is kept
+        let needed = match settings.output_format {
+            OutputFormat::Bitwig | OutputFormat::Zip if settings.keep_raw => size * 2,
+            _ => size,
+        };
+        match util::available_space(&settings.output_dir) {
+            Some(available) if available < needed && !settings.is_dry_run => {
+                if !settings.ignore_disk_space {
+                    return Err(RunError::DiskSpace { needed, available }.into());
+                }
+                warn!(
+                    "Only {} is free for {} of recordings",
+                    util::format_size(available),
+                    util::format_size(needed)
+                );
+            }
+            Some(available) => debug!("{} free for recordings", util::format_size(available)),
+            None => debug!("Could not find out how much space is free for recordings"),
+        }
+    }
+
+    if settings.show_plan {
+        let plan = plan::Plan {
+            zones: planned.clone(),
+            inputs: mic_dirs.len(),
+            duration: Duration::from_secs_f64(frames as f64 / f64::from(sample_rate)),
+            size,
+            output_directory: settings.should_save.then(|| settings.output_dir.clone()),
+            devices: devices.describe(&args)?,
+        };
+        frontend.progress(Progress::Planned(&plan));
+        if !settings.confirmed && !frontend.confirm(Question::Plan(&plan))? {
+            return Err(RunError::PlanRejected.into());
+        }
+    }
+
+    if settings.is_dry_run {
+        return Ok(Outcome::DryRun(seq.into_iter().collect()));
+    }
+
+    let sound_off = devices::sound_off(&settings);
+    let patch_events = midi_setup.patch_events(&settings);
+
+    // a plugin is sent the program along with the sequence, rather than over MIDI
+    let mut midi_connection = match devices.plugin {
+        Some(_) => None,
+        None => Some(midi_setup.connect(
+            &args,
+            &sound_off,
+            &patch_events,
+            settings.should_save.then_some(mic_dirs.as_slice()),
+        )?),
+    };
+
+    if let Some((spacing, floor)) = settings.measurement {
+        let (Some(input_device), Some(midi_connection)) =
+            (&devices.input_device, &mut midi_connection)
+        else {
+            return Err(RunError::MeasurePlugin.into());
+        };
+
+        let levels = response::sweep(
+            input_device,
+            devices.sample_format,
+            &devices.input_config,
+            devices.selection,
+            midi_connection,
+            seq,
+        )?;
+
+        let recommendation =
+            response::recommend(&levels, spacing.0, floor.0).ok_or(RunError::NothingHeard)?;
+        info!(
+            "{} velocity layers from {} to {} would each span up to {}dB",
+            recommendation.layers,
+            recommendation.range.start(),
+            recommendation.range.end(),
+            spacing.0
+        );
+        if !recommendation.crossovers.is_empty() {
+            info!(
+                "For even steps in loudness, layers would start at velocities {}",
+                recommendation
+                    .crossovers
+                    .iter()
+                    .map(u8::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        return Ok(Outcome::Measured {
+            levels,
+            recommendation,
+        });
+    }
+
+    if devices.plugin.is_some() && settings.calibration.is_some() {
+        warn!("Not calibrating, as a plugin or simulation is played directly with a fixed latency");
+    }
+
+    let calibrated_latency = match (
+        settings.calibration,
+        &devices.input_device,
+        &mut midi_connection,
+    ) {
+        (Some((notes, tolerance, threshold)), Some(input_device), Some(midi_connection)) => {
+            let seq = Sequencer::new(
+                Config {
+                    notes: calibration_note..=calibration_note,
+                    step: ONE,
+                    velocity_levels: ONE,
+                    round_robins: notes,
+                    length: calibration::PING_LENGTH,
+                    gap: calibration::PING_GAP,
+                    channel: settings.channel,
+                    cleanup: CLEANUP,
+                    ..Default::default()
+                },
+                sample_rate,
+            )?;
+
+            info!("Measuring latency with {notes} notes at {calibration_note}");
+            let delays = calibration::measure(
+                input_device,
+                devices.sample_format,
+                &devices.input_config,
+                devices.selection,
+                midi_connection,
+                seq,
+                threshold,
+            )?;
+            let latency = calibration::evaluate(
+                &delays,
+                usize::from(notes.get()),
+                util::frames(tolerance, sample_rate),
+            )?;
+
+            let measured = Latency {
+                frames: latency,
+                sample_rate,
+            };
+            if !frontend.confirm(Question::Latency(measured))? {
+                return Err(RunError::LatencyRejected.into());
+            }
+
+            Some(latency)
+        }
+        _ => None,
+    };
+
+    let appended = packaging::unpack(&settings)?;
+    let denoise = capture::noise_floor(&settings, &devices)?;
+
+    let captured = capture::Capture {
+        args: &args,
+        host: &host,
+        settings: &settings,
+        devices: &mut devices,
+        session: &session,
+        sound_off: &sound_off,
+        patch_events: &patch_events,
+        mic_dirs: &mic_dirs,
+        planned: &planned,
+        state: &state,
+        frontend,
+    }
+    .run(seq, midi_connection)?;
+
+    let latency = calibrated_latency.unwrap_or_else(|| state.latency());
+    let approximate = Latency {
+        frames: latency,
+        sample_rate,
+    };
+
+    if !settings.should_save {
+        info!("Test complete");
+        return Ok(Outcome::Tested((latency != 0).then_some(approximate)));
+    }
+
+    info!("Recordings complete");
+    frontend.progress(Progress::Processing);
+    if latency != 0 {
+        info!("Approximate latency: {approximate}");
+    }
+
+    let processing = processing::Processing::new(
+        &settings,
+        &captured,
+        &state,
+        latency,
+        sample_rate,
+        &mic_dirs,
+        denoise,
+    )?;
+
+    // everything has been recorded, so there is nothing left to resume
+    Session::remove(&settings.output_dir)?;
+
+    // each input gets the same processing and a manifest of its own
+    for (mic, output_dir) in mic_dirs.iter().enumerate() {
+        let entries = processing.process(
+            mic,
+            output_dir,
+            devices.mic_channels[mic],
+            captured.entries.clone(),
+        )?;
+        packaging::package(
+            &settings,
+            output_dir,
+            &entries,
+            appended.as_ref(),
+            processing.sample_start(),
+            processing.sample_rate(),
+        )?;
+    }
+
+    Ok(Outcome::Recorded)
+}
+
+/// Deal with the files of a run that already exist, as its settings say
+///
+/// Returns whether to go on and record, which may be into another directory.
+fn resolve_conflicts(
+    settings: &mut settings::Settings,
+    planned_zones: &[autosam::Zone],
+    further_inputs: usize,
+) -> anyhow::Result<bool> {
+    let name_template = &settings.name_template;
+    let file_name_prefix = &settings.file_name_prefix;
+    let keyswitches = &settings.keyswitches;
+    let pads = &settings.pads;
+    let controller_sweeps = &settings.controller_sweeps;
+    let output_format = &settings.output_format;
+    let has_vel = settings.velocity_levels > 1;
+    let has_rr = settings.round_robins > 1;
+
+    let names = planned_zones
+        .iter()
+        .map(|zone| {
+            let controllers = zone.settings().filter_map(|setting| match setting {
+                Setting::Controller { value, .. } => Some(value),
+                _ => None,
+            });
+            let keyswitch = zone.settings().find_map(|setting| match setting {
+                Setting::Keyswitch(pitch) => Some(pitch.note_number()),
+                _ => None,
+            });
+            let entry = util::NamedFile {
+                template: name_template,
+                prefix: file_name_prefix.as_ref(),
+                pitch: zone.pitch(),
+                velocity: has_vel.then_some(zone.velocity()),
+                round_robin: has_rr.then_some(zone.round_robin()),
+                keyswitch: keyswitch.and_then(|note| naming::Keyswitch::find(keyswitches, note)),
+                pad: drumkit::Pad::find(pads, zone.pitch().note_number()),
+                controllers: controller_sweeps
+                    .iter()
+                    .map(|sweep| sweep.controller)
+                    .zip(controllers)
+                    .collect(),
+                loop_points: None,
+                loop_crossfade: None,
+                gain: None,
+                tune: None,
+                sample_stop: None,
+            };
+            entry.to_string()
+        })
+        .collect::<Vec<_>>();
+
+    let existing = |output_dir: &Path| -> Vec<PathBuf> {
+        input_dirs(output_dir, further_inputs)
+            .into_iter()
+            .flat_map(|dir| {
+                let bundle = match output_format {
+                    OutputFormat::Raw => None,
+                    OutputFormat::Zip => Some(dir.with_extension("zip")),
+                    OutputFormat::Sfz => Some(dir.join(format!(
+                        "{}.sfz",
+                        file_name_prefix.as_deref().unwrap_or("instrument")
+                    ))),
+                    OutputFormat::Bitwig => Some(dir.with_extension("multisample")),
+                };
+                names
+                    .iter()
+                    .map(|name| dir.join(name))
+                    .chain(bundle)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|path| path.exists())
+            .collect()
+    };
+
+    let conflicts = existing(&settings.output_dir);
+    if let Some(first) = conflicts.first() {
+        match settings.on_conflict {
+            OnConflict::Overwrite => warn!(
+                "Replacing {} files that already exist, such as {}",
+                conflicts.len(),
+                first.display()
+            ),
+            OnConflict::Skip => {
+                warn!(
+                    "Recording nothing, as {} of the run's files already exist, such as {}",
+                    conflicts.len(),
+                    first.display()
+                );
+                return Ok(false);
+            }
+            OnConflict::Suffix => {
+                let original = std::path::absolute(&settings.output_dir)?;
+                let name = original
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let mut suffix = 2;
+                settings.output_dir = loop {
+                    let dir = original.with_file_name(format!("{name}-{suffix}"));
+                    if existing(&dir).is_empty() {
+                        break dir;
+                    }
+                    suffix += 1;
+                };
+                info!(
+                    "Recording into {}, as {} already has files of this run",
+                    settings.output_dir.display(),
+                    original.display()
+                );
+            }
+            OnConflict::Abort => {
+                return Err(RunError::Conflict(first.clone(), conflicts.len()).into())
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// The directory of each input's files, which with several inputs is one each
+fn input_dirs(output_dir: &Path, further_inputs: usize) -> Vec<PathBuf> {
+    if further_inputs == 0 {
+        return vec![output_dir.to_path_buf()];
+    }
+
+    (1..=further_inputs + 1)
+        .map(|mic| output_dir.join(format!("mic{mic}")))
+        .collect()
+}
+
+/// Find an audio host by its index or name, or the default one
+pub fn audio_host(matcher: Option<Matcher>) -> anyhow::Result<cpal::Host> {
+    Ok(if let Some(matcher) = matcher {
+        cpal::host_from_id(
+            matcher
+                .get(cpal::available_hosts(), |host| -> anyhow::Result<String> {
+                    Ok(host.name().to_string())
+                })?
+                .ok_or(match matcher {
+                    Matcher::Index(i) => RunError::InvalidHostIndex(i),
+                    Matcher::String(s) => RunError::NoSuchHost(s),
+                })?,
+        )?
+    } else {
+        cpal::default_host()
+    })
+}
+
+/// Find an input device of `host` by its index or name, or the default one
+pub fn input_device(host: &cpal::Host, matcher: Option<Matcher>) -> anyhow::Result<cpal::Device> {
+    Ok(if let Some(matcher) = matcher {
+        matcher
+            .get(host.input_devices()?, |d| d.name())?
+            .ok_or(match matcher {
+                Matcher::Index(i) => RunError::InvalidDeviceIndex(i),
+                Matcher::String(s) => RunError::NoSuchDevice(s),
+            })?
+    } else {
+        host.default_input_device()
+            .ok_or(RunError::NoDefaultInputDevice)?
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+enum RunError {
+    #[error("Selected audio host ID ({0}) does not exist")]
+    InvalidHostIndex(usize),
+    #[error("No audio host found with name like `{0}`")]
+    NoSuchHost(String),
+    #[error("Selected audio device ID ({0}) does not exist")]
+    InvalidDeviceIndex(usize),
+    #[error("No audio device found with name like `{0}`")]
+    NoSuchDevice(String),
+    #[error("No default input device was found")]
+    NoDefaultInputDevice,
+    #[error(
+        "Cannot tell which card `{0}` belongs to, select its hw: device to open it exclusively"
+    )]
+    NoCard(String),
+    #[error("Buffer size {0} is outside the {1} to {2} frames that the device supports")]
+    BufferSize(u32, u32, u32),
+    #[error("No default output device was found")]
+    NoDefaultOutputDevice,
+    #[error(
+        "At most {} input devices can be recorded at once, not {0}",
+        runtime::MAX_INPUT_DEVICES
+    )]
+    TooManyDevices(usize),
+    #[error("Input device {0} runs at {1}Hz, but the first input runs at {2}Hz")]
+    SampleRateMismatch(String, u32, u32),
+    #[error("Selected MIDI port ID ({0}) does not exist")]
+    InvalidPortIndex(usize),
+    #[error("No MIDI port found with name like `{0}`")]
+    NoSuchPort(String),
+    #[error("Could not connect to MIDI port `{0}`: {1}")]
+    MidiConnect(String, midir::ConnectErrorKind),
+    #[error("MIDI thread panicked: {0}")]
+    MidiPanic(String),
+    #[error("Plugin thread panicked: {0}")]
+    PluginPanic(String),
+    #[error("I/O thread panicked: {0}")]
+    IoPanic(String),
+    #[error("Recording was aborted after starting {0} files")]
+    Aborted(usize),
+    #[error("Input channel {0} does not exist, the device has {1}")]
+    NoSuchChannel(u16, u16),
+    #[error("No WAV files with a note to map were found in `{}`", .0.display())]
+    NothingToMap(PathBuf),
+    #[error("The measured latency was not accepted")]
+    LatencyRejected,
+    #[error("The plan was not accepted")]
+    PlanRejected,
+    #[error("{} of the run's files already exist, such as {}, choose what to do with --on-conflict", .1, .0.display())]
+    Conflict(PathBuf, usize),
+    #[error("The velocity response can only be measured from an audio input, not a plugin or simulation")]
+    MeasurePlugin,
+    #[error("No velocity was heard above the floor")]
+    NothingHeard,
+    #[error("Only Bitwig multisamples can be appended to")]
+    AppendFormat,
+    #[error("Can only add to an existing multisample when recording from a single input")]
+    AppendDevices,
+    #[error(
+        "Recordings need about {} but only {} is free, pass --ignore-disk-space to start anyway",
+        util::format_size(*.needed),
+        util::format_size(*.available)
+    )]
+    DiskSpace { needed: u64, available: u64 },
+}
@@ -0,0 +1,355 @@
+use std::{io::Write as _, path::Path};
+
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use autosam::midi::Pitch;
+
+use crate::{analysis, mapping::KeyMap, naming::Dynamics, util};
+
+/// A sample found in the directory, with what its name and audio say about it
+#[derive(Debug)]
+pub struct Sample {
+    pub file: String,
+    pub note: u8,
+    pub velocity: Option<u8>,
+    pub round_robin: Option<u8>,
+    /// Cents that the sample is above its note, if its pitch was measured
+    pub tune: Option<f32>,
+}
+
+/// What a file's name says about it
+#[derive(Debug, Default)]
+struct Name {
+    note: Option<u8>,
+    velocity: Option<u8>,
+    round_robin: Option<u8>,
+}
+
+/// Read the note, velocity and round robin from a file name, without its extension
+///
+/// The name is split into words at underscores, spaces and dots. A word may
+/// be a note name (`C#4`, `Eb2`) or number, a velocity (`V96`, `vel96`), a
+/// dynamic marking from `dynamics`, or a round robin (`RR2`). Words joined by
+/// hyphens are also tried one by one, so `Piano-C4-ff` is read as well. A
+/// number is only taken as the note if no note name is found.
+fn parse_name(stem: &str, dynamics: &Dynamics) -> Name {
+    let mut name = Name::default();
+    let mut number = None;
+
+    let words = stem
+        .split(['_', ' ', '.'])
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            // a note in octave -1 is written with a hyphen, e.g. C-1
+            if parse_note(word).is_some() {
+                vec![word]
+            } else {
+                word.split('-').filter(|w| !w.is_empty()).collect()
+            }
+        });
+
+    for word in words {
+        if let Some(note) = parse_note(word) {
+            name.note.get_or_insert(note);
+        } else if let Some(velocity) = prefixed_number(word, &["velocity", "vel", "v"]) {
+            name.velocity.get_or_insert(velocity);
+        } else if let Some(rr) = prefixed_number(word, &["rr"]) {
+            name.round_robin.get_or_insert(rr);
+        } else if let Some(velocity) = dynamics.velocity(word) {
+            name.velocity.get_or_insert(velocity);
+        } else if let Ok(n) = word.parse::<u8>() {
+            number.get_or_insert(n);
+        }
+    }
+
+    if name.note.is_none() {
+        name.note = number.filter(|n| *n < 128);
+    }
+
+    name
+}
+
+/// Read a note name, with a sharp or flat, e.g. `C#4` or `Bb2`
+fn parse_note(word: &str) -> Option<u8> {
+    if !word.starts_with(|c: char| matches!(c.to_ascii_uppercase(), 'A'..='G')) {
+        return None;
+    }
+
+    if let Ok(pitch) = word.parse::<Pitch>() {
+        return Some(pitch.note_number());
+    }
+
+    // flats are written as the sharp below
+    let mut chars = word.chars();
+    let letter = chars.next()?;
+    if chars.next() != Some('b') {
+        return None;
+    }
+    let natural: Pitch = format!("{letter}{}", chars.as_str()).parse().ok()?;
+    natural.note_number().checked_sub(1)
+}
+
+/// Read a number after one of the given prefixes, ignoring case
+fn prefixed_number(word: &str, prefixes: &[&str]) -> Option<u8> {
+    let lower = word.to_ascii_lowercase();
+    prefixes.iter().find_map(|prefix| {
+        let digits = lower.strip_prefix(prefix)?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok().filter(|n| *n < 128)
+    })
+}
+
+/// Find the WAV files in a directory, and work out the note and layers of each
+///
+/// With `detect_pitch`, the pitch of every sample is measured, giving the
+/// note of files whose name has none and tuning the rest exactly. Files
+/// whose note can't be found either way are left out.
+pub fn scan(dir: &Path, dynamics: &Dynamics, detect_pitch: bool) -> anyhow::Result<Vec<Sample>> {
+    let mut files: Vec<String> = dir
+        .read_dir()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let is_wav = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+            (path.is_file() && is_wav).then(|| path.file_name()?.to_str().map(String::from))?
+        })
+        .collect();
+    files.sort();
+
+    let mut samples = Vec::with_capacity(files.len());
+
+    for file in files {
+        let stem = file
+            .rsplit_once('.')
+            .map_or(file.as_str(), |(stem, _)| stem);
+        let name = parse_name(stem, dynamics);
+
+        let heard = if detect_pitch {
+            measure_pitch(&dir.join(&file))?
+        } else {
+            None
+        };
+
+        let (note, tune) = match (name.note, heard) {
+            (Some(note), Some(frequency)) => {
+                let expected = 440.0 * 2f32.powf((f32::from(note) - 69.0) / 12.0);
+                let cents = 1200.0 * (frequency / expected).log2();
+
+                // more than half a semitone out is a different note, not a tuning error
+                if cents.abs() > 50.0 {
+                    warn!("{file} is named as note {note}, but sounds at {frequency:.1}Hz");
+                    (note, None)
+                } else {
+                    (note, Some(cents))
+                }
+            }
+            (Some(note), None) => (note, None),
+            (None, Some(frequency)) => {
+                let heard = 69.0 + 12.0 * (frequency / 440.0).log2();
+                let note = heard.round().clamp(0.0, 127.0);
+                debug!("{file} sounds like note {note} ({frequency:.1}Hz)");
+                (note as u8, Some((heard - note) * 100.0))
+            }
+            (None, None) => {
+                warn!("Could not find the note of {file}, leaving it out");
+                continue;
+            }
+        };
+
+        samples.push(Sample {
+            file,
+            note,
+            velocity: name.velocity,
+            round_robin: name.round_robin,
+            tune,
+        });
+    }
+
+    number_round_robins(&mut samples);
+
+    Ok(samples)
+}
+
+/// Measure the pitch in the middle of a file, in Hz
+fn measure_pitch(path: &Path) -> anyhow::Result<Option<f32>> {
+    let sample_rate = hound::WavReader::open(path)?.spec().sample_rate;
+    let samples = analysis::read_mono(path)?;
+    let region = samples.len() / 4..samples.len() * 9 / 10;
+
+    let frequency = analysis::detect_pitch(&samples, region, sample_rate);
+    if frequency.is_none() {
+        warn!("Could not measure the pitch of {}", path.display());
+    }
+
+    Ok(frequency)
+}
+
+/// Sort the samples by note and velocity, numbering the round robins of each layer from 0
+///
+/// Samples of the same layer are taken in the order of their round robin
+/// number, then of their name, so unnumbered takes still alternate.
+fn number_round_robins(samples: &mut [Sample]) {
+    samples.sort_by(|a, b| {
+        (a.note, b.velocity, a.round_robin.is_none(), a.round_robin)
+            .cmp(&(b.note, a.velocity, b.round_robin.is_none(), b.round_robin))
+            .then_with(|| a.file.cmp(&b.file))
+    });
+
+    let mut position = 0;
+    for idx in 0..samples.len() {
+        let same_layer = idx > 0
+            && samples[idx - 1].note == samples[idx].note
+            && samples[idx - 1].velocity == samples[idx].velocity;
+        position = if same_layer { position + 1 } else { 0 };
+        samples[idx].round_robin = Some(position);
+    }
+}
+
+/// Number of round robins in the layer of a sample
+fn layer_length(samples: &[Sample], sample: &Sample) -> usize {
+    samples
+        .iter()
+        .filter(|s| s.note == sample.note && s.velocity == sample.velocity)
+        .count()
+}
+
+/// Lowest velocity of a layer, just above the next softer layer of the same note
+fn velocity_low(samples: &[Sample], note: u8, velocity: u8) -> Option<u8> {
+    samples
+        .iter()
+        .filter(|s| s.note == note)
+        .filter_map(|s| s.velocity.filter(|v| *v < velocity))
+        .max()
+        .map(|next| next + 1)
+}
+
+/// Write an SFZ instrument for the samples next to them
+pub fn write_sfz(
+    dir: &Path,
+    name: &str,
+    samples: &[Sample],
+    key_map: KeyMap,
+    key_xfade: u8,
+) -> anyhow::Result<()> {
+    let path = dir.join(format!("{name}.sfz"));
+    let mut f = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    let roots: Vec<u8> = samples.iter().map(|s| s.note).collect();
+
+    for (idx, sample) in samples.iter().enumerate() {
+        let new_layer = idx == 0
+            || samples[idx - 1].note != sample.note
+            || samples[idx - 1].velocity != sample.velocity;
+
+        if new_layer {
+            write!(f, "<group> pitch_keycenter={}", sample.note)?;
+
+            let keys = key_map.range(&roots, sample.note, key_xfade);
+            if let Some(low) = keys.low {
+                write!(f, " lokey={low}")?;
+            }
+            if let Some(high) = keys.high {
+                write!(f, " hikey={high}")?;
+            }
+            if let (Some(low), Some(fade)) = (keys.low, keys.low_fade) {
+                write!(f, " xfin_lokey={low} xfin_hikey={}", low + fade)?;
+            }
+            if let (Some(high), Some(fade)) = (keys.high, keys.high_fade) {
+                write!(f, " xfout_lokey={} xfout_hikey={high}", high - fade)?;
+            }
+
+            if let Some(velocity) = sample.velocity {
+                // the loudest layer of each note reaches the top velocity
+                let louder = samples
+                    .iter()
+                    .any(|s| s.note == sample.note && s.velocity > sample.velocity);
+                if louder {
+                    write!(f, " hivel={velocity}")?;
+                }
+                if let Some(low) = velocity_low(samples, sample.note, velocity) {
+                    write!(f, " lovel={low}")?;
+                }
+            }
+
+            let length = layer_length(samples, sample);
+            if length > 1 {
+                write!(f, " seq_length={length}")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        write!(f, "<region> sample={}", sample.file)?;
+        if layer_length(samples, sample) > 1 {
+            if let Some(rr) = sample.round_robin {
+                write!(f, " seq_position={}", rr + 1)?;
+            }
+        }
+        match sample.tune.map(|cents| -cents.round() as i32) {
+            Some(0) | None => {}
+            Some(tune) => write!(f, " tune={tune}")?,
+        }
+        writeln!(f)?;
+    }
+
+    f.flush()?;
+    info!("Wrote {}", path.display());
+
+    Ok(())
+}
+
+/// Write a Bitwig multisample manifest next to the samples, and pack them
+/// into a `.multisample` bundle beside the directory
+pub fn write_bitwig(
+    dir: &Path,
+    name: &str,
+    samples: &[Sample],
+    key_map: KeyMap,
+    key_xfade: u8,
+) -> anyhow::Result<()> {
+    let roots: Vec<u8> = samples.iter().map(|s| s.note).collect();
+
+    let multi = dot_multisample::Multisample::default()
+        .with_name(name)
+        .with_generator("multirec")
+        .with_samples(samples.iter().map(|sample| {
+            let keys = key_map.range(&roots, sample.note, key_xfade);
+            let key = dot_multisample::Key::default()
+                .with_root(sample.note)
+                .with_low(keys.low)
+                .with_high(keys.high)
+                .with_low_fade(keys.low_fade)
+                .with_high_fade(keys.high_fade)
+                .with_tune(
+                    sample
+                        .tune
+                        .map(|cents| (f64::from(-cents) / 100.0 * 1000.0).round() / 1000.0),
+                );
+
+            let velocity = sample.velocity.map(|v| {
+                dot_multisample::ZoneInfo::default()
+                    .with_high(v)
+                    .with_low(velocity_low(samples, sample.note, v))
+            });
+
+            dot_multisample::Sample::default()
+                .with_file(std::path::PathBuf::from(&sample.file))
+                .with_key(key)
+                .with_velocity(velocity)
+                .with_zone_logic(dot_multisample::ZoneLogic::RoundRobin)
+        }));
+
+    let mut manifest_file = util::Utf8File::xml(dir.join("multisample.xml"))?;
+    let mut ser = quick_xml::se::Serializer::new(&mut manifest_file);
+    ser.indent('\t', 1);
+    multi.serialize(ser)?;
+
+    let bundle = dir.with_extension("multisample");
+    util::archive(dir, &bundle, zip::CompressionMethod::Stored, false)?;
+    info!("Wrote {}", bundle.display());
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+/// How the keys between sampled notes are shared out among the samples
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum KeyMap {
+    /// Play each key with the nearest sample, splitting halfway between them
+    #[default]
+    Nearest,
+    /// Play each key with the sample at or above it, transposed down
+    Down,
+    /// Play each key with the sample at or below it, transposed up
+    Up,
+    /// Stretch each sample to the next ones, fading between the two across the whole gap
+    Stretch,
+    /// Play each sample only on its own key, as for the pads of a drum kit
+    Single,
+}
+
+/// The keys that a sample is played for, and how far it fades in and out at either end
+///
+/// An end that is `None` is left open, reaching the end of the keyboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyRange {
+    pub low: Option<u8>,
+    pub high: Option<u8>,
+    /// Keys over which the sample fades in, from `low`
+    pub low_fade: Option<u8>,
+    /// Keys over which the sample fades out, up to `high`
+    pub high_fade: Option<u8>,
+}
+
+impl KeyMap {
+    /// The keys of the sample at `root`, among samples at each of `roots`
+    ///
+    /// The outermost samples reach the ends of the keyboard, except with
+    /// `single`. Apart from with `stretch` and `single`, neighbouring samples overlap by `xfade` keys, fading
+    /// between each other there.
+    pub fn range(self, roots: &[u8], root: u8, xfade: u8) -> KeyRange {
+        let below = roots.iter().copied().filter(|n| *n < root).max();
+        let above = roots.iter().copied().filter(|n| *n > root).min();
+
+        let (low, high) = match self {
+            Self::Nearest => (
+                below.map(|below| (root - below) / 2 + below),
+                above.map(|above| ((above - root) / 2 + root).saturating_sub(1).max(root)),
+            ),
+            Self::Down => (below.map(|below| below + 1), above.map(|_| root)),
+            Self::Up => (below.map(|_| root), above.map(|above| above - 1)),
+            Self::Single => {
+                return KeyRange {
+                    low: Some(root),
+                    high: Some(root),
+                    ..Default::default()
+                }
+            }
+            Self::Stretch => {
+                let low = below.map(|below| below + 1);
+                let high = above.map(|above| above - 1);
+                return KeyRange {
+                    low,
+                    high,
+                    low_fade: low.map(|low| root - low).filter(|fade| *fade > 0),
+                    high_fade: high.map(|high| high - root).filter(|fade| *fade > 0),
+                };
+            }
+        };
+
+        if xfade == 0 {
+            return KeyRange {
+                low,
+                high,
+                ..Default::default()
+            };
+        }
+
+        // the overlap straddles the split, with any odd key above it
+        KeyRange {
+            low: low.map(|low| low.saturating_sub(xfade / 2)),
+            high: high.map(|high| high.saturating_add(xfade - xfade / 2).min(127)),
+            low_fade: low.map(|_| xfade),
+            high_fade: high.map(|_| xfade),
+        }
+    }
+}
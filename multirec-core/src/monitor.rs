@@ -0,0 +1,129 @@
+use std::{
+    io::Write,
+    path::Path,
+    time::{Instant, SystemTime},
+};
+
+use midir::MidiOutputConnection;
+
+use crate::rtp;
+
+/// A MIDI output that logs every message sent through it, when monitoring
+pub struct MidiOut {
+    connection: Connection,
+    monitor: Option<Monitor>,
+}
+
+/// Where MIDI messages are sent
+pub enum Connection {
+    /// A local MIDI port
+    Port(MidiOutputConnection),
+    /// A network MIDI session
+    Network(rtp::Session),
+}
+
+impl Connection {
+    fn send(&mut self, message: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Port(connection) => Ok(connection.send(message)?),
+            Self::Network(session) => session.send(message),
+        }
+    }
+}
+
+/// Where sent messages are logged, and when logging began
+struct Monitor {
+    out: Box<dyn Write + Send>,
+    started: Instant,
+}
+
+impl MidiOut {
+    /// Send messages on the connection, logging them to `monitor` if given
+    ///
+    /// A monitor path of `-` logs to standard error.
+    pub fn new(connection: Connection, monitor: Option<&Path>) -> anyhow::Result<Self> {
+        let monitor = match monitor {
+            Some(path) => {
+                let mut out: Box<dyn Write + Send> = if path == Path::new("-") {
+                    Box::new(std::io::stderr())
+                } else {
+                    Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+                };
+                writeln!(out, "Time (UTC)\tElapsed\tFrame\tBytes\tMessage")?;
+                Some(Monitor {
+                    out,
+                    started: Instant::now(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            connection,
+            monitor,
+        })
+    }
+
+    /// Send a message, which was played at `frame` of the sequence if it is part of one
+    pub fn send(&mut self, message: &[u8], frame: Option<u64>) -> anyhow::Result<()> {
+        let result = self.connection.send(message);
+
+        if let Some(monitor) = &mut self.monitor {
+            let seconds = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            let time_of_day = seconds.as_secs() % 86_400;
+            let frame = frame.map_or_else(|| "-".to_string(), |frame| frame.to_string());
+            let bytes: Vec<_> = message.iter().map(|b| format!("{b:02X}")).collect();
+            let failed = if result.is_err() { " (failed)" } else { "" };
+
+            let line = writeln!(
+                monitor.out,
+                "{:02}:{:02}:{:02}.{:03}\t{:.3}\t{frame}\t{}\t{}{failed}",
+                time_of_day / 3600,
+                time_of_day / 60 % 60,
+                time_of_day % 60,
+                seconds.subsec_millis(),
+                monitor.started.elapsed().as_secs_f64(),
+                bytes.join(" "),
+                describe(message),
+            );
+            if let Err(e) = line.and_then(|_| monitor.out.flush()) {
+                log::warn!("Stopped monitoring MIDI: {e}");
+                self.monitor = None;
+            }
+        }
+
+        result
+    }
+}
+
+/// What a MIDI message does, e.g. `Note On ch1 60 vel 100`
+fn describe(message: &[u8]) -> String {
+    let Some(status) = message.first() else {
+        return String::new();
+    };
+    let data = |idx: usize| message.get(idx).copied().unwrap_or_default();
+    let channel = (status & 0x0F) + 1;
+
+    match status & 0xF0 {
+        0x80 => format!("Note Off ch{channel} {} vel {}", data(1), data(2)),
+        0x90 => format!("Note On ch{channel} {} vel {}", data(1), data(2)),
+        0xA0 => format!("Poly Pressure ch{channel} {} {}", data(1), data(2)),
+        0xB0 => format!("CC ch{channel} {} = {}", data(1), data(2)),
+        0xC0 => format!("Program ch{channel} {}", data(1)),
+        0xD0 => format!("Channel Pressure ch{channel} {}", data(1)),
+        0xE0 => {
+            let bend = (i32::from(data(2)) << 7 | i32::from(data(1))) - 0x2000;
+            format!("Pitch Bend ch{channel} {bend:+}")
+        }
+        _ => match status {
+            0xF0 => format!("SysEx ({} bytes)", message.len()),
+            0xF8 => "Clock".to_string(),
+            0xFA => "Start".to_string(),
+            0xFB => "Continue".to_string(),
+            0xFC => "Stop".to_string(),
+            _ => "System".to_string(),
+        },
+    }
+}
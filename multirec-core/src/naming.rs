@@ -0,0 +1,421 @@
+use autosam::midi::{ParsePitchError, Pitch};
+
+/// The format that files are named with by default
+pub const DEFAULT_TEMPLATE: &str = "[{prefix}_][{art}_]{pitch}[_V{vel}][_{cc}][_RR{rr}]";
+
+/// The default format when velocities are named with dynamics
+pub const DYNAMICS_TEMPLATE: &str = "[{prefix}_][{art}_]{pitch}[_{dyn}][_{cc}][_RR{rr}]";
+
+/// The format that drum kit files are named with by default
+pub const DRUMKIT_TEMPLATE: &str = "[{prefix}_]{pad}[_V{vel}][_RR{rr}]";
+
+/// The default drum kit format when velocities are named with dynamics
+pub const DRUMKIT_DYNAMICS_TEMPLATE: &str = "[{prefix}_]{pad}[_{dyn}][_RR{rr}]";
+
+/// The usual dynamic markings, each with the lowest velocity it stands for
+pub const DEFAULT_DYNAMICS: &str = "ppp:1,pp:16,p:32,mp:48,mf:64,f:80,ff:96,fff:112";
+
+/// A pattern for the names of recorded files
+///
+/// Tokens in braces are replaced with a property of the recording, and
+/// may give a width to pad to, e.g. `{vel:03}`. Text in square brackets
+/// is left out if any token inside has no value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+    dynamics: Dynamics,
+    /// Pattern for the directory that each file goes in, if any
+    directory: Option<Vec<Part>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Token(Token, Width),
+    Optional(Vec<Part>),
+}
+
+/// Minimum width of a token's value, and whether to pad with zeros
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Width {
+    min: usize,
+    zeros: bool,
+}
+
+/// A property of a recording that a name can include
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    /// The file name prefix
+    Prefix,
+    /// Note name and octave, e.g. `C#4`
+    Pitch,
+    /// Note name, e.g. `C#`
+    Note,
+    /// Octave number, e.g. `4`
+    Octave,
+    /// MIDI note number
+    Midi,
+    /// Velocity
+    Velocity,
+    /// Round robin, counting from 1
+    RoundRobin,
+    /// Name of the velocity, e.g. `mf`
+    Dynamic,
+    /// Name of the articulation selected by a keyswitch
+    Articulation,
+    /// Values of swept controllers, e.g. `CC74-64`
+    Controllers,
+    /// Name of the drum pad the note plays
+    Pad,
+}
+
+impl Token {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "prefix" => Self::Prefix,
+            "pitch" => Self::Pitch,
+            "note" => Self::Note,
+            "octave" => Self::Octave,
+            "midi" => Self::Midi,
+            "vel" => Self::Velocity,
+            "rr" => Self::RoundRobin,
+            "dyn" => Self::Dynamic,
+            "art" => Self::Articulation,
+            "cc" => Self::Controllers,
+            "pad" => Self::Pad,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Unknown token `{{{0}}}`")]
+    UnknownToken(String),
+    #[error("Invalid width in `{{{0}}}`")]
+    InvalidWidth(String),
+    #[error("Unclosed `{0}`")]
+    Unclosed(char),
+    #[error("Unexpected `{0}`")]
+    Unexpected(char),
+    #[error("Optional sections cannot be nested")]
+    Nested,
+    #[error("File names cannot contain path separators")]
+    PathSeparator,
+}
+
+impl core::str::FromStr for NameTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut optional: Option<Vec<Part>> = None;
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            let part = match c {
+                '{' => {
+                    let mut inner = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        inner.push(c);
+                    }
+
+                    if !closed {
+                        return Err(TemplateError::Unclosed('{'));
+                    }
+
+                    let (name, width) = inner.split_once(':').unwrap_or((&inner, ""));
+                    let token = Token::from_name(name)
+                        .ok_or_else(|| TemplateError::UnknownToken(inner.clone()))?;
+                    let width = match width {
+                        "" => Width::default(),
+                        width => Width {
+                            min: width
+                                .parse()
+                                .map_err(|_| TemplateError::InvalidWidth(inner.clone()))?,
+                            zeros: width.starts_with('0'),
+                        },
+                    };
+
+                    Part::Token(token, width)
+                }
+                '[' if optional.is_some() => return Err(TemplateError::Nested),
+                '[' => {
+                    optional = Some(Vec::new());
+                    continue;
+                }
+                ']' => match optional.take() {
+                    Some(inner) => Part::Optional(inner),
+                    None => return Err(TemplateError::Unexpected(']')),
+                },
+                '}' => return Err(TemplateError::Unexpected('}')),
+                '/' | '\\' => return Err(TemplateError::PathSeparator),
+                c => Part::Text(c.to_string()),
+            };
+
+            let target = optional.as_mut().unwrap_or(&mut parts);
+
+            // keep runs of text together
+            match (target.last_mut(), part) {
+                (Some(Part::Text(text)), Part::Text(more)) => text.push_str(&more),
+                (_, part) => target.push(part),
+            }
+        }
+
+        if optional.is_some() {
+            return Err(TemplateError::Unclosed('['));
+        }
+
+        Ok(Self {
+            parts,
+            dynamics: DEFAULT_DYNAMICS.parse().unwrap_or_default(),
+            directory: None,
+        })
+    }
+}
+
+impl NameTemplate {
+    /// Name velocities for the `{dyn}` token with this table
+    pub fn with_dynamics(self, dynamics: Dynamics) -> Self {
+        Self { dynamics, ..self }
+    }
+
+    /// Put each file in a subdirectory named by another template
+    ///
+    /// A file whose directory name comes out empty is left at the top.
+    pub fn in_directory(self, directory: NameTemplate) -> Self {
+        Self {
+            directory: Some(directory.parts),
+            ..self
+        }
+    }
+
+    /// The name for a velocity, as given by the `{dyn}` token
+    pub fn dynamic(&self, velocity: u8) -> &str {
+        self.dynamics.name(velocity)
+    }
+
+    /// Write a name, getting the value of each token from `value`
+    ///
+    /// The name starts with its directory and a `/`, if it has one.
+    pub fn render(
+        &self,
+        f: &mut impl core::fmt::Write,
+        value: impl Fn(Token) -> Option<String>,
+    ) -> core::fmt::Result {
+        if let Some(directory) = &self.directory {
+            let mut name = String::new();
+            render_template(&mut name, directory, &value)?;
+            if !name.is_empty() {
+                f.write_str(&name)?;
+                f.write_char('/')?;
+            }
+        }
+
+        render_template(f, &self.parts, &value)
+    }
+}
+
+fn render_template(
+    f: &mut impl core::fmt::Write,
+    parts: &[Part],
+    value: &impl Fn(Token) -> Option<String>,
+) -> core::fmt::Result {
+    for part in parts {
+        match part {
+            Part::Optional(inner) => {
+                let mut section = String::new();
+                if render_parts(&mut section, inner, value).is_some() {
+                    f.write_str(&section)?;
+                }
+            }
+            part => {
+                render_parts(f, core::slice::from_ref(part), value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How recorded files are sorted into directories
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Layout {
+    /// Every file at the top of the output directory
+    #[default]
+    Flat,
+    /// A directory for each note, or for each pad of a drum kit
+    PerNote,
+    /// A directory for each velocity layer
+    PerLayer,
+}
+
+impl Layout {
+    /// Template for the directory of each file, if files are sorted into them
+    ///
+    /// Layers are named by their dynamic when velocities are named with dynamics.
+    pub fn directory(self, drumkit: bool, dynamics: bool) -> Option<&'static str> {
+        match self {
+            Self::Flat => None,
+            Self::PerNote if drumkit => Some("{pad}"),
+            Self::PerNote => Some("{pitch}"),
+            Self::PerLayer if dynamics => Some("[{dyn}]"),
+            Self::PerLayer => Some("[V{vel}]"),
+        }
+    }
+}
+
+/// Write parts, stopping with `None` at a token that has no value
+fn render_parts(
+    f: &mut impl core::fmt::Write,
+    parts: &[Part],
+    value: &impl Fn(Token) -> Option<String>,
+) -> Option<()> {
+    for part in parts {
+        match part {
+            Part::Text(text) => f.write_str(text).ok()?,
+            Part::Token(token, Width { min, zeros: true }) => {
+                write!(f, "{:0>min$}", value(*token)?).ok()?
+            }
+            Part::Token(token, Width { min, zeros: false }) => {
+                write!(f, "{:>min$}", value(*token)?).ok()?
+            }
+            Part::Optional(_) => {}
+        }
+    }
+
+    Some(())
+}
+
+/// Names for ranges of velocities, such as dynamic markings
+///
+/// Written as a comma-separated list of names, each with the lowest velocity
+/// it applies to, e.g. `p:1,mf:64,ff:100`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dynamics(Vec<(u8, String)>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum DynamicsError {
+    #[error("Expected `name:velocity`, found `{0}`")]
+    Format(String),
+    #[error("Invalid velocity in `{0}`")]
+    Velocity(String),
+    #[error("No names given")]
+    Empty,
+}
+
+impl core::str::FromStr for Dynamics {
+    type Err = DynamicsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut names = s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (name, velocity) = entry
+                    .split_once(':')
+                    .ok_or_else(|| DynamicsError::Format(entry.to_string()))?;
+                let velocity = velocity
+                    .trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|v| *v < 128)
+                    .ok_or_else(|| DynamicsError::Velocity(entry.to_string()))?;
+
+                Ok((velocity, name.trim().to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if names.is_empty() {
+            return Err(DynamicsError::Empty);
+        }
+
+        names.sort_by_key(|(velocity, _)| *velocity);
+        Ok(Self(names))
+    }
+}
+
+impl Dynamics {
+    /// The name covering a velocity, or the lowest one if none does
+    pub fn name(&self, velocity: u8) -> &str {
+        self.0
+            .iter()
+            .rev()
+            .find(|(lowest, _)| *lowest <= velocity)
+            .or(self.0.first())
+            .map_or("", |(_, name)| name)
+    }
+
+    /// The highest velocity that a name covers, ignoring case
+    pub fn velocity(&self, name: &str) -> Option<u8> {
+        let idx = self
+            .0
+            .iter()
+            .position(|(_, n)| n.eq_ignore_ascii_case(name))?;
+
+        Some(
+            self.0
+                .get(idx + 1)
+                .map_or(127, |(next, _)| next.saturating_sub(1)),
+        )
+    }
+}
+
+/// A keyswitch note, and the name of the articulation it selects
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyswitch {
+    pub pitch: Pitch,
+    pub label: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyswitchError {
+    #[error(transparent)]
+    Pitch(#[from] ParsePitchError),
+    #[error("Articulation names cannot be empty")]
+    EmptyLabel,
+    #[error("Articulation names cannot contain path separators")]
+    PathSeparator,
+}
+
+impl Keyswitch {
+    /// Look up the keyswitch for a note
+    pub fn find(keyswitches: &[Self], note: u8) -> Option<&Self> {
+        keyswitches
+            .iter()
+            .find(|keyswitch| keyswitch.pitch.note_number() == note)
+    }
+}
+
+impl core::str::FromStr for Keyswitch {
+    type Err = KeyswitchError;
+
+    /// Parse a note with an optional name, e.g. `C0=legato`
+    ///
+    /// Without a name, the articulation is named after the note.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pitch, label) = match s.split_once('=') {
+            Some((pitch, label)) => (pitch.trim().parse::<Pitch>()?, label.trim().to_string()),
+            None => {
+                let pitch = s.trim().parse::<Pitch>()?;
+                (pitch, pitch.to_string())
+            }
+        };
+
+        if label.is_empty() {
+            return Err(KeyswitchError::EmptyLabel);
+        }
+        if label.contains(['/', '\\']) {
+            return Err(KeyswitchError::PathSeparator);
+        }
+
+        Ok(Self { pitch, label })
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use cpal::{traits::DeviceTrait, traits::StreamTrait, FromSample};
+use log::{debug, error};
+
+use crate::runtime::ChannelSelection;
+
+/// Name of the recording of the noise floor, inside the output directory
+pub const FILE_NAME: &str = "noise.wav";
+
+/// Collects a fixed number of frames from the input
+struct Listener {
+    samples: rtrb::Producer<f32>,
+    channels: usize,
+    selection: ChannelSelection,
+    remaining: usize,
+    done: Arc<AtomicBool>,
+}
+
+impl Listener {
+    fn process<T>(&mut self, input: &[T])
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>,
+    {
+        for frame in input.chunks(self.channels) {
+            if self.remaining == 0 {
+                self.done.store(true, Ordering::Release);
+                return;
+            }
+
+            let (samples, len) = self.selection.pick(frame);
+            for sample in &samples[..len] {
+                if let Err(e) = self.samples.push(*sample) {
+                    error!("Out of capacity in noise buffer: {e}");
+                }
+            }
+            self.remaining -= 1;
+        }
+    }
+}
+
+/// Record the input while nothing is played, returning its interleaved samples
+pub fn capture(
+    device: &cpal::Device,
+    sample_format: cpal::SampleFormat,
+    config: &cpal::StreamConfig,
+    selection: ChannelSelection,
+    frames: usize,
+) -> anyhow::Result<Vec<f32>> {
+    let (sample_tx, mut sample_rx) = rtrb::RingBuffer::new(frames * selection.len());
+    let done = Arc::new(AtomicBool::new(false));
+
+    let listener = Listener {
+        samples: sample_tx,
+        channels: usize::from(config.channels),
+        selection,
+        remaining: frames,
+        done: done.clone(),
+    };
+
+    let stream = match sample_format {
+        cpal::SampleFormat::I8 => input_stream::<i8>(device, config, listener)?,
+        cpal::SampleFormat::I16 => input_stream::<i16>(device, config, listener)?,
+        cpal::SampleFormat::I32 => input_stream::<i32>(device, config, listener)?,
+        cpal::SampleFormat::F32 => input_stream::<f32>(device, config, listener)?,
+        sample_format => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported sample format '{sample_format}'"
+            )))
+        }
+    };
+
+    debug!("Recording the noise floor");
+    stream.play()?;
+
+    let mut samples = Vec::with_capacity(frames * selection.len());
+    loop {
+        let is_done = done.load(Ordering::Acquire);
+
+        while let Ok(sample) = sample_rx.pop() {
+            samples.push(sample);
+        }
+
+        if is_done {
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    drop(stream);
+
+    Ok(samples)
+}
+
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut listener: Listener,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &_| listener.process(data),
+        |e| error!("Encountered an error while recording the noise floor: {e}"),
+        None,
+    )
+}
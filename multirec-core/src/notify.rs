@@ -0,0 +1,178 @@
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use log::{debug, info, warn};
+use serde::Serialize;
+
+/// Somewhere to say that a run is over
+#[derive(Clone, Debug)]
+pub enum Target {
+    /// A notification on this computer's desktop
+    Desktop,
+    /// A URL to POST a JSON summary to
+    Webhook(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TargetError {
+    #[error("Expected `desktop` or an http:// or https:// URL, found `{0}`")]
+    Unknown(String),
+}
+
+impl std::str::FromStr for Target {
+    type Err = TargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("desktop") {
+            Ok(Self::Desktop)
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Webhook(s.to_string()))
+        } else {
+            Err(TargetError::Unknown(s.to_string()))
+        }
+    }
+}
+
+/// How a run ended
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Completed,
+    /// Stopped by the user
+    Aborted,
+    /// Stopped by an error
+    Failed,
+}
+
+/// What is sent when a run is over
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub outcome: Outcome,
+    pub message: String,
+    pub output_directory: Option<PathBuf>,
+    pub elapsed_seconds: f64,
+    /// The line shown by chat services such as Slack, which read this field of a webhook
+    pub text: String,
+}
+
+impl Summary {
+    pub fn new(
+        outcome: Outcome,
+        message: String,
+        output_directory: Option<PathBuf>,
+        elapsed: Duration,
+    ) -> Self {
+        let time = crate::util::clock_time(elapsed);
+        let text = match &output_directory {
+            Some(dir) => format!("multirec: {message} ({}, after {time})", dir.display()),
+            None => format!("multirec: {message} (after {time})"),
+        };
+
+        Self {
+            outcome,
+            message,
+            output_directory,
+            elapsed_seconds: elapsed.as_secs_f64(),
+            text,
+        }
+    }
+}
+
+/// Send the summary to every target, warning about any that fail
+pub fn send(targets: &[Target], summary: &Summary) {
+    for target in targets {
+        let result = match target {
+            Target::Desktop => desktop(summary),
+            Target::Webhook(url) => webhook(url, summary),
+        };
+
+        match result {
+            Ok(()) => debug!("Sent notification to {target:?}"),
+            Err(e) => warn!("Could not send notification to {target:?}: {e}"),
+        }
+    }
+
+    if !targets.is_empty() {
+        info!("Sent notifications that the run is over");
+    }
+}
+
+/// Show a notification with the desktop's own tool
+fn desktop(summary: &Summary) -> anyhow::Result<()> {
+    let title = match summary.outcome {
+        Outcome::Completed => "Sampling complete",
+        Outcome::Aborted => "Sampling aborted",
+        Outcome::Failed => "Sampling failed",
+    };
+
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            apple_string(&summary.text),
+            apple_string(title)
+        ));
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", WINDOWS_BALLOON]);
+        command.env("MULTIREC_TITLE", title);
+        command.env("MULTIREC_TEXT", &summary.text);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=multirec", title, &summary.text]);
+        command
+    };
+
+    let status = command.status()?;
+    if !status.success() {
+        anyhow::bail!("Notifier exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Shows a balloon from the notification area, with the title and text from the environment
+const WINDOWS_BALLOON: &str = "Add-Type -AssemblyName System.Windows.Forms; \
+    $n = New-Object System.Windows.Forms.NotifyIcon; \
+    $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+    $n.ShowBalloonTip(10000, $env:MULTIREC_TITLE, $env:MULTIREC_TEXT, 'Info'); \
+    Start-Sleep -Seconds 10; $n.Dispose()";
+
+/// Quote a string for AppleScript
+fn apple_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// POST the summary as JSON, with curl
+fn webhook(url: &str, summary: &Summary) -> anyhow::Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "30"])
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                anyhow::anyhow!("curl, which sends webhooks, was not found on the PATH")
+            }
+            _ => e.into(),
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&serde_json::to_vec(summary)?)?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("curl exited with {status}");
+    }
+
+    Ok(())
+}
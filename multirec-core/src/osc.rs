@@ -0,0 +1,237 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use autosam::midi::Pitch;
+use log::{debug, info, warn};
+
+use crate::runtime::RunState;
+
+/// How often the status is sent to each client
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Largest message that is read
+const MAX_PACKET: usize = 1536;
+
+/// Most clients that are sent the status, the earliest being dropped first
+const MAX_CLIENTS: usize = 8;
+
+/// Controls a run over OSC, and reports its progress to every client heard from
+///
+/// Commands, with no arguments or a non-zero first argument (so that a
+/// button sending 0 on release is ignored):
+///
+/// - `/multirec/start`: resume a paused run
+/// - `/multirec/pause`: pause, once the current zone is complete
+/// - `/multirec/skip`: pass over the rest of the current note
+/// - `/multirec/abort`: stop without finishing the current zone
+///
+/// Status, sent a few times a second:
+///
+/// - `/multirec/progress ii`: zones completed, and in total
+/// - `/multirec/zone sii`: the current pitch, velocity and round robin (from 1)
+/// - `/multirec/state s`: `running`, `paused`, `aborting` or `done`
+pub struct Server {
+    socket: UdpSocket,
+    state: Arc<RunState>,
+    total_zones: usize,
+    clients: Vec<SocketAddr>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OscError {
+    #[error("Could not listen for OSC on {0}: {1}")]
+    Bind(SocketAddr, std::io::Error),
+}
+
+impl Server {
+    pub fn bind(
+        address: SocketAddr,
+        state: Arc<RunState>,
+        total_zones: usize,
+    ) -> Result<Self, OscError> {
+        let socket = UdpSocket::bind(address).map_err(|e| OscError::Bind(address, e))?;
+        socket
+            .set_read_timeout(Some(STATUS_INTERVAL / 4))
+            .map_err(|e| OscError::Bind(address, e))?;
+        info!("Listening for OSC on {address}");
+
+        Ok(Self {
+            socket,
+            state,
+            total_zones,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Handle commands and send the status until the run is done
+    pub fn run(mut self) {
+        let mut buffer = [0; MAX_PACKET];
+        let mut last_status = Instant::now() - STATUS_INTERVAL;
+
+        loop {
+            let is_done = self.state.done();
+
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) => {
+                    if !self.clients.contains(&from) {
+                        debug!("OSC client {from} connected");
+                        if self.clients.len() == MAX_CLIENTS {
+                            self.clients.remove(0);
+                        }
+                        self.clients.push(from);
+                    }
+
+                    let mut commands = Vec::new();
+                    read_packet(&buffer[..len], &mut commands);
+                    for (address, pressed) in commands {
+                        self.command(&address, pressed);
+                    }
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => warn!("Could not read OSC message: {e}"),
+            }
+
+            if is_done || last_status.elapsed() >= STATUS_INTERVAL {
+                self.send_status();
+                last_status = Instant::now();
+            }
+
+            if is_done {
+                break;
+            }
+        }
+    }
+
+    fn command(&self, address: &str, pressed: bool) {
+        if !pressed {
+            return;
+        }
+
+        match address {
+            "/multirec/start" => self.state.set_paused(false),
+            "/multirec/pause" => self.state.set_paused(true),
+            "/multirec/skip" => self.state.skip_note(),
+            "/multirec/abort" => self.state.abort(),
+            _ => {
+                debug!("Ignoring OSC message {address}");
+                return;
+            }
+        }
+        info!("Received OSC command {address}");
+    }
+
+    fn send_status(&self) {
+        let (pitch, velocity, round_robin) = self.state.note(std::sync::atomic::Ordering::Acquire);
+        let pitch = Pitch::new(pitch).map_or_else(|_| pitch.to_string(), |p| p.to_string());
+        let state = self.state.activity();
+
+        let messages = [
+            message(
+                "/multirec/progress",
+                &[
+                    Arg::Int(self.state.completed() as i32),
+                    Arg::Int(self.total_zones as i32),
+                ],
+            ),
+            message(
+                "/multirec/zone",
+                &[
+                    Arg::Str(&pitch),
+                    Arg::Int(i32::from(velocity)),
+                    Arg::Int(i32::from(round_robin) + 1),
+                ],
+            ),
+            message("/multirec/state", &[Arg::Str(state)]),
+        ];
+
+        for client in &self.clients {
+            for message in &messages {
+                if let Err(e) = self.socket.send_to(message, client) {
+                    debug!("Could not send OSC status to {client}: {e}");
+                }
+            }
+        }
+    }
+}
+
+enum Arg<'a> {
+    Int(i32),
+    Str(&'a str),
+}
+
+/// Encode an OSC message
+fn message(address: &str, args: &[Arg]) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_string(&mut out, address);
+
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            Arg::Int(_) => 'i',
+            Arg::Str(_) => 's',
+        }))
+        .collect();
+    put_string(&mut out, &tags);
+
+    for arg in args {
+        match arg {
+            Arg::Int(value) => out.extend_from_slice(&value.to_be_bytes()),
+            Arg::Str(value) => put_string(&mut out, value),
+        }
+    }
+
+    out
+}
+
+/// Append a null-terminated string, padded to a multiple of four bytes
+fn put_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(s.as_bytes());
+    out.resize((out.len() / 4 + 1) * 4, 0);
+}
+
+/// Take a padded string from the start of `data`, returning it and what follows
+fn take_string(data: &[u8]) -> Option<(&str, &[u8])> {
+    let end = data.iter().position(|b| *b == 0)?;
+    let s = std::str::from_utf8(&data[..end]).ok()?;
+    let next = ((end / 4 + 1) * 4).min(data.len());
+    Some((s, &data[next..]))
+}
+
+/// Read the messages of a packet, as each address and whether its first argument is non-zero
+///
+/// Bundles are read in full, ignoring their time tags.
+fn read_packet(data: &[u8], commands: &mut Vec<(String, bool)>) {
+    if let Some(mut elements) = data.strip_prefix(b"#bundle\0") {
+        elements = elements.get(8..).unwrap_or_default();
+        while let Some((size, rest)) = elements.split_first_chunk::<4>() {
+            let size = u32::from_be_bytes(*size) as usize;
+            let Some(element) = rest.get(..size) else {
+                return;
+            };
+            read_packet(element, commands);
+            elements = &rest[size..];
+        }
+        return;
+    }
+
+    let Some((address, rest)) = take_string(data) else {
+        return;
+    };
+    let (tags, args) = take_string(rest).unwrap_or((",", &[]));
+
+    let first = args.first_chunk::<4>().copied();
+    let pressed = match (tags.as_bytes().get(1), first) {
+        (Some(b'i'), Some(bytes)) => i32::from_be_bytes(bytes) != 0,
+        (Some(b'f'), Some(bytes)) => f32::from_be_bytes(bytes) != 0.0,
+        (Some(b'F'), _) => false,
+        _ => true,
+    };
+
+    commands.push((address.to_string(), pressed));
+}
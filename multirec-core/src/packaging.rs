@@ -0,0 +1,687 @@
+use std::{io::Write as _, path::Path};
+
+use log::{error, info, warn};
+use serde::Serialize;
+
+use crate::{arguments::*, settings::Settings, util, util::NamedFile};
+
+/// Colours of the groups of successive velocity layers in Bitwig output, loudest first
+const GROUP_COLORS: [dot_multisample::Color; 8] = [
+    [0xd9, 0x2e, 0x24],
+    [0xff, 0x83, 0x3e],
+    [0xe4, 0xb7, 0x4e],
+    [0x73, 0x98, 0x14],
+    [0x00, 0xa0, 0x94],
+    [0x44, 0xc8, 0xff],
+    [0x5b, 0x61, 0xc6],
+    [0xc9, 0x66, 0xcc],
+];
+
+/// Unpack the bundle that a run adds to, so its samples are next to the new ones
+pub fn unpack(
+    settings: &Settings,
+) -> anyhow::Result<Option<dot_multisample::Multisample<'static>>> {
+    let output_dir = &settings.output_dir;
+    let Settings {
+        should_save,
+        append,
+        ..
+    } = *settings;
+
+    // the samples of the bundle being added to are unpacked next to the new ones
+    Ok(match output_dir.with_extension("multisample") {
+        bundle if should_save && append && bundle.exists() => {
+            let multi = util::unpack(&bundle, output_dir)?;
+            info!(
+                "Adding to {} samples in {}",
+                multi.samples().len(),
+                bundle.display()
+            );
+            Some(multi)
+        }
+        bundle if should_save && append => {
+            warn!(
+                "{} does not exist yet, so it will be created",
+                bundle.display()
+            );
+            None
+        }
+        _ => None,
+    })
+}
+
+/// Write the manifest of one input's files, and pack them up, as the output format asks
+///
+/// `sample_start` is where playback of each file starts, in frames at `sample_rate`.
+pub fn package(
+    settings: &Settings,
+    output_dir: &Path,
+    entries: &[NamedFile<'_, &String>],
+    appended: Option<&dot_multisample::Multisample<'_>>,
+    sample_start: usize,
+    sample_rate: u32,
+) -> anyhow::Result<()> {
+    let (compression, zipped_name) = match settings.output_format {
+        OutputFormat::Raw => return Ok(()),
+        OutputFormat::Zip => (
+            zip::CompressionMethod::Deflated,
+            output_dir.with_extension("zip"),
+        ),
+        OutputFormat::Sfz => {
+            return write_sfz(settings, output_dir, entries, sample_start, sample_rate)
+        }
+        OutputFormat::Bitwig => {
+            write_bitwig(settings, output_dir, entries, appended, sample_start)?;
+            (
+                zip::CompressionMethod::Stored,
+                output_dir.with_extension("multisample"),
+            )
+        }
+    };
+
+    // each recording is removed as soon as the archive holds it, unless they are kept
+    if let Err(e) = util::archive(output_dir, &zipped_name, compression, !settings.keep_raw) {
+        error!(
+            "Failed to write {}, recordings not yet packed are kept in {}",
+            zipped_name.display(),
+            output_dir.display()
+        );
+        return Err(e);
+    }
+
+    if settings.keep_raw {
+        info!("Kept recordings in {}", output_dir.display());
+    } else {
+        std::fs::remove_dir_all(output_dir)?;
+    }
+
+    Ok(())
+}
+
+/// The name of a file's group, after the pad, articulation, dynamic and controllers, where those are in use
+fn group_label(
+    name_template: &NameTemplate,
+    file: &NamedFile<'_, &String>,
+    with_dynamic: bool,
+) -> Option<String> {
+    let pad = file.pad.map(|pad| pad.name.clone());
+    let articulation = file.keyswitch.map(|keyswitch| keyswitch.label.clone());
+    // a pad keeps all of its velocity layers in one group
+    let dynamic = file
+        .velocity
+        .filter(|_| with_dynamic && file.pad.is_none())
+        .map(|velocity| name_template.dynamic(velocity).to_string());
+    let controllers = file
+        .controllers
+        .iter()
+        .map(|(controller, value)| format!("CC{controller}-{value}"));
+
+    let parts: Vec<_> = pad
+        .into_iter()
+        .chain(articulation)
+        .chain(dynamic)
+        .chain(controllers)
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// Write an SFZ instrument that plays the files
+fn write_sfz(
+    settings: &Settings,
+    output_dir: &Path,
+    entries: &[NamedFile<'_, &String>],
+    sample_start: usize,
+    sample_rate: u32,
+) -> anyhow::Result<()> {
+    let Settings {
+        file_name_prefix,
+        name_template,
+        keyswitches,
+        controller_sweeps,
+        note_layers,
+        ..
+    } = settings;
+    let Settings {
+        key_map,
+        key_xfade,
+        label_groups,
+        round_robins,
+        ..
+    } = *settings;
+    let has_rr = round_robins > 1;
+
+    let manifest_name = if let Some(p) = &file_name_prefix {
+        p
+    } else {
+        "instrument"
+    };
+    let mut f = std::fs::File::create(output_dir.join(format!("{manifest_name}.sfz")))?;
+
+    let roots: Vec<u8> = entries.iter().map(|f| f.pitch.note_number()).collect();
+
+    let keyswitch_notes = keyswitches.iter().map(|k| k.pitch.note_number());
+    if let (Some(low), Some(high)) = (keyswitch_notes.clone().min(), keyswitch_notes.max()) {
+        writeln!(f, "<global> sw_lokey={low} sw_hikey={high}")?;
+    }
+
+    let mut prev_note = None;
+    let mut prev_velo = None;
+    let mut prev_keyswitch = None;
+    let mut prev_controllers = None;
+
+    for (idx, file) in entries.iter().enumerate() {
+        let current_note = file.pitch.note_number();
+        let note_is_new = Some(current_note) != prev_note;
+        let velo_is_new = file.velocity != prev_velo;
+        let keyswitch_is_new = file.keyswitch != prev_keyswitch;
+        let controllers_are_new = Some(&file.controllers) != prev_controllers;
+
+        if note_is_new || velo_is_new || keyswitch_is_new || controllers_are_new {
+            write!(f, "<group> pitch_keycenter={current_note}")?;
+            prev_note = Some(current_note);
+
+            let keys = key_map.range(&roots, current_note, key_xfade);
+            if let Some(low) = keys.low {
+                write!(f, " lokey={low}")?;
+            }
+            if let Some(high) = keys.high {
+                write!(f, " hikey={high}")?;
+            }
+            if let (Some(low), Some(fade)) = (keys.low, keys.low_fade) {
+                write!(f, " xfin_lokey={low} xfin_hikey={}", low + fade)?;
+            }
+            if let (Some(high), Some(fade)) = (keys.high, keys.high_fade) {
+                write!(f, " xfout_lokey={} xfout_hikey={high}", high - fade)?;
+            }
+
+            if velo_is_new {
+                if prev_velo > file.velocity {
+                    write!(f, " hivel={}", file.velocity.unwrap())?;
+                }
+                prev_velo = file.velocity;
+
+                if let Some(next_velo) = entries[idx..].iter().find_map(|f| {
+                    (f.pitch == file.pitch && f.velocity < file.velocity)
+                        .then_some(f.velocity)
+                        .flatten()
+                }) {
+                    write!(f, " lowvel={}", next_velo + 1)?;
+                }
+            }
+
+            if has_rr {
+                let length = note_layers[usize::from(current_note)]
+                    .map_or(round_robins, |layers| layers.round_robins.get());
+                write!(f, " seq_length={length}")?;
+            }
+
+            if let Some(keyswitch) = file.keyswitch {
+                write!(f, " sw_last={}", keyswitch.pitch.note_number())?;
+            }
+            prev_keyswitch = file.keyswitch;
+
+            for (controller, value) in &file.controllers {
+                if let Some(sweep) = controller_sweeps
+                    .iter()
+                    .find(|s| s.controller == *controller)
+                {
+                    let range = sweep.range(*value);
+                    write!(
+                        f,
+                        " locc{controller}={} hicc{controller}={}",
+                        range.start(),
+                        range.end()
+                    )?;
+                }
+            }
+            prev_controllers = Some(&file.controllers);
+
+            if let Some(label) = group_label(name_template, file, label_groups) {
+                write!(f, " group_label={label}")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        write!(f, "<region> sample={file}")?;
+
+        if sample_start > 0 {
+            write!(f, " offset={sample_start}")?;
+        }
+
+        if let Some(rr) = file.round_robin {
+            write!(f, " seq_position={}", rr + 1)?;
+        }
+
+        if let Some(gain) = file.gain {
+            write!(f, " volume={gain:.2}")?;
+        }
+
+        match file.tune.map(|cents| -cents.round() as i32) {
+            Some(0) | None => {}
+            Some(tune) => write!(f, " tune={tune}")?,
+        }
+
+        if let Some(stop) = file.sample_stop {
+            write!(f, " end={}", stop.saturating_sub(1))?;
+        }
+
+        if let Some(points) = &file.loop_points {
+            // with a tail to play, only loop while the key is held
+            let mode = if file.sample_stop.is_some() {
+                "loop_sustain"
+            } else {
+                "loop_continuous"
+            };
+            write!(
+                f,
+                " loop_mode={mode} loop_start={} loop_end={}",
+                points.start,
+                points.end - 1
+            )?;
+
+            if let Some(length) = file.loop_crossfade {
+                let seconds = length as f64 / f64::from(sample_rate);
+                write!(f, " loop_crossfade={seconds:.4}")?;
+            }
+        }
+
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+/// Find the sample of a bundle that each recorded zone replaces, if any
+///
+/// Zones are matched by their root, velocity layer and round robin, so the
+/// files can be named differently from the bundle's. A bundle's layers count
+/// down from the loudest velocity at each root, and its round robins follow
+/// the order of the samples in the same layer.
+fn replaced_samples(
+    bundle: &[dot_multisample::Sample<'_>],
+    recorded: impl Iterator<Item = (u8, Option<u8>, Option<u8>)>,
+) -> Vec<Option<usize>> {
+    let root = |sample: &dot_multisample::Sample<'_>| sample.key().as_ref()?.root();
+    let velocity_of =
+        |sample: &dot_multisample::Sample<'_>| sample.velocity().as_ref().and_then(|v| v.high());
+    let layer = |note: u8, velocity: Option<u8>| {
+        let mut velocities: Vec<u8> = bundle
+            .iter()
+            .filter(|sample| root(sample) == Some(note))
+            .map(|sample| velocity_of(sample).unwrap_or(127))
+            .collect();
+        velocities.sort_unstable_by(|a, b| b.cmp(a));
+        velocities.dedup();
+        velocities
+            .iter()
+            .position(|v| *v == velocity.unwrap_or(127))
+    };
+
+    let mut zones: Vec<Option<(u8, usize, usize)>> = Vec::new();
+    for sample in bundle {
+        let zone = root(sample).and_then(|note| {
+            let layer = layer(note, velocity_of(sample))?;
+            let round_robin = zones
+                .iter()
+                .filter(|zone| matches!(zone, Some((n, l, _)) if (*n, *l) == (note, layer)))
+                .count();
+            Some((note, layer, round_robin))
+        });
+        zones.push(zone);
+    }
+
+    recorded
+        .map(|(note, velocity, round_robin)| {
+            let zone = (
+                note,
+                layer(note, velocity)?,
+                usize::from(round_robin.unwrap_or(0)),
+            );
+            zones.iter().position(|z| *z == Some(zone))
+        })
+        .collect()
+}
+
+/// Write the manifest of a Bitwig multisample, keeping the samples of the bundle added to
+fn write_bitwig<'b>(
+    settings: &Settings,
+    output_dir: &Path,
+    entries: &[NamedFile<'_, &String>],
+    appended: Option<&'b dot_multisample::Multisample<'_>>,
+    sample_start: usize,
+) -> anyhow::Result<()> {
+    let Settings {
+        file_name_prefix,
+        name_template,
+        pads,
+        controller_sweeps,
+        metadata,
+        ..
+    } = settings;
+    let Settings {
+        key_map,
+        key_xfade,
+        label_groups,
+        velocity_levels,
+        ..
+    } = *settings;
+    let has_vel = velocity_levels > 1;
+
+    // samples already in the bundle are kept, unless their zone was just recorded again
+    let bundle = appended.map_or(&[][..], |multi| multi.samples());
+    let replaced = replaced_samples(
+        bundle,
+        entries
+            .iter()
+            .map(|f| (f.pitch.note_number(), f.velocity, f.round_robin)),
+    );
+    let previous: Vec<_> = bundle
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !replaced.contains(&Some(*idx)))
+        .map(|(_, sample)| sample)
+        .collect();
+
+    // a replaced sample named differently would otherwise be packed with the bundle
+    for (f, idx) in entries.iter().zip(&replaced) {
+        if let Some(old) = idx.map(|idx| bundle[idx].file()) {
+            if old != Path::new(&f.to_string()) {
+                std::fs::remove_file(output_dir.join(old))?;
+            }
+        }
+    }
+
+    // velocity layers always get a group each, coloured by layer, unless pads group them
+    let group_label =
+        |file: &NamedFile<'_, _>| group_label(name_template, file, label_groups || has_vel);
+    let mut layers: Vec<u8> = entries.iter().filter_map(|f| f.velocity).collect();
+    layers.sort_unstable_by(|a, b| b.cmp(a));
+    layers.dedup();
+    let group_color = |file: &NamedFile<'_, _>| {
+        if let Some(pad) = file.pad {
+            let index = pads.iter().position(|p| p == pad)?;
+            return Some(GROUP_COLORS[index % GROUP_COLORS.len()]);
+        }
+
+        let layer = layers.iter().position(|v| Some(*v) == file.velocity)?;
+        Some(GROUP_COLORS[layer % GROUP_COLORS.len()]).filter(|_| has_vel)
+    };
+
+    // one group per label, with those of the bundle first and the rest in the order they were recorded
+    let mut groups: Vec<(String, Option<dot_multisample::Color>)> = appended
+        .iter()
+        .flat_map(|multi| multi.groups())
+        .map(|group| (group.name().to_string(), group.color()))
+        .collect();
+    for file in entries {
+        if let Some(label) = group_label(file) {
+            if !groups.iter().any(|(name, _)| *name == label) {
+                groups.push((label, group_color(file)));
+            }
+        }
+    }
+
+    // key ranges are shared out among the neighbours, old samples included
+    let zones: Vec<(u8, Option<u8>)> = entries
+        .iter()
+        .map(|f| (f.pitch.note_number(), f.velocity))
+        .chain(previous.iter().filter_map(|sample| {
+            let root = sample.key().as_ref()?.root()?;
+            Some((root, sample.velocity().as_ref().and_then(|v| v.high())))
+        }))
+        .collect();
+
+    let roots: Vec<u8> = zones.iter().map(|(n, _)| *n).collect();
+    let key_range = |note: u8| key_map.range(&roots, note, key_xfade);
+
+    let velocity_low = |note: u8, velocity: u8| {
+        zones
+            .iter()
+            .filter(|(n, _)| *n == note)
+            .filter_map(|(_, v)| v.filter(|v| *v < velocity))
+            .max()
+            .map(|next_vel| next_vel + 1)
+    };
+
+    let keep = |sample: &dot_multisample::Sample<'b>| {
+        let Some(root) = sample.key().as_ref().and_then(|key| key.root()) else {
+            return sample.clone();
+        };
+
+        let keys = key_range(root);
+        let key = sample.key().clone().map(|key| {
+            key.with_low(keys.low)
+                .with_high(keys.high)
+                .with_low_fade(keys.low_fade)
+                .with_high_fade(keys.high_fade)
+        });
+        let velocity = sample.velocity().clone().map(|vel| match vel.high() {
+            Some(high) => vel.with_low(velocity_low(root, high)),
+            None => vel,
+        });
+        let group = sample
+            .group()
+            .and_then(|idx| appended.as_ref()?.groups().get(usize::try_from(idx).ok()?))
+            .and_then(|group| groups.iter().position(|(g, _)| g == group.name()))
+            .map(|i| i as isize);
+
+        sample
+            .clone()
+            .with_key(key)
+            .with_velocity(velocity)
+            .with_group(group)
+    };
+
+    let record = |f: &NamedFile<'_, _>| {
+        let note = f.pitch.note_number();
+        let keys = key_range(note);
+        let key = dot_multisample::Key::default()
+            .with_root(note)
+            .with_low(keys.low)
+            .with_high(keys.high)
+            .with_low_fade(keys.low_fade)
+            .with_high_fade(keys.high_fade)
+            .with_tune(
+                f.tune
+                    .map(|cents| (f64::from(-cents) / 100.0 * 1000.0).round() / 1000.0),
+            );
+
+        let velocity = f.velocity.map(|v| {
+            dot_multisample::ZoneInfo::default()
+                .with_high(v)
+                .with_low(velocity_low(note, v))
+        });
+
+        // a single swept controller can be mapped to the select range
+        let select = match (controller_sweeps.as_slice(), f.controllers.as_slice()) {
+            ([sweep], [(_, value)]) => {
+                let range = sweep.range(*value);
+                Some(
+                    dot_multisample::ZoneInfo::default()
+                        .with_low(*range.start())
+                        .with_high(*range.end()),
+                )
+            }
+            _ => None,
+        };
+
+        dot_multisample::Sample::default()
+            .with_file(std::path::PathBuf::from(format!("{f}")))
+            .with_sample_start((sample_start > 0).then_some(sample_start as f64))
+            .with_sample_stop(f.sample_stop.map(|stop| stop as f64))
+            .with_gain(f.gain.map(|gain| (f64::from(gain) * 100.0).round() / 100.0))
+            .with_key(key)
+            .with_velocity(velocity)
+            .with_select(select)
+            .with_zone_logic(dot_multisample::ZoneLogic::RoundRobin)
+            .with_group(group_label(f).and_then(|label| {
+                groups
+                    .iter()
+                    .position(|(g, _)| *g == label)
+                    .map(|i| i as isize)
+            }))
+            .with_loop(f.loop_points.as_ref().map(|points| {
+                dot_multisample::Loop::default()
+                    .with_mode(dot_multisample::LoopMode::Loop)
+                    .with_start(points.start as f64)
+                    .with_stop(points.end as f64)
+                    .with_fade(f.loop_crossfade.map(|length| {
+                        (length as f64 / points.len() as f64 * 1000.0).round() / 1000.0
+                    }))
+            }))
+    };
+
+    // each recorded file takes the place of the sample it replaces, and the rest follow
+    let samples: Vec<_> = bundle
+        .iter()
+        .enumerate()
+        .map(
+            |(idx, sample)| match replaced.iter().position(|replaced| *replaced == Some(idx)) {
+                Some(entry) => record(&entries[entry]),
+                None => keep(sample),
+            },
+        )
+        .chain(
+            entries
+                .iter()
+                .zip(&replaced)
+                .filter(|(_, replaced)| replaced.is_none())
+                .map(|(f, _)| record(f)),
+        )
+        .collect();
+
+    let mut multi = dot_multisample::Multisample::default()
+        .with_generator("multirec")
+        .with_groups(groups.iter().map(|(name, color)| {
+            dot_multisample::Group::default()
+                .with_name(name.as_str())
+                .with_color(*color)
+        }))
+        .with_samples(samples);
+
+    if let Some(p) = &file_name_prefix {
+        multi = multi.with_name(p);
+    } else if let Some(name) = appended.as_ref().map(|multi| multi.name()) {
+        multi = multi.with_name(name);
+    }
+
+    // details not given again are kept from the bundle being added to
+    if let Some(metadata) = &metadata {
+        let category = metadata
+            .category
+            .as_deref()
+            .or(appended.as_ref().map(|multi| multi.category()));
+        if let Some(category) = category {
+            multi = multi.with_category(category);
+        }
+
+        let creator = metadata
+            .creator
+            .as_deref()
+            .or(appended.as_ref().map(|multi| multi.creator()));
+        if let Some(creator) = creator {
+            multi = multi.with_creator(creator);
+        }
+
+        let description = metadata
+            .description
+            .as_deref()
+            .or(appended.as_ref().map(|multi| multi.description()));
+        if let Some(description) = description {
+            multi = multi.with_description(description);
+        }
+
+        if !metadata.keywords.is_empty() {
+            multi = multi.with_keywords(metadata.keywords.iter().map(String::as_str));
+        } else if let Some(appended) = &appended {
+            multi = multi.with_keywords(appended.keywords().iter().cloned());
+        }
+    }
+
+    let mut manifest_file = util::Utf8File::xml(output_dir.join("multisample.xml"))?;
+    let mut ser = quick_xml::se::Serializer::new(&mut manifest_file);
+    ser.indent('\t', 1);
+    multi.serialize(ser)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(note: u8, velocity: u8) -> dot_multisample::Sample<'static> {
+        dot_multisample::Sample::default()
+            .with_file(std::path::PathBuf::from(format!("{note}_V{velocity}.wav")))
+            .with_key(dot_multisample::Key::default().with_root(note))
+            .with_velocity(dot_multisample::ZoneInfo::default().with_high(velocity))
+    }
+
+    #[test]
+    fn rerecording_a_narrowed_velocity_range_replaces_its_layers() {
+        // as recorded with `--velocity-layers 2 --velocity-range 60..127`
+        let bundle = dot_multisample::Multisample::default().with_samples([
+            sample(48, 127),
+            sample(48, 93),
+            sample(50, 127),
+            sample(50, 93),
+        ]);
+
+        let plan = util::SamplingPlan::from_multisample(&bundle).unwrap();
+        let config = autosam::Config {
+            notes: 48..=50,
+            note_list: Some(plan.notes),
+            velocity_levels: plan.velocity_layers,
+            velocity_range: plan.velocity_range,
+            round_robins: plan.round_robins,
+            note_layers: plan.note_layers,
+            ..Default::default()
+        };
+        let whole = crate::plan::zones(autosam::Sequencer::new(config.clone(), 48000).unwrap());
+        let selection: ZoneSelection = "C3".parse().unwrap();
+        let config = autosam::Config {
+            zone_list: Some(selection.resolve(&whole).unwrap()),
+            ..config
+        };
+        let recorded = crate::plan::zones(autosam::Sequencer::new(config, 48000).unwrap());
+
+        let mut velocities: Vec<_> = recorded.iter().map(|zone| zone.velocity()).collect();
+        velocities.sort_unstable();
+        assert_eq!(velocities, [93, 127]);
+
+        let mut replaced = replaced_samples(
+            bundle.samples(),
+            recorded.iter().map(|zone| {
+                (
+                    zone.pitch().note_number(),
+                    Some(zone.velocity()),
+                    Some(zone.round_robin()),
+                )
+            }),
+        );
+        replaced.sort_unstable();
+        assert_eq!(replaced, [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn recorded_zones_replace_samples_by_layer_and_round_robin() {
+        let bundle = [
+            sample(60, 127),
+            sample(60, 127),
+            sample(60, 64),
+            sample(60, 64),
+        ];
+
+        let replaced = replaced_samples(
+            &bundle,
+            [
+                (60, Some(64), Some(1)),
+                (60, Some(127), Some(0)),
+                (62, Some(127), Some(0)),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(replaced, [Some(3), Some(0), None]);
+    }
+}
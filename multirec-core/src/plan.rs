@@ -0,0 +1,113 @@
+use std::{path::PathBuf, time::Duration};
+
+use autosam::{
+    midi::{Event, NoteState},
+    AdvanceResult, Sequencer, Zone,
+};
+
+/// What a run is about to do, shown before anything is played
+#[derive(Debug)]
+pub struct Plan {
+    /// Pitch and velocity layer of each zone, in the order they are played
+    pub zones: Vec<(u8, u8)>,
+    /// Number of inputs, each of which records a file for every zone
+    pub inputs: usize,
+    pub duration: Duration,
+    /// Space the recordings take up, if they are saved
+    pub size: Option<u64>,
+    pub output_directory: Option<PathBuf>,
+    /// What each device is used for, and its name
+    pub devices: Vec<(&'static str, String)>,
+}
+
+impl Plan {
+    fn notes(&self) -> usize {
+        let mut notes: Vec<u8> = self.zones.iter().map(|(pitch, _)| *pitch).collect();
+        notes.sort_unstable();
+        notes.dedup();
+        notes.len()
+    }
+
+    fn layers(&self) -> usize {
+        let mut layers: Vec<u8> = self.zones.iter().map(|(_, layer)| *layer).collect();
+        layers.sort_unstable();
+        layers.dedup();
+        layers.len()
+    }
+
+    /// Most takes of any one zone
+    fn round_robins(&self) -> usize {
+        let mut zones = self.zones.clone();
+        zones.sort_unstable();
+        zones
+            .chunk_by(|a, b| a == b)
+            .map(<[_]>::len)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Plan:")?;
+
+        write!(f, "  Files:       {}", self.zones.len() * self.inputs)?;
+        if self.inputs > 1 {
+            write!(
+                f,
+                " ({} from each of {} inputs)",
+                self.zones.len(),
+                self.inputs
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "  Zones:       {} notes, {} velocity layers, {} round robins",
+            self.notes(),
+            self.layers(),
+            self.round_robins()
+        )?;
+        writeln!(
+            f,
+            "  Duration:    about {}",
+            crate::util::clock_time(self.duration)
+        )?;
+        if let Some(size) = self.size {
+            writeln!(f, "  Disk usage:  about {}", crate::util::format_size(size))?;
+        }
+        if let Some(dir) = &self.output_directory {
+            writeln!(f, "  Output:      {}", dir.display())?;
+        }
+
+        for (role, name) in &self.devices {
+            writeln!(f, "  {:<13}{name}", format!("{role}:"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The zones left in a sequence in the order they are played
+pub fn zones(mut seq: Sequencer) -> Vec<Zone> {
+    let mut zones = Vec::new();
+    let mut current = None;
+
+    loop {
+        match seq.advance(usize::MAX) {
+            AdvanceResult::SequenceComplete => return zones,
+            AdvanceResult::Event {
+                event: Event::Note(note),
+                ..
+            } if note.state() == NoteState::On => {
+                // the same test as the audio callback, so the counts agree
+                let zone = seq.zone();
+                if zone.is_some() && zone != current {
+                    current = zone;
+                    zones.extend(zone);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,697 @@
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::{debug, info};
+
+use autosam::midi::{Event, NoteState};
+
+use crate::{
+    input_file::InputFile,
+    runtime::{AudioProcessor, Capture, RunState, TimedEvent},
+    simulate::Simulator,
+};
+
+/// Frames processed by each call into the plugin
+///
+/// Events reach the plugin one block after the frame they were sent at, so
+/// this is also the latency of a plugin that responds instantly.
+pub const BLOCK_SIZE: u32 = 256;
+
+/// Bindings to the parts of the CLAP ABI that a host needs to play and record a plugin
+///
+/// These follow `clap/plugin.h` and friends from CLAP 1.2, and only cover what
+/// [`Plugin`] calls. Function pointers are not optional in CLAP, so a plugin
+/// that leaves one null is not supported.
+#[allow(non_camel_case_types)]
+mod clap {
+    use std::ffi::{c_char, c_void};
+
+    pub const VERSION: clap_version = clap_version {
+        major: 1,
+        minor: 2,
+        revision: 0,
+    };
+
+    pub const PLUGIN_FACTORY_ID: &std::ffi::CStr = c"clap.plugin-factory";
+    pub const EXT_AUDIO_PORTS: &std::ffi::CStr = c"clap.audio-ports";
+
+    pub const CORE_EVENT_SPACE_ID: u16 = 0;
+    pub const EVENT_NOTE_ON: u16 = 0;
+    pub const EVENT_NOTE_OFF: u16 = 1;
+    pub const EVENT_MIDI: u16 = 10;
+
+    pub const PROCESS_ERROR: i32 = 0;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct clap_version {
+        pub major: u32,
+        pub minor: u32,
+        pub revision: u32,
+    }
+
+    #[repr(C)]
+    pub struct clap_plugin_entry {
+        pub clap_version: clap_version,
+        pub init: unsafe extern "C" fn(plugin_path: *const c_char) -> bool,
+        pub deinit: unsafe extern "C" fn(),
+        pub get_factory: unsafe extern "C" fn(factory_id: *const c_char) -> *const c_void,
+    }
+
+    #[repr(C)]
+    pub struct clap_plugin_factory {
+        pub get_plugin_count: unsafe extern "C" fn(factory: *const clap_plugin_factory) -> u32,
+        pub get_plugin_descriptor: unsafe extern "C" fn(
+            factory: *const clap_plugin_factory,
+            index: u32,
+        ) -> *const clap_plugin_descriptor,
+        pub create_plugin: unsafe extern "C" fn(
+            factory: *const clap_plugin_factory,
+            host: *const clap_host,
+            plugin_id: *const c_char,
+        ) -> *const clap_plugin,
+    }
+
+    #[repr(C)]
+    pub struct clap_plugin_descriptor {
+        pub clap_version: clap_version,
+        pub id: *const c_char,
+        pub name: *const c_char,
+        pub vendor: *const c_char,
+        pub url: *const c_char,
+        pub manual_url: *const c_char,
+        pub support_url: *const c_char,
+        pub version: *const c_char,
+        pub description: *const c_char,
+        pub features: *const *const c_char,
+    }
+
+    #[repr(C)]
+    pub struct clap_host {
+        pub clap_version: clap_version,
+        pub host_data: *mut c_void,
+        pub name: *const c_char,
+        pub vendor: *const c_char,
+        pub url: *const c_char,
+        pub version: *const c_char,
+        pub get_extension: unsafe extern "C" fn(
+            host: *const clap_host,
+            extension_id: *const c_char,
+        ) -> *const c_void,
+        pub request_restart: unsafe extern "C" fn(host: *const clap_host),
+        pub request_process: unsafe extern "C" fn(host: *const clap_host),
+        pub request_callback: unsafe extern "C" fn(host: *const clap_host),
+    }
+
+    #[repr(C)]
+    pub struct clap_plugin {
+        pub desc: *const clap_plugin_descriptor,
+        pub plugin_data: *mut c_void,
+        pub init: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+        pub destroy: unsafe extern "C" fn(plugin: *const clap_plugin),
+        pub activate: unsafe extern "C" fn(
+            plugin: *const clap_plugin,
+            sample_rate: f64,
+            min_frames_count: u32,
+            max_frames_count: u32,
+        ) -> bool,
+        pub deactivate: unsafe extern "C" fn(plugin: *const clap_plugin),
+        pub start_processing: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+        pub stop_processing: unsafe extern "C" fn(plugin: *const clap_plugin),
+        pub reset: unsafe extern "C" fn(plugin: *const clap_plugin),
+        pub process:
+            unsafe extern "C" fn(plugin: *const clap_plugin, process: *const clap_process) -> i32,
+        pub get_extension:
+            unsafe extern "C" fn(plugin: *const clap_plugin, id: *const c_char) -> *const c_void,
+        pub on_main_thread: unsafe extern "C" fn(plugin: *const clap_plugin),
+    }
+
+    #[repr(C)]
+    pub struct clap_plugin_audio_ports {
+        pub count: unsafe extern "C" fn(plugin: *const clap_plugin, is_input: bool) -> u32,
+        pub get: unsafe extern "C" fn(
+            plugin: *const clap_plugin,
+            index: u32,
+            is_input: bool,
+            info: *mut clap_audio_port_info,
+        ) -> bool,
+    }
+
+    #[repr(C)]
+    pub struct clap_audio_port_info {
+        pub id: u32,
+        pub name: [c_char; 256],
+        pub flags: u32,
+        pub channel_count: u32,
+        pub port_type: *const c_char,
+        pub in_place_pair: u32,
+    }
+
+    #[repr(C)]
+    pub struct clap_process {
+        pub steady_time: i64,
+        pub frames_count: u32,
+        pub transport: *const c_void,
+        pub audio_inputs: *const clap_audio_buffer,
+        pub audio_outputs: *mut clap_audio_buffer,
+        pub audio_inputs_count: u32,
+        pub audio_outputs_count: u32,
+        pub in_events: *const clap_input_events,
+        pub out_events: *const clap_output_events,
+    }
+
+    #[repr(C)]
+    pub struct clap_audio_buffer {
+        pub data32: *mut *mut f32,
+        pub data64: *mut *mut f64,
+        pub channel_count: u32,
+        pub latency: u32,
+        pub constant_mask: u64,
+    }
+
+    #[repr(C)]
+    pub struct clap_input_events {
+        pub ctx: *mut c_void,
+        pub size: unsafe extern "C" fn(list: *const clap_input_events) -> u32,
+        pub get: unsafe extern "C" fn(
+            list: *const clap_input_events,
+            index: u32,
+        ) -> *const clap_event_header,
+    }
+
+    #[repr(C)]
+    pub struct clap_output_events {
+        pub ctx: *mut c_void,
+        pub try_push: unsafe extern "C" fn(
+            list: *const clap_output_events,
+            event: *const clap_event_header,
+        ) -> bool,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct clap_event_header {
+        pub size: u32,
+        pub time: u32,
+        pub space_id: u16,
+        pub r#type: u16,
+        pub flags: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct clap_event_note {
+        pub header: clap_event_header,
+        pub note_id: i32,
+        pub port_index: i16,
+        pub channel: i16,
+        pub key: i16,
+        pub velocity: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct clap_event_midi {
+        pub header: clap_event_header,
+        pub port_index: u16,
+        pub data: [u8; 3],
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("`{}` is not a CLAP plugin: {1}", .0.display())]
+    NotClap(PathBuf, libloading::Error),
+    #[error("`{}` could not be initialized", .0.display())]
+    Init(PathBuf),
+    #[error("`{}` contains no plugins", .0.display())]
+    Empty(PathBuf),
+    #[error("Plugin `{0}` could not be created")]
+    Create(String),
+    #[error("Plugin `{0}` could not be activated at {1} Hz")]
+    Activate(String, u32),
+    #[error("Plugin `{0}` has no audio outputs")]
+    NoOutputs(String),
+    #[error("Plugin `{0}` could not start processing")]
+    StartProcessing(String),
+    #[error("Plugin `{0}` failed while processing audio")]
+    Process(String),
+}
+
+/// One event queued for the next block, as either of the layouts the plugin reads
+#[repr(C)]
+#[derive(Clone, Copy)]
+union QueuedEvent {
+    header: clap::clap_event_header,
+    note: clap::clap_event_note,
+    midi: clap::clap_event_midi,
+}
+
+impl QueuedEvent {
+    fn new(event: &Event, time: u32) -> Self {
+        let header = |r#type, size: usize| clap::clap_event_header {
+            size: size as u32,
+            time,
+            space_id: clap::CORE_EVENT_SPACE_ID,
+            r#type,
+            flags: 0,
+        };
+
+        // notes are sent as note events, which every instrument understands
+        if let Some(note) = event.note() {
+            let (r#type, velocity) = match note.state() {
+                NoteState::On => (clap::EVENT_NOTE_ON, f64::from(note.velocity()) / 127.0),
+                NoteState::Off => (clap::EVENT_NOTE_OFF, 0.0),
+            };
+            return Self {
+                note: clap::clap_event_note {
+                    header: header(r#type, std::mem::size_of::<clap::clap_event_note>()),
+                    note_id: -1,
+                    port_index: 0,
+                    channel: i16::from(note.channel().number()),
+                    key: i16::from(note.pitch().note_number()),
+                    velocity,
+                },
+            };
+        }
+
+        let message = event.as_midi_message();
+        let mut data = [0; 3];
+        for (byte, value) in data.iter_mut().zip(message.iter()) {
+            *byte = *value;
+        }
+
+        Self {
+            midi: clap::clap_event_midi {
+                header: header(
+                    clap::EVENT_MIDI,
+                    std::mem::size_of::<clap::clap_event_midi>(),
+                ),
+                port_index: 0,
+                data,
+            },
+        }
+    }
+}
+
+unsafe extern "C" fn events_size(list: *const clap::clap_input_events) -> u32 {
+    // SAFETY: `render` points `ctx` at its queue, which outlives the call to `process`
+    let events = &*((*list).ctx as *const Vec<QueuedEvent>);
+    events.len() as u32
+}
+
+unsafe extern "C" fn events_get(
+    list: *const clap::clap_input_events,
+    index: u32,
+) -> *const clap::clap_event_header {
+    // SAFETY: as in `events_size`
+    let events = &*((*list).ctx as *const Vec<QueuedEvent>);
+    events
+        .get(index as usize)
+        // every variant starts with the header, so it can be read from any of them
+        .map_or(std::ptr::null(), |event| &event.header)
+}
+
+unsafe extern "C" fn events_push(
+    _list: *const clap::clap_output_events,
+    _event: *const clap::clap_event_header,
+) -> bool {
+    // nothing the plugin sends back is needed
+    true
+}
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap::clap_host,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    std::ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap::clap_host) {}
+
+/// A CLAP instrument plugin, loaded and activated, to play the sequence into
+pub struct Plugin {
+    name: String,
+    plugin: *const clap::clap_plugin,
+    entry: *const clap::clap_plugin_entry,
+    /// Must outlive the plugin, which keeps a pointer to it
+    _host: Box<clap::clap_host>,
+    /// Channels of each audio input, which are fed silence
+    inputs: Vec<u32>,
+    /// Channels of each audio output, the first of which is recorded
+    outputs: Vec<u32>,
+    sample_rate: u32,
+    active: bool,
+    /// Must outlive everything above, which points into it
+    _library: libloading::Library,
+}
+
+// SAFETY: CLAP lets a plugin be processed on a different thread than it was created on
+unsafe impl Send for Plugin {}
+
+/// Find the binary inside a plugin, which may be a macOS bundle
+fn binary_path(path: &Path) -> PathBuf {
+    match path.file_stem() {
+        Some(stem) if path.is_dir() => path.join("Contents").join("MacOS").join(stem),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl Plugin {
+    /// Load the first plugin in a `.clap` file, and activate it at a sample rate
+    pub fn load(path: &Path, sample_rate: u32) -> anyhow::Result<Self> {
+        // SAFETY: loading runs the library's initializers, which a plugin the user
+        // chose to run is trusted with
+        let library = unsafe { libloading::Library::new(binary_path(path)) }
+            .map_err(|e| PluginError::NotClap(path.to_path_buf(), e))?;
+
+        // SAFETY: `clap_entry` is declared by the CLAP ABI as a `clap_plugin_entry`
+        let entry = unsafe {
+            *library
+                .get::<*const clap::clap_plugin_entry>(b"clap_entry\0")
+                .map_err(|e| PluginError::NotClap(path.to_path_buf(), e))?
+        };
+
+        let path_name = CString::new(path.to_string_lossy().as_bytes())?;
+        if entry.is_null() {
+            return Err(PluginError::Init(path.to_path_buf()).into());
+        }
+        // SAFETY: `entry` is not null, and points into the library, which outlives it
+        if !unsafe { ((*entry).init)(path_name.as_ptr()) } {
+            return Err(PluginError::Init(path.to_path_buf()).into());
+        }
+
+        let host = Box::new(clap::clap_host {
+            clap_version: clap::VERSION,
+            host_data: std::ptr::null_mut(),
+            name: c"multirec".as_ptr(),
+            vendor: c"".as_ptr(),
+            url: c"".as_ptr(),
+            version: c"0.2.0".as_ptr(),
+            get_extension: host_get_extension,
+            request_restart: host_request,
+            request_process: host_request,
+            request_callback: host_request,
+        });
+
+        // SAFETY: `entry` was initialized above, and the factory is checked for null
+        let factory = unsafe { ((*entry).get_factory)(clap::PLUGIN_FACTORY_ID.as_ptr()) }
+            as *const clap::clap_plugin_factory;
+        if factory.is_null() || unsafe { ((*factory).get_plugin_count)(factory) } == 0 {
+            unsafe { ((*entry).deinit)() };
+            return Err(PluginError::Empty(path.to_path_buf()).into());
+        }
+
+        // SAFETY: the factory has at least one plugin, and what it describes
+        // is checked for null before being read
+        let descriptor = unsafe { ((*factory).get_plugin_descriptor)(factory, 0) };
+        let (id, name) = match unsafe { descriptor.as_ref() } {
+            Some(descriptor) if !descriptor.id.is_null() && !descriptor.name.is_null() => (
+                descriptor.id,
+                unsafe { CStr::from_ptr(descriptor.name) }
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            _ => {
+                unsafe { ((*entry).deinit)() };
+                return Err(PluginError::Create(path.display().to_string()).into());
+            }
+        };
+
+        // SAFETY: `host` is boxed and kept alongside the plugin, which may hold on to it
+        let plugin = unsafe { ((*factory).create_plugin)(factory, &*host, id) };
+        if plugin.is_null() || !unsafe { ((*plugin).init)(plugin) } {
+            if !plugin.is_null() {
+                // SAFETY: a plugin that failed to initialize must still be destroyed
+                unsafe { ((*plugin).destroy)(plugin) };
+            }
+            // SAFETY: nothing from the library is used after this
+            unsafe { ((*entry).deinit)() };
+            return Err(PluginError::Create(name).into());
+        }
+
+        let mut this = Self {
+            name,
+            plugin,
+            entry,
+            _host: host,
+            inputs: Vec::new(),
+            outputs: vec![2],
+            sample_rate,
+            active: false,
+            _library: library,
+        };
+
+        // without the audio ports extension, assume a single stereo output
+        // SAFETY: `plugin` was created and initialized above, and the extension is
+        // checked for null
+        let ports = unsafe { ((*plugin).get_extension)(plugin, clap::EXT_AUDIO_PORTS.as_ptr()) }
+            as *const clap::clap_plugin_audio_ports;
+        if !ports.is_null() {
+            let channels = |is_input| {
+                // SAFETY: `ports` is not null, and `info` is plain data the plugin fills in
+                let count = unsafe { ((*ports).count)(plugin, is_input) };
+                (0..count)
+                    .map(|index| {
+                        let mut info: clap::clap_audio_port_info = unsafe { std::mem::zeroed() };
+                        if unsafe { ((*ports).get)(plugin, index, is_input, &mut info) } {
+                            info.channel_count
+                        } else {
+                            0
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            };
+            this.inputs = channels(true);
+            this.outputs = channels(false);
+        }
+
+        if this.outputs.first().map_or(true, |channels| *channels == 0) {
+            return Err(PluginError::NoOutputs(this.name.clone()).into());
+        }
+
+        // SAFETY: the plugin is initialized, and is deactivated on drop
+        if !unsafe { ((*plugin).activate)(plugin, f64::from(sample_rate), 1, BLOCK_SIZE) } {
+            return Err(PluginError::Activate(this.name.clone(), sample_rate).into());
+        }
+        this.active = true;
+        debug!(
+            "Activated {} with inputs {:?} and outputs {:?}",
+            this.name, this.inputs, this.outputs
+        );
+
+        Ok(this)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Channels of the output that is recorded
+    pub fn channels(&self) -> u16 {
+        self.outputs[0] as u16
+    }
+
+    /// The stream that the plugin's output takes the place of
+    pub fn stream_config(&self) -> cpal::StreamConfig {
+        cpal::StreamConfig {
+            channels: self.channels(),
+            sample_rate: cpal::SampleRate(self.sample_rate),
+            buffer_size: cpal::BufferSize::Fixed(BLOCK_SIZE),
+        }
+    }
+
+    /// Play the sequence into the plugin, and record its output, until the sequence is done
+    ///
+    /// `setup` is sent before anything else, and the plugin's main output is
+    /// recorded. Unless `offline`, blocks are processed no faster than they would
+    /// play, so that the run can be followed as it happens. Offline, each block
+    /// waits only for the writer to make room for it.
+    pub fn render(
+        &mut self,
+        processor: &mut AudioProcessor<f32>,
+        mut events: rtrb::Consumer<TimedEvent>,
+        setup: &[Event],
+        state: &RunState,
+        offline: bool,
+    ) -> anyhow::Result<()> {
+        let plugin = self.plugin;
+        // SAFETY: the plugin was activated in `load`, and stops processing below
+        if !unsafe { ((*plugin).start_processing)(plugin) } {
+            return Err(PluginError::StartProcessing(self.name.clone()).into());
+        }
+
+        let block = BLOCK_SIZE as usize;
+        let mut input_data: Vec<Vec<Vec<f32>>> = self
+            .inputs
+            .iter()
+            .map(|channels| vec![vec![0.0; block]; *channels as usize])
+            .collect();
+        let mut output_data: Vec<Vec<Vec<f32>>> = self
+            .outputs
+            .iter()
+            .map(|channels| vec![vec![0.0; block]; *channels as usize])
+            .collect();
+        let mut input_pointers: Vec<Vec<*mut f32>> = input_data
+            .iter_mut()
+            .map(|port| port.iter_mut().map(|c| c.as_mut_ptr()).collect())
+            .collect();
+        let mut output_pointers: Vec<Vec<*mut f32>> = output_data
+            .iter_mut()
+            .map(|port| port.iter_mut().map(|c| c.as_mut_ptr()).collect())
+            .collect();
+        let buffer = |pointers: &mut Vec<*mut f32>| clap::clap_audio_buffer {
+            data32: pointers.as_mut_ptr(),
+            data64: std::ptr::null_mut(),
+            channel_count: pointers.len() as u32,
+            latency: 0,
+            constant_mask: 0,
+        };
+        let inputs: Vec<_> = input_pointers.iter_mut().map(buffer).collect();
+        let mut outputs: Vec<_> = output_pointers.iter_mut().map(buffer).collect();
+
+        let mut queue: Vec<QueuedEvent> = setup.iter().map(|e| QueuedEvent::new(e, 0)).collect();
+        let mut next_queue = Vec::new();
+        let out_events = clap::clap_output_events {
+            ctx: std::ptr::null_mut(),
+            try_push: events_push,
+        };
+
+        let block_time = Duration::from_secs(u64::from(BLOCK_SIZE)) / self.sample_rate;
+        let started = Instant::now();
+        let mut frame = vec![0.0; output_data[0].len()];
+        let mut steady_time = 0;
+        let result = loop {
+            if state.done() {
+                break Ok(());
+            }
+
+            // a block's samples and a marker for each zone that could start in it
+            while offline && !processor.writer.has_room(block * frame.len() + block) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            let in_events = clap::clap_input_events {
+                ctx: &mut queue as *mut Vec<QueuedEvent> as *mut c_void,
+                size: events_size,
+                get: events_get,
+            };
+            let process = clap::clap_process {
+                steady_time,
+                frames_count: BLOCK_SIZE,
+                transport: std::ptr::null(),
+                audio_inputs: inputs.as_ptr(),
+                audio_outputs: outputs.as_mut_ptr(),
+                audio_inputs_count: inputs.len() as u32,
+                audio_outputs_count: outputs.len() as u32,
+                in_events: &in_events,
+                out_events: &out_events,
+            };
+            // SAFETY: every buffer and event list in `process` outlives the call
+            if unsafe { ((*plugin).process)(plugin, &process) } == clap::PROCESS_ERROR {
+                // the processor winds the run down once it sees that it was aborted
+                state.abort();
+                processor.write_input_data::<f32>(&[]);
+                break Err(PluginError::Process(self.name.clone()).into());
+            }
+            queue.clear();
+
+            // events sent at each frame reach the plugin at the same frame of the next block
+            for idx in 0..block {
+                for (sample, channel) in frame.iter_mut().zip(&output_data[0]) {
+                    *sample = channel[idx];
+                }
+                processor.write_input_data::<f32>(&frame);
+
+                while let Ok(TimedEvent { event, .. }) = events.pop() {
+                    next_queue.push(QueuedEvent::new(&event, idx as u32));
+                }
+            }
+            std::mem::swap(&mut queue, &mut next_queue);
+
+            steady_time += i64::from(BLOCK_SIZE);
+            let due = block_time * (steady_time / i64::from(BLOCK_SIZE)) as u32;
+            if let Some(wait) = due.checked_sub(started.elapsed()).filter(|_| !offline) {
+                std::thread::sleep(wait);
+            }
+        };
+
+        // SAFETY: processing was started above
+        unsafe { ((*plugin).stop_processing)(plugin) };
+        info!(
+            "Rendered {:?} of audio from {} in {:?}",
+            Duration::from_secs_f64(steady_time as f64 / f64::from(self.sample_rate)),
+            self.name,
+            started.elapsed()
+        );
+
+        result
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        // SAFETY: the plugin is torn down in the reverse order of `load`, before
+        // the library is unloaded
+        unsafe {
+            if self.active {
+                ((*self.plugin).deactivate)(self.plugin);
+            }
+            ((*self.plugin).destroy)(self.plugin);
+            ((*self.entry).deinit)();
+        }
+    }
+}
+
+/// What plays the sequence in place of a MIDI port and audio input
+pub enum Instrument {
+    Plugin(Plugin),
+    Simulator(Simulator),
+    File(InputFile),
+}
+
+impl Instrument {
+    /// What the instrument is, for the plan of a run
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Plugin(_) => "Plugin",
+            Self::Simulator(_) => "Simulation",
+            Self::File(_) => "Input file",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Plugin(plugin) => plugin.name(),
+            Self::Simulator(simulator) => simulator.name(),
+            Self::File(file) => file.name(),
+        }
+    }
+
+    pub fn stream_config(&self) -> cpal::StreamConfig {
+        match self {
+            Self::Plugin(plugin) => plugin.stream_config(),
+            Self::Simulator(simulator) => simulator.stream_config(),
+            Self::File(file) => file.stream_config(),
+        }
+    }
+
+    /// Play the sequence and record what the instrument makes of it, until the sequence is done
+    pub fn render(
+        &mut self,
+        processor: &mut AudioProcessor<f32>,
+        events: rtrb::Consumer<TimedEvent>,
+        setup: &[Event],
+        state: &RunState,
+        offline: bool,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Plugin(plugin) => plugin.render(processor, events, setup, state, offline),
+            Self::Simulator(simulator) => simulator.render(processor, events, state, offline),
+            Self::File(file) => file.render(processor, events, state),
+        }
+    }
+}
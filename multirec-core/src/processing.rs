@@ -0,0 +1,535 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use log::{debug, info, warn};
+
+use autosam::midi::Pitch;
+
+use crate::{
+    analysis, analysis::StereoContent, arguments::*, capture::Captured, chunks, report,
+    settings::Settings, util, util::NamedFile, RunState,
+};
+
+/// How the files of a run are processed once they are recorded
+pub struct Processing<'a> {
+    settings: &'a Settings,
+    /// Frames of latency, at the rate the files were recorded at
+    latency: usize,
+    /// Sample rate that the files were recorded at
+    recorded_rate: u32,
+    latency_compensation: LatencyCompensation,
+    /// How far edit points may move to reach a zero crossing, in frames
+    zero_snap: Option<usize>,
+    /// The noise floor recorded before the run, and the strength to remove it at
+    denoise: Option<(PathBuf, f64)>,
+    slipped: &'a [String],
+    state: &'a RunState,
+    /// Number of inputs recorded, each into a directory of its own
+    inputs: usize,
+    recorded: SystemTime,
+}
+
+impl<'a> Processing<'a> {
+    /// Look over what was recorded, warning about anything that went wrong
+    pub fn new(
+        settings: &'a Settings,
+        captured: &'a Captured<'_>,
+        state: &'a RunState,
+        latency: usize,
+        recorded_rate: u32,
+        mic_dirs: &[PathBuf],
+        denoise: Option<(PathBuf, f64)>,
+    ) -> anyhow::Result<Self> {
+        let Settings {
+            retries,
+            trim_start,
+            latency_compensation,
+            zero_snap,
+            ..
+        } = *settings;
+        let output_format = &settings.output_format;
+
+        let dropped = state.dropped_samples();
+        if dropped > 0 {
+            warn!("{dropped} samples were lost because writing fell behind");
+        }
+
+        let slipped = &captured.slipped;
+        if !slipped.is_empty() {
+            warn!(
+                "Audio timing slipped while recording {}, consider recording them again",
+                slipped.join(", ")
+            );
+        }
+
+        if let Some((threshold, _, _)) = retries {
+            let retries = state.retries();
+            if retries > 0 {
+                info!("Recorded {retries} zones again, as they were not recorded properly the first time");
+            }
+
+            let mut silent = Vec::new();
+            for entry in &captured.entries {
+                if analysis::peak_level(mic_dirs[0].join(entry.to_string()))? <= threshold {
+                    silent.push(entry.to_string());
+                }
+            }
+
+            if !silent.is_empty() {
+                warn!(
+                    "Nothing was heard in {} files: {}",
+                    silent.len(),
+                    silent.join(", ")
+                );
+            }
+        }
+
+        let latency_compensation = match latency_compensation {
+            LatencyCompensation::Off => LatencyCompensation::Off,
+            _ if latency == 0 => {
+                warn!("No latency was measured to compensate for");
+                LatencyCompensation::Off
+            }
+            _ if trim_start.is_some() => {
+                warn!("Not compensating for latency, as the silence before each note was trimmed");
+                LatencyCompensation::Off
+            }
+            LatencyCompensation::Offset
+                if matches!(output_format, OutputFormat::Raw | OutputFormat::Zip) =>
+            {
+                warn!("Sample offsets can only be written to SFZ and Bitwig output");
+                LatencyCompensation::Off
+            }
+            compensation => compensation,
+        };
+
+        let zero_snap = zero_snap.map(|distance| util::frames(distance, recorded_rate));
+
+        if denoise.is_some() && mic_dirs.len() > 1 {
+            warn!("The noise profile is only removed from the first input");
+        }
+
+        Ok(Processing {
+            settings,
+            latency,
+            recorded_rate,
+            latency_compensation,
+            zero_snap,
+            denoise,
+            slipped,
+            state,
+            inputs: mic_dirs.len(),
+            recorded: captured.recorded,
+        })
+    }
+
+    /// Sample rate of the files once they are processed
+    ///
+    /// Files are converted to the target rate before the steps that count
+    /// their frames.
+    pub fn sample_rate(&self) -> u32 {
+        self.settings.target_rate.unwrap_or(self.recorded_rate)
+    }
+
+    /// Convert a number of frames at the recorded rate to the processed one
+    pub fn rescale(&self, frames: usize) -> usize {
+        (frames as f64 * f64::from(self.sample_rate()) / f64::from(self.recorded_rate)).round()
+            as usize
+    }
+
+    /// Where playback of each file starts, past the latency if it is compensated for that way
+    pub fn sample_start(&self) -> usize {
+        match self.latency_compensation {
+            LatencyCompensation::Offset => self.rescale(self.latency),
+            _ => 0,
+        }
+    }
+
+    /// Process the files of one input, in its own directory
+    ///
+    /// Each input gets the same processing, and its entries are returned with
+    /// what was found out about them to write to its manifest.
+    pub fn process<'s>(
+        &self,
+        mic: usize,
+        output_dir: &Path,
+        channels: u16,
+        mut entries: Vec<NamedFile<'s, &'s String>>,
+    ) -> anyhow::Result<Vec<NamedFile<'s, &'s String>>> {
+        let Processing {
+            settings,
+            latency,
+            recorded_rate,
+            latency_compensation,
+            zero_snap,
+            slipped,
+            state,
+            inputs,
+            recorded,
+            ..
+        } = *self;
+        let denoise = &self.denoise;
+        let Settings {
+            trim_start,
+            trim_end,
+            mono,
+            normalize,
+            target_rate,
+            pitch_detection,
+            loop_search,
+            render_crossfades,
+            tail,
+            ..
+        } = *settings;
+        let Settings {
+            output_format,
+            post_command,
+            tuning,
+            metadata,
+            file_name_prefix,
+            report_paths,
+            ..
+        } = settings;
+        let sample_rate = self.sample_rate();
+
+        if latency_compensation == LatencyCompensation::Trim {
+            for entry in &entries {
+                debug!("Removing {latency} frames of latency from {entry}");
+                analysis::drop_start(output_dir.join(entry.to_string()), latency)?;
+            }
+        }
+
+        if let (Some((threshold, _)), Some(distance)) = (trim_start, zero_snap) {
+            // start on a rising crossing just before the sound, within the guard
+            for entry in &entries {
+                let path = output_dir.join(entry.to_string());
+                let samples = analysis::read_mono(&path)?;
+                let onset = analysis::peak_levels(&path)?
+                    .iter()
+                    .position(|level| *level > threshold)
+                    .unwrap_or(0);
+                let region = onset.saturating_sub(distance)..onset + 1;
+
+                if let Some(start) = analysis::zero_crossing(&samples, onset, region, Some(true)) {
+                    debug!("Moving the start of {entry} to frame {start}");
+                    analysis::drop_start(&path, start)?;
+                }
+            }
+        }
+
+        if let Some((path, strength)) = denoise.as_ref().filter(|_| mic == 0) {
+            let profile = analysis::noise_profile(path)?;
+
+            for entry in &entries {
+                debug!("Removing the noise floor from {entry}");
+                analysis::denoise(output_dir.join(entry.to_string()), &profile, *strength)?;
+            }
+        }
+
+        if let Some((threshold, hold)) = trim_end {
+            let hold = util::frames(hold, recorded_rate);
+
+            for entry in &entries {
+                let path = output_dir.join(entry.to_string());
+                let mut end = analysis::decay_end(&analysis::peak_levels(&path)?, threshold, hold);
+                if let Some(distance) = zero_snap {
+                    let samples = analysis::read_mono(&path)?;
+                    let region = end.saturating_sub(distance)..end + distance;
+                    if let Some(last) = analysis::zero_crossing(&samples, end, region, None) {
+                        end = last + 1;
+                    }
+                }
+                debug!("Trimming {entry} to {end} frames");
+                analysis::truncate(&path, end)?;
+            }
+        }
+
+        if let Some((mode, threshold)) = mono {
+            let contents = entries
+                .iter()
+                .map(|entry| {
+                    analysis::stereo_content(output_dir.join(entry.to_string()), threshold)
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let is_mono = contents
+                .iter()
+                .all(|content| *content != Some(StereoContent::Stereo));
+
+            if mode == MonoMode::On || is_mono {
+                for (entry, content) in entries.iter().zip(contents) {
+                    if let Some(content) = content {
+                        debug!("Writing {entry} as mono ({content:?})");
+                        analysis::to_mono(output_dir.join(entry.to_string()), content)?;
+                    }
+                }
+            } else {
+                info!("Keeping stereo files, as the channels differ");
+            }
+        }
+
+        if let Some((mode, target, linked, in_manifest)) = normalize {
+            let in_manifest = in_manifest
+                && match output_format {
+                    OutputFormat::Sfz | OutputFormat::Bitwig => true,
+                    OutputFormat::Raw | OutputFormat::Zip => {
+                        warn!("Raw and zip output have no manifest, so the recordings are normalized instead");
+                        false
+                    }
+                };
+
+            let levels = entries
+                .iter()
+                .map(|entry| {
+                    let path = output_dir.join(entry.to_string());
+                    match mode {
+                        Normalize::Peak => analysis::peak_level(path),
+                        Normalize::Rms => analysis::rms_level(path),
+                        Normalize::Off => Ok(target),
+                    }
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let gains: Vec<_> = entries
+                .iter()
+                .zip(&levels)
+                .map(|(entry, level)| {
+                    // the loudest of the linked samples decides their gain
+                    let level = if linked {
+                        entries
+                            .iter()
+                            .zip(&levels)
+                            .filter(|(other, _)| other.pitch == entry.pitch)
+                            .fold(0.0, |loudest: f32, (_, level)| loudest.max(*level))
+                    } else {
+                        *level
+                    };
+
+                    (level > 0.0).then(|| target / level)
+                })
+                .collect();
+
+            for (entry, gain) in entries.iter_mut().zip(gains) {
+                let Some(gain) = gain else {
+                    continue;
+                };
+
+                if in_manifest {
+                    let gain = 20.0 * gain.log10();
+                    debug!("Setting the gain of {entry} to {gain:.2}dB");
+                    entry.gain = Some(gain);
+                } else {
+                    debug!("Normalizing {entry} by {gain:.2}x");
+                    analysis::apply_gain(output_dir.join(entry.to_string()), gain)?;
+                }
+            }
+        }
+
+        if let Some(rate) = target_rate {
+            for entry in &entries {
+                debug!("Resampling {entry} to {rate}Hz");
+                analysis::resample(output_dir.join(entry.to_string()), rate)?;
+            }
+        }
+
+        if let Some((command, jobs)) = &post_command {
+            let paths: Vec<_> = entries
+                .iter()
+                .map(|entry| output_dir.join(entry.to_string()))
+                .collect();
+            let failures = command.run_all(&paths, *jobs);
+
+            if !failures.is_empty() {
+                warn!(
+                    "The post-processing command failed on {} files, which were left as recorded",
+                    failures.len()
+                );
+            }
+        }
+
+        // the middle of the sustain, clear of the attack and release
+        let sustain_region = |sustain: Duration| {
+            let sustain = util::frames(sustain, sample_rate);
+            let start = if trim_start.is_some() || latency_compensation == LatencyCompensation::Trim
+            {
+                0
+            } else {
+                self.rescale(latency)
+            };
+            start + sustain / 4..start + sustain * 9 / 10
+        };
+
+        if let Some(sustain) = pitch_detection {
+            let region = sustain_region(sustain);
+
+            for entry in &mut entries {
+                let samples = analysis::read_mono(output_dir.join(entry.to_string()))?;
+                let Some(frequency) = analysis::detect_pitch(&samples, region.clone(), sample_rate)
+                else {
+                    warn!("Could not measure the pitch of {entry}");
+                    continue;
+                };
+
+                let note = entry.pitch.note_number();
+                let offset = tuning
+                    .as_ref()
+                    .map_or(0.0, |(tuning, root, _)| tuning.cents(note, *root) as f32);
+                let expected = 440.0 * 2f32.powf((f32::from(note) - 69.0 + offset / 100.0) / 12.0);
+                let cents = 1200.0 * (frequency / expected).log2();
+
+                // more than half a semitone out is a different note, not a tuning error
+                if cents.abs() > 50.0 {
+                    let heard = (69.0 + 12.0 * (frequency / 440.0).log2()).round();
+                    match Pitch::new(heard.clamp(0.0, 127.0) as u8) {
+                        Ok(heard) => warn!(
+                            "{entry} sounds like {heard} ({frequency:.1}Hz), not {}",
+                            entry.pitch
+                        ),
+                        Err(_) => {
+                            warn!("{entry} sounds at {frequency:.1}Hz, not {}", entry.pitch)
+                        }
+                    }
+                    continue;
+                }
+
+                debug!("{entry} is {cents:+.1} cents from {}", entry.pitch);
+                entry.tune = Some(cents);
+            }
+        }
+
+        if let Some(sustain) = loop_search {
+            let region = sustain_region(sustain);
+
+            for entry in &mut entries {
+                let samples = analysis::read_mono(output_dir.join(entry.to_string()))?;
+                entry.loop_points = analysis::find_loop(&samples, region.clone());
+
+                // both points are rising crossings, so keep the frame on whichever side is quieter
+                if zero_snap.is_some() {
+                    let snap = |frame: usize| {
+                        analysis::zero_crossing(&samples, frame, frame..frame + 1, Some(true))
+                            .unwrap_or(frame)
+                    };
+                    entry.loop_points = entry
+                        .loop_points
+                        .take()
+                        .map(|points| snap(points.start)..snap(points.end));
+                }
+
+                match &entry.loop_points {
+                    Some(points) => debug!("Loop points for {entry}: {points:?}"),
+                    None => warn!("Could not find loop points for {entry}"),
+                }
+
+                let Some(points) = &entry.loop_points else {
+                    continue;
+                };
+                let Some(length) = analysis::crossfade_length(&samples, points) else {
+                    debug!("No room to crossfade the loop in {entry}");
+                    continue;
+                };
+
+                if render_crossfades {
+                    debug!("Crossfading the loop in {entry} over {length} frames");
+                    analysis::render_crossfade(
+                        output_dir.join(entry.to_string()),
+                        points.clone(),
+                        length,
+                    )?;
+                } else {
+                    entry.loop_crossfade = Some(length);
+                }
+            }
+        }
+
+        // the whole file is played, tail included, once the key is released
+        if tail.is_some() {
+            for entry in &mut entries {
+                let frames = hound::WavReader::open(output_dir.join(entry.to_string()))?.duration();
+                entry.sample_stop = Some(frames as usize);
+            }
+        }
+
+        // after all processing, as rewriting the audio drops any extra chunks
+        for entry in &entries {
+            let path = output_dir.join(entry.to_string());
+            let spec = hound::WavReader::open(&path)?.spec();
+            let played = entry.describe();
+            chunks::add_chunk(&path, *b"bext", &chunks::bext(&played, recorded, spec))?;
+
+            let keywords = metadata
+                .as_ref()
+                .map(|metadata| metadata.keywords.join("; "));
+            let tags = [
+                (
+                    *b"ISFT",
+                    Some(concat!("multirec ", env!("CARGO_PKG_VERSION"))),
+                ),
+                (*b"INAM", file_name_prefix.as_deref()),
+                (
+                    *b"IART",
+                    metadata.as_ref().and_then(|m| m.creator.as_deref()),
+                ),
+                (
+                    *b"IGNR",
+                    metadata.as_ref().and_then(|m| m.category.as_deref()),
+                ),
+                (
+                    *b"ISBJ",
+                    metadata.as_ref().and_then(|m| m.description.as_deref()),
+                ),
+                (*b"IKEY", keywords.as_deref().filter(|k| !k.is_empty())),
+                (*b"ICMT", Some(played.as_str())),
+            ];
+            chunks::add_chunk(&path, *b"LIST", &chunks::info(&tags))?;
+        }
+
+        if !report_paths.is_empty() {
+            let mut files = entries
+                .iter()
+                .map(|entry| report::FileReport::new(output_dir, entry, loop_search.is_some()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            report::flag_inverted_layers(&mut files);
+            for file in files.iter_mut().filter(|file| slipped.contains(&file.file)) {
+                file.warnings.push(report::TIMING_SLIP_WARNING.into());
+            }
+
+            for file in &files {
+                for warning in &file.warnings {
+                    warn!("{}: {warning}", file.file);
+                }
+            }
+
+            let report = report::Report {
+                sample_rate,
+                channels,
+                latency_frames: self.rescale(latency),
+                latency_seconds: latency as f64 / f64::from(recorded_rate),
+                dropouts: state.dropouts(),
+                dropped_samples: state.dropped_samples(),
+                timing_slips: state.timing_slips(),
+                files,
+            };
+            for path in report_paths {
+                let path = if inputs > 1 {
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    let name = match path.extension() {
+                        Some(ext) => {
+                            format!("{stem}-mic{}.{}", mic + 1, ext.to_string_lossy())
+                        }
+                        None => format!("{stem}-mic{}", mic + 1),
+                    };
+                    path.with_file_name(name)
+                } else {
+                    path.clone()
+                };
+                report.write(&path)?;
+                info!("Wrote report to {}", path.display());
+            }
+        }
+
+        Ok(entries)
+    }
+}
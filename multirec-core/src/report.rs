@@ -0,0 +1,423 @@
+use std::{fmt::Write as _, path::Path};
+
+use serde::Serialize;
+
+use crate::{analysis, util::NamedFile};
+
+/// Peak level at which a recording counts as clipped
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Peak level below which a recording is probably missing its sound, about -60 dBFS
+const SILENCE_LEVEL: f32 = 0.001;
+
+/// Difference in loudness that a softer layer may be louder than the layer above it by, in LU
+///
+/// Round robins of the same layer differ by about this much anyway.
+const LOUDNESS_TOLERANCE: f32 = 0.5;
+
+/// Warning given to a file whose zone slipped out of step with the clock while it was recorded
+pub const TIMING_SLIP_WARNING: &str =
+    "audio timing slipped while recording, so it may be out of step with its notes";
+
+/// A summary of a run, for scripts and QA tools
+#[derive(Serialize)]
+pub struct Report {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Latency measured between a NoteOn and its sound, in frames
+    pub latency_frames: usize,
+    pub latency_seconds: f64,
+    /// Stream errors and overflowed buffers during the run
+    pub dropouts: usize,
+    /// Samples lost because the file writer fell behind
+    pub dropped_samples: usize,
+    /// Zones during which the input's buffers fell out of step with the clock
+    pub timing_slips: usize,
+    pub files: Vec<FileReport>,
+}
+
+#[derive(Serialize)]
+pub struct FileReport {
+    pub file: String,
+    pub pitch: u8,
+    pub note: String,
+    pub velocity: Option<u8>,
+    /// Round robin, counting from 1 as in the file name
+    pub round_robin: Option<u8>,
+    /// Name of the articulation selected by a keyswitch
+    pub articulation: Option<String>,
+    /// Values of swept controllers, by controller number
+    pub controllers: std::collections::BTreeMap<u8, u8>,
+    /// Highest sample level in dBFS, or `None` if the file is silent
+    pub peak_dbfs: Option<f32>,
+    /// Average level in dBFS, or `None` if the file is silent
+    pub rms_dbfs: Option<f32>,
+    /// Integrated loudness in LUFS, or `None` if the file is too quiet to measure
+    pub loudness_lufs: Option<f32>,
+    /// Highest level between the samples as well as at them, in dBTP
+    pub true_peak_dbtp: Option<f32>,
+    pub frames: u32,
+    pub duration_seconds: f64,
+    pub loop_start: Option<usize>,
+    pub loop_end: Option<usize>,
+    /// Crossfade at the loop end set in the manifest, in frames
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_crossfade: Option<usize>,
+    /// Gain set in the manifest, in dB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f32>,
+    /// How far the sample was measured to be from its note, in cents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tune_cents: Option<f32>,
+    pub warnings: Vec<String>,
+}
+
+impl FileReport {
+    /// Describe a recorded file, after any processing has been applied
+    pub fn new<S: AsRef<str>>(
+        dir: impl AsRef<Path>,
+        entry: &NamedFile<'_, S>,
+        loops_detected: bool,
+    ) -> anyhow::Result<Self> {
+        let path = dir.as_ref().join(entry.to_string());
+        let reader = hound::WavReader::open(&path)?;
+        let frames = reader.duration();
+        let sample_rate = reader.spec().sample_rate;
+        drop(reader);
+
+        let peak = analysis::peak_level(&path)?;
+        let rms = analysis::rms_level(&path)?;
+        let loudness = analysis::loudness(&path)?;
+
+        let mut warnings = Vec::new();
+        if peak >= CLIP_LEVEL {
+            warnings.push("clipped".to_string());
+        }
+        if peak < SILENCE_LEVEL {
+            warnings.push("silent or nearly silent".to_string());
+        }
+        if loops_detected && entry.loop_points.is_none() {
+            warnings.push("no loop points found".to_string());
+        }
+
+        Ok(Self {
+            file: entry.to_string(),
+            pitch: entry.pitch.note_number(),
+            note: entry.pitch.to_string(),
+            velocity: entry.velocity,
+            round_robin: entry.round_robin.map(|rr| rr + 1),
+            articulation: entry.keyswitch.map(|keyswitch| keyswitch.label.clone()),
+            controllers: entry.controllers.iter().copied().collect(),
+            peak_dbfs: (peak > 0.0).then(|| 20.0 * peak.log10()),
+            rms_dbfs: (rms > 0.0).then(|| 20.0 * rms.log10()),
+            loudness_lufs: loudness.integrated,
+            true_peak_dbtp: loudness.true_peak,
+            frames,
+            duration_seconds: f64::from(frames) / f64::from(sample_rate.max(1)),
+            loop_start: entry.loop_points.as_ref().map(|points| points.start),
+            loop_end: entry.loop_points.as_ref().map(|points| points.end),
+            loop_crossfade: entry.loop_crossfade,
+            gain_db: entry.gain,
+            tune_cents: entry.tune,
+            warnings,
+        })
+    }
+}
+
+/// Warn about velocity layers that are louder than the layer above them
+///
+/// Layers are compared among the files of the same note, articulation and
+/// controller values, each by the average loudness of its round robins. A
+/// softer layer that sounds louder usually means the instrument's velocity
+/// response is not set up as expected.
+pub fn flag_inverted_layers(files: &mut [FileReport]) {
+    let mut inverted = Vec::new();
+
+    for (idx, file) in files.iter().enumerate() {
+        let Some(velocity) = file.velocity else {
+            continue;
+        };
+        let same_zone = |other: &&FileReport| {
+            other.pitch == file.pitch
+                && other.articulation == file.articulation
+                && other.controllers == file.controllers
+        };
+        let layer_loudness = |velocity: u8| {
+            let levels: Vec<f32> = files
+                .iter()
+                .filter(same_zone)
+                .filter(|other| other.velocity == Some(velocity))
+                .filter_map(|other| other.loudness_lufs)
+                .collect();
+            (!levels.is_empty()).then(|| levels.iter().sum::<f32>() / levels.len() as f32)
+        };
+
+        let Some(above) = files
+            .iter()
+            .filter(same_zone)
+            .filter_map(|other| other.velocity.filter(|v| *v > velocity))
+            .min()
+        else {
+            continue;
+        };
+
+        if let (Some(loudness), Some(louder)) = (layer_loudness(velocity), layer_loudness(above)) {
+            if loudness > louder + LOUDNESS_TOLERANCE {
+                inverted.push((
+                    idx,
+                    format!(
+                        "velocity {velocity} is louder than velocity {above} above it \
+                        ({loudness:.1} vs {louder:.1} LUFS)"
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (idx, warning) in inverted {
+        files[idx].warnings.push(warning);
+    }
+}
+
+/// Headings of the columns of a CSV or HTML report
+const COLUMNS: [&str; 17] = [
+    "File",
+    "Note",
+    "Velocity",
+    "Round robin",
+    "Articulation",
+    "Controllers",
+    "Duration (s)",
+    "Frames",
+    "Peak (dBFS)",
+    "RMS (dBFS)",
+    "Loudness (LUFS)",
+    "True peak (dBTP)",
+    "Tuning (cents)",
+    "Latency (ms)",
+    "Loop start",
+    "Loop end",
+    "Warnings",
+];
+
+impl Report {
+    /// Write the report as JSON, or as CSV or HTML if the path ends in `.csv` or `.html`
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") => std::fs::write(path, self.csv())?,
+            Some("html" | "htm") => std::fs::write(path, self.html())?,
+            _ => {
+                let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                serde_json::to_writer_pretty(file, self)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A row of cells for each file, in the order of [`COLUMNS`]
+    fn rows(&self) -> impl Iterator<Item = [String; COLUMNS.len()]> + '_ {
+        fn optional<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+        fn level(value: Option<f32>) -> String {
+            value.map(|v| format!("{v:.1}")).unwrap_or_default()
+        }
+
+        let latency = format!("{:.1}", self.latency_seconds * 1000.0);
+        self.files.iter().map(move |file| {
+            [
+                file.file.clone(),
+                file.note.clone(),
+                optional(file.velocity),
+                optional(file.round_robin),
+                optional(file.articulation.as_ref()),
+                file.controllers
+                    .iter()
+                    .map(|(controller, value)| format!("CC{controller}={value}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                format!("{:.3}", file.duration_seconds),
+                file.frames.to_string(),
+                level(file.peak_dbfs),
+                level(file.rms_dbfs),
+                level(file.loudness_lufs),
+                level(file.true_peak_dbtp),
+                level(file.tune_cents),
+                latency.clone(),
+                optional(file.loop_start),
+                optional(file.loop_end),
+                file.warnings.join("; "),
+            ]
+        })
+    }
+
+    /// One line per file, with the column headings first
+    fn csv(&self) -> String {
+        // quote cells that would otherwise be split, doubling any quotes inside
+        fn cell(text: &str) -> String {
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text.to_string()
+            }
+        }
+
+        let mut csv = String::new();
+        let header: Vec<_> = COLUMNS.iter().map(|heading| cell(heading)).collect();
+        csv.push_str(&header.join(","));
+        csv.push_str("\r\n");
+        for row in self.rows() {
+            let row: Vec<_> = row.iter().map(|text| cell(text)).collect();
+            csv.push_str(&row.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    /// A page with a summary of the run and a table of the files, with those that have warnings highlighted
+    fn html(&self) -> String {
+        fn escape(text: &str) -> String {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        }
+
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+            <title>multirec report</title>\n<style>\n\
+            body { font-family: sans-serif; }\n\
+            table { border-collapse: collapse; font-size: 0.9em; }\n\
+            th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; text-align: right; }\n\
+            th { background: #eee; position: sticky; top: 0; }\n\
+            td:first-child, td:last-child { text-align: left; }\n\
+            tr.warning { background: #fdd; }\n\
+            </style>\n</head>\n<body>\n",
+        );
+
+        let warned = self.files.iter().filter(|f| !f.warnings.is_empty()).count();
+        let _ = writeln!(
+            html,
+            "<p>{} files at {} Hz, {} channels. Latency {:.1} ms, {} dropouts, \
+            {} dropped samples, {} timing slips. {warned} files have warnings.</p>",
+            self.files.len(),
+            self.sample_rate,
+            self.channels,
+            self.latency_seconds * 1000.0,
+            self.dropouts,
+            self.dropped_samples,
+            self.timing_slips,
+        );
+
+        html.push_str("<table>\n<tr>");
+        for heading in COLUMNS {
+            let _ = write!(html, "<th>{}</th>", escape(heading));
+        }
+        html.push_str("</tr>\n");
+        for (file, row) in self.files.iter().zip(self.rows()) {
+            html.push_str(if file.warnings.is_empty() {
+                "<tr>"
+            } else {
+                "<tr class=\"warning\">"
+            });
+            for text in &row {
+                let _ = write!(html, "<td>{}</td>", escape(text));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, warnings: &[&str]) -> FileReport {
+        FileReport {
+            file: name.to_string(),
+            pitch: 60,
+            note: "C4".to_string(),
+            velocity: Some(127),
+            round_robin: None,
+            articulation: None,
+            controllers: [(1, 64), (74, 0)].into_iter().collect(),
+            peak_dbfs: Some(-1.04),
+            rms_dbfs: None,
+            loudness_lufs: None,
+            true_peak_dbtp: None,
+            frames: 48000,
+            duration_seconds: 1.0,
+            loop_start: None,
+            loop_end: None,
+            loop_crossfade: None,
+            gain_db: None,
+            tune_cents: None,
+            warnings: warnings.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    fn report(files: Vec<FileReport>) -> Report {
+        Report {
+            sample_rate: 48000,
+            channels: 2,
+            latency_frames: 96,
+            latency_seconds: 0.002,
+            dropouts: 0,
+            dropped_samples: 0,
+            timing_slips: 0,
+            files,
+        }
+    }
+
+    #[test]
+    fn csv_quotes_cells() {
+        let csv = report(vec![
+            file("C4.wav", &[]),
+            file("a,\"b\".wav", &["clipped", "no loop points found"]),
+        ])
+        .csv();
+
+        let lines: Vec<_> = csv.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(!csv.replace("\r\n", "").contains('\n'));
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            "C4.wav,C4,127,,,CC1=64 CC74=0,1.000,48000,-1.0,,,,,2.0,,,"
+        );
+        assert!(lines[2].starts_with("\"a,\"\"b\"\".wav\",C4,"));
+        assert!(lines[2].ends_with(",clipped; no loop points found"));
+    }
+
+    #[test]
+    fn csv_of_no_files() {
+        assert_eq!(
+            report(Vec::new()).csv(),
+            format!("{}\r\n", COLUMNS.join(","))
+        );
+    }
+
+    #[test]
+    fn html_escapes_and_highlights() {
+        let html = report(vec![
+            file("<b>&\".wav", &[]),
+            file("D4.wav", &["silent or nearly silent"]),
+        ])
+        .html();
+
+        assert!(html.contains("<td>&lt;b&gt;&amp;&quot;.wav</td>"));
+        assert!(!html.contains("<b>"));
+        assert_eq!(html.matches("<tr class=\"warning\">").count(), 1);
+        assert!(html.contains("<tr class=\"warning\"><td>D4.wav</td>"));
+        assert!(html.contains("2 files at 48000 Hz, 2 channels. Latency 2.0 ms"));
+        assert!(html.contains("1 files have warnings"));
+        assert_eq!(html.matches("<th>").count(), COLUMNS.len());
+    }
+}
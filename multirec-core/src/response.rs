@@ -0,0 +1,259 @@
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use cpal::{traits::DeviceTrait, traits::StreamTrait, FromSample};
+use log::{debug, error, warn};
+
+use autosam::{
+    midi::{Event, NoteState},
+    AdvanceResult, Sequencer,
+};
+
+use crate::{monitor::MidiOut, runtime::ChannelSelection};
+
+/// How much quieter a louder velocity may be before it is reported, in dB
+const INVERSION_TOLERANCE: f32 = 1.0;
+
+/// Most velocity layers to suggest
+const MAX_LAYERS: u8 = 32;
+
+/// Measures the loudness of each note of a sequence, from its NoteOn until the next
+struct Sweeper {
+    seq: Sequencer,
+    sender: rtrb::Producer<Event>,
+    /// Velocity and RMS level of each note played
+    levels: rtrb::Producer<(u8, f32)>,
+    channels: usize,
+    selection: ChannelSelection,
+    /// Velocity of the note being measured, with its sum of squares and frame count
+    current: Option<(u8, f64, usize)>,
+    done: Arc<AtomicBool>,
+}
+
+impl Sweeper {
+    fn process<T>(&mut self, input: &[T])
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>,
+    {
+        for frame in input.chunks(self.channels) {
+            if self.done.load(Ordering::Relaxed) {
+                return;
+            }
+
+            loop {
+                match self.seq.advance(1) {
+                    AdvanceResult::NoEventsInFrame => break,
+                    AdvanceResult::SequenceComplete => {
+                        self.finish_note();
+                        self.done.store(true, Ordering::Release);
+                        break;
+                    }
+                    AdvanceResult::Event { event, .. } => {
+                        if let Some(note) = event.note().filter(|n| n.state() == NoteState::On) {
+                            self.finish_note();
+                            self.current = Some((note.velocity(), 0.0, 0));
+                        }
+
+                        if let Err(e) = self.sender.push(event) {
+                            error!("Out of capacity in event buffer: {e}");
+                        }
+                    }
+                }
+            }
+
+            if let Some((_, sum, count)) = &mut self.current {
+                let (samples, len) = self.selection.pick(frame);
+                *sum += samples[..len]
+                    .iter()
+                    .map(|s| f64::from(*s).powi(2))
+                    .sum::<f64>()
+                    / len.max(1) as f64;
+                *count += 1;
+            }
+        }
+    }
+
+    fn finish_note(&mut self) {
+        let Some((velocity, sum, count)) = self.current.take() else {
+            return;
+        };
+
+        let rms = (sum / count.max(1) as f64).sqrt() as f32;
+        if let Err(e) = self.levels.push((velocity, rms)) {
+            error!("Out of capacity in level buffer: {e}");
+        }
+    }
+}
+
+/// Play a sequence of notes, returning the velocity and RMS level of each in dBFS
+pub fn sweep(
+    device: &cpal::Device,
+    sample_format: cpal::SampleFormat,
+    config: &cpal::StreamConfig,
+    selection: ChannelSelection,
+    midi_connection: &mut MidiOut,
+    seq: Sequencer,
+) -> anyhow::Result<Vec<(u8, f32)>> {
+    let (note_tx, mut note_rx) = rtrb::RingBuffer::new(64);
+    let (level_tx, mut level_rx) = rtrb::RingBuffer::new(256);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let sweeper = Sweeper {
+        seq,
+        sender: note_tx,
+        levels: level_tx,
+        channels: usize::from(config.channels),
+        selection,
+        current: None,
+        done: done.clone(),
+    };
+
+    let stream = match sample_format {
+        cpal::SampleFormat::I8 => input_stream::<i8>(device, config, sweeper)?,
+        cpal::SampleFormat::I16 => input_stream::<i16>(device, config, sweeper)?,
+        cpal::SampleFormat::I32 => input_stream::<i32>(device, config, sweeper)?,
+        cpal::SampleFormat::F32 => input_stream::<f32>(device, config, sweeper)?,
+        sample_format => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported sample format '{sample_format}'"
+            )))
+        }
+    };
+
+    debug!("Measuring the velocity response");
+    stream.play()?;
+
+    let mut levels = Vec::new();
+    loop {
+        let is_done = done.load(Ordering::Acquire);
+
+        while let Ok(event) = note_rx.pop() {
+            let msg = event.as_midi_message();
+            debug!("Sending event {:?}", &*msg);
+            midi_connection.send(&msg, None)?;
+        }
+
+        while let Ok((velocity, rms)) = level_rx.pop() {
+            let level = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+            debug!("Velocity {velocity} played at {level:.1}dB");
+            levels.push((velocity, level));
+        }
+
+        if is_done {
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    drop(stream);
+
+    Ok(levels)
+}
+
+/// Velocity layers that would split up the loudness of an instrument evenly
+#[derive(Debug)]
+pub struct Recommendation {
+    pub layers: u8,
+    /// Velocities from the softest that is heard up to the loudest
+    pub range: RangeInclusive<u8>,
+    /// Lowest velocity of each layer above the softest, for even steps in loudness
+    pub crossovers: Vec<u8>,
+}
+
+impl Recommendation {
+    /// The options for a run that records these layers
+    pub fn options(&self) -> String {
+        format!(
+            "--velocity-layers {} --velocity-range {}..{}",
+            self.layers,
+            self.range.start(),
+            self.range.end()
+        )
+    }
+}
+
+/// Suggest velocity layers from the level of a note at each velocity, in dBFS
+///
+/// There are enough layers that each covers no more than `spacing` dB,
+/// from the softest velocity heard above `floor` to the loudest. Returns
+/// `None` if no velocity was heard.
+pub fn recommend(levels: &[(u8, f32)], spacing: f32, floor: f32) -> Option<Recommendation> {
+    let mut heard: Vec<(u8, f32)> = levels
+        .iter()
+        .copied()
+        .filter(|(_, level)| *level > floor)
+        .collect();
+    heard.sort_by_key(|(velocity, _)| *velocity);
+
+    for pair in heard.windows(2) {
+        let ((softer, quiet), (louder, loud)) = (pair[0], pair[1]);
+        if quiet - loud > INVERSION_TOLERANCE {
+            warn!(
+                "Velocity {louder} is {:.1}dB quieter than {softer}",
+                quiet - loud
+            );
+        }
+    }
+
+    let (&(lowest, _), &(highest, _)) = (heard.first()?, heard.last()?);
+    let quietest = heard
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(f32::MAX, f32::min);
+    let loudest = heard
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(f32::MIN, f32::max);
+    let span = loudest - quietest;
+
+    let width = highest - lowest + 1;
+    let layers = ((span / spacing.max(0.1)).ceil() as u8).clamp(1, MAX_LAYERS.min(width));
+
+    // each layer begins where the level first reaches its share of the span
+    let crossovers = (1..layers)
+        .filter_map(|layer| {
+            let target = quietest + span * f32::from(layer) / f32::from(layers);
+            heard.windows(2).find_map(|pair| {
+                let ((v0, l0), (v1, l1)) = (pair[0], pair[1]);
+                if l0 >= target {
+                    return Some(v0);
+                }
+                (l1 >= target).then(|| {
+                    let position = (target - l0) / (l1 - l0);
+                    (f32::from(v0) + position * f32::from(v1 - v0)).ceil() as u8
+                })
+            })
+        })
+        .collect();
+
+    Some(Recommendation {
+        layers,
+        range: lowest..=highest,
+        crossovers,
+    })
+}
+
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut sweeper: Sweeper,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &_| sweeper.process(data),
+        |e| error!("Encountered an error while measuring the velocity response: {e}"),
+        None,
+    )
+}
@@ -0,0 +1,301 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+
+/// Marks an AppleMIDI session command, in place of an RTP header
+const COMMAND_SIGNATURE: [u8; 2] = [0xFF, 0xFF];
+
+const PROTOCOL_VERSION: u32 = 2;
+
+/// RTP payload type used for MIDI
+const PAYLOAD_TYPE: u8 = 0x61;
+
+/// Times each invitation is sent before giving up
+const INVITATION_ATTEMPTS: usize = 4;
+
+/// Time to wait for each reply to an invitation or clock sync
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time between clock syncs, which keep the session from timing out
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest MIDI list that fits the command section's 12-bit length
+const MAX_COMMAND_LEN: usize = 0x0FFF;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RtpError {
+    #[error("No answer from the network MIDI session at {0}")]
+    NoAnswer(SocketAddr),
+    #[error("The network MIDI session at {0} declined the invitation")]
+    Declined(SocketAddr),
+    #[error("The network MIDI session at {0} was ended by the other side")]
+    Ended(SocketAddr),
+    #[error("A MIDI message of {0} bytes is too long to send over the network")]
+    TooLong(usize),
+    #[error("The network MIDI session at {0} has no data port after its control port")]
+    NoDataPort(SocketAddr),
+}
+
+/// A network MIDI (AppleMIDI / RTP-MIDI) session, with this end as the initiator
+///
+/// The session is joined on the given control port and the data port after
+/// it. Messages are sent one per packet, without a recovery journal. The
+/// clocks are synchronized every few seconds from a thread of its own,
+/// which keeps the session alive on the other end.
+pub struct Session {
+    address: SocketAddr,
+    data_address: SocketAddr,
+    control: Arc<UdpSocket>,
+    data: Arc<UdpSocket>,
+    token: u32,
+    ssrc: u32,
+    started: Instant,
+    sequence: u16,
+    ended: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Session {
+    /// Join the session whose control port is at `address`
+    pub fn connect(address: SocketAddr, name: &str) -> anyhow::Result<Self> {
+        let data_port = address
+            .port()
+            .checked_add(1)
+            .ok_or(RtpError::NoDataPort(address))?;
+        let data_address = SocketAddr::new(address.ip(), data_port);
+        let (control, data) = bind_pair(address)?;
+
+        // unique enough to tell this session from others on the same peer
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos()
+            ^ std::process::id().rotate_left(16);
+        let token = seed.wrapping_mul(0x9E37_79B9);
+        let ssrc = seed.wrapping_mul(0x85EB_CA6B) | 1;
+
+        let invitation = session_command(*b"IN", token, ssrc, Some(name));
+        invite(&control, address, &invitation)?;
+        invite(&data, data_address, &invitation)?;
+        info!("Joined network MIDI session at {address}");
+
+        let session = Self {
+            address,
+            data_address,
+            control: Arc::new(control),
+            data: Arc::new(data),
+            token,
+            ssrc,
+            started: Instant::now(),
+            sequence: 0,
+            ended: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        session.keep_alive()?;
+
+        Ok(session)
+    }
+
+    /// Send a MIDI message, timestamped with the time it is sent
+    pub fn send(&mut self, message: &[u8]) -> anyhow::Result<()> {
+        if self.ended.load(Ordering::Acquire) {
+            return Err(RtpError::Ended(self.address).into());
+        }
+        if message.len() > MAX_COMMAND_LEN {
+            return Err(RtpError::TooLong(message.len()).into());
+        }
+
+        let mut packet = Vec::with_capacity(14 + message.len());
+        packet.extend_from_slice(&[0x80, PAYLOAD_TYPE]);
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        packet.extend_from_slice(&(timestamp(self.started) as u32).to_be_bytes());
+        packet.extend_from_slice(&self.ssrc.to_be_bytes());
+
+        // a short header holds lengths up to 15, and a long one the rest
+        if message.len() < 16 {
+            packet.push(message.len() as u8);
+        } else {
+            packet.extend_from_slice(&(0x8000 | message.len() as u16).to_be_bytes());
+        }
+        packet.extend_from_slice(message);
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.data.send_to(&packet, self.data_address)?;
+
+        Ok(())
+    }
+
+    /// Synchronize the clocks now and then, and answer the other side's syncs, until dropped
+    fn keep_alive(&self) -> anyhow::Result<()> {
+        let sockets = [self.control.clone(), self.data.clone()];
+        let (address, data_address) = (self.address, self.data_address);
+        let (ssrc, started) = (self.ssrc, self.started);
+        let ended = self.ended.clone();
+        let stop = self.stop.clone();
+
+        for socket in &sockets {
+            socket.set_read_timeout(Some(REPLY_TIMEOUT / 4))?;
+        }
+
+        std::thread::Builder::new()
+            .name("rtp-midi".into())
+            .spawn(move || {
+                let mut last_sync: Option<Instant> = None;
+                let mut buffer = [0; 512];
+
+                while !stop.load(Ordering::Acquire) {
+                    if last_sync.map_or(true, |last| last.elapsed() >= CLOCK_SYNC_INTERVAL) {
+                        let sync = clock_sync(ssrc, 0, [timestamp(started), 0, 0]);
+                        if let Err(e) = sockets[1].send_to(&sync, data_address) {
+                            debug!("Could not sync clocks with {address}: {e}");
+                        }
+                        last_sync = Some(Instant::now());
+                    }
+
+                    for socket in &sockets {
+                        let Ok((len, from)) = socket.recv_from(&mut buffer) else {
+                            continue;
+                        };
+                        let packet = &buffer[..len];
+                        if packet.get(..2) != Some(&COMMAND_SIGNATURE[..]) {
+                            continue;
+                        }
+
+                        match packet.get(2..4) {
+                            Some(b"CK") if packet.len() >= 36 => {
+                                let count = packet[8];
+                                let mut times = [0; 3];
+                                for (idx, time) in times.iter_mut().enumerate() {
+                                    let start = 12 + idx * 8;
+                                    *time = u64::from_be_bytes(
+                                        packet[start..start + 8].try_into().unwrap_or_default(),
+                                    );
+                                }
+
+                                // answer the step after the one received, up to the third
+                                if count < 2 {
+                                    times[usize::from(count) + 1] = timestamp(started);
+                                    let reply = clock_sync(ssrc, count + 1, times);
+                                    if let Err(e) = socket.send_to(&reply, from) {
+                                        debug!("Could not sync clocks with {address}: {e}");
+                                    }
+                                }
+                            }
+                            Some(b"BY") => {
+                                warn!("The network MIDI session at {address} was ended");
+                                ended.store(true, Ordering::Release);
+                                return;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if !self.ended.load(Ordering::Acquire) {
+            let bye = session_command(*b"BY", self.token, self.ssrc, None);
+            let _ = self.control.send_to(&bye, self.address);
+        }
+    }
+}
+
+/// Bind a control socket and the data socket on the port after it, as peers expect
+fn bind_pair(address: SocketAddr) -> anyhow::Result<(UdpSocket, UdpSocket)> {
+    let any = if address.is_ipv4() { "0.0.0.0" } else { "::" };
+
+    for _ in 0..16 {
+        let control = UdpSocket::bind((any, 0))?;
+        let port = control.local_addr()?.port();
+        if let Ok(data) = UdpSocket::bind((any, port.wrapping_add(1))) {
+            return Ok((control, data));
+        }
+    }
+
+    // fall back to any two ports, which most peers accept as replies come from them
+    Ok((UdpSocket::bind((any, 0))?, UdpSocket::bind((any, 0))?))
+}
+
+/// Send an invitation until it is accepted
+fn invite(socket: &UdpSocket, address: SocketAddr, invitation: &[u8]) -> anyhow::Result<()> {
+    socket.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    let mut buffer = [0; 512];
+
+    for _ in 0..INVITATION_ATTEMPTS {
+        socket.send_to(invitation, address)?;
+
+        let Ok((len, _)) = socket.recv_from(&mut buffer) else {
+            continue;
+        };
+        let reply = &buffer[..len];
+        if reply.get(..2) != Some(&COMMAND_SIGNATURE[..]) {
+            continue;
+        }
+        match reply.get(2..4) {
+            Some(b"OK") => return Ok(()),
+            Some(b"NO") => return Err(RtpError::Declined(address).into()),
+            _ => {}
+        }
+    }
+
+    Err(RtpError::NoAnswer(address).into())
+}
+
+/// An invitation or goodbye, with the session's name for an invitation
+fn session_command(command: [u8; 2], token: u32, ssrc: u32, name: Option<&str>) -> Vec<u8> {
+    let mut packet = COMMAND_SIGNATURE.to_vec();
+    packet.extend_from_slice(&command);
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.extend_from_slice(&token.to_be_bytes());
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+    if let Some(name) = name {
+        packet.extend_from_slice(name.as_bytes());
+        packet.push(0);
+    }
+    packet
+}
+
+/// A step of a clock sync, with the times of the steps so far
+fn clock_sync(ssrc: u32, count: u8, times: [u64; 3]) -> Vec<u8> {
+    let mut packet = COMMAND_SIGNATURE.to_vec();
+    packet.extend_from_slice(b"CK");
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+    packet.extend_from_slice(&[count, 0, 0, 0]);
+    for time in times {
+        packet.extend_from_slice(&time.to_be_bytes());
+    }
+    packet
+}
+
+/// Time since the session began, in the 100 microsecond units of its clock
+fn timestamp(started: Instant) -> u64 {
+    (started.elapsed().as_micros() / 100) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_port_has_no_data_port() {
+        let address = "127.0.0.1:65535".parse().unwrap();
+        let error = Session::connect(address, "test").err().unwrap();
+        assert!(matches!(
+            error.downcast_ref::<RtpError>(),
+            Some(RtpError::NoDataPort(_))
+        ));
+    }
+}
@@ -0,0 +1,840 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use cpal::FromSample;
+use log::error;
+
+use autosam::{
+    dimension::Setting,
+    midi::{Event, NoteState},
+    AdvanceResult, Sequencer, Zone,
+};
+
+use crate::util::MaybeSample;
+
+/// Level that counts as clipping, just below full scale since integer formats cannot reach it
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Marks a byte that holds a keyswitch or controller value, as 7-bit data leaves the top bit free
+const PRESENT_FLAG: u8 = 0x80;
+
+/// Most input devices that can be recorded at once
+pub const MAX_INPUT_DEVICES: usize = 4;
+
+/// Largest difference between the audio received in a zone and the time it took, beyond a buffer
+const CLOCK_TOLERANCE: Duration = Duration::from_millis(20);
+/// Number of buffers' time between two buffers that counts as a gap
+const CALLBACK_GAP_BUFFERS: u32 = 4;
+/// Shortest time between two buffers that counts as a gap, however short the buffers are
+const MIN_CALLBACK_GAP: Duration = Duration::from_millis(20);
+
+pub struct RunState {
+    note_data: AtomicU32,
+    /// Values of the current zone's swept controllers, outermost first
+    controllers: AtomicU32,
+    done: AtomicBool,
+    latency: AtomicUsize,
+    /// Number of zones begun so far
+    zones: AtomicUsize,
+    /// Peak level of each channel since the last read, as `f32` bits
+    peaks: [AtomicU32; 2],
+    clipped: AtomicUsize,
+    dropouts: AtomicUsize,
+    /// Zones played again because they were not recorded properly
+    retries: AtomicUsize,
+    /// Samples lost because the writer fell behind
+    dropped_samples: AtomicUsize,
+    /// Zones during which the input's timing slipped
+    timing_slips: AtomicUsize,
+    paused: AtomicBool,
+    aborted: AtomicBool,
+    /// Whether the rest of the current note was asked to be skipped
+    skip_requested: AtomicBool,
+    /// Zones passed over without being recorded
+    skipped: AtomicUsize,
+}
+
+impl RunState {
+    pub fn new(initial_pitch: u8) -> Self {
+        Self {
+            note_data: AtomicU32::new(u32::from_be_bytes([0, initial_pitch, 127, 0])),
+            controllers: AtomicU32::new(0),
+            done: AtomicBool::new(false),
+            latency: AtomicUsize::new(0),
+            zones: AtomicUsize::new(0),
+            peaks: Default::default(),
+            clipped: AtomicUsize::new(0),
+            dropouts: AtomicUsize::new(0),
+            retries: AtomicUsize::new(0),
+            dropped_samples: AtomicUsize::new(0),
+            timing_slips: AtomicUsize::new(0),
+            paused: AtomicBool::new(false),
+            aborted: AtomicBool::new(false),
+            skip_requested: AtomicBool::new(false),
+            skipped: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    pub fn zones(&self) -> usize {
+        self.zones.load(Ordering::Acquire)
+    }
+
+    /// Take the peak level of a channel since the last call
+    pub fn take_peak(&self, channel: usize) -> f32 {
+        self.peaks
+            .get(channel)
+            .map_or(0.0, |peak| f32::from_bits(peak.swap(0, Ordering::AcqRel)))
+    }
+
+    /// Number of frames that reached full scale
+    pub fn clipped(&self) -> usize {
+        self.clipped.load(Ordering::Acquire)
+    }
+
+    /// Number of stream errors and overflowed buffers
+    pub fn dropouts(&self) -> usize {
+        self.dropouts.load(Ordering::Acquire)
+    }
+
+    pub fn dropout(&self) {
+        self.dropouts.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Number of samples lost because the writer fell behind
+    pub fn dropped_samples(&self) -> usize {
+        self.dropped_samples.load(Ordering::Acquire)
+    }
+
+    /// Number of zones during which the input's buffers fell out of step with the clock
+    pub fn timing_slips(&self) -> usize {
+        self.timing_slips.load(Ordering::Acquire)
+    }
+
+    /// Number of zones played again because they were not recorded properly
+    pub fn retries(&self) -> usize {
+        self.retries.load(Ordering::Acquire)
+    }
+
+    /// Count a zone that is about to be played again, and not as a new one
+    pub fn retry(&self) {
+        self.retries.fetch_add(1, Ordering::AcqRel);
+        self.zones.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Pause or resume, returning whether the run is now paused
+    ///
+    /// A zone in progress is completed first, so pausing takes effect between zones.
+    pub fn toggle_pause(&self) -> bool {
+        !self.paused.fetch_xor(true, Ordering::AcqRel)
+    }
+
+    /// Pause or resume, taking effect between zones like [`RunState::toggle_pause`]
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Release);
+    }
+
+    /// Pass over the rest of the current note, once the zone being recorded is complete
+    pub fn skip_note(&self) {
+        self.skip_requested.store(true, Ordering::Release);
+    }
+
+    /// Number of zones passed over without being recorded
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Acquire)
+    }
+
+    /// What the run is doing: `running`, `paused`, `aborting` or `done`
+    pub fn activity(&self) -> &'static str {
+        if self.aborted() {
+            "aborting"
+        } else if self.done() {
+            "done"
+        } else if self.paused() {
+            "paused"
+        } else {
+            "running"
+        }
+    }
+
+    /// Number of zones that have been recorded in full or passed over
+    pub fn completed(&self) -> usize {
+        let started = self.zones() + self.skipped();
+        if self.done() && !self.aborted() {
+            started
+        } else {
+            started.saturating_sub(1)
+        }
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted.load(Ordering::Acquire)
+    }
+
+    /// Stop the run as soon as possible, without finishing the current zone
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::Release);
+    }
+
+    pub fn latency(&self) -> usize {
+        self.latency.load(Ordering::Acquire)
+    }
+
+    /// The keyswitch note of the current zone, if it has one
+    pub fn keyswitch(&self, ordering: Ordering) -> Option<u8> {
+        let [keyswitch, ..] = self.note_data.load(ordering).to_be_bytes();
+        (keyswitch & PRESENT_FLAG != 0).then_some(keyswitch & !PRESENT_FLAG)
+    }
+
+    /// The values of the current zone's swept controllers, outermost first
+    pub fn controllers(&self, ordering: Ordering) -> Vec<u8> {
+        self.controllers
+            .load(ordering)
+            .to_be_bytes()
+            .into_iter()
+            .filter(|value| value & PRESENT_FLAG != 0)
+            .map(|value| value & !PRESENT_FLAG)
+            .collect()
+    }
+
+    pub fn note(&self, ordering: Ordering) -> (u8, u8, u8) {
+        let [_, note, velocity, round_robin] = self.note_data.load(ordering).to_be_bytes();
+        (note, velocity, round_robin)
+    }
+
+    pub fn new_note(&self, zone: &Zone) {
+        let mut controllers = [0; 4];
+        let values = zone.settings().filter_map(|setting| match setting {
+            Setting::Controller { value, .. } => Some(value | PRESENT_FLAG),
+            _ => None,
+        });
+        for (slot, value) in controllers.iter_mut().zip(values) {
+            *slot = value;
+        }
+        self.controllers
+            .store(u32::from_be_bytes(controllers), Ordering::Release);
+
+        self.note_data.store(
+            u32::from_be_bytes([
+                zone.settings()
+                    .find_map(|setting| match setting {
+                        Setting::Keyswitch(pitch) => Some(pitch.note_number() | PRESENT_FLAG),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+                zone.pitch().note_number(),
+                zone.velocity(),
+                zone.round_robin(),
+            ]),
+            Ordering::Release,
+        );
+        self.zones.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Holds back the quiet start of each recording, apart from a short guard before the attack
+pub struct StartTrimmer {
+    threshold: f32,
+    /// Most recent quiet samples, interleaved
+    guard: VecDeque<f32>,
+    guard_len: usize,
+    waiting: bool,
+}
+
+impl StartTrimmer {
+    pub fn new(threshold: f32, guard_frames: usize, channels: usize) -> Self {
+        Self {
+            threshold,
+            // allocated up front, so the audio callback never has to
+            guard: VecDeque::with_capacity(guard_frames * channels),
+            guard_len: guard_frames * channels,
+            waiting: true,
+        }
+    }
+
+    /// Start waiting for the next attack
+    fn restart(&mut self) {
+        self.guard.clear();
+        self.waiting = true;
+    }
+
+    /// Keep a quiet frame in the guard, dropping the oldest one if it is full
+    fn hold(&mut self, frame: impl ExactSizeIterator<Item = f32>) {
+        if self.guard_len < frame.len() {
+            return;
+        }
+
+        while self.guard.len() + frame.len() > self.guard_len {
+            self.guard.pop_front();
+        }
+
+        self.guard.extend(frame);
+    }
+}
+
+/// Ends the gap after each zone once the input has stayed quiet for long enough
+pub struct GapDetector {
+    threshold: f32,
+    hold: usize,
+    /// Least number of frames to wait after each event, however quiet
+    tail: usize,
+    /// Consecutive quiet frames since the most recent event
+    quiet: usize,
+    /// Frames since the most recent event
+    elapsed: usize,
+}
+
+impl GapDetector {
+    pub fn new(threshold: f32, hold_frames: usize, tail_frames: usize) -> Self {
+        Self {
+            threshold,
+            hold: hold_frames,
+            tail: tail_frames,
+            quiet: 0,
+            elapsed: 0,
+        }
+    }
+}
+
+/// Notices zones that are never heard
+pub struct DeadNoteDetector {
+    threshold: f32,
+    heard: bool,
+}
+
+impl DeadNoteDetector {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            heard: false,
+        }
+    }
+}
+
+/// Plays zones again that were not recorded properly
+#[derive(Default)]
+pub struct Retries {
+    max: u8,
+    /// Whether to retry zones that lost samples because the writer fell behind
+    dropouts: bool,
+    /// Retries of the current zone so far
+    attempts: u8,
+    /// Whether the next zone to start is a retry
+    pending: bool,
+    /// Whether the current zone lost samples
+    dropped: bool,
+}
+
+impl Retries {
+    pub fn new(max: u8, dropouts: bool) -> Self {
+        Self {
+            max,
+            dropouts,
+            ..Default::default()
+        }
+    }
+}
+
+/// Notices when the input's buffers stop arriving in step with the wall clock
+///
+/// The audio is counted from the start of each zone, so the slow drift
+/// between the device's clock and the system's does not build up over a run.
+/// A zone that slips is reported once.
+pub struct ClockMonitor {
+    sample_rate: f64,
+    /// Time and frame count that the current zone is measured from
+    reference: Option<(Instant, u64)>,
+    /// When the last buffer arrived, and how long it was
+    last: Option<(Instant, Duration)>,
+    frames: u64,
+    slipped: bool,
+}
+
+impl ClockMonitor {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: f64::from(sample_rate),
+            reference: None,
+            last: None,
+            frames: 0,
+            slipped: false,
+        }
+    }
+
+    /// Measure a buffer arriving now, returning whether the zone has just slipped
+    fn check(&mut self, frames: usize) -> bool {
+        let now = Instant::now();
+        let length = Duration::from_secs_f64(frames as f64 / self.sample_rate);
+        let (start, counted) = *self.reference.get_or_insert((now, self.frames));
+
+        let is_gap = self.last.is_some_and(|(at, previous)| {
+            now - at > (previous * CALLBACK_GAP_BUFFERS).max(MIN_CALLBACK_GAP)
+        });
+        let received = Duration::from_secs_f64((self.frames - counted) as f64 / self.sample_rate);
+        let is_drifting = received.abs_diff(now - start) > CLOCK_TOLERANCE + length;
+
+        self.last = Some((now, length));
+        self.frames += frames as u64;
+
+        let is_new = (is_gap || is_drifting) && !self.slipped;
+        self.slipped |= is_gap || is_drifting;
+        is_new
+    }
+
+    /// Measure from the start of a new zone
+    fn restart(&mut self) {
+        self.reference = None;
+        self.slipped = false;
+    }
+}
+
+/// Sends samples and file markers to the writer thread
+///
+/// A marker that does not fit is held back, and samples are dropped until
+/// it can be sent, so they are never written to the wrong file.
+pub struct WriterQueue<U> {
+    producer: rtrb::Producer<MaybeSample<U>>,
+    pending: Option<MaybeSample<U>>,
+    overflowing: bool,
+    state: Arc<RunState>,
+}
+
+impl<U> WriterQueue<U> {
+    pub fn new(producer: rtrb::Producer<MaybeSample<U>>, state: Arc<RunState>) -> Self {
+        Self {
+            producer,
+            pending: None,
+            overflowing: false,
+            state,
+        }
+    }
+
+    /// Whether this many samples can be queued without dropping any
+    pub fn has_room(&self, samples: usize) -> bool {
+        self.pending.is_none() && self.producer.slots() >= samples
+    }
+
+    /// Queue a sample or marker, returning `false` if anything was dropped
+    fn push(&mut self, item: MaybeSample<U>) -> bool {
+        if let Some(marker) = self.pending.take() {
+            if let Err(rtrb::PushError::Full(marker)) = self.producer.push(marker) {
+                self.pending = Some(marker);
+            }
+        }
+
+        let result = match self.pending {
+            Some(_) => Err(rtrb::PushError::Full(item)),
+            None => self.producer.push(item),
+        };
+
+        let Err(rtrb::PushError::Full(item)) = result else {
+            self.overflowing = false;
+            return true;
+        };
+
+        // report each overflow once, rather than every sample lost to it
+        if !self.overflowing {
+            error!("Out of capacity in I/O buffer, dropping samples");
+            self.state.dropout();
+            self.overflowing = true;
+        }
+
+        match item {
+            MaybeSample::Sample(_) => {
+                self.state.dropped_samples.fetch_add(1, Ordering::AcqRel);
+            }
+            // a slip is still counted, without holding back the next file's start
+            MaybeSample::Slip => {}
+            _ if self.pending.is_some() => {
+                error!("Lost the start of a file, as the I/O buffer is full");
+            }
+            marker => self.pending = Some(marker),
+        }
+
+        false
+    }
+}
+
+/// The channels of each input frame that are recorded, at most two
+#[derive(Clone, Copy)]
+pub struct ChannelSelection {
+    indices: [usize; 2],
+    len: usize,
+}
+
+impl ChannelSelection {
+    /// Record the first channels of the input, up to two
+    pub fn first(available: usize) -> Self {
+        Self {
+            indices: [0, 1],
+            len: available.clamp(1, 2),
+        }
+    }
+
+    /// Record one or two particular channels, counting from 0
+    pub fn new(indices: &[usize]) -> Self {
+        let mut selection = Self {
+            indices: [0; 2],
+            len: indices.len().clamp(1, 2),
+        };
+        for (slot, index) in selection.indices.iter_mut().zip(indices) {
+            *slot = *index;
+        }
+
+        selection
+    }
+
+    /// Number of channels recorded
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The selected samples of an input frame, as floating point
+    pub fn pick<T>(&self, frame: &[T]) -> ([f32; 2], usize)
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>,
+    {
+        let mut samples = [0.0; 2];
+        for (sample, index) in samples.iter_mut().zip(&self.indices[..self.len]) {
+            *sample = frame.get(*index).map_or(0.0, |s| f32::from_sample_(*s));
+        }
+
+        (samples, self.len)
+    }
+}
+
+/// Receives audio from an input device's callback
+pub trait Capture: Send + 'static {
+    fn write_input_data<T>(&mut self, input: &[T])
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>;
+}
+
+/// Queues the selected channels of a further input device, for the [`AudioProcessor`] to record
+pub struct MicFeed {
+    pub producer: rtrb::Producer<f32>,
+    /// Number of interleaved channels coming from the device
+    pub channels: usize,
+    pub selection: ChannelSelection,
+    pub state: Arc<RunState>,
+}
+
+impl Capture for MicFeed {
+    fn write_input_data<T>(&mut self, input: &[T])
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>,
+    {
+        for frame in input.chunks(self.channels) {
+            let (samples, len) = self.selection.pick(frame);
+            if self.producer.slots() < len {
+                self.state.dropped_samples.fetch_add(len, Ordering::AcqRel);
+                continue;
+            }
+
+            for sample in &samples[..len] {
+                let _ = self.producer.push(*sample);
+            }
+        }
+    }
+}
+
+/// The frames of a further input device, taken one for each frame of the first
+///
+/// Every device's files are cut at the same frames, so they line up. A few
+/// frames are kept queued to ride out the devices' callbacks arriving at
+/// different times, and the queue is kept near that length as their clocks
+/// drift apart.
+pub struct MicInput {
+    consumer: rtrb::Consumer<f32>,
+    /// Number of channels recorded from the device
+    channels: usize,
+    /// Frames to keep queued
+    prebuffer: usize,
+    started: bool,
+}
+
+impl MicInput {
+    pub fn new(consumer: rtrb::Consumer<f32>, channels: usize, prebuffer_frames: usize) -> Self {
+        Self {
+            consumer,
+            channels,
+            prebuffer: prebuffer_frames.max(1),
+            started: false,
+        }
+    }
+
+    /// Fill a frame from the device, with silence if it has nothing ready
+    fn next_frame(&mut self, frame: &mut [f32], state: &RunState) {
+        let queued = self.consumer.slots() / self.channels;
+        if !self.started && queued < self.prebuffer {
+            frame.fill(0.0);
+            return;
+        }
+        self.started = true;
+
+        if queued == 0 {
+            frame.fill(0.0);
+            state
+                .dropped_samples
+                .fetch_add(frame.len(), Ordering::AcqRel);
+            return;
+        }
+
+        // this device is running fast, so skip a frame to catch up
+        if queued > self.prebuffer * 2 {
+            for _ in 0..self.channels {
+                let _ = self.consumer.pop();
+            }
+        }
+
+        for sample in frame {
+            *sample = self.consumer.pop().unwrap_or_default();
+        }
+    }
+}
+
+/// An event of the sequence, with the frame it was played at
+pub struct TimedEvent {
+    pub frame: u64,
+    pub event: Event,
+}
+
+pub struct AudioProcessor<U> {
+    pub seq: Sequencer,
+    pub sender: rtrb::Producer<TimedEvent>,
+    pub writer: WriterQueue<U>,
+    /// Number of interleaved channels coming from the device
+    pub channels: usize,
+    pub selection: ChannelSelection,
+    pub state: Arc<RunState>,
+    pub latency_timer: Option<usize>,
+    pub trim_start: Option<StartTrimmer>,
+    pub auto_gap: Option<GapDetector>,
+    pub dead_notes: Option<DeadNoteDetector>,
+    pub retries: Retries,
+    /// The zone currently being recorded
+    pub zone: Option<Zone>,
+    /// Further devices recorded alongside, each frame of theirs following the frame of this one
+    pub mics: Vec<MicInput>,
+    /// Frames of the sequence played so far
+    pub frame: u64,
+    /// Checks the timing of a live input, which a plugin or file does not have
+    pub clock: Option<ClockMonitor>,
+}
+
+impl Capture for AudioProcessor<f32> {
+    fn write_input_data<T>(&mut self, input: &[T])
+    where
+        T: cpal::Sample,
+        f32: FromSample<T>,
+    {
+        let mut peaks = [0.0_f32; 2];
+        let mut clipped = 0;
+        for frame in input.chunks(self.channels) {
+            let (samples, len) = self.selection.pick(frame);
+            for (peak, sample) in peaks.iter_mut().zip(&samples[..len]) {
+                *peak = peak.max(sample.abs());
+            }
+            if samples[..len].iter().any(|s| s.abs() >= CLIP_LEVEL) {
+                clipped += 1;
+            }
+        }
+
+        // non-negative floats order the same way as their bits
+        for (shared, peak) in self.state.peaks.iter().zip(peaks) {
+            shared.fetch_max(peak.to_bits(), Ordering::AcqRel);
+        }
+        self.state.clipped.fetch_add(clipped, Ordering::AcqRel);
+
+        if self.state.aborted() {
+            self.state.done.store(true, Ordering::Release);
+            return;
+        }
+
+        if let Some(clock) = &mut self.clock {
+            if clock.check(input.len() / self.channels) && self.zone.is_some() {
+                self.state.timing_slips.fetch_add(1, Ordering::AcqRel);
+                self.writer.push(MaybeSample::Slip);
+            }
+        }
+
+        for frame in input.chunks(self.channels) {
+            let (samples, len) = self.selection.pick(frame);
+
+            // the other devices keep pace even while paused, so they stay in line
+            let mut recorded = [0.0; 2 * MAX_INPUT_DEVICES];
+            recorded[..len].copy_from_slice(&samples[..len]);
+            let mut width = len;
+            for mic in &mut self.mics {
+                mic.next_frame(&mut recorded[width..width + mic.channels], &self.state);
+                width += mic.channels;
+            }
+            let recorded = &recorded[..width];
+
+            if self.state.skip_requested.swap(false, Ordering::AcqRel) {
+                self.seq.skip_note();
+                self.state
+                    .skipped
+                    .store(self.seq.skipped_zones(), Ordering::Release);
+            }
+
+            // time stands still while paused, once the current zone is complete
+            if self.state.paused() && !self.seq.note_held() {
+                continue;
+            }
+
+            let frame = &samples[..len];
+
+            if let Some(t) = &mut self.latency_timer {
+                *t += 1;
+            }
+
+            let frame_time = self.frame;
+            self.frame += 1;
+
+            // several events can occur in the same frame
+            loop {
+                match self.seq.advance(1) {
+                    AdvanceResult::NoEventsInFrame => break,
+                    AdvanceResult::SequenceComplete => {
+                        self.state.done.store(true, Ordering::Release);
+                        break;
+                    }
+                    AdvanceResult::Event { position: _, event } => {
+                        // only count silence that follows the latest event, e.g. a NoteOff
+                        if let Some(detector) = &mut self.auto_gap {
+                            detector.quiet = 0;
+                            detector.elapsed = 0;
+                        }
+
+                        // repeated notes within a zone continue the same recording
+                        let zone = self.seq.zone().filter(|zone| {
+                            event.note().is_some_and(|n| n.state() == NoteState::On)
+                                && self.zone != Some(*zone)
+                        });
+                        if let Some(zone) = zone {
+                            self.zone = Some(zone);
+                            self.latency_timer = Some(0);
+                            self.state.new_note(&zone);
+                            if let Some(trimmer) = &mut self.trim_start {
+                                trimmer.restart();
+                            }
+
+                            if let Some(detector) = &mut self.dead_notes {
+                                detector.heard = false;
+                            }
+                            if let Some(clock) = &mut self.clock {
+                                clock.restart();
+                            }
+
+                            self.retries.dropped = false;
+                            let marker = if std::mem::take(&mut self.retries.pending) {
+                                MaybeSample::Retry
+                            } else {
+                                self.retries.attempts = 0;
+                                MaybeSample::Break
+                            };
+                            self.writer.push(marker);
+                        }
+
+                        let is_zone_end = event.note().is_some_and(|n| n.state() == NoteState::Off)
+                            && !self.seq.note_held();
+                        if is_zone_end {
+                            self.state
+                                .skipped
+                                .store(self.seq.skipped_zones(), Ordering::Release);
+                        }
+
+                        let event = TimedEvent {
+                            frame: frame_time,
+                            event,
+                        };
+                        if let Err(e) = self.sender.push(event) {
+                            error!("Out of capacity in event buffer: {e}");
+                            self.state.dropout();
+                        }
+
+                        // a zone that was not heard before its release, or lost samples, is played again
+                        let is_dead = self.dead_notes.as_ref().is_some_and(|d| !d.heard);
+                        let is_damaged = self.retries.dropouts && self.retries.dropped;
+                        if is_zone_end
+                            && (is_dead || is_damaged)
+                            && self.retries.attempts < self.retries.max
+                            && self.seq.repeat_zone()
+                        {
+                            self.retries.attempts += 1;
+                            self.retries.pending = true;
+                            self.zone = None;
+                            self.state.retry();
+                        }
+                    }
+                }
+            }
+
+            if let Some(detector) = &mut self.dead_notes {
+                if frame.iter().any(|s| s.abs() > detector.threshold) {
+                    detector.heard = true;
+                }
+            }
+
+            if let Some(detector) = &mut self.auto_gap {
+                let is_decayed = frame.iter().all(|s| s.abs() <= detector.threshold);
+                detector.elapsed += 1;
+
+                if is_decayed {
+                    detector.quiet += 1;
+                    if detector.quiet >= detector.hold && detector.elapsed >= detector.tail {
+                        self.seq.end_gap();
+                    }
+                } else {
+                    detector.quiet = 0;
+                }
+            }
+
+            let threshold = self.trim_start.as_ref().map_or(0.0, |t| t.threshold);
+            let is_quiet = frame.iter().all(|s| s.abs() <= threshold);
+
+            if !is_quiet {
+                if let Some(t) = self.latency_timer.take() {
+                    self.state.latency.fetch_max(t, Ordering::Release);
+                }
+            }
+
+            if let Some(trimmer) = self.trim_start.as_mut().filter(|t| t.waiting) {
+                if is_quiet {
+                    trimmer.hold(recorded.iter().copied());
+                    continue;
+                }
+
+                trimmer.waiting = false;
+                for sample in trimmer.guard.drain(..) {
+                    if !self.writer.push(MaybeSample::Sample(sample)) {
+                        self.retries.dropped = true;
+                    }
+                }
+            }
+
+            for sample in recorded {
+                if !self.writer.push(MaybeSample::Sample(*sample)) {
+                    self.retries.dropped = true;
+                }
+            }
+        }
+    }
+}
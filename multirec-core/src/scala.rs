@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use autosam::midi::PitchBend;
+
+/// A scale read from a Scala (`.scl`) file
+///
+/// The scale repeats every period, which is its last degree (usually an octave).
+#[derive(Clone, Debug)]
+pub struct Tuning {
+    pub description: String,
+    /// Each degree above the first, in cents, ending with the period
+    degrees: Vec<f64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScalaError {
+    #[error("Could not read Scala file `{0}`: {1}")]
+    Read(String, std::io::Error),
+    #[error("Scala file is missing its number of notes")]
+    MissingCount,
+    #[error("Invalid number of notes `{0}`")]
+    Count(String),
+    #[error("Scala file lists {found} of its {expected} notes")]
+    MissingNotes { expected: usize, found: usize },
+    #[error("Invalid pitch `{0}`, expected cents (e.g. `701.955`) or a ratio (e.g. `3/2`)")]
+    Pitch(String),
+    #[error("The scale's period must be above its first note")]
+    Period,
+}
+
+impl Tuning {
+    pub fn read(path: &Path) -> Result<Self, ScalaError> {
+        std::fs::read_to_string(path)
+            .map_err(|e| ScalaError::Read(path.display().to_string(), e))?
+            .parse()
+    }
+
+    /// Offset of a note from equal temperament, in cents
+    ///
+    /// The first degree of the scale falls on `root`, which keeps its equal-tempered pitch.
+    pub fn cents(&self, note: u8, root: u8) -> f64 {
+        let steps = i32::from(note) - i32::from(root);
+        let len = self.degrees.len() as i32;
+        let period = self.degrees[self.degrees.len() - 1];
+
+        let degree = steps.rem_euclid(len) as usize;
+        let above = match degree {
+            0 => 0.0,
+            degree => self.degrees[degree - 1],
+        };
+        let cents = f64::from(steps.div_euclid(len)) * period + above;
+
+        cents - f64::from(steps) * 100.0
+    }
+
+    /// Pitch bend that tunes each note, with the instrument's bend range in semitones
+    ///
+    /// Also returns the notes whose offset is beyond the bend range, which
+    /// are bent as far as they go.
+    pub fn bends(&self, root: u8, bend_range: u8) -> ([PitchBend; 128], Vec<u8>) {
+        let mut bends = [PitchBend::CENTER; 128];
+        let mut out_of_range = Vec::new();
+        let range = f64::from(bend_range) * 100.0;
+
+        for (note, bend) in (0..=127).zip(&mut bends) {
+            let amount = self.cents(note, root) / range;
+            if amount.abs() > 1.0 {
+                out_of_range.push(note);
+            }
+
+            let value = f64::from(PitchBend::CENTER.value()) * (1.0 + amount.clamp(-1.0, 1.0));
+            let value = value.round().min(f64::from(PitchBend::MAX.value()));
+            // clamped to the 14-bit range above
+            *bend = PitchBend::new(value as u16).unwrap();
+        }
+
+        (bends, out_of_range)
+    }
+}
+
+impl std::str::FromStr for Tuning {
+    type Err = ScalaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().filter(|line| !line.starts_with('!'));
+
+        let description = lines.next().unwrap_or_default().trim().to_string();
+        let count = lines.next().ok_or(ScalaError::MissingCount)?.trim();
+        let count: usize = count
+            .split_whitespace()
+            .next()
+            .and_then(|count| count.parse().ok())
+            .filter(|count| *count > 0)
+            .ok_or_else(|| ScalaError::Count(count.to_string()))?;
+
+        let degrees = lines
+            .filter_map(|line| line.split_whitespace().next())
+            .take(count)
+            .map(parse_pitch)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if degrees.len() < count {
+            return Err(ScalaError::MissingNotes {
+                expected: count,
+                found: degrees.len(),
+            });
+        }
+        if degrees[count - 1] <= 0.0 {
+            return Err(ScalaError::Period);
+        }
+
+        Ok(Self {
+            description,
+            degrees,
+        })
+    }
+}
+
+/// Read a pitch in cents (with a decimal point) or as a ratio, returning cents
+fn parse_pitch(s: &str) -> Result<f64, ScalaError> {
+    let invalid = || ScalaError::Pitch(s.to_string());
+
+    if s.contains('.') {
+        return s.parse().map_err(|_| invalid());
+    }
+
+    let (numerator, denominator) = s.split_once('/').unwrap_or((s, "1"));
+    let numerator: f64 = numerator.parse::<u64>().map_err(|_| invalid())? as f64;
+    let denominator: f64 = denominator.parse::<u64>().map_err(|_| invalid())? as f64;
+    if numerator == 0.0 || denominator == 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(1200.0 * (numerator / denominator).log2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JUST: &str = "! just.scl\n\
+        !\n\
+        5-limit just intonation\n \
+        12\n\
+        !\n\
+        16/15\n9/8\n6/5\n5/4\n4/3\n45/32\n3/2\n8/5\n5/3\n9/5\n15/8\n2/1\n";
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn equal_temperament_is_untouched() {
+        let text = format!(
+            "12-TET\n12\n{}2\n",
+            (1..12)
+                .map(|n| format!("{}.0\n", n * 100))
+                .collect::<String>()
+        );
+        let tuning: Tuning = text.parse().unwrap();
+        assert_eq!(tuning.description, "12-TET");
+        assert!((0..=127).all(|note| close(tuning.cents(note, 60), 0.0)));
+
+        let (bends, out_of_range) = tuning.bends(60, 2);
+        assert!(bends.iter().all(|bend| *bend == PitchBend::CENTER));
+        assert!(out_of_range.is_empty());
+    }
+
+    #[test]
+    fn just_intonation() {
+        let tuning: Tuning = JUST.parse().unwrap();
+        assert_eq!(tuning.description, "5-limit just intonation");
+
+        let fifth = 1200.0 * 1.5f64.log2() - 700.0;
+        let third = 1200.0 * 1.25f64.log2() - 400.0;
+        for root in [0, 62] {
+            assert!(close(tuning.cents(root, root), 0.0));
+            assert!(close(tuning.cents(root + 7, root), fifth));
+            assert!(close(tuning.cents(root + 12 + 4, root), third));
+        }
+        // the scale repeats below the root as well as above it
+        assert!(close(tuning.cents(57, 62), fifth));
+        assert!(close(tuning.cents(55, 62), -fifth));
+        assert!(close(tuning.cents(62 - 24, 62), 0.0));
+    }
+
+    #[test]
+    fn period_other_than_an_octave() {
+        // a single step of a fifth, so each note is a fifth above the last
+        let tuning: Tuning = "fifths\n1\n3/2\n".parse().unwrap();
+        let fifth = 1200.0 * 1.5f64.log2();
+        assert!(close(tuning.cents(61, 60), fifth - 100.0));
+        assert!(close(tuning.cents(58, 60), 200.0 - 2.0 * fifth));
+
+        // bent past two semitones from the root onwards
+        let (_, out_of_range) = tuning.bends(60, 2);
+        assert!(!out_of_range.contains(&60));
+        assert!(out_of_range.contains(&127));
+    }
+
+    #[test]
+    fn invalid_files() {
+        let parse = |text: &str| text.parse::<Tuning>().unwrap_err();
+
+        assert!(matches!(
+            parse("! only a comment\n"),
+            ScalaError::MissingCount
+        ));
+        assert!(matches!(parse("no count\n"), ScalaError::MissingCount));
+        assert!(matches!(parse("x\ntwelve\n"), ScalaError::Count(_)));
+        assert!(matches!(parse("x\n0\n"), ScalaError::Count(_)));
+        assert!(matches!(
+            parse("x\n3\n100.0\n\n2/1\n"),
+            ScalaError::MissingNotes {
+                expected: 3,
+                found: 2
+            }
+        ));
+        assert!(matches!(parse("x\n1\nfifth\n"), ScalaError::Pitch(_)));
+        assert!(matches!(parse("x\n1\n0/1\n"), ScalaError::Pitch(_)));
+        assert!(matches!(parse("x\n1\n3/0\n"), ScalaError::Pitch(_)));
+        assert!(matches!(parse("x\n1\n-100.0\n"), ScalaError::Period));
+        assert!(matches!(parse("x\n1\n1/1\n"), ScalaError::Period));
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    drumkit::Pad,
+    naming::{Keyswitch, NameTemplate},
+    util::NamedFile,
+};
+
+/// Name of the file that a run's progress is kept in, inside its output directory
+pub const FILE_NAME: &str = "multirec-session.toml";
+
+/// The progress of a run, for resuming it if it is interrupted
+#[derive(Default, Serialize, Deserialize)]
+pub struct Session {
+    /// The command line that started the run
+    pub args: Vec<String>,
+    /// Files that have been recorded in full, one per zone, in the order they were played
+    pub files: Vec<SessionFile>,
+    /// Zones passed over without being recorded, which are not played again either
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub skipped: usize,
+    /// Contents of the config file the run was started with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<toml::Table>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionFile {
+    pub pitch: u8,
+    pub velocity: Option<u8>,
+    pub round_robin: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyswitch: Option<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub controllers: Vec<(u8, u8)>,
+}
+
+impl Session {
+    pub fn path(dir: impl AsRef<Path>) -> PathBuf {
+        dir.as_ref().join(FILE_NAME)
+    }
+
+    pub fn load(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(Self::path(dir))?;
+        Ok(toml::from_str(&text)?)
+    }
+
+    /// Record the files completed so far, and the number of zones skipped
+    ///
+    /// The session is written to a temporary file first and then moved into
+    /// place, so an interruption never leaves it half written.
+    pub fn save<S>(
+        dir: impl AsRef<Path>,
+        args: &[String],
+        config: Option<&toml::Table>,
+        files: &[NamedFile<'_, S>],
+        skipped: usize,
+    ) -> anyhow::Result<()> {
+        let session = Self {
+            args: args.to_vec(),
+            files: files.iter().map(SessionFile::from).collect(),
+            skipped,
+            config: config.cloned(),
+        };
+
+        let path = Self::path(dir);
+        let temp = path.with_extension("toml.tmp");
+        std::fs::write(&temp, toml::to_string(&session)?)?;
+        std::fs::rename(temp, path)?;
+
+        Ok(())
+    }
+
+    /// Forget about a run once it is complete
+    pub fn remove(dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        match std::fs::remove_file(Self::path(dir)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S> From<&NamedFile<'_, S>> for SessionFile {
+    fn from(file: &NamedFile<'_, S>) -> Self {
+        Self {
+            pitch: file.pitch.note_number(),
+            velocity: file.velocity,
+            round_robin: file.round_robin,
+            keyswitch: file
+                .keyswitch
+                .map(|keyswitch| keyswitch.pitch.note_number()),
+            controllers: file.controllers.clone(),
+        }
+    }
+}
+
+impl SessionFile {
+    pub fn named<'t, S>(
+        &self,
+        template: &'t NameTemplate,
+        prefix: Option<S>,
+        keyswitches: &'t [Keyswitch],
+        pads: &'t [Pad],
+    ) -> anyhow::Result<NamedFile<'t, S>> {
+        Ok(NamedFile {
+            template,
+            prefix,
+            pitch: autosam::midi::Pitch::new(self.pitch)?,
+            velocity: self.velocity,
+            round_robin: self.round_robin,
+            keyswitch: self
+                .keyswitch
+                .and_then(|note| Keyswitch::find(keyswitches, note)),
+            pad: Pad::find(pads, self.pitch),
+            controllers: self.controllers.clone(),
+            loop_points: None,
+            loop_crossfade: None,
+            gain: None,
+            tune: None,
+            sample_stop: None,
+        })
+    }
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
@@ -0,0 +1,614 @@
+use std::{
+    num::{NonZeroU8, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use autosam::{
+    dimension::{Dimension, Dimensions, Values},
+    midi::{Channel, ChannelRotation, Mpe, Pitch},
+    Cleanup, Config, NoteLayers, VelocityOrder,
+};
+
+use crate::{
+    arguments::*, count_in, drumkit, mapping, naming, scala, util, RunError, CLEANUP, ONE,
+};
+
+/// How far an edit point may move to reach a zero crossing
+const ZERO_CROSSING_DISTANCE: Duration = Duration::from_millis(5);
+
+/// What a task asks of a run, resolved from its options
+///
+/// Tasks that only play notes leave most of these at their defaults, which
+/// neither save nor process anything.
+pub struct Settings {
+    pub channel: Channel,
+    pub mpe: Option<Mpe>,
+    pub output_dir: PathBuf,
+    pub file_name_prefix: Option<String>,
+    pub name_template: naming::NameTemplate,
+    /// Whether groups are named after their dynamics
+    pub label_groups: bool,
+    pub keyswitches: Vec<Keyswitch>,
+    pub pads: Vec<drumkit::Pad>,
+    pub controller_sweeps: Vec<ControllerSweep>,
+    pub output_format: OutputFormat,
+    pub keep_raw: bool,
+    pub ignore_disk_space: bool,
+    pub append: bool,
+    /// Command to run on each file, and how many to run at once
+    pub post_command: Option<(PostCommand, NonZeroUsize)>,
+    pub metadata: Option<Metadata>,
+    pub bit_depth: BitDepth,
+    /// Length of the sustain to look for loop points in
+    pub loop_search: Option<Duration>,
+    pub render_crossfades: bool,
+    /// Length of the sustain to measure the pitch in
+    pub pitch_detection: Option<Duration>,
+    pub tuning: Option<(scala::Tuning, u8, u8)>,
+    /// How far edit points may move to reach a zero crossing
+    pub zero_snap: Option<Duration>,
+    pub report_paths: Vec<PathBuf>,
+    pub target_rate: Option<u32>,
+    pub on_conflict: OnConflict,
+    /// Output device to audition recordings on, if they are auditioned
+    pub audition: Option<Option<Matcher>>,
+    pub single_take: bool,
+    pub trim_start: Option<(f32, Duration)>,
+    pub trim_end: Option<(f32, Duration)>,
+    pub normalize: Option<(Normalize, f32, bool, bool)>,
+    pub mono: Option<(MonoMode, f32)>,
+    pub calibration: Option<(NonZeroU8, Duration, f32)>,
+    pub noise_floor: Option<(Duration, Option<f64>)>,
+    pub count_in: Option<count_in::CountIn<Option<Matcher>>>,
+    pub zone_selection: Option<ZoneSelection>,
+    pub latency_compensation: LatencyCompensation,
+    pub retries: Option<(f32, u8, bool)>,
+    pub auto_gap: Option<(f32, Duration)>,
+    /// Time recorded after each zone's NoteOff, which is played in full
+    pub tail: Option<Duration>,
+    pub key_map: mapping::KeyMap,
+    pub key_xfade: u8,
+    pub round_robin_channels: Option<ChannelRotation>,
+    pub velocity_levels: u8,
+    pub round_robins: u8,
+    pub note_layers: [Option<NoteLayers>; 128],
+    pub show_plan: bool,
+    /// Whether the plan can be recorded without asking
+    pub confirmed: bool,
+    /// Spacing and floor of the velocity layers to recommend
+    pub measurement: Option<(Decibels, Decibels)>,
+    pub is_dry_run: bool,
+    pub should_save: bool,
+}
+
+impl Settings {
+    /// Resolve the options of a task, along with the sequence it plays
+    pub fn resolve(task: Task, args: &Setup) -> anyhow::Result<(Self, Config)> {
+        let channel = Channel::new(args.midi_channel.get() - 1)?;
+        let mpe = args.mpe.map(|member_channels| Mpe {
+            member_channels,
+            ..Default::default()
+        });
+
+        let mut output_dir = std::env::current_dir()?;
+        let mut file_name_prefix = None;
+        let mut name_template: naming::NameTemplate = naming::DEFAULT_TEMPLATE.parse()?;
+        let mut label_groups = false;
+        let mut keyswitches = Vec::new();
+        let mut pads = Vec::new();
+        let mut controller_sweeps: Vec<ControllerSweep> = Vec::new();
+        let mut output_format = OutputFormat::Raw;
+        let mut keep_raw = false;
+        let mut ignore_disk_space = false;
+        let mut append = false;
+        let mut post_command = None;
+        let mut metadata = None;
+        let mut bit_depth = BitDepth::Int16;
+        let mut loop_search = None;
+        let mut render_crossfades = false;
+        let mut pitch_detection = None;
+        let mut tuning = None;
+        let mut zero_snap = None;
+        let mut report_paths = Vec::new();
+        let mut target_rate = None;
+        let mut on_conflict = OnConflict::Overwrite;
+        let mut audition_args = None;
+        let mut single_take = false;
+        let mut trim_end = None;
+        let mut normalize = None;
+        let mut mono = None;
+        let mut calibration = None;
+        let mut noise_floor = None;
+        let mut count_in = None;
+        let mut zone_selection = None;
+        let mut latency_compensation = LatencyCompensation::Off;
+        let mut retries = None;
+        let mut tail = None;
+        let mut key_map = mapping::KeyMap::Nearest;
+        let mut key_xfade = 0;
+        let mut round_robin_channels = None;
+        let mut show_plan = false;
+        let mut confirmed = true;
+        let mut measurement = None;
+        let is_dry_run;
+        let config;
+        let should_save;
+        let trim_start;
+        let auto_gap;
+
+        match task {
+            Task::Test {
+                dry_run,
+                note,
+                timing,
+            } => {
+                is_dry_run = dry_run;
+                let (length, gap, clock) = timing.resolve()?;
+
+                info!("Testing note {note} with sustain time {length:?} and release time {gap:?}");
+
+                should_save = false;
+                trim_start = None;
+                auto_gap = None;
+                config = Config {
+                    notes: note.note_number()..=note.note_number(),
+                    step: ONE,
+                    velocity_levels: ONE,
+                    round_robins: ONE,
+                    length,
+                    gap,
+                    channel,
+                    mpe,
+                    cleanup: CLEANUP,
+                    clock,
+                    ..Default::default()
+                };
+            }
+            Task::Measure {
+                note,
+                points,
+                layer_spacing,
+                floor,
+                timing,
+            } => {
+                is_dry_run = false;
+                let (length, gap, clock) = timing.resolve()?;
+
+                info!("Measuring the velocity response of note {note} at {points} velocities");
+
+                should_save = false;
+                trim_start = None;
+                auto_gap = None;
+                measurement = Some((layer_spacing, floor));
+                config = Config {
+                    notes: note.note_number()..=note.note_number(),
+                    step: ONE,
+                    velocity_levels: points,
+                    velocity_order: VelocityOrder::SoftToLoud,
+                    round_robins: ONE,
+                    length,
+                    gap,
+                    channel,
+                    mpe,
+                    cleanup: CLEANUP,
+                    clock,
+                    ..Default::default()
+                };
+            }
+            Task::Run(RunOptions {
+                dry_run,
+                yes,
+                start,
+                end,
+                step,
+                notes: note_list,
+                scale,
+                drumkit,
+                velocity_layers,
+                velocity_range,
+                from_multisample,
+                zones,
+                soft_first,
+                round_robins,
+                round_robin_passes,
+                rr_channels,
+                keyswitch: keyswitch_args,
+                cc,
+                trim_start: trim_start_args,
+                trim_end: trim_end_args,
+                mono: mono_args,
+                gap: gap_args,
+                calibration: calibration_args,
+                noise_floor: noise_args,
+                count_in: count_in_args,
+                latency_compensation: compensation,
+                retries: retry_args,
+                normalization,
+                key_map: map,
+                key_xfade: xfade,
+                detect_loops,
+                render_loop_xfade,
+                detect_pitch,
+                snap_to_zero,
+                report,
+                target_rate: resample_to,
+                audition: should_audition,
+                audition_device,
+                single_take: is_single_take,
+                timing,
+                humanize,
+                burst,
+                poly_pressure,
+                microtuning,
+                output_directory,
+                ignore_disk_space: ignore_space,
+                on_conflict: conflict_policy,
+                file_prefix,
+                name_template: template,
+                layout,
+                dynamics,
+                format,
+                keep_raw: keep,
+                append: should_append,
+                post_cmd,
+                post_jobs,
+                metadata: metadata_args,
+                bit_depth: depth,
+                notify: _,
+            }) => {
+                is_dry_run = dry_run;
+                show_plan = true;
+                confirmed = yes || dry_run;
+                let (length, mut gap, clock) = timing.resolve()?;
+
+                // the tail is recorded in the gap after each zone
+                tail = gap_args.tail();
+                if let Some(tail) = tail {
+                    gap = gap.max(tail);
+                }
+
+                let pad_map = drumkit.map(drumkit::PadMap::read).transpose()?;
+
+                output_format = format;
+                // each pad of a kit gets a key of its own
+                key_map = if pad_map.is_some() {
+                    mapping::KeyMap::Single
+                } else {
+                    map
+                };
+                key_xfade = xfade;
+                keep_raw = keep;
+                ignore_disk_space = ignore_space;
+                append = should_append;
+                post_command = post_cmd.map(|command| {
+                    let jobs = post_jobs.unwrap_or_else(|| {
+                        std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+                    });
+                    (command, jobs)
+                });
+                if append && !matches!(output_format, OutputFormat::Bitwig) {
+                    return Err(RunError::AppendFormat.into());
+                }
+                if append && args.input_device.len() > 1 {
+                    return Err(RunError::AppendDevices.into());
+                }
+                metadata = Some(metadata_args);
+                bit_depth = depth;
+                file_name_prefix = file_prefix;
+                label_groups = dynamics.is_some();
+                let (default_template, dynamics_template) = if pad_map.is_some() {
+                    (naming::DRUMKIT_TEMPLATE, naming::DRUMKIT_DYNAMICS_TEMPLATE)
+                } else {
+                    (naming::DEFAULT_TEMPLATE, naming::DYNAMICS_TEMPLATE)
+                };
+                name_template = match (template, dynamics) {
+                    (Some(template), None) => template,
+                    (Some(template), Some(dynamics)) => template.with_dynamics(dynamics),
+                    (None, None) => default_template.parse()?,
+                    (None, Some(dynamics)) => dynamics_template
+                        .parse::<naming::NameTemplate>()?
+                        .with_dynamics(dynamics),
+                };
+                if let Some(directory) = layout.directory(pad_map.is_some(), label_groups) {
+                    name_template = name_template.in_directory(directory.parse()?);
+                }
+                if let Some(d) = output_directory {
+                    output_dir = d;
+                }
+
+                // articulations outermost, so each keyswitch is only changed once per pass
+                let mut dimensions = Dimensions::new();
+                if !keyswitch_args.is_empty() {
+                    let mut notes = Values::new();
+                    for keyswitch in &keyswitch_args {
+                        notes = notes.with(keyswitch.pitch.note_number())?;
+                    }
+                    dimensions = dimensions.with(Dimension::Keyswitch(notes))?;
+
+                    info!(
+                        "Recording articulations {}",
+                        keyswitch_args
+                            .iter()
+                            .map(|keyswitch| format!("{} ({})", keyswitch.label, keyswitch.pitch))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                for sweep in &cc {
+                    dimensions = dimensions.with(Dimension::Controller {
+                        controller: sweep.controller,
+                        values: sweep.values,
+                    })?;
+
+                    info!(
+                        "Recording at controller {} values {}",
+                        sweep.controller,
+                        sweep
+                            .values
+                            .iter()
+                            .map(|value| value.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                if round_robin_passes {
+                    for dimension in Dimensions::round_robin_passes().as_slice() {
+                        dimensions = dimensions.with(*dimension)?;
+                    }
+                }
+                keyswitches = keyswitch_args;
+                controller_sweeps = cc;
+                zone_selection = zones;
+
+                // pads without their own layers use the rest of the options
+                let mut note_layers = [None; 128];
+                if let Some(pad_map) = &pad_map {
+                    for pad in &pad_map.0 {
+                        note_layers[usize::from(pad.pitch.note_number())] = Some(NoteLayers {
+                            velocity_levels: pad.velocity_layers.unwrap_or(velocity_layers),
+                            round_robins: pad.round_robins.unwrap_or(round_robins),
+                        });
+                    }
+
+                    info!(
+                        "Recording pads {}",
+                        pad_map
+                            .0
+                            .iter()
+                            .map(|pad| format!("{} ({})", pad.name, pad.pitch))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+
+                let (notes, step, note_list, velocity_layers, velocity_range, round_robins) =
+                    match from_multisample {
+                        Some(path) => {
+                            let plan = util::SamplingPlan::from_multisample(
+                                &util::read_multisample(&path)?,
+                            )?;
+                            info!("Recording the zones of {} again", path.display());
+                            note_layers = plan.note_layers;
+                            (
+                                plan.notes.iter().next().unwrap_or(0)
+                                    ..=plan.notes.iter().last().unwrap_or(0),
+                                ONE,
+                                Some(plan.notes),
+                                plan.velocity_layers,
+                                plan.velocity_range,
+                                plan.round_robins,
+                            )
+                        }
+                        None => {
+                            let range = start.note_number()..=end.note_number();
+                            let list = match (note_list, scale, &pad_map) {
+                                (Some(list), ..) => Some(list.0),
+                                (None, Some(scale), _) => Some(scale.notes(range.clone())),
+                                (None, None, Some(pad_map)) => Some(
+                                    pad_map.0.iter().try_fold(Values::new(), |notes, pad| {
+                                        notes.with(pad.pitch.note_number())
+                                    })?,
+                                ),
+                                (None, None, None) => None,
+                            };
+                            // the lowest and highest listed notes bound the run
+                            let range = list
+                                .and_then(|list| Some(list.iter().next()?..=list.iter().last()?))
+                                .unwrap_or(range);
+                            // the grid is big enough for the pad with the most layers
+                            let layers = note_layers.iter().flatten();
+                            (
+                                range,
+                                step,
+                                list,
+                                layers
+                                    .clone()
+                                    .map(|layers| layers.velocity_levels)
+                                    .max()
+                                    .unwrap_or(velocity_layers),
+                                velocity_range.0,
+                                layers
+                                    .map(|layers| layers.round_robins)
+                                    .max()
+                                    .unwrap_or(round_robins),
+                            )
+                        }
+                    };
+                let (start, end) = (Pitch::new(*notes.start())?, Pitch::new(*notes.end())?);
+
+                info!(
+                    "Recording {} \
+                    with {velocity_layers} velocity layer{}{}, \
+                    sustain time {length:?} and release time {gap:?}",
+                    match note_list {
+                        Some(list) => format!(
+                            "notes {}",
+                            list.iter()
+                                .filter_map(|note| Pitch::new(note).ok())
+                                .map(|pitch| pitch.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                        None if step.get() == 1 => format!("every note from {start} until {end}"),
+                        None => format!("every {step} notes from {start} until {end}"),
+                    },
+                    if velocity_layers.get() == 1 { "" } else { "s" },
+                    if round_robins.get() == 1 {
+                        String::new()
+                    } else {
+                        format!(" and {round_robins} round-robin variations")
+                    },
+                );
+
+                tuning = microtuning.resolve()?;
+                let tuning_bends = tuning.as_ref().map(|(tuning, root, bend_range)| {
+                    info!("Tuning to the scale \"{}\"", tuning.description);
+                    let (bends, out_of_range) = tuning.bends(*root, *bend_range);
+
+                    let out_of_range: Vec<_> = out_of_range
+                        .into_iter()
+                        .filter(|note| notes.contains(note))
+                        .filter_map(|note| Pitch::new(note).ok())
+                        .map(|pitch| pitch.to_string())
+                        .collect();
+                    if !out_of_range.is_empty() {
+                        warn!(
+                            "The tuning of {} is beyond the bend range of {bend_range} semitones",
+                            out_of_range.join(", "),
+                        );
+                    }
+
+                    bends
+                });
+
+                pads = pad_map.map(|pad_map| pad_map.0).unwrap_or_default();
+                should_save = true;
+                trim_start = trim_start_args.resolve();
+                trim_end = trim_end_args.resolve();
+                auto_gap = gap_args.resolve();
+                calibration = calibration_args.resolve();
+                noise_floor = noise_args.resolve();
+                count_in = count_in_args.resolve(
+                    clock.map_or(Duration::from_secs(1), |tempo| tempo.beats(1.0)),
+                    channel,
+                )?;
+                latency_compensation = compensation;
+                retries = Some(retry_args.resolve());
+                normalize = normalization.resolve();
+                mono = mono_args.resolve();
+                report_paths = report;
+                target_rate = resample_to;
+                on_conflict = conflict_policy;
+                audition_args = should_audition.then_some(audition_device);
+                single_take = is_single_take;
+                if detect_loops {
+                    loop_search = Some(length);
+                    render_crossfades = render_loop_xfade;
+                }
+                if detect_pitch {
+                    pitch_detection = Some(length);
+                }
+                if snap_to_zero {
+                    zero_snap = Some(ZERO_CROSSING_DISTANCE);
+                }
+                if !rr_channels.is_empty() {
+                    round_robin_channels = Some(rr_channels.iter().try_fold(
+                        ChannelRotation::new(),
+                        |rotation, channel| {
+                            Channel::new(channel.get() - 1).map(|channel| rotation.with(channel))
+                        },
+                    )?);
+                }
+                config = Config {
+                    notes,
+                    step,
+                    note_list,
+                    velocity_levels: velocity_layers,
+                    velocity_range,
+                    velocity_order: if soft_first {
+                        VelocityOrder::SoftToLoud
+                    } else {
+                        VelocityOrder::LoudToSoft
+                    },
+                    round_robins,
+                    note_layers,
+                    length,
+                    gap,
+                    channel,
+                    mpe,
+                    round_robin_channels,
+                    // leave the instrument untuned afterwards
+                    cleanup: Cleanup {
+                        reset_all_controllers: tuning.is_some(),
+                        ..CLEANUP
+                    },
+                    zone_reset: gap_args.zone_reset(),
+                    humanize: humanize.resolve(),
+                    clock,
+                    burst: burst.resolve(),
+                    dimensions,
+                    poly_pressure: poly_pressure.resolve(),
+                    tuning: tuning_bends,
+                    ..Default::default()
+                };
+            }
+        }
+
+        let settings = Settings {
+            velocity_levels: config.velocity_levels.get(),
+            round_robins: config.round_robins.get(),
+            note_layers: config.note_layers,
+            channel,
+            mpe,
+            output_dir,
+            file_name_prefix,
+            name_template,
+            label_groups,
+            keyswitches,
+            pads,
+            controller_sweeps,
+            output_format,
+            keep_raw,
+            ignore_disk_space,
+            append,
+            post_command,
+            metadata,
+            bit_depth,
+            loop_search,
+            render_crossfades,
+            pitch_detection,
+            tuning,
+            zero_snap,
+            report_paths,
+            target_rate,
+            on_conflict,
+            audition: audition_args,
+            single_take,
+            trim_start,
+            trim_end,
+            normalize,
+            mono,
+            calibration,
+            noise_floor,
+            count_in,
+            zone_selection,
+            latency_compensation,
+            retries,
+            auto_gap,
+            tail,
+            key_map,
+            key_xfade,
+            round_robin_channels,
+            show_plan,
+            confirmed,
+            measurement,
+            is_dry_run,
+            should_save,
+        };
+
+        Ok((settings, config))
+    }
+}
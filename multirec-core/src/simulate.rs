@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use autosam::midi::{Event, NoteState};
+
+use crate::{
+    plugin::BLOCK_SIZE,
+    runtime::{AudioProcessor, Capture, RunState, TimedEvent},
+};
+
+/// Time for a tone to reach its level after the note starts
+const ATTACK_TIME: Duration = Duration::from_millis(5);
+/// Time for a tone to fall by 60 dB after the note ends
+const RELEASE_TIME: Duration = Duration::from_millis(300);
+/// Level of a tone played at full velocity
+const FULL_LEVEL: f32 = 0.5;
+/// Level below which a released tone is dropped
+const SILENT: f32 = 1e-5;
+
+/// What a simulated instrument plays
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Simulation {
+    /// A sine tone at each note's pitch, louder the higher its velocity
+    Tones,
+    /// Nothing at all
+    Silence,
+}
+
+/// A sounding note of the simulated instrument
+struct Voice {
+    channel: u8,
+    key: u8,
+    /// Phase advance per frame, in radians
+    step: f32,
+    phase: f32,
+    level: f32,
+    target: f32,
+}
+
+/// A stand-in for the MIDI port and audio input, which plays synthetic audio
+pub struct Simulator {
+    simulation: Simulation,
+    sample_rate: u32,
+    voices: Vec<Voice>,
+}
+
+impl Simulator {
+    pub fn new(simulation: Simulation, sample_rate: u32) -> Self {
+        Self {
+            simulation,
+            sample_rate,
+            voices: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self.simulation {
+            Simulation::Tones => "simulated tones",
+            Simulation::Silence => "simulated silence",
+        }
+    }
+
+    /// The stream that the simulation takes the place of
+    pub fn stream_config(&self) -> cpal::StreamConfig {
+        cpal::StreamConfig {
+            channels: 2,
+            sample_rate: cpal::SampleRate(self.sample_rate),
+            buffer_size: cpal::BufferSize::Fixed(BLOCK_SIZE),
+        }
+    }
+
+    /// Start or stop a voice for a note event, ignoring everything else
+    fn play(&mut self, event: &Event) {
+        if matches!(self.simulation, Simulation::Silence) {
+            return;
+        }
+        let Some(note) = event.note() else {
+            return;
+        };
+
+        let channel = note.channel().number();
+        let key = note.pitch().note_number();
+        match note.state() {
+            NoteState::On => {
+                let velocity = f32::from(note.velocity()) / 127.0;
+                let frequency = 440.0 * 2f32.powf((f32::from(key) - 69.0) / 12.0);
+                self.voices.push(Voice {
+                    channel,
+                    key,
+                    step: std::f32::consts::TAU * frequency / self.sample_rate as f32,
+                    phase: 0.0,
+                    level: 0.0,
+                    target: FULL_LEVEL * velocity * velocity,
+                });
+            }
+            NoteState::Off => {
+                for voice in &mut self.voices {
+                    if voice.channel == channel && voice.key == key {
+                        voice.target = 0.0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Play the sequence and record the simulated audio, until the sequence is done
+    ///
+    /// Events take effect at the next frame. Unless `offline`, frames are
+    /// produced no faster than they would play.
+    pub fn render(
+        &mut self,
+        processor: &mut AudioProcessor<f32>,
+        mut events: rtrb::Consumer<TimedEvent>,
+        state: &RunState,
+        offline: bool,
+    ) -> anyhow::Result<()> {
+        let rate = self.sample_rate as f32;
+        let attack = 1.0 / (ATTACK_TIME.as_secs_f32() * rate);
+        // -60 dB over the release time
+        let release = 0.001f32.powf(1.0 / (RELEASE_TIME.as_secs_f32() * rate));
+
+        let block = BLOCK_SIZE as usize;
+        let block_time = Duration::from_secs(u64::from(BLOCK_SIZE)) / self.sample_rate;
+        let started = Instant::now();
+        let mut blocks = 0;
+        while !state.done() {
+            // a block's samples and a marker for each zone that could start in it
+            while offline && !processor.writer.has_room(block * 2 + block) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            for _ in 0..block {
+                let mut sample = 0.0;
+                for voice in &mut self.voices {
+                    if voice.target > voice.level {
+                        voice.level = (voice.level + attack * voice.target).min(voice.target);
+                    } else {
+                        voice.level = (voice.level * release).max(voice.target);
+                    }
+                    sample += voice.level * voice.phase.sin();
+                    voice.phase = (voice.phase + voice.step) % std::f32::consts::TAU;
+                }
+                self.voices
+                    .retain(|voice| voice.target > 0.0 || voice.level > SILENT);
+
+                processor.write_input_data::<f32>(&[sample, sample]);
+
+                while let Ok(TimedEvent { event, .. }) = events.pop() {
+                    self.play(&event);
+                }
+            }
+
+            blocks += 1;
+            if let Some(wait) = (block_time * blocks)
+                .checked_sub(started.elapsed())
+                .filter(|_| !offline)
+            {
+                std::thread::sleep(wait);
+            }
+        }
+
+        info!(
+            "Simulated {:?} of audio in {:?}",
+            block_time * blocks,
+            started.elapsed()
+        );
+        Ok(())
+    }
+}
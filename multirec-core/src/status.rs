@@ -0,0 +1,163 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use autosam::midi::Pitch;
+use log::{debug, info};
+use serde::Serialize;
+
+use crate::runtime::RunState;
+
+/// How long to wait between checks for a connection
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest a client is given to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Serves the progress of a run as JSON over HTTP, at `/` and `/status`
+pub struct Server {
+    listener: TcpListener,
+    state: Arc<RunState>,
+    total_zones: usize,
+    started: Instant,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatusError {
+    #[error("Could not serve the status on {0}: {1}")]
+    Bind(SocketAddr, std::io::Error),
+}
+
+#[derive(Serialize)]
+struct Status {
+    state: &'static str,
+    zone: Zone,
+    completed: usize,
+    total: usize,
+    elapsed_seconds: f64,
+    /// Estimated from the time taken by the zones completed so far
+    remaining_seconds: Option<f64>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Zone {
+    pitch: String,
+    note: u8,
+    velocity: u8,
+    /// Counting from 1
+    round_robin: u8,
+}
+
+impl Server {
+    pub fn bind(
+        address: SocketAddr,
+        state: Arc<RunState>,
+        total_zones: usize,
+    ) -> Result<Self, StatusError> {
+        let listener = TcpListener::bind(address)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|e| StatusError::Bind(address, e))?;
+        info!("Serving status on http://{address}/status");
+
+        Ok(Self {
+            listener,
+            state,
+            total_zones,
+            started: Instant::now(),
+        })
+    }
+
+    /// Answer requests until the run is done
+    pub fn run(self) {
+        while !self.state.done() {
+            match self.listener.accept() {
+                Ok((stream, from)) => {
+                    if let Err(e) = self.respond(stream) {
+                        debug!("Could not answer status request from {from}: {e}");
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => debug!("Could not accept status request: {e}"),
+            }
+        }
+    }
+
+    fn respond(&self, stream: TcpStream) -> std::io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        // the headers are not needed, but are read so the client sees a clean close
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/" | "/status")) => (
+                "200 OK",
+                serde_json::to_string_pretty(&self.status()).map_err(std::io::Error::other)?,
+            ),
+            (Some("GET"), _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+            _ => (
+                "405 Method Not Allowed",
+                r#"{"error":"method not allowed"}"#.to_string(),
+            ),
+        };
+
+        let mut stream = reader.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        stream.flush()
+    }
+
+    fn status(&self) -> Status {
+        let (note, velocity, round_robin) = self.state.note(Ordering::Acquire);
+        let completed = self.state.completed();
+        let elapsed = self.started.elapsed().as_secs_f64();
+
+        let mut warnings = Vec::new();
+        let counts = [
+            (self.state.clipped(), "frames clipped"),
+            (self.state.dropouts(), "audio dropouts"),
+            (self.state.dropped_samples(), "samples lost"),
+            (self.state.timing_slips(), "zones with slipped timing"),
+            (self.state.retries(), "zones recorded again"),
+        ];
+        for (count, what) in counts {
+            if count > 0 {
+                warnings.push(format!("{count} {what}"));
+            }
+        }
+
+        Status {
+            state: self.state.activity(),
+            zone: Zone {
+                pitch: Pitch::new(note).map_or_else(|_| note.to_string(), |p| p.to_string()),
+                note,
+                velocity,
+                round_robin: round_robin + 1,
+            },
+            completed,
+            total: self.total_zones,
+            elapsed_seconds: (elapsed * 10.0).round() / 10.0,
+            remaining_seconds: (completed > 0).then(|| {
+                let remaining = self.total_zones.saturating_sub(completed) as f64;
+                (elapsed / completed as f64 * remaining * 10.0).round() / 10.0
+            }),
+            warnings,
+        }
+    }
+}
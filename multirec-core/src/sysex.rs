@@ -0,0 +1,136 @@
+use std::{path::Path, sync::mpsc, time::Duration};
+
+use log::{debug, info};
+use midir::{Ignore, MidiInput, MidiInputPort};
+
+use crate::monitor::MidiOut;
+
+/// Name of the instrument's patch dump, inside the output directory
+pub const DUMP_FILE_NAME: &str = "patch.syx";
+
+/// A complete System Exclusive message, from `F0` to `F7`
+#[derive(Clone, Debug)]
+pub struct SysEx(pub Vec<u8>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SysExError {
+    #[error("Expected hex bytes, e.g. `F0 7E 7F 09 01 F7`")]
+    Format,
+    #[error("A SysEx message must start with F0 and end with F7")]
+    Framing,
+    #[error("SysEx data byte {0:02X} is above 7F")]
+    DataByte(u8),
+    #[error("Could not read SysEx file `{0}`: {1}")]
+    Read(String, std::io::Error),
+    #[error("SysEx file `{0}` holds no complete message")]
+    Empty(String),
+    #[error("Could not listen on MIDI input port: {0}")]
+    Connect(String),
+    #[error("No patch dump was received within {0:?} of asking for it")]
+    NoDump(Duration),
+}
+
+impl SysEx {
+    fn new(bytes: Vec<u8>) -> Result<Self, SysExError> {
+        let [0xF0, data @ .., 0xF7] = bytes.as_slice() else {
+            return Err(SysExError::Framing);
+        };
+        if let Some(byte) = data.iter().find(|b| **b > 0x7F) {
+            return Err(SysExError::DataByte(*byte));
+        }
+
+        Ok(Self(bytes))
+    }
+
+    /// Read every message in a `.syx` file, in order
+    ///
+    /// Anything between messages is ignored, as some editors pad their dumps.
+    pub fn read_file(path: &Path) -> Result<Vec<Self>, SysExError> {
+        let name = path.display().to_string();
+        let bytes = std::fs::read(path).map_err(|e| SysExError::Read(name.clone(), e))?;
+
+        let mut messages = Vec::new();
+        let mut rest = bytes.as_slice();
+        while let Some(start) = rest.iter().position(|b| *b == 0xF0) {
+            let Some(len) = rest[start..].iter().position(|b| *b == 0xF7) else {
+                break;
+            };
+            messages.push(Self::new(rest[start..=start + len].to_vec())?);
+            rest = &rest[start + len + 1..];
+        }
+
+        if messages.is_empty() {
+            return Err(SysExError::Empty(name));
+        }
+
+        Ok(messages)
+    }
+}
+
+impl std::str::FromStr for SysEx {
+    type Err = SysExError;
+
+    /// Parse hex bytes, with or without spaces between them
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<_> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            return Err(SysExError::Format);
+        }
+
+        let bytes = digits
+            .chunks(2)
+            .map(|pair| {
+                let pair: String = pair.iter().collect();
+                u8::from_str_radix(&pair, 16).map_err(|_| SysExError::Format)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Self::new(bytes)
+    }
+}
+
+/// Ask the instrument for a dump of its patch, and collect the SysEx it sends back
+///
+/// The dump is complete once nothing more arrives for `timeout`, which is
+/// also how long the first message is waited for.
+pub fn capture_dump(
+    mut input: MidiInput,
+    port: &MidiInputPort,
+    output: &mut MidiOut,
+    request: &SysEx,
+    timeout: Duration,
+) -> anyhow::Result<Vec<u8>> {
+    input.ignore(Ignore::TimeAndActiveSense);
+    let port_name = input.port_name(port)?;
+
+    let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+    let _connection = input
+        .connect(
+            port,
+            "autosam-dump",
+            move |_, message, _| {
+                // long dumps may arrive in pieces, so anything that isn't a
+                // channel or real-time message is kept
+                if matches!(message.first(), Some(0x00..=0x7F | 0xF0..=0xF7)) {
+                    let _ = sender.send(message.to_vec());
+                }
+            },
+            (),
+        )
+        .map_err(|e| SysExError::Connect(e.to_string()))?;
+
+    info!("Requesting a patch dump, listening on MIDI input port {port_name}");
+    output.send(&request.0, None)?;
+
+    let mut dump = Vec::new();
+    while let Ok(message) = receiver.recv_timeout(timeout) {
+        debug!("Received {} bytes of patch dump", message.len());
+        dump.extend(message);
+    }
+
+    if dump.is_empty() {
+        return Err(SysExError::NoDump(timeout).into());
+    }
+
+    Ok(dump)
+}
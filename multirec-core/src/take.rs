@@ -0,0 +1,131 @@
+use std::{path::Path, time::Duration};
+
+use log::debug;
+
+use crate::util;
+
+/// Name of the continuous recording of a single-take run, inside each input's directory
+pub const FILE_NAME: &str = "take.wav";
+
+/// Level that the signal must rise above to count as a note's onset (about -50dB)
+const ONSET_THRESHOLD: f32 = 0.003;
+
+/// Time after a zone's start on the timeline that its onset is looked for in
+const ONSET_WINDOW: Duration = Duration::from_millis(500);
+
+/// Time before a zone's start whose level the onset must rise above, so a tail is not taken for it
+const ONSET_BACKGROUND: Duration = Duration::from_millis(10);
+
+/// Where each zone starts in the take, aligned to the onsets heard in it
+///
+/// `cuts` are the frames that each zone's first NoteOn was played at. Each
+/// zone is moved to start the same time before its onset, which is the
+/// median time from a NoteOn to the onset after it, so that MIDI jitter does
+/// not move the start of the samples. Zones whose onset is not found keep
+/// their place on the timeline, and their indices are returned as well.
+pub fn align(samples: &[f32], cuts: &[usize], sample_rate: u32) -> (Vec<usize>, Vec<usize>) {
+    let window = util::frames(ONSET_WINDOW, sample_rate);
+    let background = util::frames(ONSET_BACKGROUND, sample_rate);
+
+    let onsets: Vec<Option<usize>> = cuts
+        .iter()
+        .enumerate()
+        .map(|(idx, &cut)| {
+            let cut = cut.min(samples.len());
+            let end = cuts
+                .get(idx + 1)
+                .map_or(samples.len(), |next| (*next).min(samples.len()))
+                .min(cut + window);
+            let before = samples[cut.saturating_sub(background)..cut]
+                .iter()
+                .fold(0.0, |peak: f32, s| peak.max(s.abs()));
+            let threshold = ONSET_THRESHOLD.max(before * 2.0);
+
+            (cut..end).find(|frame| samples[*frame].abs() > threshold)
+        })
+        .collect();
+
+    let mut latencies: Vec<usize> = onsets
+        .iter()
+        .zip(cuts)
+        .filter_map(|(onset, cut)| onset.map(|onset| onset - cut))
+        .collect();
+    latencies.sort_unstable();
+    let Some(latency) = latencies.get(latencies.len() / 2).copied() else {
+        return (cuts.to_vec(), (0..cuts.len()).collect());
+    };
+    debug!("Notes were heard a median of {latency} frames after they were played");
+
+    let mut starts: Vec<usize> = Vec::with_capacity(cuts.len());
+    let mut missing = Vec::new();
+    for (idx, (onset, cut)) in onsets.iter().zip(cuts).enumerate() {
+        let start = match onset {
+            Some(onset) => onset.saturating_sub(latency),
+            None => {
+                missing.push(idx);
+                *cut
+            }
+        };
+        // each zone keeps at least one frame
+        starts.push(start.max(starts.last().map_or(0, |last| last + 1)));
+    }
+
+    (starts, missing)
+}
+
+/// Split the take in a directory into a file for each zone, starting at the given frames
+///
+/// The frames before the first zone are left out, and the last zone runs to
+/// the end of the take. The files keep the take's sample format.
+pub fn split(dir: &Path, starts: &[usize], names: &[String]) -> anyhow::Result<()> {
+    let reader = hound::WavReader::open(dir.join(FILE_NAME))?;
+    let spec = reader.spec();
+
+    match spec.sample_format {
+        hound::SampleFormat::Float => copy(reader.into_samples::<f32>(), dir, spec, starts, names),
+        hound::SampleFormat::Int => copy(reader.into_samples::<i32>(), dir, spec, starts, names),
+    }
+}
+
+fn copy<S: hound::Sample>(
+    mut samples: impl Iterator<Item = hound::Result<S>>,
+    dir: &Path,
+    spec: hound::WavSpec,
+    starts: &[usize],
+    names: &[String],
+) -> anyhow::Result<()> {
+    let channels = usize::from(spec.channels);
+    let mut position = 0;
+
+    for (idx, name) in names.iter().enumerate() {
+        let start = starts[idx];
+        let end = starts.get(idx + 1).copied().unwrap_or(usize::MAX);
+
+        while position < start && samples.by_ref().take(channels).count() == channels {
+            position += 1;
+        }
+
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        while position < end {
+            let frame: Vec<S> = samples
+                .by_ref()
+                .take(channels)
+                .collect::<hound::Result<_>>()?;
+            if frame.len() < channels {
+                break;
+            }
+
+            for sample in frame {
+                writer.write_sample(sample)?;
+            }
+            position += 1;
+        }
+        writer.finalize()?;
+    }
+
+    Ok(())
+}
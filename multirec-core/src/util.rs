@@ -0,0 +1,699 @@
+use std::{io::Write as _, num::NonZeroU8, time::Duration};
+
+use cpal::{traits::DeviceTrait, SampleRate};
+use log::warn;
+
+use crate::{
+    arguments::BitDepth,
+    checksum,
+    drumkit::Pad,
+    naming::{Keyswitch, NameTemplate, Token},
+};
+
+const PREFERRED_SAMPLE_RATE: u32 = 96_000;
+const BACKUP_SAMPLE_RATE: u32 = 48_000;
+
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    Index(usize),
+    String(String),
+}
+
+impl std::str::FromStr for Matcher {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if let Ok(idx) = s.parse() {
+            Self::Index(idx)
+        } else {
+            Self::String(s.to_lowercase())
+        })
+    }
+}
+
+impl std::fmt::Display for Matcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(idx) => write!(f, "#{idx}"),
+            Self::String(s) => write!(f, "the first matching `{s}`"),
+        }
+    }
+}
+
+impl Matcher {
+    pub fn get<T, E>(
+        &self,
+        iter: impl IntoIterator<Item = T>,
+        accessor: impl Fn(&T) -> Result<String, E>,
+    ) -> Result<Option<T>, E> {
+        match self {
+            Self::Index(idx) => Ok(iter.into_iter().nth(*idx)),
+            Self::String(s) => {
+                for item in iter {
+                    let name = accessor(&item)?;
+                    if name.to_lowercase().contains(s) {
+                        return Ok(Some(item));
+                    }
+                }
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A level in decibels relative to full scale, e.g. `-60dB`
+#[derive(Clone, Copy, Debug)]
+pub struct Decibels(pub f32);
+
+impl Decibels {
+    /// The level as a linear amplitude, where full scale is `1.0`
+    pub fn amplitude(self) -> f32 {
+        10_f32.powf(self.0 / 20.0)
+    }
+}
+
+impl std::str::FromStr for Decibels {
+    type Err = std::num::ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = s
+            .strip_suffix("dB")
+            .or_else(|| s.strip_suffix("db"))
+            .unwrap_or(s);
+
+        number.trim().parse().map(Self)
+    }
+}
+
+/// Free space for new files on the volume that holds a path, in bytes
+///
+/// The path does not have to exist yet, in which case its nearest existing
+/// parent is checked. Returns `None` where this cannot be found out.
+#[cfg(unix)]
+pub fn available_space(path: &std::path::Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // a relative path runs out of parents at the current directory
+    let existing = path
+        .ancestors()
+        .map(|p| match p.as_os_str().is_empty() {
+            true => std::path::Path::new("."),
+            false => p,
+        })
+        .find(|p| p.exists())?;
+    let c_path = std::ffi::CString::new(existing.as_os_str().as_bytes()).ok()?;
+
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is a valid C string, and `stats` is only read if it was filled in
+    if unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stats = unsafe { stats.assume_init() };
+
+    // the field types differ between platforms
+    #[allow(clippy::useless_conversion)]
+    Some(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_: &std::path::Path) -> Option<u64> {
+    None
+}
+
+/// Write a number of bytes in the largest unit that keeps it above 1
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Convert a span of time to a whole number of frames
+pub fn frames(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_secs_f64() * f64::from(sample_rate)) as usize
+}
+
+#[derive(Debug)]
+pub enum MaybeSample<T> {
+    Break,
+    /// Start the previous file over, as its zone is being recorded again
+    Retry,
+    /// The input's timing slipped while the current file was being recorded
+    Slip,
+    Sample(T),
+}
+
+/// Converts captured samples to the bit depth of the saved files
+///
+/// When reducing precision, triangular (TPDF) dither is added before rounding.
+pub struct Quantizer {
+    depth: BitDepth,
+    dither: bool,
+    noise: u32,
+}
+
+impl Quantizer {
+    pub fn new(depth: BitDepth, dither: bool) -> Self {
+        Self {
+            depth,
+            dither,
+            noise: 0x9E37_79B9,
+        }
+    }
+
+    pub fn write<W>(&mut self, writer: &mut hound::WavWriter<W>, sample: f32) -> hound::Result<()>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        let scale = match self.depth {
+            BitDepth::Int16 => 32_768.0,
+            BitDepth::Int24 => 8_388_608.0,
+            BitDepth::Float32 => return writer.write_sample(sample),
+        };
+
+        let dither = if self.dither {
+            self.random() - self.random()
+        } else {
+            0.0
+        };
+
+        writer.write_sample((sample * scale + dither).round().clamp(-scale, scale - 1.0) as i32)
+    }
+
+    /// A uniformly distributed value between 0 and 1
+    fn random(&mut self) -> f32 {
+        // xorshift32
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+
+        (self.noise >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+#[derive(Clone)]
+pub struct NamedFile<'t, S> {
+    pub template: &'t NameTemplate,
+    pub prefix: Option<S>,
+    pub pitch: autosam::midi::Pitch,
+    pub velocity: Option<u8>,
+    pub round_robin: Option<u8>,
+    pub keyswitch: Option<&'t Keyswitch>,
+    pub pad: Option<&'t Pad>,
+    /// Swept controllers and their values, outermost first
+    pub controllers: Vec<(u8, u8)>,
+    pub loop_points: Option<std::ops::Range<usize>>,
+    /// Length of the crossfade at the loop end, in frames, when it is set in the manifest
+    pub loop_crossfade: Option<usize>,
+    /// Gain to play the file at, in dB, when it is set in the manifest rather than the audio
+    pub gain: Option<f32>,
+    /// How far the file was measured to be from its note, in cents
+    pub tune: Option<f32>,
+    /// Number of frames to play, up to the end of the tail after the NoteOff, when it is set in the manifest
+    pub sample_stop: Option<usize>,
+}
+
+impl<S> NamedFile<'_, S> {
+    /// What was played to record the file, e.g. `C4 (MIDI 60), velocity 127, round robin 2`
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!(
+            "{} (MIDI {})",
+            self.pitch,
+            self.pitch.note_number()
+        )];
+        if let Some(velocity) = self.velocity {
+            parts.push(format!("velocity {velocity}"));
+        }
+        if let Some(round_robin) = self.round_robin {
+            parts.push(format!("round robin {}", round_robin + 1));
+        }
+        if let Some(keyswitch) = self.keyswitch {
+            parts.push(format!("articulation {}", keyswitch.label));
+        }
+        if let Some(pad) = self.pad {
+            parts.push(format!("pad {}", pad.name));
+        }
+        for (controller, value) in &self.controllers {
+            parts.push(format!("CC{controller} {value}"));
+        }
+
+        parts.join(", ")
+    }
+}
+
+impl<S> core::fmt::Display for NamedFile<'_, S>
+where
+    S: AsRef<str>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pitch = self.pitch.to_string();
+        let (note, octave) = pitch.split_at(
+            pitch
+                .find(|c: char| c.is_ascii_digit() || c == '-')
+                .unwrap_or(0),
+        );
+
+        self.template.render(f, |token| match token {
+            Token::Prefix => self.prefix.as_ref().map(|p| p.as_ref().to_string()),
+            Token::Pitch => Some(pitch.clone()),
+            Token::Note => Some(note.to_string()),
+            Token::Octave => Some(octave.to_string()),
+            Token::Midi => Some(self.pitch.note_number().to_string()),
+            Token::Velocity => self.velocity.map(|v| v.to_string()),
+            Token::RoundRobin => self.round_robin.map(|rr| (rr + 1).to_string()),
+            Token::Articulation => self.keyswitch.map(|keyswitch| keyswitch.label.clone()),
+            Token::Pad => self.pad.map(|pad| pad.name.clone()),
+            Token::Controllers => (!self.controllers.is_empty()).then(|| {
+                self.controllers
+                    .iter()
+                    .map(|(controller, value)| format!("CC{controller}-{value}"))
+                    .collect::<Vec<_>>()
+                    .join("_")
+            }),
+            Token::Dynamic => self
+                .velocity
+                .map(|velocity| self.template.dynamic(velocity).to_string()),
+        })?;
+
+        f.write_str(".wav")
+    }
+}
+
+/// Pack every file in a directory and its subdirectories into a zip archive
+///
+/// The archive is written under a temporary name and only moved into place once
+/// it is complete, so a failure never leaves a truncated archive behind. A
+/// list of the files' SHA-256 checksums is packed along with them, in place of
+/// any list already in the directory.
+///
+/// With `move_files`, each file is streamed into the archive and removed as
+/// soon as its entry is complete, so packing needs room for little more than
+/// one copy of the files. If packing then fails, the archive is finished with
+/// the files moved into it so far and kept under its temporary name.
+pub fn archive(
+    directory: &std::path::Path,
+    path: &std::path::Path,
+    compression: zip::CompressionMethod,
+    move_files: bool,
+) -> anyhow::Result<()> {
+    let partial = path.with_extension("part");
+    let file = std::io::BufWriter::new(std::fs::File::create(&partial)?);
+    let mut zip_writer = zip::ZipWriter::new(file);
+    let mut moved = 0;
+
+    let mut result = (|| -> anyhow::Result<()> {
+        let opts = zip::write::FileOptions::default().compression_method(compression);
+
+        let mut files = Vec::new();
+        let mut directories = vec![directory.to_path_buf()];
+        while let Some(dir) = directories.pop() {
+            for entry in dir.read_dir()? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if path.is_file() {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+
+        // an entry is only complete once the next one is started
+        let mut packed = None;
+        let mut remove_packed = |packed: Option<std::path::PathBuf>| -> std::io::Result<()> {
+            if let (Some(file), true) = (packed, move_files) {
+                std::fs::remove_file(file)?;
+                moved += 1;
+            }
+            Ok(())
+        };
+
+        let mut sums = Vec::new();
+        for file in files {
+            let Ok(name) = file.strip_prefix(directory) else {
+                continue;
+            };
+            if name.as_os_str() == checksum::FILE_NAME {
+                continue;
+            }
+            // zip archives always separate directories with slashes
+            let name = name
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            zip_writer.start_file(name.as_str(), opts)?;
+            remove_packed(packed.take())?;
+            let hash = checksum::copy(&mut std::fs::File::open(&file)?, &mut zip_writer)?;
+            sums.push((name, hash));
+            packed = Some(file);
+        }
+
+        zip_writer.start_file(checksum::FILE_NAME, opts)?;
+        remove_packed(packed.take())?;
+        zip_writer.write_all(checksum::list(&sums).as_bytes())?;
+
+        zip_writer.finish()?.flush()?;
+        Ok(())
+    })();
+
+    if result.is_ok() {
+        result = std::fs::rename(&partial, path).map_err(Into::into);
+    } else if moved > 0 {
+        // the files moved so far are only in the archive, so it is kept
+        let _ = zip_writer.finish().map(|mut file| file.flush());
+        result = result.map_err(|e| {
+            anyhow::anyhow!("{e}, after moving {moved} files into {}", partial.display())
+        });
+    } else {
+        drop(zip_writer);
+        let _ = std::fs::remove_file(&partial);
+    }
+
+    result
+}
+
+/// Unpack a Bitwig multisample into a directory and read its manifest
+///
+/// Files that are already in the directory are left alone, as they were
+/// recorded more recently than the ones in the archive.
+pub fn unpack(
+    path: &std::path::Path,
+    directory: &std::path::Path,
+) -> anyhow::Result<dot_multisample::Multisample<'static>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut manifest = None;
+
+    std::fs::create_dir_all(directory)?;
+
+    for idx in 0..archive.len() {
+        let mut file = archive.by_index(idx)?;
+        let Some(name) = file.enclosed_name().map(std::path::PathBuf::from) else {
+            warn!("Skipping {} in {}", file.name(), path.display());
+            continue;
+        };
+
+        if name.as_os_str() == "multisample.xml" {
+            let mut text = String::new();
+            std::io::Read::read_to_string(&mut file, &mut text)?;
+            manifest = Some(text);
+            continue;
+        }
+
+        // the checksums are worked out afresh when the bundle is packed again
+        if file.is_dir() || name.as_os_str() == checksum::FILE_NAME {
+            continue;
+        }
+
+        let target = directory.join(name);
+        if !target.exists() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::io::copy(&mut file, &mut std::fs::File::create(target)?)?;
+        }
+    }
+
+    let Some(manifest) = manifest else {
+        anyhow::bail!("{} has no multisample.xml", path.display());
+    };
+
+    Ok(quick_xml::de::from_str::<dot_multisample::Multisample>(&manifest)?.to_owned())
+}
+
+/// Read the manifest of a Bitwig multisample, or a bare `multisample.xml`
+pub fn read_multisample(
+    path: &std::path::Path,
+) -> anyhow::Result<dot_multisample::Multisample<'static>> {
+    let text = if path.extension().is_some_and(|ext| ext == "xml") {
+        std::fs::read_to_string(path)?
+    } else {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("multisample.xml")?, &mut text)?;
+        text
+    };
+
+    Ok(quick_xml::de::from_str::<dot_multisample::Multisample>(&text)?.to_owned())
+}
+
+/// The zones to record again to replace the samples of a multisample
+pub struct SamplingPlan {
+    pub notes: autosam::dimension::Values,
+    pub velocity_layers: NonZeroU8,
+    pub velocity_range: std::ops::RangeInclusive<u8>,
+    pub round_robins: NonZeroU8,
+    /// Fewer velocity layers or round robins for some notes, indexed by note number
+    pub note_layers: [Option<autosam::NoteLayers>; 128],
+}
+
+impl SamplingPlan {
+    /// Work out the notes, velocity layers and round robins that play exactly a multisample's samples
+    ///
+    /// The velocity range is the one that the sequencer spreads the layers
+    /// over to play the samples' velocities, so a multisample that no
+    /// sequence plays, e.g. one with humanized velocities, is an error.
+    pub fn from_multisample(multi: &dot_multisample::Multisample<'_>) -> anyhow::Result<Self> {
+        let mut zones = std::collections::BTreeMap::new();
+        for sample in multi.samples() {
+            let Some(root) = sample.key().as_ref().and_then(|key| key.root()) else {
+                anyhow::bail!(
+                    "{} has no root key to record it at",
+                    sample.file().display()
+                );
+            };
+            let velocity = sample.velocity().as_ref().and_then(|v| v.high());
+            *zones
+                .entry((root, velocity.unwrap_or(127)))
+                .or_insert(0usize) += 1;
+        }
+
+        let Some(top) = zones.keys().map(|(_, velocity)| *velocity).max() else {
+            anyhow::bail!("The multisample has no samples");
+        };
+
+        let notes = zones
+            .keys()
+            .try_fold(autosam::dimension::Values::new(), |notes, (note, _)| {
+                notes.with(*note)
+            })?;
+        let layers = |note: u8| {
+            let counts = zones
+                .range((note, 0)..=(note, 127))
+                .map(|(_, count)| *count);
+            (counts.clone().count(), counts.max().unwrap_or(1))
+        };
+        let velocity_layers = notes.iter().map(|note| layers(note).0).max().unwrap_or(1);
+        let round_robins = notes.iter().map(|note| layers(note).1).max().unwrap_or(1);
+        let velocity_layers = NonZeroU8::try_from(u8::try_from(velocity_layers)?)?;
+        let round_robins = NonZeroU8::try_from(u8::try_from(round_robins)?)?;
+
+        let mut note_layers = [None; 128];
+        for note in notes.iter() {
+            let (levels, rrs) = layers(note);
+            if (levels, rrs) != (velocity_layers.get().into(), round_robins.get().into()) {
+                note_layers[usize::from(note)] = Some(autosam::NoteLayers {
+                    velocity_levels: NonZeroU8::try_from(levels as u8)?,
+                    round_robins: NonZeroU8::try_from(rrs as u8)?,
+                });
+            }
+        }
+
+        // the layers are played at the top of their share of the range, so find its bottom
+        for low in 0..=top {
+            let plan = Self {
+                notes,
+                velocity_layers,
+                velocity_range: low..=top,
+                round_robins,
+                note_layers,
+            };
+            let config = autosam::Config {
+                notes: notes.iter().next().unwrap_or(0)..=notes.iter().last().unwrap_or(0),
+                note_list: Some(plan.notes),
+                velocity_levels: plan.velocity_layers,
+                velocity_range: plan.velocity_range.clone(),
+                round_robins: plan.round_robins,
+                note_layers: plan.note_layers,
+                ..Default::default()
+            };
+            let Ok(seq) = autosam::Sequencer::new(config, BACKUP_SAMPLE_RATE) else {
+                continue;
+            };
+
+            let mut played = std::collections::BTreeMap::new();
+            for zone in crate::plan::zones(seq) {
+                *played
+                    .entry((zone.pitch().note_number(), zone.velocity()))
+                    .or_insert(0usize) += 1;
+            }
+            if played == zones {
+                return Ok(plan);
+            }
+        }
+
+        anyhow::bail!(
+            "The multisample's zones cannot be recorded again exactly, \
+            as no velocity range plays its velocities ({})",
+            zones
+                .keys()
+                .map(|(_, velocity)| *velocity)
+                .collect::<std::collections::BTreeSet<_>>()
+                .iter()
+                .rev()
+                .map(u8::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+pub struct Utf8File(std::fs::File);
+
+impl Utf8File {
+    pub fn xml(name: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let mut f = std::fs::File::create(name)?;
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        Ok(Self(f))
+    }
+}
+
+impl std::fmt::Write for Utf8File {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0.write_all(s.as_bytes()).map_err(|_| std::fmt::Error)
+    }
+
+    fn write_fmt(&mut self, args: std::fmt::Arguments<'_>) -> std::fmt::Result {
+        self.0.write_fmt(args).map_err(|_| std::fmt::Error)
+    }
+}
+
+pub fn get_best_config(
+    input_device: &cpal::Device,
+) -> Result<cpal::SupportedStreamConfig, anyhow::Error> {
+    let get_config_with_sample_rate = |sr| {
+        move |c: cpal::SupportedStreamConfigRange| {
+            (c.min_sample_rate().0..=c.max_sample_rate().0)
+                .contains(&sr)
+                .then(|| c.with_sample_rate(SampleRate(sr)))
+        }
+    };
+
+    if let Some(c) = input_device
+        .supported_input_configs()?
+        .find_map(get_config_with_sample_rate(PREFERRED_SAMPLE_RATE))
+    {
+        return Ok(c);
+    }
+
+    warn!("Device does not support preferred sample rate of {PREFERRED_SAMPLE_RATE}");
+
+    if let Some(c) = input_device
+        .supported_input_configs()?
+        .find_map(get_config_with_sample_rate(BACKUP_SAMPLE_RATE))
+    {
+        return Ok(c);
+    }
+
+    warn!("Device does not support backup sample rate of {BACKUP_SAMPLE_RATE}");
+
+    Ok(input_device.default_input_config()?)
+}
+
+/// Format a duration as hours, minutes and seconds
+pub fn clock_time(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multisample(zones: &[(u8, u8)]) -> dot_multisample::Multisample<'static> {
+        dot_multisample::Multisample::default().with_samples(zones.iter().map(
+            |(note, velocity)| {
+                dot_multisample::Sample::default()
+                    .with_file(std::path::PathBuf::from(format!("{note}_{velocity}.wav")))
+                    .with_key(dot_multisample::Key::default().with_root(*note))
+                    .with_velocity(dot_multisample::ZoneInfo::default().with_high(*velocity))
+            },
+        ))
+    }
+
+    #[test]
+    fn archive_moves_files_into_it() {
+        let dir = std::env::temp_dir().join(format!("multirec-archive-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("C4.wav"), "C4").unwrap();
+        std::fs::write(dir.join("sub").join("D4.wav"), "D4").unwrap();
+        let path = dir.with_extension("zip");
+
+        archive(&dir, &path, zip::CompressionMethod::Stored, true).unwrap();
+        assert!(!dir.join("C4.wav").exists());
+        assert!(!dir.join("sub").join("D4.wav").exists());
+        assert!(!path.with_extension("part").exists());
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<_> = zip.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["C4.wav", checksum::FILE_NAME, "sub/D4.wav"]);
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("sub/D4.wav").unwrap(), &mut text).unwrap();
+        assert_eq!(text, "D4");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sampling_plan_uses_the_sequencers_velocities() {
+        let plan = SamplingPlan::from_multisample(&multisample(&[
+            (60, 127),
+            (60, 84),
+            (60, 41),
+            (64, 127),
+            (64, 84),
+            (64, 41),
+        ]))
+        .unwrap();
+
+        assert_eq!(plan.velocity_layers.get(), 3);
+        assert_eq!(plan.velocity_range, 0..=127);
+        assert_eq!(plan.round_robins.get(), 1);
+    }
+
+    #[test]
+    fn sampling_plan_keeps_irregular_notes() {
+        let plan = SamplingPlan::from_multisample(&multisample(&[(48, 127), (50, 127), (55, 127)]))
+            .unwrap();
+
+        assert_eq!(plan.notes.iter().collect::<Vec<_>>(), [48, 50, 55]);
+        assert_eq!(plan.velocity_layers.get(), 1);
+    }
+
+    #[test]
+    fn sampling_plan_rejects_velocities_no_sequence_plays() {
+        assert!(
+            SamplingPlan::from_multisample(&multisample(&[(60, 127), (60, 100), (60, 10),]))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    io::Read as _,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use dot_multisample::{LoopMode, Multisample, Sample};
+
+use crate::checksum;
+
+/// A Bitwig multisample, either packed or unpacked into a directory
+enum Bundle {
+    Directory(PathBuf),
+    Archive(zip::ZipArchive<std::fs::File>),
+}
+
+impl Bundle {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        Ok(if path.is_dir() {
+            Self::Directory(path.to_path_buf())
+        } else {
+            Self::Archive(zip::ZipArchive::new(std::fs::File::open(path)?)?)
+        })
+    }
+
+    /// Read a file from the bundle, or `None` if it isn't there
+    fn read(&mut self, name: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+        match self {
+            Self::Directory(dir) => match std::fs::read(dir.join(name)) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            Self::Archive(archive) => {
+                let name = name.to_string_lossy().replace('\\', "/");
+                let mut file = match archive.by_name(&name) {
+                    Ok(file) => file,
+                    Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes)?;
+                Ok(Some(bytes))
+            }
+        }
+    }
+}
+
+/// The keys, velocities and select values that a sample plays for
+#[derive(Clone, PartialEq)]
+struct Zone {
+    keys: RangeInclusive<u8>,
+    velocities: RangeInclusive<u8>,
+    select: RangeInclusive<u8>,
+    /// The keys left once the fades at either end are taken off
+    solid_keys: RangeInclusive<u8>,
+}
+
+impl Zone {
+    fn new(sample: &Sample<'_>) -> Self {
+        let key = sample.key().clone().unwrap_or_default();
+        let (low, high) = (key.low().unwrap_or(0), key.high().unwrap_or(127));
+        let range = |info: &Option<dot_multisample::ZoneInfo>| {
+            info.as_ref().map_or(0..=127, |info| {
+                info.low().unwrap_or(0)..=info.high().unwrap_or(127)
+            })
+        };
+
+        Self {
+            keys: low..=high,
+            velocities: range(sample.velocity()),
+            select: range(sample.select()),
+            solid_keys: low.saturating_add(key.low_fade().unwrap_or(0))
+                ..=high.saturating_sub(key.high_fade().unwrap_or(0)),
+        }
+    }
+
+    /// Whether this and another zone are played for the same keys, velocities and select values
+    ///
+    /// Zones of the same range take turns as round robins, and zones only
+    /// overlapping where one fades out and the other fades in are crossfaded.
+    fn clashes_with(&self, other: &Self) -> bool {
+        let same = self.keys == other.keys
+            && self.velocities == other.velocities
+            && self.select == other.select;
+
+        !same
+            && overlap(&self.solid_keys, &other.solid_keys).is_some()
+            && overlap(&self.velocities, &other.velocities).is_some()
+            && overlap(&self.select, &other.select).is_some()
+    }
+}
+
+fn overlap(a: &RangeInclusive<u8>, b: &RangeInclusive<u8>) -> Option<RangeInclusive<u8>> {
+    let range = *a.start().max(b.start())..=*a.end().min(b.end());
+    (!range.is_empty()).then_some(range)
+}
+
+fn format_range(range: &RangeInclusive<u8>) -> String {
+    if range.start() == range.end() {
+        range.start().to_string()
+    } else {
+        format!("{}-{}", range.start(), range.end())
+    }
+}
+
+/// Check a Bitwig multisample for problems, returning a description of each
+///
+/// The manifest must be readable, and every sample in it present and a
+/// readable WAV file, with its start, stop and loop points inside the audio.
+/// Samples that are played for the same keys and velocities without being
+/// round robins of each other, and keys or velocities that no sample plays
+/// for, are reported as well. If the bundle lists the checksums of its
+/// files, each file listed must be there and still match.
+pub fn verify(path: &Path) -> anyhow::Result<Vec<String>> {
+    let mut bundle = Bundle::open(path)?;
+    let mut problems = Vec::new();
+
+    let Some(manifest) = bundle.read(Path::new("multisample.xml"))? else {
+        problems.push("There is no multisample.xml".to_string());
+        return Ok(problems);
+    };
+    let manifest = match String::from_utf8(manifest)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| Ok(quick_xml::de::from_str::<Multisample>(&text)?.to_owned()))
+    {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            problems.push(format!("The manifest is not valid: {e}"));
+            return Ok(problems);
+        }
+    };
+
+    if manifest.samples().is_empty() {
+        problems.push("The manifest lists no samples".to_string());
+    }
+
+    for sample in manifest.samples() {
+        let name = sample.file().display();
+        let mut problem = |message: String| problems.push(format!("{name}: {message}"));
+
+        check_mapping(&manifest, sample, &mut problem);
+
+        let Some(bytes) = bundle.read(sample.file())? else {
+            problem("The file is missing".to_string());
+            continue;
+        };
+        match frames(bytes) {
+            Ok(frames) => check_points(sample, frames, &mut problem),
+            Err(e) => problem(format!("The file is not a readable WAV file: {e}")),
+        }
+    }
+
+    check_zones(manifest.samples(), &mut problems);
+    check_sums(&mut bundle, &mut problems)?;
+
+    Ok(problems)
+}
+
+/// Check every file against the bundle's list of checksums, if it has one
+fn check_sums(bundle: &mut Bundle, problems: &mut Vec<String>) -> anyhow::Result<()> {
+    let Some(list) = bundle.read(Path::new(checksum::FILE_NAME))? else {
+        return Ok(());
+    };
+    let sums = checksum::parse(&String::from_utf8_lossy(&list));
+    if sums.is_empty() {
+        problems.push(format!("{} lists no checksums", checksum::FILE_NAME));
+    }
+
+    for (name, expected) in sums {
+        let Some(bytes) = bundle.read(Path::new(&name))? else {
+            problems.push(format!(
+                "{name}: The file is listed in {} but missing",
+                checksum::FILE_NAME
+            ));
+            continue;
+        };
+
+        let mut hash = checksum::Sha256::default();
+        hash.update(&bytes);
+        if hash.finish() != expected {
+            problems.push(format!(
+                "{name}: The checksum does not match, so the file has changed since it was packed"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Read every sample of a WAV file, returning its length in frames
+fn frames(bytes: Vec<u8>) -> anyhow::Result<u64> {
+    let mut reader = hound::WavReader::new(std::io::Cursor::new(bytes))?;
+    let frames = reader.duration();
+
+    match reader.spec().sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().try_for_each(|s| s.map(drop))?,
+        hound::SampleFormat::Int => reader.samples::<i32>().try_for_each(|s| s.map(drop))?,
+    }
+
+    Ok(u64::from(frames))
+}
+
+/// Check that a sample's key, velocity and group make sense
+fn check_mapping(
+    manifest: &Multisample<'_>,
+    sample: &Sample<'_>,
+    problem: &mut impl FnMut(String),
+) {
+    match sample.key() {
+        None => problem("No key range or root key is given".to_string()),
+        Some(key) => {
+            let (low, high) = (key.low().unwrap_or(0), key.high().unwrap_or(127));
+            if low > high {
+                problem(format!("The lowest key {low} is above the highest {high}"));
+            }
+            if key.root().is_some_and(|root| root > 127) || high > 127 {
+                problem("A key is above 127".to_string());
+            }
+            let fades =
+                u16::from(key.low_fade().unwrap_or(0)) + u16::from(key.high_fade().unwrap_or(0));
+            if low <= high && fades > u16::from(high - low) + 1 {
+                problem(format!(
+                    "Key fades of {fades} keys are wider than the key range"
+                ));
+            }
+        }
+    }
+
+    if let Some(velocity) = sample.velocity() {
+        let (low, high) = (velocity.low().unwrap_or(0), velocity.high().unwrap_or(127));
+        if low > high {
+            problem(format!(
+                "The lowest velocity {low} is above the highest {high}"
+            ));
+        } else if high > 127 {
+            problem("A velocity is above 127".to_string());
+        }
+    }
+
+    if let Some(group) = sample.group() {
+        // a negative group stands for none
+        if usize::try_from(group).is_ok_and(|group| group >= manifest.groups().len()) {
+            problem(format!("Group {group} does not exist"));
+        }
+    }
+}
+
+/// Check that a sample's start, stop and loop points are inside its audio
+fn check_points(sample: &Sample<'_>, frames: u64, problem: &mut impl FnMut(String)) {
+    let frames = frames as f64;
+    let start = sample.sample_start().unwrap_or(0.0);
+    let stop = sample.sample_stop().unwrap_or(frames);
+
+    if !(0.0..frames).contains(&start) {
+        problem(format!(
+            "The sample start {start} is outside its {frames} frames"
+        ));
+    }
+    if stop > frames {
+        problem(format!(
+            "The sample stop {stop} is past its {frames} frames"
+        ));
+    }
+    if stop <= start {
+        problem(format!(
+            "The sample stop {stop} is not after its start {start}"
+        ));
+    }
+
+    let Some(points) = sample.r#loop() else {
+        return;
+    };
+    if points.mode().unwrap_or_default() == LoopMode::Off {
+        return;
+    }
+
+    let (Some(loop_start), Some(loop_stop)) = (points.start(), points.stop()) else {
+        problem("The loop is missing its start or stop".to_string());
+        return;
+    };
+    if loop_stop <= loop_start {
+        problem(format!(
+            "The loop stop {loop_stop} is not after its start {loop_start}"
+        ));
+    }
+    if loop_start < start || loop_stop > stop {
+        problem(format!(
+            "The loop {loop_start}-{loop_stop} is outside the sample's {start}-{stop}"
+        ));
+    }
+    if points
+        .fade()
+        .is_some_and(|fade| !(0.0..=1.0).contains(&fade))
+    {
+        problem("The loop's crossfade is not between 0 and 1".to_string());
+    }
+}
+
+/// Report samples that clash with each other, and keys and velocities that no sample plays for
+fn check_zones(samples: &[Sample<'_>], problems: &mut Vec<String>) {
+    let zones: Vec<Zone> = samples.iter().map(Zone::new).collect();
+
+    for (idx, zone) in zones.iter().enumerate() {
+        for (other_idx, other) in zones.iter().enumerate().skip(idx + 1) {
+            if !zone.clashes_with(other) {
+                continue;
+            }
+
+            // both are known to overlap by now
+            let keys = overlap(&zone.solid_keys, &other.solid_keys).unwrap_or(0..=0);
+            let velocities = overlap(&zone.velocities, &other.velocities).unwrap_or(0..=0);
+            problems.push(format!(
+                "{} and {} overlap on keys {} at velocities {}",
+                samples[idx].file().display(),
+                samples[other_idx].file().display(),
+                format_range(&keys),
+                format_range(&velocities),
+            ));
+        }
+    }
+
+    let (Some(lowest), Some(highest)) = (
+        zones.iter().map(|zone| *zone.keys.start()).min(),
+        zones.iter().map(|zone| *zone.keys.end()).max(),
+    ) else {
+        return;
+    };
+
+    // velocities from 1, as 0 is a NoteOff
+    let missing = |key: u8| -> Vec<u8> {
+        (1..=127)
+            .filter(|velocity| {
+                !zones
+                    .iter()
+                    .any(|zone| zone.keys.contains(&key) && zone.velocities.contains(velocity))
+            })
+            .collect()
+    };
+
+    // keys are reported in runs that lack the same velocities
+    let mut runs: Vec<(RangeInclusive<u8>, Vec<u8>)> = Vec::new();
+    for key in lowest..=highest {
+        let velocities = missing(key);
+        match runs.last_mut() {
+            Some((keys, missing)) if *missing == velocities => *keys = *keys.start()..=key,
+            _ => runs.push((key..=key, velocities)),
+        }
+    }
+
+    problems.extend(
+        runs.iter()
+            .filter(|(_, missing)| !missing.is_empty())
+            .map(|(keys, missing)| gap(keys, missing)),
+    );
+}
+
+/// Describe keys that lack samples for some velocities
+fn gap(keys: &RangeInclusive<u8>, missing: &[u8]) -> String {
+    let keys = if keys.start() == keys.end() {
+        format!("Key {}", keys.start())
+    } else {
+        format!("Keys {}", format_range(keys))
+    };
+
+    if missing.len() == 127 {
+        return format!("{keys}: no sample plays");
+    }
+
+    // consecutive velocities are joined into ranges
+    let mut ranges: Vec<RangeInclusive<u8>> = Vec::new();
+    for &velocity in missing {
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == velocity => *range = *range.start()..=velocity,
+            _ => ranges.push(velocity..=velocity),
+        }
+    }
+    let ranges: Vec<String> = ranges.iter().map(format_range).collect();
+
+    format!(
+        "{keys}: no sample plays at velocities {}",
+        ranges.join(", ")
+    )
+}
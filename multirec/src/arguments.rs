@@ -1,83 +1,283 @@
-use std::{num::NonZeroU8, path::PathBuf};
+use std::{fmt::Write as _, path::PathBuf};
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, FromArgMatches, Parser};
 
-use autosam::midi::Pitch;
-
-use crate::{util::Matcher, ONE};
+use multirec_core::{
+    arguments::{ConvertFormat, ZoneSelection},
+    MapOptions, Setup, Task,
+};
 
 #[derive(Parser)]
 #[command(author, version, about)]
 pub struct Args {
     #[clap(subcommand)]
     pub cmd: Command,
-    /// Select an audio host by index
+    #[command(flatten)]
+    pub setup: Setup,
+    /// Read default options from a TOML file (see `multirec init`)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Show a live dashboard while recording, with controls to pause or abort
     #[arg(long)]
-    pub host: Option<Matcher>,
-    /// Select an audio input to record from
-    #[arg(long, short = 'i')]
-    pub input_device: Option<Matcher>,
-    /// Select a MIDI port to output to
-    #[arg(long, default_value = "0")]
-    pub midi_port: Matcher,
-    /// Select a MIDI channel to send on
-    #[arg(long, short = 'c', default_value_t = ONE)]
-    pub midi_channel: NonZeroU8,
+    pub tui: bool,
     /// Specify verbosity of log messages
     #[arg(long, default_value = "warn")]
     pub min_log_level: log::LevelFilter,
+    /// Also append every log message, down to debug level, to this file
+    ///
+    /// Messages are written with timestamps whatever --min-log-level is, so
+    /// that an unattended run can be looked into afterwards.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// The command line these were parsed from, saved with a run's session
+    #[arg(skip)]
+    pub argv: Vec<String>,
+}
+
+impl Args {
+    /// Parse the command line, taking default values from the config file it names
+    ///
+    /// Like [`Parser::parse`], this exits with a message if the arguments or
+    /// the file are invalid. The file's contents are returned as well.
+    pub fn parse_with_config() -> (Self, Option<toml::Table>) {
+        let argv: Vec<_> = std::env::args_os().collect();
+
+        let config = Self::command()
+            .ignore_errors(true)
+            .try_get_matches_from(&argv)
+            .ok()
+            .and_then(|matches| matches.get_one::<PathBuf>("config").cloned());
+
+        let table = config.map(|path| {
+            std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| text.parse::<toml::Table>().map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| {
+                    Self::command()
+                        .error(
+                            ErrorKind::Io,
+                            format!("Could not read config file `{}`: {e}", path.display()),
+                        )
+                        .exit()
+                })
+        });
+
+        match Self::try_parse_with(argv, table.as_ref()) {
+            Ok(args) => (args, table),
+            Err(e) => e.exit(),
+        }
+    }
+
+    /// Parse a command line, with default values from the contents of a config file
+    ///
+    /// Top-level keys are global options, and the `run` and `test` tables
+    /// hold the options of those commands. Keys are the long option names.
+    pub fn try_parse_with<I, T>(argv: I, config: Option<&toml::Table>) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let mut command = Self::command();
+
+        for (key, value) in config.into_iter().flatten() {
+            command = match value {
+                toml::Value::Table(section) if matches!(key.as_str(), "run" | "test") => {
+                    let mut subcommand = command
+                        .find_subcommand(key)
+                        .cloned()
+                        .unwrap_or_else(|| clap::Command::new(key.clone()));
+                    for (key, value) in section {
+                        subcommand = with_default(subcommand, key, value)?;
+                    }
+
+                    command.mut_subcommand(key, |_| subcommand)
+                }
+                _ => with_default(command, key, value)?,
+            };
+        }
+
+        let argv: Vec<std::ffi::OsString> = argv.into_iter().map(Into::into).collect();
+        let mut args = Self::from_arg_matches(&command.try_get_matches_from(&argv)?)?;
+        args.argv = argv
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        Ok(args)
+    }
+}
+
+/// Use a config file value as the default for the option with that long name
+fn with_default(
+    command: clap::Command,
+    key: &str,
+    value: &toml::Value,
+) -> Result<clap::Command, clap::Error> {
+    let id = command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(key) && key != "config")
+        .map(|arg| arg.get_id().clone());
+
+    let value = match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    };
+
+    match (id, value) {
+        (Some(id), Some(value)) => Ok(command.mut_arg(id, |arg| arg.default_value(value))),
+        (Some(_), None) => Err(command.clone().error(
+            ErrorKind::InvalidValue,
+            format!("Config file value for `{key}` must be a string, number or boolean"),
+        )),
+        (None, _) => {
+            let name = command.get_name().to_string();
+            Err(command.clone().error(
+                ErrorKind::UnknownArgument,
+                format!("Unknown option `{key}` in config file (for `{name}`)"),
+            ))
+        }
+    }
+}
+
+/// A config file with every option commented out, showing its default value
+pub fn config_template() -> String {
+    let mut command = Args::command();
+    command.build();
+
+    let mut template = String::from(
+        "# multirec config file, for use with --config\n\
+        # Options given on the command line take precedence over these.\n",
+    );
+    write_options(&mut template, &command);
+
+    for name in ["run", "test"] {
+        if let Some(subcommand) = command.find_subcommand(name) {
+            let _ = write!(template, "\n[{name}]\n");
+            write_options(&mut template, subcommand);
+        }
+    }
+
+    template
 }
 
+fn write_options(template: &mut String, command: &clap::Command) {
+    for arg in command.get_arguments() {
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+
+        if matches!(long, "help" | "version" | "config") {
+            continue;
+        }
+
+        let value = match arg.get_default_values() {
+            [value] => {
+                let value = value.to_string_lossy();
+                if value.parse::<f64>().is_ok_and(f64::is_finite) || value.parse::<bool>().is_ok() {
+                    value.into_owned()
+                } else {
+                    toml::Value::String(value.into_owned()).to_string()
+                }
+            }
+            _ => format!(
+                "<{}>",
+                arg.get_value_names()
+                    .and_then(|names| names.first())
+                    .map_or_else(|| long.to_uppercase(), |name| name.to_string())
+            ),
+        };
+
+        let _ = writeln!(template);
+        if let Some(help) = arg.get_help() {
+            let _ = writeln!(template, "# {help}");
+        }
+        let _ = writeln!(template, "# {long} = {value}");
+    }
+}
+
+// parsed once at startup, so the size of `Task` does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(clap::Subcommand)]
 pub enum Command {
     /// Display information about the system
     #[clap(subcommand)]
     Show(Show),
-    /// Run the auto-sampling routine
-    Run {
-        /// Multi-sample package format to generate
-        #[arg(long, short = 'f', default_value = "raw")]
-        format: OutputFormat,
-        /// Directory to save recordings in [default: current]
-        #[arg(long, short = 'o')]
-        output_directory: Option<PathBuf>,
-        /// Prefix for file names
-        #[arg(long, short = 'p')]
-        file_prefix: Option<String>,
-        /// Print configuration and exit
-        #[clap(long, short = 'n')]
-        dry_run: bool,
-        /// Lowest note to sample (MIDI note name or number)
-        #[arg(long, default_value = "21")]
-        start: Pitch,
-        /// Highest note to sample (MIDI note name or number)
-        #[arg(long, default_value = "108")]
-        end: Pitch,
-        /// Step between notes, in semitones
-        #[arg(long, default_value_t = ONE)]
-        step: NonZeroU8,
-        /// Number of velocity layers to sample
-        #[arg(long, default_value_t = ONE)]
-        velocity_layers: NonZeroU8,
-        /// Number of round-robin samples to take of each velocity layer
-        #[arg(long, default_value_t = ONE)]
-        round_robins: NonZeroU8,
-        /// Discard silence at the beginning of each sample
+    #[command(flatten)]
+    Task(Task),
+    /// Write a config file template, listing every option with its default
+    Init {
+        /// File to write [default: print to standard output]
+        output: Option<PathBuf>,
+    },
+    /// Build an SFZ or Bitwig multisample from a directory of existing WAV files, without recording
+    ///
+    /// Each file's note, velocity and round robin are read from its name,
+    /// e.g. `Piano_C#4_V96_RR2.wav` or `Piano-Eb2-ff.wav`. Files of the same
+    /// note and velocity without round robin numbers are played in turn, in
+    /// the order of their names.
+    Map(MapOptions),
+    /// Check a Bitwig multisample for problems, exiting with an error if there are any
+    ///
+    /// Checks that the manifest can be read, that every sample is present
+    /// and a readable WAV file with its start, stop and loop points inside
+    /// the audio, and that the samples cover their keys and velocities
+    /// without gaps or overlaps, apart from round robins and crossfades.
+    /// Files are also checked against the SHA-256 checksums packed with
+    /// them, in `checksums.txt`, if the bundle has any.
+    Verify {
+        /// Multisample to check, packed or unpacked into a directory
+        bundle: PathBuf,
+    },
+    /// Convert a Bitwig multisample into another sampler's format, without recording again
+    ///
+    /// SFZ and DecentSampler instruments are written to a directory along
+    /// with the samples. An unpacked Bitwig multisample (a directory with a
+    /// multisample.xml) can also be packed into a bundle.
+    Convert {
+        /// Multisample to convert, packed or unpacked into a directory
+        input: PathBuf,
+        /// Format to convert to
         #[arg(long)]
-        trim_start: bool,
-        #[clap(flatten)]
-        timing: Timing,
+        to: ConvertFormat,
+        /// Where to write the instrument [default: beside the input, named after it]
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Open a window to choose the devices and notes to sample, then record them
+    ///
+    /// The window previews the keys each sample is played on, and shows the
+    /// run's progress and input levels. Options that the form leaves out are
+    /// taken from the config file, as for `run`.
+    Gui,
+    /// Continue an interrupted run, skipping the zones it already recorded
+    ///
+    /// The run's options are taken from the session file in its output
+    /// directory, apart from --tui and --min-log-level. Its plan is not
+    /// confirmed again.
+    Resume {
+        /// Output directory of the run to continue
+        directory: PathBuf,
     },
-    /// Play a single note to check routing configuration
-    Test {
-        /// Print configuration and exit
-        #[clap(long, short = 'n')]
-        dry_run: bool,
-        /// Note to test (MIDI note name or number)
-        #[arg(long, default_value = "48")]
-        note: Pitch,
-        #[clap(flatten)]
-        timing: Timing,
+    /// Record some zones of a Bitwig multisample again, replacing their samples in the bundle
+    ///
+    /// The bundle's zones are planned as by `run --from-multisample`, and
+    /// only those listed are played. Each new file takes the place of the
+    /// sample with the same root, velocity layer and round robin. Any options
+    /// of `run` can follow, such as the --file-prefix of the first run.
+    Rerecord {
+        /// Bitwig multisample to patch
+        bundle: PathBuf,
+        /// Zones to record again, e.g. `C2@v127 rr2, F#3@v64`, as for `run --zones`
+        ///
+        /// A note alone stands for every velocity and round robin at it. At
+        /// most 512 zones can be recorded again at once.
+        #[arg(long, value_name = "ZONES")]
+        zones: ZoneSelection,
+        /// Options for the run, as for `run`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        run_args: Vec<String>,
     },
 }
 
@@ -87,24 +287,47 @@ pub enum Show {
     AudioHosts,
     /// List available audio devices for the selected host
     AudioDevices,
+    /// List every stream configuration that the selected input device supports
+    ///
+    /// Each line gives a sample format and channel count, with the range of
+    /// sample rates and buffer sizes that can be used with them.
+    AudioConfigs,
     /// List available MIDI ports
     MidiPorts,
 }
 
-#[derive(Parser)]
-pub struct Timing {
-    /// Length of each note before sending NoteOff message, in seconds
-    #[arg(long, default_value_t = 1.0)]
-    pub sustain: f64,
-    /// Time to wait after NoteOff before starting next note, in seconds
-    #[arg(long, default_value_t = 0.5)]
-    pub release: f64,
-}
+#[cfg(test)]
+mod tests {
+    use multirec_core::RunOptions;
+
+    use super::*;
+
+    #[test]
+    fn defaults_match_the_command_line() {
+        let args = Args::try_parse_with(["multirec", "run"], None).unwrap();
+        let Command::Task(Task::Run(options)) = args.cmd else {
+            panic!("`run` was not parsed as a run");
+        };
+
+        assert_eq!(
+            format!("{:?}", args.setup),
+            format!("{:?}", Setup::default())
+        );
+        assert_eq!(
+            format!("{options:?}"),
+            format!("{:?}", RunOptions::default())
+        );
+    }
+
+    #[test]
+    fn config_file_sets_defaults() {
+        let config: toml::Table = "midi-channel = 3\n[run]\nstart = \"C3\"\n".parse().unwrap();
+        let args = Args::try_parse_with(["multirec", "run"], Some(&config)).unwrap();
+        let Command::Task(Task::Run(options)) = args.cmd else {
+            panic!("`run` was not parsed as a run");
+        };
 
-#[derive(Clone, clap::ValueEnum)]
-pub enum OutputFormat {
-    Raw,
-    Zip,
-    Sfz,
-    Bitwig,
+        assert_eq!(args.setup.midi_channel.get(), 3);
+        assert_eq!(options.start.to_string(), "C3");
+    }
 }
@@ -0,0 +1,805 @@
+use std::{
+    sync::{atomic::Ordering, Arc, Condvar, Mutex, MutexGuard},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use clap::ValueEnum as _;
+use cpal::traits::{DeviceTrait, HostTrait};
+use eframe::egui;
+use midir::MidiOutput;
+
+use autosam::midi::Pitch;
+use multirec_core::{
+    arguments::{KeyMap, Matcher, OutputFormat},
+    Frontend, Outcome, Progress, Question, RunConfig, RunState, Session, Setup, Task,
+};
+
+use crate::{
+    arguments::{Args, Command},
+    tui,
+};
+
+/// How often the window is drawn while a run goes on
+const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// Number of log lines shown below the run
+const LOG_LINES: usize = 6;
+
+/// Colours of the sampled keys in the keymap, alternating from sample to sample
+const SAMPLE_COLORS: [egui::Color32; 2] = [
+    egui::Color32::from_rgb(0x73, 0x98, 0x14),
+    egui::Color32::from_rgb(0x00, 0xa0, 0x94),
+];
+
+/// Colour of the key being recorded
+const PLAYING_COLOR: egui::Color32 = egui::Color32::from_rgb(0xff, 0x83, 0x3e);
+
+/// Global options that the form chooses, by their long and short names
+const CHOSEN_OPTIONS: [(&str, Option<&str>); 3] = [
+    ("--host", None),
+    ("--input-device", Some("-i")),
+    ("--midi-port", None),
+];
+
+/// The settings of a run, as chosen so far
+struct Form {
+    hosts: Vec<cpal::HostId>,
+    host: usize,
+    inputs: Vec<String>,
+    input: usize,
+    ports: Vec<String>,
+    port: usize,
+    /// What takes the place of the audio input and MIDI port, if the command line names one
+    instrument: Option<String>,
+    start: u8,
+    end: u8,
+    step: u8,
+    layers: u8,
+    round_robins: u8,
+    key_map: KeyMap,
+    format: usize,
+    output: String,
+}
+
+impl Form {
+    /// Start from the devices of the command line, and the options for `run` of the config file
+    fn new(setup: &Setup, config_file: Option<&toml::Table>) -> anyhow::Result<Self> {
+        let Command::Task(Task::Run(defaults)) =
+            Args::try_parse_with(["multirec", "run"], config_file)?.cmd
+        else {
+            unreachable!("the command line is `run`");
+        };
+
+        let instrument = match (&setup.simulate, &setup.plugin, &setup.input_file) {
+            (Some(simulation), ..) => simulation
+                .to_possible_value()
+                .map(|value| format!("Simulated {}", value.get_name())),
+            (_, Some(path), _) => Some(format!("Plugin {}", path.display())),
+            (.., Some(path)) => Some(format!("Recording {}", path.display())),
+            _ => None,
+        };
+
+        let hosts = cpal::available_hosts();
+        let default_host = cpal::default_host().id();
+        let host_names: Vec<_> = hosts.iter().map(|id| id.name().to_string()).collect();
+        let host = setup
+            .host
+            .as_ref()
+            .and_then(|host| pick(host, &host_names))
+            .or_else(|| hosts.iter().position(|id| *id == default_host));
+
+        let ports = match &setup.rtp_midi {
+            _ if instrument.is_some() => Vec::new(),
+            Some(address) => vec![format!("Network session at {address}")],
+            None => {
+                let midi_output = MidiOutput::new("multirec setup")?;
+                midi_output
+                    .ports()
+                    .iter()
+                    .map(|port| midi_output.port_name(port).unwrap_or_default())
+                    .collect()
+            }
+        };
+        let port = pick(&setup.midi_port, &ports);
+
+        let format = OutputFormat::value_variants()
+            .iter()
+            .position(|format| format.to_possible_value() == defaults.format.to_possible_value());
+
+        let mut form = Self {
+            hosts,
+            host: host.unwrap_or_default(),
+            inputs: Vec::new(),
+            input: 0,
+            ports,
+            port: port.unwrap_or_default(),
+            instrument,
+            start: defaults.start.note_number(),
+            end: defaults.end.note_number(),
+            step: defaults.step.get(),
+            layers: defaults.velocity_layers.get(),
+            round_robins: defaults.round_robins.get(),
+            key_map: defaults.key_map,
+            format: format.unwrap_or_default(),
+            output: defaults
+                .output_directory
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+        };
+        form.list_inputs(setup.input_device.first());
+        Ok(form)
+    }
+
+    /// List the inputs of the selected host, starting from the given one or its default one
+    fn list_inputs(&mut self, input: Option<&Matcher>) {
+        let Some(host) = self
+            .hosts
+            .get(self.host)
+            .and_then(|id| cpal::host_from_id(*id).ok())
+        else {
+            self.inputs.clear();
+            return;
+        };
+
+        self.inputs = host
+            .input_devices()
+            .map(|devices| devices.map(|d| d.name().unwrap_or_default()).collect())
+            .unwrap_or_default();
+        let default = host.default_input_device().and_then(|d| d.name().ok());
+        self.input = input
+            .and_then(|input| pick(input, &self.inputs))
+            .or_else(|| {
+                self.inputs
+                    .iter()
+                    .position(|name| Some(name) == default.as_ref())
+            })
+            .unwrap_or_default();
+    }
+
+    fn notes(&self) -> impl Iterator<Item = u8> {
+        (self.start..=self.end).step_by(self.step.into())
+    }
+
+    /// The command line of the run chosen, after the global options the window was opened with
+    fn argv(&self, global: &[String]) -> Vec<String> {
+        let mut argv = global.to_vec();
+        if self.instrument.is_none() {
+            if !self.hosts.is_empty() {
+                argv.extend(["--host".into(), self.host.to_string()]);
+            }
+            if !self.inputs.is_empty() {
+                argv.extend(["--input-device".into(), self.input.to_string()]);
+            }
+            if !self.ports.is_empty() {
+                argv.extend(["--midi-port".into(), self.port.to_string()]);
+            }
+        }
+
+        let name = |value: Option<clap::builder::PossibleValue>| {
+            value
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default()
+        };
+        argv.extend([
+            "run".into(),
+            "--start".into(),
+            self.start.to_string(),
+            "--end".into(),
+            self.end.to_string(),
+            "--step".into(),
+            self.step.to_string(),
+            "--velocity-layers".into(),
+            self.layers.to_string(),
+            "--round-robins".into(),
+            self.round_robins.to_string(),
+            "--key-map".into(),
+            name(self.key_map.to_possible_value()),
+            "--format".into(),
+            name(OutputFormat::value_variants()[self.format].to_possible_value()),
+        ]);
+        if !self.output.is_empty() {
+            argv.extend(["--output-directory".into(), self.output.clone()]);
+        }
+        argv
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("setup")
+            .num_columns(2)
+            .spacing([16.0, 6.0])
+            .show(ui, |ui| {
+                if let Some(instrument) = &self.instrument {
+                    ui.label("Instrument");
+                    ui.label(instrument);
+                    ui.end_row();
+                } else {
+                    ui.label("Audio host");
+                    let host = self.host;
+                    choose(ui, "host", &mut self.host, &self.hosts, |id| {
+                        id.name().to_string()
+                    });
+                    if self.host != host {
+                        self.list_inputs(None);
+                    }
+                    ui.end_row();
+
+                    ui.label("Audio input");
+                    choose(ui, "input", &mut self.input, &self.inputs, String::clone);
+                    ui.end_row();
+
+                    ui.label("MIDI port");
+                    choose(ui, "port", &mut self.port, &self.ports, String::clone);
+                    ui.end_row();
+                }
+
+                ui.label("Lowest note");
+                if ui.add(note_value(&mut self.start)).changed() {
+                    self.end = self.end.max(self.start);
+                }
+                ui.end_row();
+
+                ui.label("Highest note");
+                if ui.add(note_value(&mut self.end)).changed() {
+                    self.start = self.start.min(self.end);
+                }
+                ui.end_row();
+
+                ui.label("Step");
+                ui.add(
+                    egui::DragValue::new(&mut self.step)
+                        .range(1..=127)
+                        .suffix(" semitones"),
+                );
+                ui.end_row();
+
+                ui.label("Velocity layers");
+                ui.add(egui::DragValue::new(&mut self.layers).range(1..=127));
+                ui.end_row();
+
+                ui.label("Round robins");
+                ui.add(egui::DragValue::new(&mut self.round_robins).range(1..=127));
+                ui.end_row();
+
+                ui.label("Key map");
+                egui::ComboBox::from_id_salt("key map")
+                    .selected_text(name(self.key_map.to_possible_value()))
+                    .show_ui(ui, |ui| {
+                        for key_map in KeyMap::value_variants() {
+                            ui.selectable_value(
+                                &mut self.key_map,
+                                *key_map,
+                                name(key_map.to_possible_value()),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Format");
+                choose(
+                    ui,
+                    "format",
+                    &mut self.format,
+                    OutputFormat::value_variants(),
+                    |format| name(format.to_possible_value()),
+                );
+                ui.end_row();
+
+                ui.label("Output directory");
+                ui.add(egui::TextEdit::singleline(&mut self.output).hint_text("current directory"));
+                ui.end_row();
+            });
+    }
+
+    /// Draw the keys of the range, marking those sampled and the keys that each sample plays
+    fn show_keymap(&self, ui: &mut egui::Ui, playing: Option<u8>) {
+        // whole octaves, from the C below the lowest note
+        let low = self.start - self.start % 12;
+        let high = (self.end - self.end % 12).saturating_add(11).min(127);
+        let roots: Vec<u8> = self.notes().collect();
+
+        let key_height = 56.0;
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(ui.available_width(), 84.0), egui::Sense::hover());
+        let rect = response.rect;
+        let key_width = rect.width() / f32::from(high - low + 1);
+        let left = |note: u8| rect.left() + f32::from(note - low) * key_width;
+
+        for note in low..=high {
+            let is_black = matches!(note % 12, 1 | 3 | 6 | 8 | 10);
+            let height = if is_black {
+                key_height * 0.6
+            } else {
+                key_height
+            };
+            let key = egui::Rect::from_min_size(
+                egui::pos2(left(note), rect.top()),
+                egui::vec2((key_width - 1.0).max(1.0), height),
+            );
+            let color = match roots.iter().position(|root| *root == note) {
+                _ if playing == Some(note) => PLAYING_COLOR,
+                Some(idx) => SAMPLE_COLORS[idx % SAMPLE_COLORS.len()],
+                None if is_black => egui::Color32::from_gray(40),
+                None => egui::Color32::from_gray(225),
+            };
+            painter.rect_filled(key, 1.0, color);
+
+            if note % 12 == 0 {
+                painter.text(
+                    egui::pos2(left(note), rect.top() + key_height + 12.0),
+                    egui::Align2::LEFT_TOP,
+                    note_name(note),
+                    egui::FontId::proportional(11.0),
+                    ui.visuals().text_color(),
+                );
+            }
+        }
+
+        // the keys each sample is played on, below the keyboard
+        for (idx, root) in roots.iter().enumerate() {
+            let keys = self.key_map.range(&roots, *root, 0);
+            let (from, to) = (
+                keys.low.unwrap_or(0).max(low),
+                keys.high.unwrap_or(127).min(high),
+            );
+            let band = egui::Rect::from_min_max(
+                egui::pos2(left(from), rect.top() + key_height + 3.0),
+                egui::pos2(left(to) + key_width - 1.0, rect.top() + key_height + 8.0),
+            );
+            painter.rect_filled(band, 1.0, SAMPLE_COLORS[idx % SAMPLE_COLORS.len()]);
+        }
+
+        let notes = roots.len();
+        let files = notes * usize::from(self.layers) * usize::from(self.round_robins);
+        ui.label(format!(
+            "{notes} notes × {} layers × {} round robins = {files} files",
+            self.layers, self.round_robins
+        ));
+    }
+}
+
+/// The index of the item that a matcher from the command line picks
+fn pick(matcher: &Matcher, names: &[String]) -> Option<usize> {
+    match matcher {
+        Matcher::Index(idx) => (*idx < names.len()).then_some(*idx),
+        Matcher::String(s) => names
+            .iter()
+            .position(|name| name.to_lowercase().contains(s)),
+    }
+}
+
+fn name(value: Option<clap::builder::PossibleValue>) -> String {
+    value
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn note_name(note: u8) -> String {
+    Pitch::new(note).map_or_else(|_| note.to_string(), |pitch| pitch.to_string())
+}
+
+/// A drag value for a note, shown and typed by name
+fn note_value(note: &mut u8) -> egui::DragValue<'_> {
+    egui::DragValue::new(note)
+        .range(0..=127)
+        .custom_formatter(|note, _| note_name(note as u8))
+        .custom_parser(|text| {
+            let pitch = text.trim().parse::<Pitch>().ok()?;
+            Some(f64::from(pitch.note_number()))
+        })
+}
+
+/// A drop-down list to choose an item by its index
+fn choose<T>(
+    ui: &mut egui::Ui,
+    id: &str,
+    selected: &mut usize,
+    items: &[T],
+    label: impl Fn(&T) -> String,
+) {
+    let text = items.get(*selected).map_or_else(|| "(none)".into(), &label);
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(text)
+        .width(320.0)
+        .show_ui(ui, |ui| {
+            for (idx, item) in items.iter().enumerate() {
+                ui.selectable_value(selected, idx, label(item));
+            }
+        });
+}
+
+/// What a run shows in the window, and what the window asks of it
+#[derive(Default)]
+struct Status {
+    /// What the run is doing
+    activity: &'static str,
+    /// A question waiting for an answer, with the label of the button that says yes
+    question: Option<(String, &'static str)>,
+    answer: Option<bool>,
+    zones: usize,
+    completed: usize,
+    /// Pitch, velocity and round robin being recorded
+    note: Option<(u8, u8, u8)>,
+    meters: Vec<f32>,
+    paused: bool,
+    clipped: usize,
+    dropouts: usize,
+    slips: usize,
+    last_file: Option<String>,
+    /// Requests from the window, carried out on the run's thread
+    toggle_pause: bool,
+    skip_note: bool,
+    stop: bool,
+}
+
+/// The window's side of a run, which goes on in a thread of its own
+struct Shared {
+    ctx: egui::Context,
+    status: Mutex<Status>,
+    answered: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Status)) {
+        change(&mut self.lock());
+        self.ctx.request_repaint();
+    }
+
+    /// Answer the question being asked, if any, or stop the run
+    fn answer(&self, answer: Option<bool>) {
+        let mut status = self.lock();
+        match answer {
+            Some(answer) => status.answer = Some(answer),
+            None => status.stop = true,
+        }
+        self.answered.notify_all();
+    }
+
+    /// Show the levels and progress of the run until it is done, carrying out the window's requests
+    fn watch(&self, state: &RunState, channels: usize) {
+        let mut last_frame = Instant::now();
+
+        while !state.done() {
+            let decay = tui::METER_DECAY.powf(last_frame.elapsed().as_secs_f32());
+            last_frame = Instant::now();
+
+            self.update(|status| {
+                status.meters.resize(channels.min(2), 0.0);
+                for (channel, meter) in status.meters.iter_mut().enumerate() {
+                    *meter = (*meter * decay).max(state.take_peak(channel));
+                }
+
+                if std::mem::take(&mut status.toggle_pause) {
+                    state.toggle_pause();
+                }
+                if std::mem::take(&mut status.skip_note) {
+                    state.skip_note();
+                }
+                if status.stop {
+                    state.abort();
+                }
+
+                status.activity = state.activity();
+                status.completed = state.completed();
+                status.note = Some(state.note(Ordering::Acquire));
+                status.paused = state.paused();
+                status.clipped = state.clipped();
+                status.dropouts = state.dropouts();
+                status.slips = state.timing_slips();
+            });
+
+            std::thread::sleep(FRAME_TIME);
+        }
+
+        self.update(|status| status.note = None);
+    }
+}
+
+impl Frontend for Shared {
+    fn progress(&self, progress: Progress<'_>) {
+        match progress {
+            Progress::Planned(_) => self.update(|status| status.activity = "Planning"),
+            Progress::Started { zones, .. } => self.update(|status| {
+                status.activity = "Starting";
+                status.zones = zones;
+            }),
+            Progress::Recorded { file, recorded } => self.update(|status| {
+                status.last_file = Some(file.to_string());
+                status.completed = recorded;
+            }),
+            Progress::Capturing {
+                channels, state, ..
+            } => self.watch(state, channels),
+            Progress::Processing => self.update(|status| status.activity = "Processing"),
+            _ => {}
+        }
+    }
+
+    fn confirm(&self, question: Question<'_>) -> anyhow::Result<bool> {
+        let question = match question {
+            Question::Plan(plan) => (plan.to_string(), "Record"),
+            Question::Latency(latency) => (format!("Measured latency: {latency}"), "Accept"),
+        };
+
+        let mut status = self.lock();
+        status.question = Some(question);
+        status.answer = None;
+        self.ctx.request_repaint();
+
+        while status.answer.is_none() && !status.stop {
+            status = self
+                .answered
+                .wait(status)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        status.question = None;
+        Ok(status.answer.take().unwrap_or(false))
+    }
+}
+
+/// A run going on in the background
+struct Run {
+    shared: Arc<Shared>,
+    thread: JoinHandle<anyhow::Result<Outcome>>,
+}
+
+struct App {
+    form: Form,
+    /// The global options of the command line, which every run starts from
+    global: Vec<String>,
+    config_file: Option<toml::Table>,
+    run: Option<Run>,
+    /// How the last run ended, or why it could not start
+    result: Option<Result<String, String>>,
+    /// The window is closed once the run stops
+    closing: bool,
+}
+
+impl App {
+    fn start(&mut self, ctx: &egui::Context) {
+        let argv = self.form.argv(&self.global);
+        let args = match Args::try_parse_with(&argv, self.config_file.as_ref()) {
+            Ok(args) => args,
+            Err(e) => {
+                self.result = Some(Err(e.to_string()));
+                return;
+            }
+        };
+        let Command::Task(task) = args.cmd else {
+            unreachable!("the command line is `run`");
+        };
+
+        let config = RunConfig {
+            setup: args.setup,
+            task,
+            session: Session {
+                args: args.argv,
+                config: self.config_file.clone(),
+                ..Default::default()
+            },
+        };
+        let shared = Arc::new(Shared {
+            ctx: ctx.clone(),
+            status: Mutex::default(),
+            answered: Condvar::new(),
+        });
+        let thread = std::thread::spawn({
+            let shared = shared.clone();
+            move || multirec_core::run_with(config, &*shared)
+        });
+
+        self.result = None;
+        self.run = Some(Run { shared, thread });
+    }
+
+    /// Take the result of the run once it is over
+    fn finish(&mut self, ctx: &egui::Context) {
+        let Some(run) = self.run.take_if(|run| run.thread.is_finished()) else {
+            return;
+        };
+
+        let completed = run.shared.lock().completed;
+        self.result = Some(match run.thread.join() {
+            Ok(Ok(Outcome::Recorded)) => Ok(format!("Recorded {completed} zones")),
+            Ok(Ok(Outcome::Skipped)) => {
+                Ok("Recorded nothing, as the run's files already exist".into())
+            }
+            Ok(Ok(_)) => Ok("Done".into()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("The run stopped unexpectedly".into()),
+        });
+
+        if self.closing {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+
+    fn show_run(ui: &mut egui::Ui, shared: &Shared) {
+        let mut status = shared.lock();
+
+        if let Some((question, yes)) = status.question.clone() {
+            drop(status);
+            ui.monospace(question);
+            ui.horizontal(|ui| {
+                if ui.button(yes).clicked() {
+                    shared.answer(Some(true));
+                }
+                if ui.button("Cancel").clicked() {
+                    shared.answer(Some(false));
+                }
+            });
+            return;
+        }
+
+        let progress = if status.zones > 0 {
+            status.completed as f32 / status.zones as f32
+        } else {
+            0.0
+        };
+        ui.add(egui::ProgressBar::new(progress).text(format!(
+            "{}: {} of {} zones",
+            status.activity, status.completed, status.zones
+        )));
+
+        if let Some((pitch, velocity, round_robin)) = status.note {
+            ui.label(format!(
+                "Playing {} at velocity {velocity}, round robin {}",
+                note_name(pitch),
+                round_robin + 1
+            ));
+        }
+
+        for (channel, level) in status.meters.iter().enumerate() {
+            let db = 20.0 * level.max(f32::MIN_POSITIVE).log10();
+            let floor = tui::METER_FLOOR.0;
+            let color = match db {
+                db if db >= -1.0 => egui::Color32::from_rgb(0xd9, 0x2e, 0x24),
+                db if db >= -12.0 => egui::Color32::from_rgb(0xe4, 0xb7, 0x4e),
+                _ => SAMPLE_COLORS[0],
+            };
+            ui.add(
+                egui::ProgressBar::new(((db - floor) / -floor).clamp(0.0, 1.0))
+                    .fill(color)
+                    .text(if db > floor {
+                        format!("Input {}: {db:.1} dB", channel + 1)
+                    } else {
+                        format!("Input {}: -inf dB", channel + 1)
+                    }),
+            );
+        }
+
+        ui.label(format!(
+            "{} clipped, {} dropouts, {} timing slips",
+            status.clipped, status.dropouts, status.slips
+        ));
+        if let Some(file) = &status.last_file {
+            ui.label(format!("Last file: {file}"));
+        }
+
+        let paused = status.paused;
+        ui.horizontal(|ui| {
+            if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+                status.toggle_pause = true;
+            }
+            if ui.button("Skip note").clicked() {
+                status.skip_note = true;
+            }
+            if ui.button("Stop").clicked() {
+                status.stop = true;
+            }
+        });
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.finish(ctx);
+
+        // a run is stopped before the window closes, so its files are left complete
+        if ctx.input(|input| input.viewport().close_requested()) {
+            if let Some(run) = &self.run {
+                self.closing = true;
+                run.shared.answer(None);
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            }
+        }
+
+        egui::TopBottomPanel::bottom("log").show(ctx, |ui| {
+            ui.add_space(4.0);
+            for line in tui::recent_log(LOG_LINES) {
+                ui.small(line);
+            }
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let playing = self.run.as_ref().and_then(|run| run.shared.lock().note);
+            ui.add_enabled_ui(self.run.is_none(), |ui| self.form.show(ui));
+            ui.separator();
+            self.form.show_keymap(ui, playing.map(|(pitch, ..)| pitch));
+            ui.separator();
+
+            match &self.run {
+                Some(run) => Self::show_run(ui, &run.shared),
+                None => {
+                    if ui.button("Record").clicked() {
+                        self.start(ctx);
+                    }
+                    match &self.result {
+                        Some(Ok(message)) => {
+                            ui.label(message);
+                        }
+                        Some(Err(message)) => {
+                            ui.colored_label(ui.visuals().error_fg_color, message);
+                        }
+                        None => {}
+                    }
+                }
+            }
+        });
+
+        if self.run.is_some() {
+            ctx.request_repaint_after(FRAME_TIME);
+        }
+    }
+}
+
+/// The global options of a command line, without those that the form chooses
+fn global_options(argv: &[String], chosen: bool) -> Vec<String> {
+    let end = argv
+        .iter()
+        .rposition(|arg| arg == "gui")
+        .unwrap_or(argv.len());
+
+    let mut global = Vec::new();
+    let mut args = argv[..end].iter();
+    while let Some(arg) = args.next() {
+        let option = CHOSEN_OPTIONS.iter().find(|(long, short)| {
+            arg == long || Some(arg.as_str()) == *short || arg.starts_with(&format!("{long}="))
+        });
+        match option {
+            Some((long, _)) if chosen && !arg.starts_with(&format!("{long}=")) => {
+                args.next();
+            }
+            Some(_) if chosen => {}
+            _ => global.push(arg.clone()),
+        }
+    }
+    global
+}
+
+/// Open a window to choose the devices and notes to sample, and record them
+///
+/// Each run is parsed from the command line the form makes, after the global
+/// options given before `gui`, with defaults from the config file as for `run`.
+pub fn show(
+    setup: &Setup,
+    argv: &[String],
+    config_file: Option<toml::Table>,
+) -> anyhow::Result<()> {
+    let form = Form::new(setup, config_file.as_ref())?;
+    let app = App {
+        global: global_options(argv, form.instrument.is_none()),
+        form,
+        config_file,
+        run: None,
+        result: None,
+        closing: false,
+    };
+
+    tui::collect_log();
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("multirec")
+            .with_inner_size([720.0, 640.0]),
+        ..Default::default()
+    };
+    let result = eframe::run_native("multirec", options, Box::new(|_| Ok(Box::new(app))));
+    tui::print_log();
+
+    result.map_err(|e| anyhow!("Could not open the window: {e}"))
+}
@@ -0,0 +1,66 @@
+use std::{fs::OpenOptions, path::Path};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{
+    arguments::{Args, Command},
+    tui::LogPipe,
+};
+
+/// Level of the messages written to a log file
+const FILE_LEVEL: LevelFilter = LevelFilter::Debug;
+
+/// Sends each message to the console and the log file, filtered for each on its own
+struct Tee {
+    console: env_logger::Logger,
+    file: env_logger::Logger,
+}
+
+impl Log for Tee {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || self.file.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.matches(record) {
+            self.console.log(record);
+        }
+        if self.file.matches(record) {
+            self.file.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        self.file.flush();
+    }
+}
+
+/// Log to standard error, or the dashboard or window when shown, and to the log file if there is one
+pub fn init(args: &Args) -> std::io::Result<()> {
+    let mut console = env_logger::Builder::new();
+    console.filter_level(args.min_log_level).parse_default_env();
+    if args.tui || matches!(args.cmd, Command::Gui) {
+        console.target(env_logger::Target::Pipe(Box::<LogPipe>::default()));
+    }
+
+    let Some(path) = &args.log_file else {
+        console.init();
+        return Ok(());
+    };
+
+    let console = console.build();
+    let file = env_logger::Builder::new()
+        .filter_level(FILE_LEVEL)
+        .write_style(env_logger::WriteStyle::Never)
+        .target(env_logger::Target::Pipe(Box::new(open(path)?)))
+        .build();
+
+    log::set_max_level(console.filter().max(file.filter()));
+    log::set_boxed_logger(Box::new(Tee { console, file })).expect("the logger is only set up once");
+    Ok(())
+}
+
+fn open(path: &Path) -> std::io::Result<std::fs::File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
@@ -0,0 +1,101 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use midir::MidiOutput;
+
+use multirec_core::arguments::Matcher;
+
+use crate::arguments::Show;
+
+/// List what `show` asks for, on standard output with headings on standard error
+pub fn show(
+    what: Show,
+    host: Option<Matcher>,
+    input_device: Option<Matcher>,
+) -> anyhow::Result<()> {
+    match what {
+        Show::AudioHosts => print_hosts(),
+        Show::AudioDevices => print_devices(multirec_core::audio_host(host)?),
+        Show::AudioConfigs => {
+            let host = multirec_core::audio_host(host)?;
+            print_configs(multirec_core::input_device(&host, input_device)?)
+        }
+        Show::MidiPorts => print_midi_ports(MidiOutput::new("MIDI Output")?),
+    }
+}
+
+fn print_hosts() -> anyhow::Result<()> {
+    eprintln!("ID\tName");
+    for (id, host) in cpal::available_hosts().into_iter().enumerate() {
+        println!("{id}\t{}", host.name());
+    }
+    Ok(())
+}
+
+fn print_devices(host: cpal::Host) -> anyhow::Result<()> {
+    eprintln!("ID\tIn\tOut\tFs Min\tFs Max\tName");
+    for (id, device) in host.devices()?.enumerate() {
+        print!("{id}\t");
+        print!(
+            "{}\t",
+            device
+                .supported_input_configs()?
+                .next()
+                .map_or(0, |cfg| cfg.channels())
+        );
+        print!(
+            "{}\t",
+            device
+                .supported_output_configs()?
+                .next()
+                .map_or(0, |cfg| cfg.channels())
+        );
+        if let Some(min_rate) = device
+            .supported_input_configs()?
+            .chain(device.supported_output_configs()?)
+            .map(|cfg| cfg.min_sample_rate().0)
+            .min()
+        {
+            print!("{min_rate:6}\t");
+        } else {
+            print!("      \t");
+        }
+        if let Some(max_rate) = device
+            .supported_input_configs()?
+            .chain(device.supported_output_configs()?)
+            .map(|cfg| cfg.max_sample_rate().0)
+            .max()
+        {
+            print!("{max_rate:6}\t");
+        } else {
+            print!("      \t");
+        }
+        println!("{}", device.name()?,);
+    }
+    Ok(())
+}
+
+fn print_configs(device: cpal::Device) -> anyhow::Result<()> {
+    eprintln!("{}", device.name()?);
+    eprintln!("Format\tChans\tFs Min\tFs Max\tBuffer");
+    for config in device.supported_input_configs()? {
+        let buffer = match config.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => format!("{min}-{max}"),
+            cpal::SupportedBufferSize::Unknown => "unknown".into(),
+        };
+        println!(
+            "{}\t{}\t{:6}\t{:6}\t{buffer}",
+            config.sample_format(),
+            config.channels(),
+            config.min_sample_rate().0,
+            config.max_sample_rate().0,
+        );
+    }
+    Ok(())
+}
+
+fn print_midi_ports(midi_output: MidiOutput) -> anyhow::Result<()> {
+    println!("ID\tName");
+    for (index, port) in midi_output.ports().into_iter().enumerate() {
+        println!("{index}\t{}", midi_output.port_name(&port)?);
+    }
+    Ok(())
+}
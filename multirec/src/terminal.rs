@@ -0,0 +1,119 @@
+use std::io::{IsTerminal as _, Write as _};
+
+use anyhow::anyhow;
+use log::error;
+
+use autosam::midi::{Event, NoteState};
+use multirec_core::{Frontend, Outcome, Progress, Question};
+
+use crate::tui::Dashboard;
+
+/// Shows a run on standard error, and asks there when it needs to
+pub struct Terminal {
+    /// Show a live dashboard while recording
+    pub dashboard: bool,
+}
+
+impl Frontend for Terminal {
+    fn progress(&self, progress: Progress<'_>) {
+        match progress {
+            Progress::Planned(plan) => eprint!("{plan}"),
+            Progress::Capturing {
+                zones,
+                channels,
+                state,
+            } if self.dashboard => {
+                // the run goes on without it, and can still be stopped with Ctrl-C
+                if let Err(e) = Dashboard::new(state, zones, channels).run() {
+                    error!("Could not show the dashboard: {e}");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn confirm(&self, question: Question<'_>) -> anyhow::Result<bool> {
+        match question {
+            Question::Plan(_) if !std::io::stdin().is_terminal() => Err(anyhow!(
+                "The plan was not confirmed, pass --yes to record without a terminal"
+            )),
+            Question::Plan(_) => Ok(confirm("Recording as planned")?),
+            Question::Latency(latency) => Ok(confirm(&format!("Measured latency: {latency}"))?),
+        }
+    }
+}
+
+/// Ask whether to continue after showing a message, if there is someone to ask
+///
+/// When standard input is not a terminal, the message is only printed.
+fn confirm(message: &str) -> std::io::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        eprintln!("{message}");
+        return Ok(true);
+    }
+
+    eprint!("{message}. Continue? [Y/n] ");
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(!answer.trim().to_lowercase().starts_with('n'))
+}
+
+/// Print what a run found out, with what can be passed on to another command on standard output
+pub fn show(outcome: &Outcome) {
+    match outcome {
+        Outcome::Recorded | Outcome::Skipped => {}
+        Outcome::DryRun(events) => {
+            eprintln!("Sample Offset       \tEvent\tPitch\tVelo\tMIDI");
+            eprintln!("--------------------\t-----\t-----\t----\t----");
+
+            for (sample_offset, event) in events {
+                let (kind, pitch, velocity) = match event {
+                    Event::Note(note) => (
+                        if note.state() == NoteState::On {
+                            "On"
+                        } else {
+                            "Off"
+                        },
+                        note.pitch().to_string(),
+                        note.velocity().to_string(),
+                    ),
+                    Event::ControlChange { .. } => ("CC", String::new(), String::new()),
+                    Event::PitchBend { .. } => ("Bend", String::new(), String::new()),
+                    Event::ChannelPressure { .. } => ("Press", String::new(), String::new()),
+                    Event::PolyPressure { pitch, .. } => {
+                        ("PolyP", pitch.to_string(), String::new())
+                    }
+                    Event::ProgramChange { .. } => ("Prog", String::new(), String::new()),
+                    Event::TimingClock => ("Clock", String::new(), String::new()),
+                    Event::Start => ("Start", String::new(), String::new()),
+                    Event::Stop => ("Stop", String::new(), String::new()),
+                };
+
+                println!(
+                    "{sample_offset:20}\t{kind}\t{pitch:5}\t{velocity:>4}\t{:?}",
+                    &*event.as_midi_message(),
+                );
+            }
+        }
+        Outcome::Tested(latency) => {
+            if let Some(latency) = latency {
+                println!("Approximate latency: {latency}");
+            }
+        }
+        Outcome::Measured {
+            levels,
+            recommendation,
+        } => {
+            eprintln!("Velo\tLevel");
+            eprintln!("----\t-----");
+            for (velocity, level) in levels {
+                eprintln!("{velocity:>4}\t{level:5.1}dB");
+            }
+
+            println!("{}", recommendation.options());
+        }
+    }
+}
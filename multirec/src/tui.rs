@@ -0,0 +1,325 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    sync::{atomic::Ordering, Mutex},
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Gauge, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use autosam::midi::Pitch;
+use multirec_core::{arguments::Decibels, clock_time, RunState};
+
+/// Time between redraws, which is also how long to wait for a key press
+const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// Lowest level shown on the input meters
+pub const METER_FLOOR: Decibels = Decibels(-60.0);
+
+/// Fraction of the displayed level that remains after a second without new peaks
+pub const METER_DECAY: f32 = 0.05;
+
+/// Log lines collected while the dashboard is shown, or `None` when it is not
+static LOG: Mutex<Option<VecDeque<String>>> = Mutex::new(None);
+
+/// A log target that feeds the dashboard while it is shown, and stderr otherwise
+#[derive(Default)]
+pub struct LogPipe {
+    partial: String,
+}
+
+impl Write for LogPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(lines) = log.as_mut() else {
+            return std::io::stderr().write(buf);
+        };
+
+        self.partial.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = self.partial.find('\n') {
+            lines.push_back(self.partial[..end].to_string());
+            self.partial.drain(..=end);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+/// Collect log lines for a view of the run, instead of printing them
+pub fn collect_log() {
+    *LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(VecDeque::new());
+}
+
+/// The last `count` log lines collected, oldest first
+pub fn recent_log(count: usize) -> Vec<String> {
+    match LOG.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(lines) => lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Stop collecting log lines, and print those collected
+pub fn print_log() {
+    let lines = LOG.lock().unwrap_or_else(|e| e.into_inner()).take();
+    for line in lines.into_iter().flatten() {
+        eprintln!("{line}");
+    }
+}
+
+/// A live view of a run, with controls to pause, skip notes or abort it
+pub struct Dashboard<'a> {
+    state: &'a RunState,
+    /// The keymap cell of each zone, in the order they are played
+    cells: Vec<usize>,
+    /// Number of zones in each cell
+    expected: Vec<usize>,
+    pitches: Vec<u8>,
+    layers: Vec<u8>,
+    channels: usize,
+    meters: [f32; 2],
+    started: Instant,
+}
+
+impl<'a> Dashboard<'a> {
+    pub fn new(state: &'a RunState, zones: &[(u8, u8)], channels: usize) -> Self {
+        let mut pitches: Vec<_> = zones.iter().map(|(pitch, _)| *pitch).collect();
+        pitches.sort_unstable();
+        pitches.dedup();
+
+        let mut layers: Vec<_> = zones.iter().map(|(_, layer)| *layer).collect();
+        layers.sort_unstable();
+        layers.dedup();
+
+        let cells: Vec<_> = zones
+            .iter()
+            .map(|(pitch, layer)| {
+                let column = pitches.binary_search(pitch).unwrap_or_default();
+                let row = layers.binary_search(layer).unwrap_or_default();
+                row * pitches.len() + column
+            })
+            .collect();
+
+        let mut expected = vec![0; pitches.len() * layers.len()];
+        for cell in &cells {
+            expected[*cell] += 1;
+        }
+
+        Self {
+            state,
+            cells,
+            expected,
+            pitches,
+            layers,
+            channels: channels.min(2),
+            meters: [0.0; 2],
+            started: Instant::now(),
+        }
+    }
+
+    /// Show the dashboard until the run is complete or aborted
+    ///
+    /// Log messages are shown in the dashboard meanwhile, and printed as usual
+    /// once it closes.
+    pub fn run(mut self) -> anyhow::Result<()> {
+        collect_log();
+
+        let mut terminal = ratatui::init();
+        let result = self.show(&mut terminal);
+        ratatui::restore();
+
+        print_log();
+        result
+    }
+
+    fn show(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        let mut last_frame = Instant::now();
+
+        while !self.state.done() {
+            let elapsed = last_frame.elapsed();
+            last_frame = Instant::now();
+
+            let decay = METER_DECAY.powf(elapsed.as_secs_f32());
+            for (channel, meter) in self.meters.iter_mut().enumerate() {
+                *meter = (*meter * decay).max(self.state.take_peak(channel));
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(FRAME_TIME)? {
+                continue;
+            }
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char(' ') | KeyCode::Char('p') => {
+                    self.state.toggle_pause();
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.state.abort();
+                }
+                KeyCode::Char('s') => self.state.skip_note(),
+                KeyCode::Char('q') | KeyCode::Esc => self.state.abort(),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [status, progress, meters, keymap, log, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(self.channels as u16 + 2),
+            Constraint::Length(self.layers.len() as u16 + 2),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let completed = self.state.completed();
+        let (pitch, velocity, round_robin) = self.state.note(Ordering::Acquire);
+        let pitch = Pitch::new(pitch).map_or_else(|_| pitch.to_string(), |p| p.to_string());
+
+        let mut line = vec![
+            Span::raw(format!(
+                "Recording {pitch} at velocity {velocity}, round robin {}",
+                round_robin + 1
+            )),
+            Span::raw(format!("  ·  {}", clock_time(self.started.elapsed()))),
+        ];
+        if self.state.aborted() {
+            line.push(Span::raw("  ABORTING").red().bold());
+        } else if self.state.paused() {
+            line.push(Span::raw("  PAUSED").yellow().bold());
+        }
+        frame.render_widget(Line::from(line), status);
+
+        let total = self.cells.len().max(1);
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio((completed as f64 / total as f64).min(1.0))
+                .label(format!("{completed}/{} zones", self.cells.len())),
+            progress,
+        );
+
+        self.draw_meters(frame, meters);
+        self.draw_keymap(frame, keymap, completed);
+
+        let visible = usize::from(log.height.saturating_sub(2));
+        let lines: Vec<_> = recent_log(visible).into_iter().map(Line::raw).collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Log")),
+            log,
+        );
+
+        frame.render_widget(
+            Line::raw("space: pause/resume  ·  s: skip note  ·  q: abort").dark_gray(),
+            help,
+        );
+    }
+
+    fn draw_meters(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let block = Block::bordered().title(format!(
+            "Input  ·  clipped frames: {}  ·  dropouts: {}  ·  timing slips: {}",
+            self.state.clipped(),
+            self.state.dropouts(),
+            self.state.timing_slips()
+        ));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let rows = Layout::vertical(vec![Constraint::Length(1); self.channels]).split(inner);
+        for (row, level) in rows.iter().zip(self.meters) {
+            let db = 20.0 * level.max(f32::MIN_POSITIVE).log10();
+            let ratio = ((db - METER_FLOOR.0) / -METER_FLOOR.0).clamp(0.0, 1.0);
+            let color = match db {
+                db if db >= -1.0 => Color::Red,
+                db if db >= -12.0 => Color::Yellow,
+                _ => Color::Green,
+            };
+
+            frame.render_widget(
+                Gauge::default()
+                    .gauge_style(Style::default().fg(color))
+                    .ratio(f64::from(ratio))
+                    .label(if db > METER_FLOOR.0 {
+                        format!("{db:.1} dB")
+                    } else {
+                        "-inf dB".to_string()
+                    }),
+                *row,
+            );
+        }
+    }
+
+    fn draw_keymap(&self, frame: &mut Frame, area: ratatui::layout::Rect, completed: usize) {
+        let title = match (self.pitches.first(), self.pitches.last()) {
+            (Some(low), Some(high)) => format!(
+                "Keymap {}–{}",
+                Pitch::new(*low).map_or_else(|_| low.to_string(), |p| p.to_string()),
+                Pitch::new(*high).map_or_else(|_| high.to_string(), |p| p.to_string()),
+            ),
+            _ => "Keymap".to_string(),
+        };
+
+        let current = (!self.state.done())
+            .then(|| self.cells.get(completed).copied())
+            .flatten();
+
+        let mut done = vec![0; self.expected.len()];
+        for cell in &self.cells[..completed.min(self.cells.len())] {
+            done[*cell] += 1;
+        }
+
+        let lines: Vec<_> =
+            self.expected
+                .chunks(self.pitches.len().max(1))
+                .zip(done.chunks(self.pitches.len().max(1)))
+                .enumerate()
+                .map(|(row, (expected, done))| {
+                    let cells =
+                        expected.iter().zip(done).enumerate().map(
+                            |(column, counts)| match counts {
+                                _ if current == Some(row * self.pitches.len() + column) => {
+                                    Span::raw("▶").cyan()
+                                }
+                                (expected, done) if done == expected => Span::raw("█").green(),
+                                (_, 0) => Span::raw("·").dark_gray(),
+                                _ => Span::raw("▒").green(),
+                            },
+                        );
+
+                    Line::from(cells.collect::<Vec<_>>())
+                })
+                .collect();
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}